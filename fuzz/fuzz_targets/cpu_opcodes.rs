@@ -0,0 +1,25 @@
+// Runs arbitrary bytes as a 6502 program. The machine is a flat 64k of RAM (see
+// mmc::flat_ram) with the input loaded at $0000, so every opcode, addressing mode and
+// page crossing is reachable, and since the vectors at $FFFA-$FFFF are usually zero,
+// BRK and interrupts land right back in the input.
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use rusticnes_core::cartridge;
+use rusticnes_core::nes::NesState;
+
+const INSTRUCTIONS_PER_RUN: usize = 10000;
+
+fuzz_target!(|data: &[u8]| {
+    let binary = &data[.. data.len().min(0x10000)];
+    let mapper = match cartridge::mapper_from_flat_binary(binary, 0x0000) {
+        Ok(mapper) => mapper,
+        Err(_) => return,
+    };
+    let mut nes = NesState::new(mapper);
+    nes.power_on();
+    for _ in 0 .. INSTRUCTIONS_PER_RUN {
+        nes.step();
+    }
+});
@@ -0,0 +1,94 @@
+// Builds a small NES 2.0 image for one of the supported mappers, then replays the rest
+// of the input as a stream of bus operations: CPU writes (anywhere from $4020 up, so
+// every register window is reachable), CPU and PPU reads, PPU writes, and the mapper's
+// clock and scanline hooks.
+// Bank registers routinely hold values far larger than the ROM behind them, so this is
+// mostly looking for indexing that forgot to wrap, and counters that overflow.
+//
+// Input layout:
+//   byte 0: index into MAPPERS
+//   byte 1: PRG ROM size, in 16k units (1-8)
+//   byte 2: CHR ROM size, in 8k units (0-8, 0 means CHR RAM)
+//   byte 3: submapper (high nybble), PRG RAM shift count (low nybble)
+//   byte 4: flags 6 mirroring bits, and whether PRG RAM is battery backed
+//   then 4 bytes per operation: kind, address low, address high, data
+
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use rusticnes_core::cartridge;
+
+// Every iNES mapper number cartridge::mapper_from_ines knows about
+const MAPPERS: &[u16] = &[
+    0, 1, 2, 3, 4, 5, 7, 9, 10, 11, 16, 18, 19, 21, 22, 23, 24, 25, 26, 28, 30, 31, 32, 33, 34,
+    48, 65, 66, 67, 68, 69, 71, 73, 75, 77, 80, 85, 89, 93, 95, 111, 153, 154, 157, 159, 184,
+    206, 207, 210, 232, 234, 682,
+];
+
+const HEADER_BYTES: usize = 5;
+
+fn build_image(data: &[u8]) -> Vec<u8> {
+    let mapper = MAPPERS[data[0] as usize % MAPPERS.len()];
+    let prg_banks = (data[1] % 8) + 1;
+    let chr_banks = data[2] % 9;
+    let submapper = data[3] >> 4;
+    let ram_shift = data[3] & 0x0F;
+    let battery = (data[4] & 0b1000_0000) != 0;
+    let prg_ram = if battery {ram_shift << 4} else {ram_shift};
+    let chr_ram = if chr_banks == 0 {7} else {0};
+
+    let mut image = vec!(
+        0x4E, 0x45, 0x53, 0x1A, prg_banks, chr_banks,
+        (((mapper & 0x0F) as u8) << 4) | (data[4] & 0b0000_1001) | if battery {0b0000_0010} else {0},
+        ((mapper & 0xF0) as u8) | 0b0000_1000, // NES 2.0
+        ((mapper >> 8) as u8) | (submapper << 4),
+        0, prg_ram, chr_ram, 0, 0, 0, 0);
+    // The contents hardly matter, but a pattern is more interesting than all zeroes
+    let rom_size = (prg_banks as usize * 0x4000) + (chr_banks as usize * 0x2000);
+    image.extend((0 .. rom_size).map(|i| (i ^ (i >> 8)) as u8));
+    return image;
+}
+
+fuzz_target!(|data: &[u8]| {
+    if data.len() < HEADER_BYTES {
+        return;
+    }
+    // Plenty of header combinations are (correctly) rejected; those aren't interesting
+    let mut mapper = match cartridge::mapper_from_file(&build_image(data)) {
+        Ok(mapper) => mapper,
+        Err(_) => return,
+    };
+
+    for op in data[HEADER_BYTES ..].chunks_exact(4) {
+        let address = op[1] as u16 | ((op[2] as u16) << 8);
+        let value = op[3];
+        match op[0] % 8 {
+            0 | 1 | 2 => mapper.write_cpu(address.max(0x4020), value),
+            3 => {mapper.read_cpu(address);},
+            4 => {
+                mapper.access_ppu(address & 0x3FFF);
+                mapper.read_ppu(address & 0x3FFF);
+            },
+            5 => mapper.write_ppu(address & 0x3FFF, value),
+            6 => {
+                for _ in 0 .. (value as u16 * 4) {
+                    mapper.clock_cpu();
+                }
+            },
+            _ => {
+                mapper.start_scanline(address % 262);
+                if value == 0 {
+                    mapper.start_vblank();
+                }
+                mapper.irq_flag();
+                mapper.mix_expansion_audio(0.0);
+                mapper.debug_read_cpu(address);
+                mapper.debug_read_ppu(address & 0x3FFF);
+            },
+        }
+    }
+
+    // Saves should always round trip, whatever state the mapper ended up in
+    let sram = mapper.get_sram();
+    mapper.load_sram(sram);
+});
@@ -37,6 +37,24 @@ pub trait AudioChannelState {
     fn mute(&mut self);
     fn unmute(&mut self);
 
+    // Whether edge_buffer marks genuine period restarts. Channels with arbitrary, CPU-driven
+    // output (PCM, the final mix) mark every sample instead, and should return false here.
+    fn has_edges(&self) -> bool { return true; }
+
+    // Timestamp (shared by sample_buffer and edge_buffer) that a scope view should align to,
+    // searching at most search_length samples back. Prefers the most recent recorded edge, and
+    // falls back to a rising zero crossing of the sample buffer when the channel has no edges,
+    // or hasn't restarted a period within the search window.
+    fn trigger_timestamp(&self, search_length: usize) -> Option<u64> {
+        let end_timestamp = self.sample_buffer().timestamp();
+        if self.has_edges() {
+            if let Some(timestamp) = self.edge_buffer().find_last_edge(search_length, end_timestamp) {
+                return Some(timestamp);
+            }
+        }
+        return self.sample_buffer().find_rising_crossing(0, search_length, end_timestamp);
+    }
+
     fn playing(&self) -> bool { return false; }
     fn rate(&self) -> PlaybackRate { return PlaybackRate::SampleRate{frequency: 0.0}; }
     fn volume(&self) -> Option<Volume> {return None}
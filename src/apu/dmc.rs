@@ -4,6 +4,40 @@ use super::ring_buffer::RingBuffer;
 use super::filters;
 use super::filters::DspFilter;
 
+// A single triggered DMC sample, decoded for piano roll displays and DPCM ripping
+#[derive(Clone)]
+pub struct DmcSampleEvent {
+    pub starting_address: u16,
+    // Where the sample lives in PRG ROM, if the mapper can say; this identifies a sample
+    // across bank switches, where starting_address alone cannot
+    pub rom_offset: Option<usize>,
+    pub sample_length: u16,
+    pub looping: bool,
+    pub period_index: u8,
+    pub initial_level: u8,
+    // The 7-bit output level after each delta bit, starting from initial_level
+    pub pcm: Vec<u8>,
+    pub raw_bytes: Vec<u8>,
+}
+
+pub fn decode_dpcm(raw_bytes: &[u8], initial_level: u8) -> Vec<u8> {
+    let mut pcm = Vec::with_capacity(raw_bytes.len() * 8);
+    let mut level = initial_level;
+    for byte in raw_bytes {
+        for bit in 0 .. 8 {
+            if (byte >> bit) & 0b1 == 0 {
+                if level >= 2 {
+                    level -= 2;
+                }
+            } else if level <= 125 {
+                level += 2;
+            }
+            pcm.push(level);
+        }
+    }
+    return pcm;
+}
+
 pub struct DmcState {
     pub name: String,
     pub chip: String,
@@ -32,6 +66,11 @@ pub struct DmcState {
     pub interrupt_flag: bool,
     pub rdy_line: bool,
     pub rdy_delay: u8,
+
+    pub period_index: u8,
+    pub sample_started: bool,
+    pub capture_samples: bool,
+    pub captured_samples: Vec<DmcSampleEvent>,
 }
 
 impl DmcState {
@@ -63,6 +102,11 @@ impl DmcState {
             interrupt_flag: false,
             rdy_line: false,
             rdy_delay: 0,
+
+            period_index: 0,
+            sample_started: false,
+            capture_samples: false,
+            captured_samples: Vec::new(),
         }
     }
 
@@ -72,7 +116,41 @@ impl DmcState {
             self.bytes_remaining, self.bits_remaining);
     }
 
+    pub fn capture_sample(&mut self, mapper: &dyn Mapper) {
+        let mut raw_bytes = Vec::with_capacity(self.sample_length as usize);
+        let mut address = self.starting_address;
+        for _ in 0 .. self.sample_length {
+            raw_bytes.push(mapper.debug_read_cpu(0x8000 | (address & 0x7FFF)).unwrap_or(0));
+            address = address.wrapping_add(1);
+        }
+        let pcm = decode_dpcm(&raw_bytes, self.output_level);
+        self.captured_samples.push(DmcSampleEvent {
+            starting_address: self.starting_address,
+            rom_offset: mapper.resolve_prg_offset(0x8000 | (self.starting_address & 0x7FFF)).map(|(_, offset)| offset),
+            sample_length: self.sample_length,
+            looping: self.looping,
+            period_index: self.period_index,
+            initial_level: self.output_level,
+            pcm: pcm,
+            raw_bytes: raw_bytes,
+        });
+    }
+
+    // Returns every sample triggered since the last call. Intended to be drained once
+    // per frame by the frontend.
+    pub fn drain_captured_samples(&mut self) -> Vec<DmcSampleEvent> {
+        return self.captured_samples.drain(..).collect();
+    }
+
     pub fn read_next_sample(&mut self, mapper: &mut dyn Mapper) {
+        if self.sample_started {
+            // Only the first fetch of a sample started by $4015 is captured; loops are
+            // implied by the looping flag
+            if self.capture_samples {
+                self.capture_sample(mapper);
+            }
+            self.sample_started = false;
+        }
         match mapper.read_cpu(0x8000 | (self.current_address & 0x7FFF)) {
             Some(byte) => self.sample_buffer = byte,
             None => self.sample_buffer = 0,
@@ -201,7 +279,7 @@ impl AudioChannelState for DmcState {
 
     fn amplitude(&self) -> f32 {
         let buffer = self.output_buffer.buffer();
-        let mut index = (self.output_buffer.index() - 256) % buffer.len();
+        let mut index = (self.output_buffer.index() + buffer.len() - 256) % buffer.len();
         let mut max = buffer[index];
         let mut min = buffer[index];
         for _i in 0 .. 256 {
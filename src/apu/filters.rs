@@ -5,6 +5,12 @@ use std::f32::consts::PI;
 pub trait DspFilter: Send {
     fn consume(&mut self, sample: f32);
     fn output(&self) -> f32;
+    // Puts the filter into the steady state it would reach after a long run of constant
+    // input at this level, and returns the resulting output
+    fn settle(&mut self, level: f32) -> f32 {
+        self.consume(level);
+        return self.output();
+    }
 }
 
 pub struct IdentityFilter {
@@ -27,6 +33,11 @@ impl DspFilter for IdentityFilter {
     fn output(&self) -> f32 {
         return self.sample;
     }
+
+    fn settle(&mut self, level: f32) -> f32 {
+        self.sample = level;
+        return level;
+    }
 }
 
 pub struct HighPassIIR {
@@ -60,6 +71,14 @@ impl DspFilter for HighPassIIR {
     fn output(&self) -> f32 {
         return self.alpha * self.previous_output + self.alpha * self.delta;
     }
+
+    // DC never makes it through, so a settled high pass outputs nothing
+    fn settle(&mut self, level: f32) -> f32 {
+        self.previous_input = level;
+        self.previous_output = 0.0;
+        self.delta = 0.0;
+        return 0.0;
+    }
 }
 
 pub struct LowPassIIR {
@@ -90,6 +109,54 @@ impl DspFilter for LowPassIIR {
     fn output(&self) -> f32 {
         return self.previous_output + self.alpha * self.delta;
     }
+
+    fn settle(&mut self, level: f32) -> f32 {
+        self.previous_output = level;
+        self.delta = 0.0;
+        return level;
+    }
+}
+
+// Adds a faint, constant hiss to the signal, like the noise floor of an RF connection.
+// The noise comes from a fixed LFSR rather than a random source, so output stays
+// reproducible from run to run.
+pub struct NoiseFloor {
+    amplitude: f32,
+    lfsr: u32,
+    sample: f32,
+}
+
+impl NoiseFloor {
+    pub fn new(amplitude: f32) -> NoiseFloor {
+        return NoiseFloor {
+            amplitude: amplitude,
+            lfsr: 0xACE1,
+            sample: 0.0,
+        }
+    }
+}
+
+impl DspFilter for NoiseFloor {
+    fn consume(&mut self, new_input: f32) {
+        // 32-bit Galois LFSR, taps 32, 22, 2, 1
+        let feedback = self.lfsr & 1;
+        self.lfsr >>= 1;
+        if feedback != 0 {
+            self.lfsr ^= 0x8020_0003;
+        }
+        let noise = ((self.lfsr & 0xFFFF) as f32 / 32768.0) - 1.0;
+        self.sample = new_input + noise * self.amplitude;
+    }
+
+    fn output(&self) -> f32 {
+        return self.sample;
+    }
+
+    // The hiss carries on regardless; only the signal underneath it settles
+    fn settle(&mut self, level: f32) -> f32 {
+        self.sample = level;
+        return level;
+    }
 }
 
 fn blackman_window(index: usize, window_size: usize) -> f32 {
@@ -158,6 +225,13 @@ impl DspFilter for LowPassFIR {
         }
         return output;
     }
+
+    fn settle(&mut self, level: f32) -> f32 {
+        for input in self.inputs.iter_mut() {
+            *input = level;
+        }
+        return self.output();
+    }
 }
 
 // essentially a thin wrapper around a DspFilter, with some bonus data to track
@@ -209,6 +283,16 @@ impl FilterChain {
         }
     }
 
+    // Settles every filter in turn, each at the steady output of the one before it, as
+    // though the input had been held at this level forever
+    pub fn settle(&mut self, input_sample: f32) {
+        let mut level = input_sample;
+        for chained in self.filters.iter_mut() {
+            level = chained.wrapped_filter.settle(level);
+            chained.period_counter = 0.0;
+        }
+    }
+
     pub fn output(&self) -> f32 {
         let final_filter = self.filters.last().unwrap();
         return final_filter.wrapped_filter.output();
@@ -1,4 +1,5 @@
 use mmc::mapper::Mapper;
+use region::Region;
 
 use std::fs::OpenOptions;
 use std::io::prelude::*;
@@ -18,9 +19,12 @@ pub use self::audio_channel::PlaybackRate;
 pub use self::audio_channel::Volume;
 pub use self::audio_channel::Timbre;
 pub use self::dmc::DmcState;
+pub use self::dmc::DmcSampleEvent;
+pub use self::dmc::decode_dpcm;
 pub use self::noise::NoiseChannelState;
 pub use self::pulse::PulseChannelState;
 pub use self::ring_buffer::RingBuffer;
+pub use self::ring_buffer::RingBufferWindow;
 pub use self::triangle::TriangleChannelState;
 
 pub use self::filters::DspFilter;
@@ -30,14 +34,81 @@ pub use self::filters::FilterChain;
 pub enum FilterType {
     Nes,
     FamiCom,
+    // The NES-101 top loader, which only has an RF output
+    NesTopLoader,
+    // A Famicom heard through its RF output rather than an AV mod
+    FamiComRf,
 }
 
+// Roughly -60 dB below full scale, about what a clean RF connection leaves behind
+const RF_NOISE_FLOOR: f32 = 0.001;
+
+// CPU cycle counts at which each frame sequencer step occurs. The 4-step sequence
+// ends on step_4, the 5-step sequence on step_5.
+// https://wiki.nesdev.com/w/index.php/APU_Frame_Counter
+#[derive(Clone, Copy)]
+pub struct FrameSequencerTiming {
+    pub step_1: u16,
+    pub step_2: u16,
+    pub step_3: u16,
+    pub step_4: u16,
+    pub step_5: u16,
+}
+
+impl FrameSequencerTiming {
+    pub fn for_region(region: Region) -> FrameSequencerTiming {
+        return match region {
+            Region::Ntsc => FrameSequencerTiming {
+                step_1: 7457,
+                step_2: 14913,
+                step_3: 22371,
+                step_4: 29829,
+                step_5: 37281,
+            },
+            Region::Pal => FrameSequencerTiming {
+                step_1: 8313,
+                step_2: 16627,
+                step_3: 24939,
+                step_4: 33253,
+                step_5: 41565,
+            },
+        }
+    }
+}
+
+pub fn noise_period_table(region: Region) -> [u16; 16] {
+    return match region {
+        Region::Ntsc => [4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068],
+        Region::Pal =>  [4, 8, 14, 30, 60, 88, 118, 148, 188, 236, 354, 472, 708,  944, 1890, 3778],
+    }
+}
+
+pub fn dmc_period_table(region: Region) -> [u16; 16] {
+    return match region {
+        Region::Ntsc => [428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106,  84,  72,  54],
+        Region::Pal =>  [398, 354, 316, 298, 276, 236, 210, 198, 176, 148, 132, 118,  98,  78,  66,  50],
+    }
+}
+
+// Receives every mixed DAC sample at the CPU clock rate, before any filtering or decimation
+pub type PcmCallback = Box<dyn FnMut(f32) + Send>;
+
 pub struct ApuState {
+    pub region: Region,
+    pub frame_timing: FrameSequencerTiming,
+    pub noise_period_table: [u16; 16],
+    pub dmc_period_table: [u16; 16],
+
     pub current_cycle: u64,
 
     pub frame_sequencer_mode: u8,
     pub frame_sequencer: u16,
     pub frame_reset_delay: u8,
+    // A $4017 write changes the sequence mode only once the delayed reset happens; until
+    // then the old sequence keeps running, IRQ steps included
+    pub pending_frame_sequencer_mode: u8,
+    // Console reset behaves as if this were written to $4017 again
+    pub last_frame_counter_write: u8,
     pub quarter_frame_counter: u32,
     pub half_frame_counter: u32,
 
@@ -58,17 +129,72 @@ pub struct ApuState {
     pub cpu_clock_rate: u64,
     pub generated_samples: u64,
     pub next_sample_at: u64,
+    // Resampling restarts from here whenever the output rate changes
+    pub resample_origin_cycle: u64,
+    pub resample_origin_samples: u64,
+
+    // Emulation speed, in percent. When pitch follows speed, fewer (or more) samples are
+    // produced per emulated second, so output played at sample_rate is pitched and timed
+    // like the emulation. Otherwise samples are produced at the normal rate, and the
+    // frontend is expected to time-stretch them to fit.
+    pub playback_speed: u32,
+    pub pitch_follows_speed: bool,
 
     // Lookup tables for emulating the mixer
     pub pulse_table: Vec<f32>,
     pub tnd_table: Vec<f32>,
 
+    // Optional fixed point mixing: the same tables requantized to MIX_FIXED_ONE, and
+    // expansion audio mixed through Mapper::mix_expansion_audio_fixed. Only the 2A03, MMC5
+    // and VRC6 mix entirely in integers; other expansion chips quantize their floating point
+    // mix. The filter chain still runs in floating point, fed with the exactly representable
+    // result.
+    pub integer_mixing: bool,
+    pub pulse_table_fixed: Vec<i32>,
+    pub tnd_table_fixed: Vec<i32>,
+
     // filter chain (todo: make this a tad more flexible)
     // also todo: make sure these are recreated when changing sample rate
 
     pub filter_type: FilterType,
     pub filter_chain: FilterChain,
     pub filter_hq: bool,
+    // Adds a faint hiss after the console filters, for the RF presets or otherwise
+    pub noise_floor: bool,
+
+    // A front-loading NES doesn't route cartridge audio to the output at all. When this
+    // is false the expansion chips are still clocked, but their output is left out of
+    // the mix.
+    pub expansion_audio: bool,
+
+    // Debug: unfiltered output at the full CPU rate, for offline analysis. Expensive,
+    // so it must be enabled explicitly and drained regularly.
+    pub hq_capture: bool,
+    pub hq_capture_2a03: Vec<f32>,
+    pub hq_capture_chips: Vec<(String, Vec<f32>)>,
+    // The final mix, exactly as fed to the filter chain
+    pub hq_capture_mix: Vec<f32>,
+    // Cheaper alternative to hq_capture for tools which process samples as they go
+    pub pcm_callback: Option<PcmCallback>,
+
+    // Ramps applied when output stops or starts abruptly (pausing, mostly), so the
+    // speaker doesn't jump straight to or from silence
+    pub last_output_sample: i16,
+    pub fade_in_length: u32,
+    pub fade_in_remaining: u32,
+    // Blends from a fixed starting sample into the live output, after resync_output
+    pub crossfade_from: i16,
+    pub crossfade_length: u32,
+    pub crossfade_remaining: u32,
+    // Emulation continues, but decimated samples are dropped (used while fast forwarding)
+    pub discard_output: bool,
+
+    // Latency bookkeeping: every sample handed to the output buffers, every sample the
+    // frontend has taken, and the failure cases on either side
+    pub output_samples_produced: u64,
+    pub output_samples_consumed: u64,
+    pub output_samples_overwritten: u64,
+    pub starved_reads: u64,
 }
 
 fn generate_pulse_table() -> Vec<f32> {
@@ -98,6 +224,21 @@ fn generate_tnd_table() -> Vec<f32> {
     return tnd_table;
 }
 
+// 1.0 in the fixed point mixing path (Q16)
+pub const MIX_FIXED_ONE: i32 = 1 << 16;
+
+pub fn to_fixed_sample(sample: f32) -> i32 {
+    return (sample * MIX_FIXED_ONE as f32).round() as i32;
+}
+
+pub fn from_fixed_sample(sample: i32) -> f32 {
+    return sample as f32 / MIX_FIXED_ONE as f32;
+}
+
+fn requantize_table(table: &[f32]) -> Vec<i32> {
+    return table.iter().map(|&entry| to_fixed_sample(entry)).collect();
+}
+
 fn recommended_buffer_size(sample_rate: u64) -> usize {
     let samples_per_frame = sample_rate / 60;
     let mut buffer_size = 1;
@@ -110,35 +251,61 @@ fn recommended_buffer_size(sample_rate: u64) -> usize {
     return buffer_size as usize;
 }
 
-fn construct_hq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_type: FilterType) -> FilterChain {
-    // https://wiki.nesdev.org/w/index.php?title=APU_Mixer
-
-    // First, no matter what the hardware specifies, we'll do a lightweight downsample to around 8x
-    // the target sample rate. This is to somewhat reduce the CPU cost of the rest of the chain
-    let mut chain = FilterChain::new();
-    let intermediate_samplerate = target_sample_rate * (2.0 + (std::f32::consts::PI / 32.0));
-    let intermediate_cutoff_frequency = target_sample_rate * 0.4;
-    // This IIR isn't especially sharp, but that's okay. We'll do a better filter later
-    // to deal with any aliasing this leaves behind
-    chain.add(Box::new(filters::LowPassIIR::new(clock_rate, intermediate_cutoff_frequency)), clock_rate);
-
+fn add_console_filters(chain: &mut FilterChain, sample_rate: f32, filter_type: FilterType) {
     match filter_type {
         FilterType::Nes => {
             //The NES hardware follows the DACs with a surprisingly involved circuit that adds several low-pass and high-pass filters:
 
             // A first-order high-pass filter at 90 Hz
-            chain.add(Box::new(filters::HighPassIIR::new(intermediate_samplerate, 90.0)), intermediate_samplerate);
+            chain.add(Box::new(filters::HighPassIIR::new(sample_rate, 90.0)), sample_rate);
             //  Another first-order high-pass filter at 440 Hz
-            chain.add(Box::new(filters::HighPassIIR::new(intermediate_samplerate, 440.0)), intermediate_samplerate);
+            chain.add(Box::new(filters::HighPassIIR::new(sample_rate, 440.0)), sample_rate);
             // A first-order low-pass filter at 14 kHz
-            chain.add(Box::new(filters::LowPassIIR::new(intermediate_samplerate, 14000.0)), intermediate_samplerate);
+            chain.add(Box::new(filters::LowPassIIR::new(sample_rate, 14000.0)), sample_rate);
         },
         FilterType::FamiCom => {
             // The Famicom hardware instead ONLY specifies a first-order high-pass filter at 37 Hz, 
             // followed by the unknown (and varying) properties of the RF modulator and demodulator. 
-            chain.add(Box::new(filters::HighPassIIR::new(intermediate_samplerate, 37.0)), intermediate_samplerate);
+            chain.add(Box::new(filters::HighPassIIR::new(sample_rate, 37.0)), sample_rate);
+        },
+        FilterType::NesTopLoader => {
+            // An approximation: the top loader's redesigned audio path lacks the front
+            // loader's 440 Hz stage, so it keeps more bass, but goes out over RF
+            chain.add(Box::new(filters::HighPassIIR::new(sample_rate, 90.0)), sample_rate);
+            add_rf_filters(chain, sample_rate);
+        },
+        FilterType::FamiComRf => {
+            chain.add(Box::new(filters::HighPassIIR::new(sample_rate, 37.0)), sample_rate);
+            add_rf_filters(chain, sample_rate);
         }
     }
+}
+
+// An approximation of RF modulation and demodulation: TV audio is band limited well below
+// what the console produces, and a typical set rolls it off further still. The exact curve
+// varies from TV to TV; this aims for the muffled character most people remember.
+fn add_rf_filters(chain: &mut FilterChain, sample_rate: f32) {
+    chain.add(Box::new(filters::LowPassIIR::new(sample_rate, 10000.0)), sample_rate);
+    chain.add(Box::new(filters::LowPassIIR::new(sample_rate, 12000.0)), sample_rate);
+    chain.add(Box::new(filters::HighPassIIR::new(sample_rate, 60.0)), sample_rate);
+}
+
+fn construct_hq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_type: FilterType, noise_floor: bool) -> FilterChain {
+    // https://wiki.nesdev.org/w/index.php?title=APU_Mixer
+
+    // First, no matter what the hardware specifies, we'll do a lightweight downsample to around 8x
+    // the target sample rate. This is to somewhat reduce the CPU cost of the rest of the chain
+    let mut chain = FilterChain::new();
+    let intermediate_samplerate = target_sample_rate * (2.0 + (std::f32::consts::PI / 32.0));
+    let intermediate_cutoff_frequency = target_sample_rate * 0.4;
+    // This IIR isn't especially sharp, but that's okay. We'll do a better filter later
+    // to deal with any aliasing this leaves behind
+    chain.add(Box::new(filters::LowPassIIR::new(clock_rate, intermediate_cutoff_frequency)), clock_rate);
+
+    add_console_filters(&mut chain, intermediate_samplerate, filter_type);
+    if noise_floor {
+        chain.add(Box::new(filters::NoiseFloor::new(RF_NOISE_FLOOR)), intermediate_samplerate);
+    }
 
     // Finally, perform a high-quality low pass, the result of which will be decimated to become the final output
     // TODO: 160 is huge! That was needed when going from 1.7 MHz -> 44.1 kHz; is it still needed when the source
@@ -150,7 +317,7 @@ fn construct_hq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_ty
     return chain;
 }
 
-fn construct_lq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_type: FilterType) -> FilterChain {
+fn construct_lq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_type: FilterType, noise_floor: bool) -> FilterChain {
     // https://wiki.nesdev.org/w/index.php?title=APU_Mixer
 
     // Quicker and more dirty. Will sound somewhat muffled.
@@ -159,64 +326,91 @@ fn construct_lq_filter_chain(clock_rate: f32, target_sample_rate: f32, filter_ty
 
     chain.add(Box::new(filters::LowPassIIR::new(clock_rate, cutoff_frequency)), clock_rate);
 
-    match filter_type {
-        FilterType::Nes => {
-            //The NES hardware follows the DACs with a surprisingly involved circuit that adds several low-pass and high-pass filters:
-
-            // A first-order high-pass filter at 90 Hz
-            chain.add(Box::new(filters::HighPassIIR::new(target_sample_rate, 90.0)), target_sample_rate);
-            //  Another first-order high-pass filter at 440 Hz
-            chain.add(Box::new(filters::HighPassIIR::new(target_sample_rate, 440.0)), target_sample_rate);
-            // A first-order low-pass filter at 14 kHz
-            chain.add(Box::new(filters::LowPassIIR::new(target_sample_rate, 14000.0)), target_sample_rate);
-        },
-        FilterType::FamiCom => {
-            // The Famicom hardware instead ONLY specifies a first-order high-pass filter at 37 Hz, 
-            // followed by the unknown (and varying) properties of the RF modulator and demodulator. 
-            chain.add(Box::new(filters::HighPassIIR::new(target_sample_rate, 37.0)), target_sample_rate);
-        }
+    add_console_filters(&mut chain, target_sample_rate, filter_type);
+    if noise_floor {
+        chain.add(Box::new(filters::NoiseFloor::new(RF_NOISE_FLOOR)), target_sample_rate);
     }
 
     return chain;
 }
 
 impl ApuState {
-    pub fn new() -> ApuState {
+    pub fn new(region: Region) -> ApuState {
         let default_samplerate = 44100;
         let output_buffer_size = recommended_buffer_size(44100);
+        let pulse_table = generate_pulse_table();
+        let tnd_table = generate_tnd_table();
+        let cpu_clock_rate = region.cpu_clock_rate();
+        let chip_name = region.chip_name();
 
         return ApuState {
+            region: region,
+            frame_timing: FrameSequencerTiming::for_region(region),
+            noise_period_table: noise_period_table(region),
+            dmc_period_table: dmc_period_table(region),
             current_cycle: 0,
             frame_sequencer_mode: 0,
             frame_sequencer: 0,
             frame_reset_delay: 0,
+            pending_frame_sequencer_mode: 0,
+            last_frame_counter_write: 0,
             quarter_frame_counter: 0,
             half_frame_counter: 0,
             frame_interrupt: false,
             disable_interrupt: false,
-            pulse_1: PulseChannelState::new("Pulse 1", "2A03", 1_789_773, true),
-            pulse_2: PulseChannelState::new("Pulse 2", "2A03", 1_789_773, false),
-            triangle: TriangleChannelState::new("Triangle", "2A03", 1_789_773),
-            noise: NoiseChannelState::new("Noise", "2A03"),
-            dmc: DmcState::new("DMC", "2A03"),
+            pulse_1: PulseChannelState::new("Pulse 1", chip_name, cpu_clock_rate, true),
+            pulse_2: PulseChannelState::new("Pulse 2", chip_name, cpu_clock_rate, false),
+            triangle: TriangleChannelState::new("Triangle", chip_name, cpu_clock_rate),
+            noise: NoiseChannelState::new("Noise", chip_name),
+            dmc: DmcState::new("DMC", chip_name),
             staging_buffer: RingBuffer::new(output_buffer_size),
             edge_buffer: RingBuffer::new(output_buffer_size),
             output_buffer: vec!(0i16; output_buffer_size),
             buffer_full: false,
             sample_rate: default_samplerate,
-            cpu_clock_rate: 1_789_773,
+            cpu_clock_rate: cpu_clock_rate,
             generated_samples: 0,
             next_sample_at: 0,
-            pulse_table: generate_pulse_table(),
-            tnd_table: generate_tnd_table(),
+            resample_origin_cycle: 0,
+            resample_origin_samples: 0,
+            playback_speed: 100,
+            pitch_follows_speed: true,
+            pulse_table_fixed: requantize_table(&pulse_table),
+            tnd_table_fixed: requantize_table(&tnd_table),
+            pulse_table: pulse_table,
+            tnd_table: tnd_table,
+            integer_mixing: false,
 
             filter_type: FilterType::FamiCom,
-            filter_chain: construct_hq_filter_chain(1789773.0, 44100.0, FilterType::FamiCom),
+            filter_chain: construct_hq_filter_chain(cpu_clock_rate as f32, default_samplerate as f32, FilterType::FamiCom, false),
             filter_hq: true,
+            noise_floor: false,
+            expansion_audio: true,
+
+            hq_capture: false,
+            hq_capture_2a03: Vec::new(),
+            hq_capture_chips: Vec::new(),
+            hq_capture_mix: Vec::new(),
+            pcm_callback: None,
+
+            last_output_sample: 0,
+            fade_in_length: 0,
+            fade_in_remaining: 0,
+            crossfade_from: 0,
+            crossfade_length: 0,
+            crossfade_remaining: 0,
+            discard_output: false,
+
+            output_samples_produced: 0,
+            output_samples_consumed: 0,
+            output_samples_overwritten: 0,
+            starved_reads: 0,
         }
     }
 
     pub fn set_buffer_size(&mut self, buffer_size: usize) {
+        // Whatever was still queued is dropped along with the old buffers
+        self.output_samples_overwritten += self.samples_queued() as u64;
         self.staging_buffer = RingBuffer::new(buffer_size);
         self.output_buffer = vec!(0i16; buffer_size);
         self.buffer_full = false;
@@ -232,15 +426,57 @@ impl ApuState {
     pub fn set_filter(&mut self, filter_type: FilterType, hq: bool) {
         self.filter_type = filter_type;
         self.filter_hq = hq;
+        self.expansion_audio = match filter_type {
+            FilterType::Nes => false,
+            FilterType::FamiCom => true,
+            FilterType::NesTopLoader => false,
+            FilterType::FamiComRf => true,
+        };
+        self.update_filter();
+    }
+
+    pub fn set_noise_floor(&mut self, enabled: bool) {
+        self.noise_floor = enabled;
         self.update_filter();
     }
 
+    // Overrides the console model's routing; call after set_filter
+    pub fn set_expansion_audio(&mut self, enabled: bool) {
+        self.expansion_audio = enabled;
+    }
+
+    pub fn set_integer_mixing(&mut self, enabled: bool) {
+        self.integer_mixing = enabled;
+    }
+
+    pub fn set_playback_speed(&mut self, percent: u32, pitch_follows_speed: bool) {
+        self.playback_speed = percent.max(1);
+        self.pitch_follows_speed = pitch_follows_speed;
+        self.update_filter();
+    }
+
+    // The rate at which samples are generated relative to emulated time
+    pub fn output_sample_rate(&self) -> u64 {
+        if self.pitch_follows_speed {
+            return (self.sample_rate * 100 / self.playback_speed as u64).max(1);
+        }
+        return self.sample_rate;
+    }
+
+    fn restart_resampling(&mut self) {
+        self.resample_origin_cycle = self.current_cycle;
+        self.resample_origin_samples = self.generated_samples;
+        self.next_sample_at = self.current_cycle + self.cpu_clock_rate / self.output_sample_rate();
+    }
+
     pub fn update_filter(&mut self) {
+        let output_sample_rate = self.output_sample_rate() as f32;
         if self.filter_hq {
-            self.filter_chain = construct_hq_filter_chain(self.cpu_clock_rate as f32, self.sample_rate as f32, self.filter_type);
+            self.filter_chain = construct_hq_filter_chain(self.cpu_clock_rate as f32, output_sample_rate, self.filter_type, self.noise_floor);
         } else {
-            self.filter_chain = construct_lq_filter_chain(self.cpu_clock_rate as f32, self.sample_rate as f32, self.filter_type);
+            self.filter_chain = construct_lq_filter_chain(self.cpu_clock_rate as f32, output_sample_rate, self.filter_type, self.noise_floor);
         }
+        self.restart_resampling();
     }
 
     pub fn channels(&self) -> Vec<& dyn AudioChannelState> {
@@ -417,13 +653,11 @@ impl ApuState {
                 self.noise.envelope.volume_register = data & 0b0000_1111;
             },
             0x400E => {
-                let noise_period = [
-                    4, 8, 16, 32, 64, 96, 128, 160, 202, 254, 380, 508, 762, 1016, 2034, 4068];
-
                 let mode =        (data & 0b1000_0000) >> 7;
                 let period_index = data & 0b0000_1111;
                 self.noise.mode = mode;
-                self.noise.period_initial = noise_period[period_index as usize];
+                self.noise.period_index = period_index;
+                self.noise.period_initial = self.noise_period_table[period_index as usize];
             },
             0x400F => {
                 let length_index = (data & 0b1111_1000) >> 3;
@@ -435,8 +669,6 @@ impl ApuState {
 
             // DMC Channel
             0x4010 => {
-                let period_table = [
-                    428, 380, 340, 320, 286, 254, 226, 214, 190, 160, 142, 128, 106,  84,  72,  54];
                 self.dmc.looping = (data & 0b0100_0000) != 0;
                 self.dmc.interrupt_enabled = (data & 0b1000_0000) != 0;
                 if !self.dmc.interrupt_enabled {
@@ -444,7 +676,8 @@ impl ApuState {
                     self.dmc.interrupt_flag = false;
                 }
                 let period_index = data & 0b0000_1111;
-                self.dmc.period_initial = period_table[period_index as usize] / 2;
+                self.dmc.period_index = period_index;
+                self.dmc.period_initial = self.dmc_period_table[period_index as usize] / 2;
             },
             0x4011 => {
                 self.dmc.output_level = data & 0b0111_1111;
@@ -483,14 +716,19 @@ impl ApuState {
                 if dmc_enable && self.dmc.bytes_remaining == 0 {
                     self.dmc.current_address = self.dmc.starting_address;
                     self.dmc.bytes_remaining = self.dmc.sample_length;
+                    self.dmc.sample_started = true;
                 }
                 self.dmc.interrupt_flag = false;
             }
 
             // Frame Counter / Interrupts
             0x4017 => {
-                self.frame_sequencer_mode = (data & 0b1000_0000) >> 7;
+                self.last_frame_counter_write = data;
+                self.pending_frame_sequencer_mode = (data & 0b1000_0000) >> 7;
+                // Unlike the mode, the interrupt inhibit flag takes effect right away
                 self.disable_interrupt =    (data & 0b0100_0000) != 0;
+                // The reset lands on the next APU cycle boundary: 3 CPU cycles away if the
+                // write happened during an APU cycle, 4 if it happened between them
                 if (self.current_cycle & 0b1) != 0 {
                     self.frame_reset_delay = 3;
                 } else {
@@ -514,6 +752,8 @@ impl ApuState {
             self.frame_reset_delay -= 1;
             if self.frame_reset_delay == 0 {
                 self.frame_sequencer = 0;
+                self.frame_sequencer_mode = self.pending_frame_sequencer_mode;
+                // Entering 5-step mode clocks everything immediately; 4-step mode doesn't
                 if self.frame_sequencer_mode == 1 {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
@@ -521,28 +761,29 @@ impl ApuState {
             }
         }
 
+        let timing = self.frame_timing;
         if self.frame_sequencer_mode == 0 {
             // 4-step sequence
             match self.frame_sequencer {
-                7457 => self.clock_quarter_frame(),
-                14913 => {
+                step if step == timing.step_1 => self.clock_quarter_frame(),
+                step if step == timing.step_2 => {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 },
-                22371 => self.clock_quarter_frame(),
-                29828 => {
+                step if step == timing.step_3 => self.clock_quarter_frame(),
+                step if step == timing.step_4 - 1 => {
                     if !self.disable_interrupt {
                         self.frame_interrupt = true;
                     }
                 },
-                29829 => {
+                step if step == timing.step_4 => {
                     if !self.disable_interrupt {
                         self.frame_interrupt = true;
                     }
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 },
-                29830 => {
+                step if step == timing.step_4 + 1 => {
                     if !self.disable_interrupt {
                         self.frame_interrupt = true;
                     }
@@ -553,17 +794,17 @@ impl ApuState {
         } else {
             match self.frame_sequencer {
                 // "5-step" sequence (uneven timing)
-                7457 => self.clock_quarter_frame(),
-                14913 => {
+                step if step == timing.step_1 => self.clock_quarter_frame(),
+                step if step == timing.step_2 => {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 },
-                22371 => self.clock_quarter_frame(),
-                37281 => {
+                step if step == timing.step_3 => self.clock_quarter_frame(),
+                step if step == timing.step_5 => {
                     self.clock_quarter_frame();
                     self.clock_half_frame();
                 },
-                37282 => {
+                step if step == timing.step_5 + 1 => {
                   self.frame_sequencer = 0;  
                 },
                 _ => ()
@@ -592,21 +833,9 @@ impl ApuState {
         self.half_frame_counter += 1;
     }
 
-    pub fn clock_apu(&mut self, mapper: &mut dyn Mapper) {
-        self.clock_frame_sequencer();
-
-        // Clock the triangle channel once per CPU cycle
-        self.triangle.clock();
-        self.noise.clock();
-
-        // Only clock Pulse channels on every other cycle
-        // (Most documentation calls this once per APU cycle)
-        if (self.current_cycle & 0b1) == 0 {
-            self.pulse_1.clock();
-            self.pulse_2.clock();
-            self.dmc.clock(mapper);
-        }
-        
+    // The mixer's output for the channels as they stand: the 2A03 alone, and the full
+    // DAC output including expansion audio
+    fn mix_current_sample(&self, mapper: &dyn Mapper) -> (f32, f32) {
         // Collect current samples from the various channels
         let pulse_1_sample = self.pulse_1.output();
         let pulse_2_sample = self.pulse_2.output();
@@ -623,25 +852,77 @@ impl ApuState {
         if !(self.pulse_2.debug_disable) {
             combined_pulse += pulse_2_sample;
         }
-        let pulse_output = self.pulse_table[combined_pulse as usize];
-        
         let tri_output = if self.triangle.debug_disable {0} else {triangle_sample};
         let noise_output = if self.noise.debug_disable {0} else {noise_sample};
         let dmc_output = if self.dmc.debug_disable {0} else {dmc_sample};
-        let tnd_output = self.tnd_table[full_tnd_index(tri_output as usize, noise_output as usize, dmc_output as usize)];
+        let tnd_index = full_tnd_index(tri_output as usize, noise_output as usize, dmc_output as usize);
+
+        if self.integer_mixing {
+            let pulse_output = self.pulse_table_fixed[combined_pulse as usize];
+            let tnd_output = self.tnd_table_fixed[tnd_index];
+            let current_2a03_fixed = (pulse_output - MIX_FIXED_ONE / 2) + (tnd_output - MIX_FIXED_ONE / 2);
+            let current_dac_fixed = if self.expansion_audio {
+                mapper.mix_expansion_audio_fixed(current_2a03_fixed)
+            } else {
+                current_2a03_fixed
+            };
+            return (from_fixed_sample(current_2a03_fixed), from_fixed_sample(current_dac_fixed));
+        } else {
+            let pulse_output = self.pulse_table[combined_pulse as usize];
+            let tnd_output = self.tnd_table[tnd_index];
+            let current_2a03_sample = (pulse_output - 0.5) + (tnd_output - 0.5);
+            let current_dac_sample = if self.expansion_audio {
+                mapper.mix_expansion_audio(current_2a03_sample)
+            } else {
+                current_2a03_sample
+            };
+            return (current_2a03_sample, current_dac_sample);
+        }
+    }
+
+    pub fn clock_apu(&mut self, mapper: &mut dyn Mapper) {
+        self.clock_frame_sequencer();
 
-        let current_2a03_sample = (pulse_output - 0.5) + (tnd_output - 0.5);
-        let current_dac_sample = mapper.mix_expansion_audio(current_2a03_sample) as f32;
+        // Clock the triangle channel once per CPU cycle
+        self.triangle.clock();
+        self.noise.clock();
+
+        // Only clock Pulse channels on every other cycle
+        // (Most documentation calls this once per APU cycle)
+        if (self.current_cycle & 0b1) == 0 {
+            self.pulse_1.clock();
+            self.pulse_2.clock();
+            self.dmc.clock(mapper);
+        }
+        
+        let (current_2a03_sample, current_dac_sample) = self.mix_current_sample(&*mapper);
+
+        if self.hq_capture {
+            self.capture_hq_sample(mapper, current_2a03_sample, current_dac_sample);
+        }
+
+        if let Some(ref mut callback) = self.pcm_callback {
+            callback(current_dac_sample);
+        }
 
         // apply filters NEW
         self.filter_chain.consume(current_dac_sample, 1.0 / (self.cpu_clock_rate as f32));
 
         if self.current_cycle >= self.next_sample_at { 
             // decimate sample
-            let composite_sample = (self.filter_chain.output() * 32767.0) as i16;
-
-            self.staging_buffer.push(composite_sample);
-            self.edge_buffer.push(true as i16);
+            let mut composite_sample = (self.filter_chain.output() * 32767.0) as i16;
+            if self.crossfade_remaining > 0 {
+                let elapsed = (self.crossfade_length - self.crossfade_remaining) as i32;
+                let remaining = self.crossfade_remaining as i32;
+                composite_sample = ((self.crossfade_from as i32 * remaining + composite_sample as i32 * elapsed) / self.crossfade_length as i32) as i16;
+                self.crossfade_remaining -= 1;
+            }
+            if self.fade_in_remaining > 0 {
+                let elapsed = (self.fade_in_length - self.fade_in_remaining) as i32;
+                composite_sample = ((composite_sample as i32 * elapsed) / self.fade_in_length as i32) as i16;
+                self.fade_in_remaining -= 1;
+            }
+            self.push_output_sample(composite_sample);
 
             // Write debug buffers from these, regardless of enable / disable status
             self.pulse_1.record_current_output();
@@ -652,15 +933,112 @@ impl ApuState {
             mapper.record_expansion_audio_output(current_2a03_sample);
 
             self.generated_samples += 1;
-            self.next_sample_at = ((self.generated_samples + 1) * self.cpu_clock_rate) / self.sample_rate;
+            let samples_since_origin = self.generated_samples - self.resample_origin_samples;
+            self.next_sample_at = self.resample_origin_cycle + ((samples_since_origin + 1) * self.cpu_clock_rate) / self.output_sample_rate();
+        }
+
+        self.current_cycle += 1;
+    }
 
-            if self.staging_buffer.index() == 0 {
-                self.output_buffer.copy_from_slice(self.staging_buffer.buffer());
-                self.buffer_full = true;
+    fn push_output_sample(&mut self, sample: i16) {
+        if self.discard_output {
+            return;
+        }
+        self.staging_buffer.push(sample);
+        self.edge_buffer.push(true as i16);
+        self.last_output_sample = sample;
+        self.output_samples_produced += 1;
+
+        if self.staging_buffer.index() == 0 {
+            if self.buffer_full {
+                // The frontend never picked up the previous buffer
+                self.output_samples_overwritten += self.output_buffer.len() as u64;
             }
+            self.output_buffer.copy_from_slice(self.staging_buffer.buffer());
+            self.buffer_full = true;
         }
+    }
 
-        self.current_cycle += 1;
+    // Appends a ramp from the most recent output sample down to silence. Emulation isn't
+    // clocked for these; they exist only so the output ends smoothly.
+    pub fn fade_out(&mut self, sample_count: usize) {
+        let start = self.last_output_sample as i32;
+        for i in 1 ..= sample_count {
+            let sample = (start * (sample_count - i) as i32) / sample_count as i32;
+            self.push_output_sample(sample as i16);
+        }
+        self.fade_in_length = 0;
+        self.fade_in_remaining = 0;
+        self.crossfade_remaining = 0;
+    }
+
+    // Scales the next sample_count samples up from silence to full volume
+    pub fn fade_in(&mut self, sample_count: usize) {
+        self.fade_in_length = sample_count as u32;
+        self.fade_in_remaining = sample_count as u32;
+    }
+
+    // For when the channels jump to a new state the output never followed them into,
+    // like a savestate load. The filter chain is settled at the new DAC level, as if
+    // it had been held there forever, so the high pass doesn't see a step, and the next
+    // sample_count samples blend from the last one output into the new audio.
+    //
+    // Samples queued before the call are left alone: they're the tail of the old state,
+    // and the crossfade picks up where they end. Frontends should let them play out
+    // rather than flushing, since flushing is what leaves a gap (and a click) behind.
+    pub fn resync_output(&mut self, mapper: &dyn Mapper, sample_count: usize) {
+        let (_, current_dac_sample) = self.mix_current_sample(mapper);
+        self.filter_chain.settle(current_dac_sample);
+        self.crossfade_from = self.last_output_sample;
+        self.crossfade_length = sample_count as u32;
+        self.crossfade_remaining = sample_count as u32;
+    }
+
+    pub fn set_hq_capture(&mut self, enabled: bool) {
+        self.hq_capture = enabled;
+        self.hq_capture_2a03.clear();
+        self.hq_capture_chips.clear();
+        self.hq_capture_mix.clear();
+    }
+
+    pub fn set_pcm_callback(&mut self, callback: PcmCallback) {
+        self.pcm_callback = Some(callback);
+    }
+
+    pub fn clear_pcm_callback(&mut self) {
+        self.pcm_callback = None;
+    }
+
+    fn capture_hq_sample(&mut self, mapper: &dyn Mapper, current_2a03_sample: f32, current_dac_sample: f32) {
+        self.hq_capture_mix.push(current_dac_sample);
+        self.hq_capture_2a03.push(current_2a03_sample);
+        let samples_so_far = self.hq_capture_2a03.len() - 1;
+        for (chip_name, chip_sample) in mapper.expansion_chip_outputs(current_2a03_sample) {
+            let existing = self.hq_capture_chips.iter().position(|(name, _)| *name == chip_name);
+            let index = match existing {
+                Some(index) => index,
+                None => {
+                    // Chips that appear late are padded, so every buffer lines up with the 2A03
+                    self.hq_capture_chips.push((chip_name, vec!(0.0; samples_so_far)));
+                    self.hq_capture_chips.len() - 1
+                }
+            };
+            self.hq_capture_chips[index].1.push(chip_sample);
+        }
+    }
+
+    // Returns everything captured since the last call: the 2A03 mix, followed by each
+    // expansion chip's own output, all at cpu_clock_rate.
+    pub fn take_hq_capture(&mut self) -> (Vec<f32>, Vec<(String, Vec<f32>)>) {
+        let apu_samples = self.hq_capture_2a03.drain(..).collect();
+        let chip_samples = self.hq_capture_chips.drain(..).collect();
+        return (apu_samples, chip_samples);
+    }
+
+    // Returns the mixed output captured since the last call, at cpu_clock_rate. This is
+    // drained separately from take_hq_capture; callers wanting both should take both.
+    pub fn take_hq_mix(&mut self) -> Vec<f32> {
+        return self.hq_capture_mix.drain(..).collect();
     }
 
     pub fn samples_queued(&self) -> usize {
@@ -700,9 +1078,42 @@ impl ApuState {
         let staging_index = self.staging_buffer.index();
         output_buffer.extend(&self.staging_buffer.buffer()[0 .. staging_index]);
         self.staging_buffer.reset();
+        if output_buffer.is_empty() {
+            self.starved_reads += 1;
+        }
+        self.output_samples_consumed += output_buffer.len() as u64;
         return output_buffer;
     }
 
+    // Samples generated by emulation but not yet consumed by the frontend. Samples lost
+    // to an overwritten buffer are excluded; they'll never be consumed.
+    pub fn pending_output_samples(&self) -> u64 {
+        return self.output_samples_produced - self.output_samples_consumed - self.output_samples_overwritten;
+    }
+
+    // Latency between emulation and the speaker, in seconds. The core can only see its own
+    // buffers, so the frontend passes in however many samples its audio device still has
+    // queued (0 if unknown). The host plays everything at sample_rate, whatever rate
+    // emulated time generated it at.
+    pub fn output_latency(&self, host_queued_samples: u64) -> f32 {
+        return (self.pending_output_samples() + host_queued_samples) as f32 / self.sample_rate as f32;
+    }
+
+    pub fn reset_latency_stats(&mut self) {
+        self.output_samples_produced = self.output_samples_consumed + self.samples_queued() as u64;
+        self.output_samples_overwritten = 0;
+        self.starved_reads = 0;
+    }
+
+    // Console reset: $4015 is cleared (by the caller, through the bus), the frame IRQ flag
+    // is cleared, and the frame counter restarts as though $4017 were written again with
+    // its last value, jitter and all.
+    pub fn reset(&mut self) {
+        self.frame_interrupt = false;
+        let last_write = self.last_frame_counter_write;
+        self.write_register(0x4017, last_write);
+    }
+
     pub fn irq_signal(&self) -> bool {
         return self.frame_interrupt || self.dmc.interrupt_flag;
     }
@@ -765,5 +1176,134 @@ impl AudioChannelState for ApuState {
 
     fn unmute(&mut self) {        
     }
+
+    fn has_edges(&self) -> bool {
+        return false;
+    }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ines::INesCartridge;
+    use mmc::none::NoneMapper;
+    use mmc::vrc6::Vrc6;
+
+    // As NesState::power_on leaves it; the noise period in particular must be loaded
+    fn powered_on_apu() -> ApuState {
+        let mut apu = ApuState::new(Region::Ntsc);
+        for address in 0x4000 ..= 0x400F {
+            apu.write_register(address, 0);
+        }
+        return apu;
+    }
+
+    fn clock(apu: &mut ApuState, cycles: u32) {
+        let mut mapper = NoneMapper::new();
+        for _ in 0 .. cycles {
+            apu.clock_apu(&mut mapper);
+        }
+    }
+
+    #[test]
+    fn frame_counter_mode_switch_waits_for_the_delayed_reset() {
+        let mut apu = powered_on_apu();
+        // Written between APU cycles, so the reset is 4 CPU cycles away
+        assert_eq!(apu.current_cycle & 0b1, 0);
+        apu.write_register(0x4017, 0x80);
+        clock(&mut apu, 3);
+        assert_eq!(apu.frame_sequencer_mode, 0);
+        assert_eq!(apu.quarter_frame_counter, 0);
+        assert_eq!(apu.half_frame_counter, 0);
+        clock(&mut apu, 1);
+        assert_eq!(apu.frame_sequencer_mode, 1);
+        // Entering 5-step mode clocks the quarter and half frame units right away
+        assert_eq!(apu.quarter_frame_counter, 1);
+        assert_eq!(apu.half_frame_counter, 1);
+    }
+
+    #[test]
+    fn frame_counter_write_during_apu_cycle_resets_sooner() {
+        let mut apu = powered_on_apu();
+        clock(&mut apu, 1);
+        assert_eq!(apu.current_cycle & 0b1, 1);
+        apu.write_register(0x4017, 0x80);
+        clock(&mut apu, 2);
+        assert_eq!(apu.frame_sequencer_mode, 0);
+        clock(&mut apu, 1);
+        assert_eq!(apu.frame_sequencer_mode, 1);
+        assert_eq!(apu.quarter_frame_counter, 1);
+    }
+
+    #[test]
+    fn four_step_write_does_not_clock_immediately() {
+        let mut apu = powered_on_apu();
+        apu.write_register(0x4017, 0x00);
+        clock(&mut apu, 4);
+        assert_eq!(apu.frame_sequencer_mode, 0);
+        assert_eq!(apu.quarter_frame_counter, 0);
+        assert_eq!(apu.half_frame_counter, 0);
+    }
+
+    // Pulse 1 at full volume with its duty output high, the triangle parked at an ultrasonic
+    // period (which mixes as 7), the DMC loaded directly to 64, and noise silent
+    fn mixer_test_apu() -> ApuState {
+        let mut apu = powered_on_apu();
+        apu.write_register(0x4015, 0b0000_0001);
+        apu.write_register(0x4000, 0b1111_1111);
+        apu.write_register(0x4002, 0x00);
+        apu.write_register(0x4003, 0x01);
+        apu.write_register(0x4011, 64);
+        apu.set_integer_mixing(true);
+        return apu;
+    }
+
+    #[test]
+    fn integer_mixing_2a03() {
+        let apu = mixer_test_apu();
+        assert_eq!((apu.pulse_1.output(), apu.triangle.output(), apu.noise.output(), apu.dmc.output()), (15, 7, 0, 64));
+        assert_eq!(apu.pulse_table_fixed[15], 9753);
+        assert_eq!(apu.tnd_table_fixed[full_tnd_index(7, 0, 64)], 28159);
+        let (nes_sample, dac_sample) = apu.mix_current_sample(&NoneMapper::new());
+        // 9753 + 28159 - MIX_FIXED_ONE
+        assert_eq!(to_fixed_sample(nes_sample), -27624);
+        assert_eq!(to_fixed_sample(dac_sample), -27624);
+    }
+
+    #[test]
+    fn integer_mixing_vrc6() {
+        let mut apu = mixer_test_apu();
+        apu.set_expansion_audio(true);
+        let mut mapper = Vrc6::from_ines(INesCartridge::test_image(24, 2, 1)).unwrap();
+        // Pulse 1 in direct volume mode at 15, so its output is high regardless of duty
+        mapper.write_cpu(0x9000, 0b1000_1111);
+        mapper.write_cpu(0x9002, 0b1000_0000);
+        assert_eq!(mapper.pulse1.output(), 15);
+        let (nes_sample, dac_sample) = apu.mix_current_sample(&mapper);
+        assert_eq!(to_fixed_sample(nes_sample), -27624);
+        // 15 steps of 653 on top of the 2A03
+        assert_eq!(to_fixed_sample(dac_sample), -27624 + 15 * 653);
+    }
+
+    #[test]
+    fn resizing_the_buffer_drops_pending_samples() {
+        let mut apu = powered_on_apu();
+        clock(&mut apu, 10000);
+        assert!(apu.pending_output_samples() > 0);
+        assert_eq!(apu.pending_output_samples(), apu.samples_queued() as u64);
+        apu.set_buffer_size(512);
+        assert_eq!(apu.pending_output_samples(), 0);
+        clock(&mut apu, 10000);
+        assert_eq!(apu.pending_output_samples(), apu.samples_queued() as u64);
+    }
+
+    #[test]
+    fn latency_is_measured_at_the_host_rate() {
+        let mut apu = powered_on_apu();
+        apu.set_playback_speed(200, true);
+        assert_eq!(apu.output_sample_rate(), apu.sample_rate / 2);
+        let host_queued = apu.sample_rate / 10;
+        assert_eq!(apu.output_latency(host_queued), 0.1);
+    }
+}
@@ -23,6 +23,7 @@ pub struct NoiseChannelState {
     pub length_counter: LengthCounterState,
 
     pub mode: u8,
+    pub period_index: u8,
     pub period_initial: u16,
     pub period_current: u16,
 
@@ -46,6 +47,7 @@ impl NoiseChannelState {
             envelope: VolumeEnvelopeState::new(),
             length_counter: LengthCounterState::new(),
             mode: 0,
+            period_index: 0,
             period_initial: 0,
             period_current: 0,
 
@@ -134,25 +136,8 @@ impl AudioChannelState for NoiseChannelState {
     }
 
     fn rate(&self) -> PlaybackRate {
-        let lsfr_index = match self.period_initial {
-            4    => {0xF},
-            8    => {0xE},
-            16   => {0xD},
-            32   => {0xC},
-            64   => {0xB},
-            96   => {0xA},
-            128  => {0x9},
-            160  => {0x8},
-            202  => {0x7},
-            254  => {0x6},
-            380  => {0x5},
-            508  => {0x4},
-            762  => {0x3},
-            1016 => {0x2},
-            2034 => {0x1},
-            4068 => {0x0},
-            _ => {0x0} // also unreachable
-        };
+        // Periods are region dependent, so report the register index instead
+        let lsfr_index = 0xF - (self.period_index as usize);
         return PlaybackRate::LfsrRate {index: lsfr_index, max: 0xF};
     }
 
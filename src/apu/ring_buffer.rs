@@ -4,22 +4,49 @@
 
 // Not intended to be generic, or particularly safe beyond rust's usual guarantees.
 
+// Every sample is also given a timestamp: the total number of samples pushed before it. Buffers
+// which are pushed in lockstep (a channel's samples and its edges, for instance) share
+// timestamps, so visualizers can correlate them without copying either buffer.
+
 pub struct RingBuffer {
     buffer: Vec<i16>,
-    index: usize
+    index: usize,
+    timestamp: u64,
+}
+
+pub struct RingBufferWindow<'a> {
+    buffer: &'a [i16],
+    index: usize,
+    remaining: usize,
+}
+
+impl<'a> Iterator for RingBufferWindow<'a> {
+    type Item = i16;
+
+    fn next(&mut self) -> Option<i16> {
+        if self.remaining == 0 {
+            return None;
+        }
+        let sample = self.buffer[self.index];
+        self.index = (self.index + 1) % self.buffer.len();
+        self.remaining -= 1;
+        return Some(sample);
+    }
 }
 
 impl RingBuffer {
     pub fn new(length: usize) -> RingBuffer {
         return RingBuffer {
             buffer: vec!(0i16; length),
-            index: 0
+            index: 0,
+            timestamp: 0,
         };
     }
 
     pub fn push(&mut self, sample: i16) {
         self.buffer[self.index] = sample;
         self.index = (self.index + 1) % self.buffer.len();
+        self.timestamp += 1;
     }
 
     pub fn buffer(&self) -> &Vec<i16> {
@@ -30,7 +57,91 @@ impl RingBuffer {
         return self.index;
     }
 
+    pub fn len(&self) -> usize {
+        return self.buffer.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.buffer.is_empty();
+    }
+
+    // The timestamp that the next pushed sample will receive
+    pub fn timestamp(&self) -> u64 {
+        return self.timestamp;
+    }
+
+    // The oldest timestamp still held in the buffer
+    pub fn oldest_timestamp(&self) -> u64 {
+        return self.timestamp.saturating_sub(self.buffer.len() as u64);
+    }
+
+    fn position_of(&self, timestamp: u64) -> usize {
+        let age = (self.timestamp - timestamp) as usize;
+        return (self.index + self.buffer.len() - age) % self.buffer.len();
+    }
+
+    pub fn sample_at(&self, timestamp: u64) -> Option<i16> {
+        if timestamp < self.oldest_timestamp() || timestamp >= self.timestamp {
+            return None;
+        }
+        return Some(self.buffer[self.position_of(timestamp)]);
+    }
+
+    // Iterates, oldest first, over the `length` samples ending just before `end_timestamp`.
+    // Returns None if any part of that window has already been overwritten or hasn't
+    // happened yet.
+    pub fn window(&self, length: usize, end_timestamp: u64) -> Option<RingBufferWindow<'_>> {
+        if end_timestamp > self.timestamp || length > self.buffer.len() {
+            return None;
+        }
+        let start_timestamp = end_timestamp.checked_sub(length as u64)?;
+        if start_timestamp < self.oldest_timestamp() {
+            return None;
+        }
+        return Some(RingBufferWindow {
+            buffer: &self.buffer,
+            index: self.position_of(start_timestamp),
+            remaining: length,
+        });
+    }
+
+    // The most recent `length` samples, oldest first
+    pub fn last(&self, length: usize) -> Option<RingBufferWindow<'_>> {
+        return self.window(length, self.timestamp);
+    }
+
+    // Searches backwards from `end_timestamp` for the most recent nonzero sample. Used on
+    // edge buffers, where a nonzero sample marks the start of a waveform period.
+    pub fn find_last_edge(&self, search_length: usize, end_timestamp: u64) -> Option<u64> {
+        let search_start = end_timestamp.saturating_sub(search_length as u64).max(self.oldest_timestamp());
+        let mut timestamp = end_timestamp.min(self.timestamp);
+        while timestamp > search_start {
+            timestamp -= 1;
+            if self.buffer[self.position_of(timestamp)] != 0 {
+                return Some(timestamp);
+            }
+        }
+        return None;
+    }
+
+    // Searches backwards from `end_timestamp` for the most recent rising crossing of `midpoint`,
+    // returning the timestamp of the first sample at or above it.
+    pub fn find_rising_crossing(&self, midpoint: i16, search_length: usize, end_timestamp: u64) -> Option<u64> {
+        let search_start = end_timestamp.saturating_sub(search_length as u64).max(self.oldest_timestamp());
+        let mut timestamp = end_timestamp.min(self.timestamp);
+        while timestamp > search_start + 1 {
+            timestamp -= 1;
+            let current = self.buffer[self.position_of(timestamp)];
+            let previous = self.buffer[self.position_of(timestamp - 1)];
+            if previous < midpoint && current >= midpoint {
+                return Some(timestamp);
+            }
+        }
+        return None;
+    }
+
     pub fn reset(&mut self) {
         self.index = 0;
+        self.timestamp = 0;
     }
-}
\ No newline at end of file
+}
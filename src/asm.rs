@@ -215,6 +215,14 @@ pub fn opcode_bytes(opcode: Opcode) -> Result<Vec<u8>, String> {
         Opcode::Sta(AddressingMode::IndexedIndirectX(byte)) => {Ok(vec![0x81, byte])},
         Opcode::Sta(AddressingMode::IndirectIndexedY(byte)) => {Ok(vec![0x91, byte])},
 
+        Opcode::Stx(AddressingMode::ZeroPage(byte)) =>         {Ok(vec![0x86, byte])},
+        Opcode::Stx(AddressingMode::ZeroPageY(byte)) =>        {Ok(vec![0x96, byte])},
+        Opcode::Stx(AddressingMode::Absolute(address)) =>      {Ok(vec![0x8E, low(address), high(address)])},
+
+        Opcode::Sty(AddressingMode::ZeroPage(byte)) =>         {Ok(vec![0x84, byte])},
+        Opcode::Sty(AddressingMode::ZeroPageX(byte)) =>        {Ok(vec![0x94, byte])},
+        Opcode::Sty(AddressingMode::Absolute(address)) =>      {Ok(vec![0x8C, low(address), high(address)])},
+
         opcode => {Err(format!("Unimplemented! {:<3?}", opcode))}
     }
 }
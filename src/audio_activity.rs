@@ -0,0 +1,144 @@
+// Per-frame summaries of which audio channels are sounding, for "chip activity" lights
+// in a frontend and for silence detection which doesn't depend on the mixed output. Every
+// output sample, each channel's playing() state is polled; at the end of each frame the
+// counts are published and start over.
+
+use apu::AudioChannelState;
+
+#[derive(Clone, Debug)]
+pub struct ChannelActivity {
+    pub name: String,
+    pub chip: String,
+    // Output samples during the last frame for which the channel reported playing
+    pub active_samples: u32,
+    // Consecutive frames, up to and including the last one, with (or without) any activity.
+    // Exactly one of these is nonzero.
+    pub active_frames: u32,
+    pub idle_frames: u32,
+}
+
+impl ChannelActivity {
+    pub fn active(&self) -> bool {
+        return self.active_samples > 0;
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct ChipActivity {
+    pub chip: String,
+    pub active_channels: usize,
+    pub total_channels: usize,
+    // The fraction of the last frame for which the chip's busiest channel was playing
+    pub duty: f32,
+}
+
+pub struct AudioActivity {
+    pub enabled: bool,
+    pub current_frame: Vec<u32>,
+    pub current_frame_samples: u32,
+    pub last_frame: Vec<ChannelActivity>,
+    pub last_frame_samples: u32,
+    last_sample_count: u64,
+}
+
+impl AudioActivity {
+    pub fn new() -> AudioActivity {
+        return AudioActivity {
+            enabled: false,
+            current_frame: Vec::new(),
+            current_frame_samples: 0,
+            last_frame: Vec::new(),
+            last_frame_samples: 0,
+            last_sample_count: 0,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.current_frame.clear();
+        self.current_frame_samples = 0;
+        self.last_frame.clear();
+        self.last_frame_samples = 0;
+    }
+
+    // Channels are polled once per output sample; generated_samples is the APU's running
+    // count of samples produced
+    pub fn due(&self, generated_samples: u64) -> bool {
+        return self.enabled && generated_samples != self.last_sample_count;
+    }
+
+    pub fn sample(&mut self, generated_samples: u64, channels: &[&dyn AudioChannelState]) {
+        self.last_sample_count = generated_samples;
+        if self.current_frame.len() != channels.len() {
+            self.current_frame = vec!(0; channels.len());
+        }
+        for (count, channel) in self.current_frame.iter_mut().zip(channels.iter()) {
+            if channel.playing() {
+                *count += 1;
+            }
+        }
+        self.current_frame_samples += 1;
+    }
+
+    pub fn end_frame(&mut self, channels: &[&dyn AudioChannelState]) {
+        if self.last_frame.len() != channels.len() {
+            self.last_frame = channels.iter().map(|channel| ChannelActivity {
+                name: channel.name(),
+                chip: channel.chip(),
+                active_samples: 0,
+                active_frames: 0,
+                idle_frames: 0,
+            }).collect();
+        }
+        for (index, activity) in self.last_frame.iter_mut().enumerate() {
+            activity.active_samples = self.current_frame.get(index).cloned().unwrap_or(0);
+            if activity.active() {
+                activity.active_frames += 1;
+                activity.idle_frames = 0;
+            } else {
+                activity.active_frames = 0;
+                activity.idle_frames += 1;
+            }
+        }
+        self.last_frame_samples = self.current_frame_samples;
+        for count in &mut self.current_frame {
+            *count = 0;
+        }
+        self.current_frame_samples = 0;
+    }
+
+    // One entry per chip, in the order channels were reported
+    pub fn chips(&self) -> Vec<ChipActivity> {
+        let mut chips: Vec<ChipActivity> = Vec::new();
+        for channel in &self.last_frame {
+            let duty = if self.last_frame_samples > 0 {channel.active_samples as f32 / self.last_frame_samples as f32} else {0.0};
+            if !chips.iter().any(|chip| chip.chip == channel.chip) {
+                chips.push(ChipActivity {
+                    chip: channel.chip.clone(),
+                    active_channels: 0,
+                    total_channels: 0,
+                    duty: 0.0,
+                });
+            }
+            let chip = chips.iter_mut().find(|chip| chip.chip == channel.chip).unwrap();
+            chip.total_channels += 1;
+            if channel.active() {
+                chip.active_channels += 1;
+            }
+            chip.duty = chip.duty.max(duty);
+        }
+        return chips;
+    }
+
+    pub fn chip_active(&self, chip: &str) -> bool {
+        return self.last_frame.iter().any(|channel| channel.chip == chip && channel.active());
+    }
+
+    pub fn any_active(&self) -> bool {
+        return self.last_frame.iter().any(|channel| channel.active());
+    }
+
+    // How many frames every channel has been idle; 0 if anything played last frame
+    pub fn idle_frames(&self) -> u32 {
+        return self.last_frame.iter().map(|channel| channel.idle_frames).min().unwrap_or(0);
+    }
+}
@@ -0,0 +1,92 @@
+// Reader for the output convention used by blargg's test ROMs, and many others since:
+// $6001-$6003 hold the signature DE B0 61 once the test has started, $6000 holds the
+// status, and $6004 onward holds a zero terminated text message.
+// https://github.com/christopherpow/nes-test-roms/blob/master/README.md
+
+use memory;
+use nes::NesState;
+
+pub const BLARGG_SIGNATURE: [u8; 3] = [0xDE, 0xB0, 0x61];
+// Longest message we'll try to read before giving up on finding the terminator
+pub const MAX_MESSAGE_LENGTH: usize = 0x1000 - 4;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BlarggStatus {
+    // The signature hasn't been written; either the test hasn't started yet, or this
+    // ROM doesn't follow the convention
+    NotPresent,
+    Running,
+    // The test wants the console reset, no sooner than 100 ms from now
+    ResetRequested,
+    // 0 means the test passed; anything else is a test specific failure code
+    Finished(u8),
+}
+
+pub fn signature_present(nes: &NesState) -> bool {
+    for (i, &expected) in BLARGG_SIGNATURE.iter().enumerate() {
+        if memory::debug_read_byte(nes, 0x6001 + i as u16) != expected {
+            return false;
+        }
+    }
+    return true;
+}
+
+pub fn status(nes: &NesState) -> BlarggStatus {
+    if !signature_present(nes) {
+        return BlarggStatus::NotPresent;
+    }
+    return match memory::debug_read_byte(nes, 0x6000) {
+        0x80 => BlarggStatus::Running,
+        0x81 => BlarggStatus::ResetRequested,
+        code => BlarggStatus::Finished(code),
+    }
+}
+
+// The text written so far. Tests update this as they go, so it's meaningful (if
+// incomplete) while still running.
+pub fn message(nes: &NesState) -> Option<String> {
+    if !signature_present(nes) {
+        return None;
+    }
+    let mut text = Vec::new();
+    for offset in 0 .. MAX_MESSAGE_LENGTH {
+        let byte = memory::debug_read_byte(nes, 0x6004 + offset as u16);
+        if byte == 0 {
+            break;
+        }
+        text.push(byte);
+    }
+    return Some(String::from_utf8_lossy(&text).to_string());
+}
+
+// Runs a test ROM which reports through $6000 until it finishes, resetting
+// when asked. Returns the ROM's message, and fails with it if the result code isn't 0.
+pub fn run_test_rom(nes: &mut NesState, max_frames: u32) -> Result<String, String> {
+    let mut reset_countdown = None;
+    for _ in 0 .. max_frames {
+        nes.run_until_vblank();
+        match status(nes) {
+            BlarggStatus::Finished(code) => {
+                let message = message(nes).unwrap_or_default().trim_end().to_string();
+                return match code {
+                    0 => Ok(message),
+                    _ => Err(format!("{}\nFailed with code {}", message, code))
+                }
+            },
+            BlarggStatus::ResetRequested => {
+                // The convention asks for at least 100 ms before the reset
+                match reset_countdown {
+                    None => reset_countdown = Some(6),
+                    Some(0) => {
+                        nes.reset();
+                        reset_countdown = None;
+                    },
+                    Some(frames) => reset_countdown = Some(frames - 1),
+                }
+            },
+            _ => {}
+        }
+    }
+    let message = message(nes).unwrap_or(String::from("(no output)"));
+    return Err(format!("Test did not finish within {} frames:\n{}", max_frames, message.trim_end()));
+}
@@ -1,27 +1,53 @@
 use mmc::mapper::*;
 use mmc::action53::Action53;
 use mmc::axrom::AxRom;
+use mmc::bandai_fcg::BandaiFcg;
+use mmc::bf9093::Bf9093;
+use mmc::bf9096::Bf9096;
 use mmc::bnrom::BnRom;
 use mmc::cnrom::CnRom;
+use mmc::color_dreams::ColorDreams;
 use mmc::fme7::Fme7;
 use mmc::fds::FdsMapper;
+use mmc::flat_ram::FlatRam;
+use mmc::gtrom::Gtrom;
 use mmc::gxrom::GxRom;
 use mmc::ines31::INes31;
+use mmc::irem_g101::IremG101;
+use mmc::irem_h3001::IremH3001;
+use mmc::lrog017::Lrog017;
+use mmc::maxi15::Maxi15;
 use mmc::mmc1::Mmc1;
 use mmc::mmc3::Mmc3;
+use mmc::mmc4::Mmc4;
 use mmc::mmc5::Mmc5;
 use mmc::n163::Namco163;
+use mmc::namco108::Namco108;
+use mmc::namco175::Namco175;
 use mmc::nrom::Nrom;
 use mmc::nsf::NsfMapper;
 use mmc::pxrom::PxRom;
 use mmc::rainbow::Rainbow;
+use mmc::ram_cart::RamCart;
+use mmc::ss88006::Ss88006;
+use mmc::sunsoft1::Sunsoft1;
+use mmc::sunsoft2::Sunsoft2;
+use mmc::sunsoft3::Sunsoft3;
+use mmc::sunsoft4::Sunsoft4;
+use mmc::taito_tc0190::TaitoTc0190;
+use mmc::taito_x1005::TaitoX1005;
+use mmc::unrom512::Unrom512;
 use mmc::uxrom::UxRom;
+use mmc::vrc1::Vrc1;
+use mmc::vrc3::Vrc3;
+use mmc::vrc4::Vrc4;
 use mmc::vrc6::Vrc6;
 use mmc::vrc7::Vrc7;
 
 use ines::INesCartridge;
 use nsf::NsfFile;
 use fds::FdsFile;
+use patch;
 
 use std::io::Read;
 
@@ -37,15 +63,49 @@ fn mapper_from_ines(ines: INesCartridge) -> Result<Box<dyn Mapper>, String> {
         5 => Box::new(Mmc5::from_ines(ines)?),
         7 => Box::new(AxRom::from_ines(ines)?),
         9 => Box::new(PxRom::from_ines(ines)?),
+        10 => Box::new(Mmc4::from_ines(ines)?),
+        11 => Box::new(ColorDreams::from_ines(ines)?),
+        16 => Box::new(BandaiFcg::from_ines(ines)?),
+        18 => Box::new(Ss88006::from_ines(ines)?),
         19 => Box::new(Namco163::from_ines(ines)?),
+        21 => Box::new(Vrc4::from_ines(ines)?),
+        22 => Box::new(Vrc4::from_ines(ines)?),
+        23 => Box::new(Vrc4::from_ines(ines)?),
         24 => Box::new(Vrc6::from_ines(ines)?),
+        25 => Box::new(Vrc4::from_ines(ines)?),
         26 => Box::new(Vrc6::from_ines(ines)?),
         28 => Box::new(Action53::from_ines(ines)?),
+        30 => Box::new(Unrom512::from_ines(ines)?),
         31 => Box::new(INes31::from_ines(ines)?),
+        32 => Box::new(IremG101::from_ines(ines)?),
+        33 => Box::new(TaitoTc0190::from_ines(ines)?),
         34 => Box::new(BnRom::from_ines(ines)?),
+        48 => Box::new(TaitoTc0190::from_ines(ines)?),
+        65 => Box::new(IremH3001::from_ines(ines)?),
         66 => Box::new(GxRom::from_ines(ines)?),
+        67 => Box::new(Sunsoft3::from_ines(ines)?),
+        68 => Box::new(Sunsoft4::from_ines(ines)?),
         69 => Box::new(Fme7::from_ines(ines)?),
+        71 => Box::new(Bf9093::from_ines(ines)?),
+        73 => Box::new(Vrc3::from_ines(ines)?),
+        75 => Box::new(Vrc1::from_ines(ines)?),
+        77 => Box::new(Lrog017::from_ines(ines)?),
+        80 => Box::new(TaitoX1005::from_ines(ines)?),
         85 => Box::new(Vrc7::from_ines(ines)?),
+        89 => Box::new(Sunsoft2::from_ines(ines)?),
+        93 => Box::new(Sunsoft2::from_ines(ines)?),
+        95 => Box::new(Namco108::from_ines(ines)?),
+        111 => Box::new(Gtrom::from_ines(ines)?),
+        153 => Box::new(BandaiFcg::from_ines(ines)?),
+        154 => Box::new(Namco108::from_ines(ines)?),
+        157 => Box::new(BandaiFcg::from_ines(ines)?),
+        159 => Box::new(BandaiFcg::from_ines(ines)?),
+        184 => Box::new(Sunsoft1::from_ines(ines)?),
+        206 => Box::new(Namco108::from_ines(ines)?),
+        207 => Box::new(TaitoX1005::from_ines(ines)?),
+        210 => Box::new(Namco175::from_ines(ines)?),
+        232 => Box::new(Bf9096::from_ines(ines)?),
+        234 => Box::new(Maxi15::from_ines(ines)?),
         682 => Box::new(Rainbow::from_ines(ines)?),
         _ => {
             return Err(format!("Unsupported iNES mapper: {}", ines.header.mapper_number()));
@@ -88,4 +148,46 @@ pub fn mapper_from_reader(file_reader: &mut dyn Read) -> Result<Box<dyn Mapper>,
 pub fn mapper_from_file(file_data: &[u8]) -> Result<Box<dyn Mapper>, String> {
     let mut file_reader = file_data;
     return mapper_from_reader(&mut file_reader);
-}
\ No newline at end of file
+}
+// Applies an IPS or BPS patch to the file in memory before loading it
+pub fn mapper_from_patched_file(file_data: &[u8], patch_data: &[u8]) -> Result<Box<dyn Mapper>, String> {
+    let patched_data = patch::apply_patch(file_data, patch_data)?;
+    return mapper_from_file(&patched_data);
+}
+
+// Headerless dumps, as produced by some dumpers and most assemblers: PRG ROM and CHR ROM as
+// separate files. These are loaded as NROM; an empty CHR means 8k of CHR RAM.
+pub fn mapper_from_raw_dumps(prg_rom: &[u8], chr_rom: &[u8], mirroring: Mirroring) -> Result<Box<dyn Mapper>, String> {
+    let prg_rom = match prg_rom.len() {
+        // An 8k PRG is mirrored to fill the smallest bank size iNES can describe
+        0x2000 => [prg_rom, prg_rom].concat(),
+        0x4000 | 0x8000 => prg_rom.to_vec(),
+        size => return Err(format!("Unsupported raw PRG ROM size: {} bytes (expected 8k, 16k or 32k)", size)),
+    };
+    if !chr_rom.is_empty() && chr_rom.len() != 0x2000 {
+        return Err(format!("Unsupported raw CHR ROM size: {} bytes (expected 0 or 8k)", chr_rom.len()));
+    }
+    let flags_6 = match mirroring {
+        Mirroring::Horizontal => 0b0000_0000,
+        Mirroring::Vertical => 0b0000_0001,
+        Mirroring::FourScreen => 0b0000_1000,
+        _ => return Err(String::from("Raw dumps support horizontal, vertical or four screen mirroring only")),
+    };
+    let mut ines_data = vec!(0x4E, 0x45, 0x53, 0x1A, (prg_rom.len() / 0x4000) as u8, (chr_rom.len() / 0x2000) as u8, flags_6);
+    ines_data.resize(16, 0);
+    ines_data.extend_from_slice(&prg_rom);
+    ines_data.extend_from_slice(chr_rom);
+    return mapper_from_file(&ines_data);
+}
+
+// A flat binary covering (part of) the 6502's address space, with no NES hardware
+// reachable. For CPU test suites; see mmc::flat_ram.
+pub fn mapper_from_flat_binary(binary: &[u8], load_address: u16) -> Result<Box<dyn Mapper>, String> {
+    return Ok(Box::new(FlatRam::new(binary, load_address)?));
+}
+
+// An empty RAM backed cartridge, for frontends which upload programs directly; see
+// mmc::ram_cart.
+pub fn ram_cart(mirroring: Mirroring) -> Box<dyn Mapper> {
+    return Box::new(RamCart::new(mirroring));
+}
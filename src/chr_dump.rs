@@ -0,0 +1,107 @@
+// Debug: snapshots of pattern table memory, for pulling graphics a game builds at runtime
+// (decompressed CHR RAM, generated fonts) out of a running emulator. The 8k the PPU
+// currently sees can be saved as a .chr file, or laid out as a tile sheet image.
+
+use mmc::mapper::Mapper;
+use palettes::NTSC_PAL;
+use ppu::PpuState;
+
+pub const TILE_SIZE: usize = 16;
+
+// The 8k visible at PPU $0000-$1FFF right now, with the mapper's current banking applied.
+// This works for any mapper, since it reads through the same path as the PPU; unmapped
+// bytes read as 0.
+pub fn banked_chr(mapper: &dyn Mapper) -> Vec<u8> {
+    return (0 .. 0x2000u16).map(|address| mapper.debug_read_ppu(address).unwrap_or(0)).collect();
+}
+
+// Every byte of CHR ROM or RAM on the cartridge, banked in or not. None for mappers which
+// don't expose their CHR memory.
+pub fn whole_chr(mapper: &dyn Mapper) -> Option<Vec<u8>> {
+    return mapper.chr().map(|chr| chr.as_vec().clone());
+}
+
+// One pixel per byte, each a 2-bit color within the tile's palette
+pub struct TileSheet {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+}
+
+impl TileSheet {
+    // Lays out tiles in CHR order, left to right, tiles_across to a row. 16 across matches
+    // what most tile editors show: one 4k pattern table per 128x128 block. Asking for 0
+    // across gets a single column.
+    pub fn new(chr: &[u8], tiles_across: usize) -> TileSheet {
+        let tiles_across = tiles_across.max(1);
+        let tile_count = chr.len() / TILE_SIZE;
+        let rows = tile_count.div_ceil(tiles_across);
+        let width = tiles_across * 8;
+        let height = rows * 8;
+        let mut pixels = vec!(0u8; width * height);
+        for tile in 0 .. tile_count {
+            let tile_x = (tile % tiles_across) * 8;
+            let tile_y = (tile / tiles_across) * 8;
+            for y in 0 .. 8 {
+                let low = chr[tile * TILE_SIZE + y];
+                let high = chr[tile * TILE_SIZE + y + 8];
+                for x in 0 .. 8 {
+                    let bit = 7 - x;
+                    let color = ((low >> bit) & 0x1) | (((high >> bit) & 0x1) << 1);
+                    pixels[(tile_y + y) * width + tile_x + x] = color;
+                }
+            }
+        }
+        return TileSheet {
+            width: width,
+            height: height,
+            pixels: pixels,
+        }
+    }
+
+    // RGBA, 4 bytes per pixel, row by row; ready to hand to a PNG encoder
+    pub fn rgba(&self, colors: &[[u8; 4]; 4]) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.pixels.len() * 4);
+        for &pixel in &self.pixels {
+            data.extend_from_slice(&colors[pixel as usize]);
+        }
+        return data;
+    }
+}
+
+// For when the game's palettes aren't meaningful, or haven't been set up yet
+pub const GRAYSCALE: [[u8; 4]; 4] = [
+    [0x00, 0x00, 0x00, 0xFF],
+    [0x55, 0x55, 0x55, 0xFF],
+    [0xAA, 0xAA, 0xAA, 0xFF],
+    [0xFF, 0xFF, 0xFF, 0xFF],
+];
+
+// The colors of one of the PPU's palettes, 0-3 for background and 4-7 for sprites. Color
+// 0 of every palette shows the backdrop, as on hardware.
+pub fn palette_colors(ppu: &PpuState, mapper: &dyn Mapper, palette: u8) -> [[u8; 4]; 4] {
+    let mut colors = [[0u8; 4]; 4];
+    for (index, color) in colors.iter_mut().enumerate() {
+        let address = if index == 0 {0x3F00} else {0x3F00 + (palette as u16 & 0x7) * 4 + index as u16};
+        let entry = (ppu.debug_read_byte(mapper, address) & 0x3F) as usize;
+        *color = [NTSC_PAL[entry * 3], NTSC_PAL[entry * 3 + 1], NTSC_PAL[entry * 3 + 2], 0xFF];
+    }
+    return colors;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_tiles_across_is_one_column() {
+        let mut chr = vec!(0u8; TILE_SIZE * 2);
+        // Top row of the second tile: color 1 in the leftmost pixel, 3 in the rightmost
+        chr[TILE_SIZE] = 0b1000_0001;
+        chr[TILE_SIZE + 8] = 0b0000_0001;
+        let sheet = TileSheet::new(&chr, 0);
+        assert_eq!((sheet.width, sheet.height), (8, 16));
+        assert_eq!(sheet.pixels[8 * 8], 1);
+        assert_eq!(sheet.pixels[8 * 8 + 7], 3);
+    }
+}
@@ -92,6 +92,10 @@ pub struct CpuState {
   pub oam_dma_address: u16,
   
   pub old_nmi_requested: bool,
+  // The interrupt state as of the poll before last, for instructions which skip polling
+  // on their final cycle
+  pub previous_old_nmi_requested: bool,
+  pub previous_irq_requested: bool,
 }
 
 impl CpuState {
@@ -112,6 +116,8 @@ impl CpuState {
       upcoming_write: false,
       
       old_nmi_requested: false,
+      previous_old_nmi_requested: false,
+      previous_irq_requested: false,
     }
   }
 }
@@ -119,18 +125,23 @@ impl CpuState {
 
 
 pub fn nmi_signal(nes: &NesState) -> bool {
-    return ((nes.ppu.control & 0x80) & (nes.ppu.status & 0x80)) != 0;
+    let ppu_nmi = ((nes.ppu.control & 0x80) & (nes.ppu.status & 0x80)) != 0;
+    return ppu_nmi || nes.mapper.nmi_flag();
 }
 
 pub fn irq_signal(nes: &NesState) -> bool {
   if nes.registers.flags.interrupts_disabled {
     return false;
   } else {
-    return nes.apu.irq_signal() || nes.mapper.irq_flag();
+    // Flat test binaries can't reach the APU, so its frame IRQ can't be acknowledged either
+    let apu_irq = nes.apu.irq_signal() && !nes.mapper.flat_address_space();
+    return apu_irq || nes.mapper.irq_flag();
   }
 }
 
 pub fn poll_for_interrupts(nes: &mut NesState) {
+  nes.cpu.previous_old_nmi_requested = nes.cpu.old_nmi_requested;
+  nes.cpu.previous_irq_requested = nes.cpu.irq_requested;
   nes.cpu.old_nmi_requested = nes.cpu.nmi_requested;
 
   let current_nmi = nmi_signal(&nes);
@@ -138,8 +149,23 @@ pub fn poll_for_interrupts(nes: &mut NesState) {
   nes.registers.flags.last_nmi = current_nmi;
   if current_nmi && !last_nmi {
     nes.cpu.nmi_requested = true;
+    if nes.interrupt_timeline.enabled {
+      let cycle = nes.cpu_cycle();
+      nes.interrupt_timeline.nmi_edge(cycle);
+    }
   }
   nes.cpu.irq_requested = irq_signal(&nes);
+  if nes.interrupt_timeline.enabled {
+    let irq_line = nes.apu.irq_signal() || nes.mapper.irq_flag();
+    let cycle = nes.cpu_cycle();
+    nes.interrupt_timeline.irq_line(irq_line, cycle);
+  }
+}
+
+// Discards the poll made at the start of this cycle, keeping the one before it
+pub fn skip_interrupt_poll(nes: &mut NesState) {
+  nes.cpu.old_nmi_requested = nes.cpu.previous_old_nmi_requested;
+  nes.cpu.irq_requested = nes.cpu.previous_irq_requested;
 }
 
 pub fn interrupt_requested(nes: &NesState) -> bool {
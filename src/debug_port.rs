@@ -0,0 +1,63 @@
+// An emulator-only "serial port" for printf style debugging in homebrew: every byte the
+// CPU writes to the chosen address is appended to a log, which the frontend can display.
+// The write otherwise behaves normally, so picking an unused address ($4018, say) keeps
+// the game running identically on real hardware.
+
+// Older output is discarded past this point
+pub const MAX_DEBUG_LOG_LENGTH: usize = 64 * 1024;
+
+pub struct DebugPort {
+    pub address: Option<u16>,
+    pub log: Vec<u8>,
+}
+
+impl DebugPort {
+    pub fn new() -> DebugPort {
+        return DebugPort {
+            address: None,
+            log: Vec::new(),
+        }
+    }
+
+    pub fn enable(&mut self, address: u16) {
+        self.address = Some(address);
+    }
+
+    pub fn disable(&mut self) {
+        self.address = None;
+    }
+
+    pub fn watch_write(&mut self, address: u16, data: u8) {
+        if self.address == Some(address) {
+            if self.log.len() >= MAX_DEBUG_LOG_LENGTH {
+                self.log.drain(0 .. MAX_DEBUG_LOG_LENGTH / 2);
+            }
+            self.log.push(data);
+        }
+    }
+
+    // The log as text. Bytes outside printable ASCII, other than newlines and tabs, are
+    // shown as hex escapes, since games tend to write raw values as well as strings.
+    pub fn text(&self) -> String {
+        let mut text = String::new();
+        for &byte in &self.log {
+            match byte {
+                b'\n' | b'\t' | 0x20 ..= 0x7E => text.push(byte as char),
+                b'\r' => {},
+                _ => text.push_str(&format!("\\x{:02X}", byte)),
+            }
+        }
+        return text;
+    }
+
+    // Returns the log as text and clears it, for frontends which append to their own view
+    pub fn take_text(&mut self) -> String {
+        let text = self.text();
+        self.log.clear();
+        return text;
+    }
+
+    pub fn clear(&mut self) {
+        self.log.clear();
+    }
+}
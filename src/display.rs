@@ -0,0 +1,89 @@
+// Display geometry helpers, so frontends scale the picture the same way. The PPU outputs
+// 256x240, but its pixels aren't square on a TV (see Region::pixel_aspect_ratio), so a
+// correct picture isn't 4:3 in general; it depends on how much of the edge is cropped.
+
+use region::Region;
+
+pub const SCREEN_WIDTH: u32 = 256;
+pub const SCREEN_HEIGHT: u32 = 240;
+
+// Pixels cropped from each edge of the 256x240 image. TVs hid roughly 8 lines at the top
+// and bottom, and games commonly leave garbage there.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Overscan {
+    pub top: u32,
+    pub bottom: u32,
+    pub left: u32,
+    pub right: u32,
+}
+
+impl Overscan {
+    pub fn none() -> Overscan {
+        return Overscan {top: 0, bottom: 0, left: 0, right: 0};
+    }
+
+    // A common default: 8 lines top and bottom, as an NTSC TV would hide
+    pub fn ntsc_default() -> Overscan {
+        return Overscan {top: 8, bottom: 8, left: 0, right: 0};
+    }
+
+    pub fn visible_width(&self) -> u32 {
+        return SCREEN_WIDTH.saturating_sub(self.left + self.right);
+    }
+
+    pub fn visible_height(&self) -> u32 {
+        return SCREEN_HEIGHT.saturating_sub(self.top + self.bottom);
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DisplayRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+// Width / height of the visible picture as it would appear on a TV
+pub fn display_aspect_ratio(region: Region, overscan: Overscan) -> f64 {
+    return (overscan.visible_width() as f64 * region.pixel_aspect_ratio()) / overscan.visible_height() as f64;
+}
+
+// The largest rectangle with the given aspect ratio that fits the target, centered.
+// With integer_scale, the height is limited to a whole multiple of the visible height
+// (if at least 1x fits), which keeps scanlines even.
+pub fn fit_rect(target_width: u32, target_height: u32, aspect_ratio: f64, visible_height: u32, integer_scale: bool) -> DisplayRect {
+    let mut height = target_height as f64;
+    if integer_scale && visible_height > 0 && target_height >= visible_height {
+        height = ((target_height / visible_height) * visible_height) as f64;
+    }
+    let mut width = height * aspect_ratio;
+    if width > target_width as f64 {
+        width = target_width as f64;
+        height = width / aspect_ratio;
+        if integer_scale && visible_height > 0 && height >= visible_height as f64 {
+            height = ((height as u32 / visible_height) * visible_height) as f64;
+            width = height * aspect_ratio;
+        }
+    }
+    let width = width.round() as u32;
+    let height = height.round() as u32;
+    return DisplayRect {
+        x: (target_width - width.min(target_width)) / 2,
+        y: (target_height - height.min(target_height)) / 2,
+        width: width,
+        height: height,
+    };
+}
+
+// Where to draw the cropped picture in a target surface, with correct pixel aspect
+pub fn display_rect(region: Region, overscan: Overscan, target_width: u32, target_height: u32, integer_scale: bool) -> DisplayRect {
+    return fit_rect(target_width, target_height, display_aspect_ratio(region, overscan), overscan.visible_height(), integer_scale);
+}
+
+// As display_rect, for frontends which prefer square pixels (pixel art purists, mostly).
+// With integer_scale this gives exact integer multiples in both directions.
+pub fn square_pixel_rect(overscan: Overscan, target_width: u32, target_height: u32, integer_scale: bool) -> DisplayRect {
+    let aspect_ratio = overscan.visible_width() as f64 / overscan.visible_height() as f64;
+    return fit_rect(target_width, target_height, aspect_ratio, overscan.visible_height(), integer_scale);
+}
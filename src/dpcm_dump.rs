@@ -0,0 +1,121 @@
+// Collects the DMC samples a game plays during a session, for preservation and reuse.
+// Samples are captured by the DMC as they start (see DmcState::capture_samples), then
+// gathered here without duplicates, noting every rate each one was played at. Each can
+// be exported as a raw .dmc file (the bytes exactly as stored in ROM) or as a WAV.
+
+use apu::DmcSampleEvent;
+use apu::decode_dpcm;
+use nes::NesState;
+
+pub struct DumpedSample {
+    pub starting_address: u16,
+    pub rom_offset: Option<usize>,
+    pub sample_length: u16,
+    pub raw_bytes: Vec<u8>,
+    // Output level when the sample was first heard, used to decode the WAV
+    pub initial_level: u8,
+    pub looping: bool,
+    // Each distinct $4010 rate index used with this sample, in the order first heard
+    pub period_indices: Vec<u8>,
+    pub play_count: u32,
+}
+
+impl DumpedSample {
+    // The rate the DMC outputs bits at for a given period index, in Hz
+    pub fn playback_rate(nes: &NesState, period_index: u8) -> u32 {
+        let period = nes.apu.dmc_period_table[(period_index & 0xF) as usize] as u64;
+        return (nes.apu.region.cpu_clock_rate() / period) as u32;
+    }
+
+    pub fn dmc_file(&self) -> Vec<u8> {
+        return self.raw_bytes.clone();
+    }
+
+    // 8-bit unsigned mono; the DMC's 7-bit level is doubled to fill the range
+    pub fn wav_file(&self, sample_rate: u32) -> Vec<u8> {
+        let pcm = decode_dpcm(&self.raw_bytes, self.initial_level);
+        let data_size = pcm.len() as u32;
+        let mut data = Vec::with_capacity(44 + pcm.len());
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(36 + data_size).to_le_bytes());
+        data.extend_from_slice(b"WAVE");
+        data.extend_from_slice(b"fmt ");
+        data.extend_from_slice(&16u32.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes()); // PCM
+        data.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        data.extend_from_slice(&sample_rate.to_le_bytes());
+        data.extend_from_slice(&sample_rate.to_le_bytes());
+        data.extend_from_slice(&1u16.to_le_bytes());
+        data.extend_from_slice(&8u16.to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&data_size.to_le_bytes());
+        for level in pcm {
+            data.push(level << 1);
+        }
+        return data;
+    }
+
+    // A descriptive file name, without extension
+    pub fn file_stem(&self) -> String {
+        return match self.rom_offset {
+            Some(offset) => format!("dmc_rom{:06X}_len{}", offset, self.raw_bytes.len()),
+            None => format!("dmc_cpu{:04X}_len{}", self.starting_address, self.raw_bytes.len()),
+        }
+    }
+}
+
+pub struct DpcmSampleLibrary {
+    pub samples: Vec<DumpedSample>,
+}
+
+impl DpcmSampleLibrary {
+    pub fn new() -> DpcmSampleLibrary {
+        return DpcmSampleLibrary {
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn start_capture(nes: &mut NesState) {
+        nes.apu.dmc.capture_samples = true;
+    }
+
+    pub fn stop_capture(nes: &mut NesState) {
+        nes.apu.dmc.capture_samples = false;
+    }
+
+    // Call regularly (once per frame is plenty) while capture is enabled
+    pub fn collect(&mut self, nes: &mut NesState) {
+        for event in nes.apu.dmc.drain_captured_samples() {
+            self.add(event);
+        }
+    }
+
+    pub fn add(&mut self, event: DmcSampleEvent) {
+        // Compare contents too, in case of CHR/PRG RAM or a mapper which can't resolve offsets
+        let existing = self.samples.iter_mut().find(|sample|
+            sample.rom_offset == event.rom_offset &&
+            sample.starting_address == event.starting_address &&
+            sample.raw_bytes == event.raw_bytes);
+        match existing {
+            Some(sample) => {
+                sample.play_count += 1;
+                sample.looping |= event.looping;
+                if !sample.period_indices.contains(&event.period_index) {
+                    sample.period_indices.push(event.period_index);
+                }
+            },
+            None => {
+                self.samples.push(DumpedSample {
+                    starting_address: event.starting_address,
+                    rom_offset: event.rom_offset,
+                    sample_length: event.sample_length,
+                    raw_bytes: event.raw_bytes,
+                    initial_level: event.initial_level,
+                    looping: event.looping,
+                    period_indices: vec!(event.period_index),
+                    play_count: 1,
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,137 @@
+// Archival recordings of the unfiltered mix at the CPU clock rate, which is as much
+// fidelity as the emulated hardware has to give. Recordings can be written out as is,
+// tagged with their true sample rate (1.789773 MHz on NTSC), or decimated offline to a
+// rate that ordinary tools can play back. The decimator never runs in real time, so it
+// can afford a much sharper filter than the APU's own output chain.
+
+use apu::ApuState;
+use std::f64::consts::PI;
+
+// Fractional delays the decimation kernel is tabulated for. The output sample position
+// snaps to the nearest of these, an error of at most 1/64th of a CPU cycle.
+const KERNEL_PHASES: usize = 32;
+
+pub struct HqRecording {
+    pub sample_rate: u32,
+    pub samples: Vec<f32>,
+}
+
+impl HqRecording {
+    pub fn new(sample_rate: u32) -> HqRecording {
+        return HqRecording {
+            sample_rate: sample_rate,
+            samples: Vec::new(),
+        }
+    }
+
+    // Starts capturing the APU's mix. Note that at the CPU rate this grows by about 7 MB
+    // per emulated second; call append_from regularly and decimate long recordings.
+    pub fn start(apu: &mut ApuState) -> HqRecording {
+        apu.set_hq_capture(true);
+        return HqRecording::new(apu.cpu_clock_rate as u32);
+    }
+
+    // Moves everything the APU has captured since the last call into the recording
+    pub fn append_from(&mut self, apu: &mut ApuState) {
+        self.samples.extend(apu.take_hq_mix());
+    }
+
+    pub fn duration_seconds(&self) -> f64 {
+        return self.samples.len() as f64 / self.sample_rate as f64;
+    }
+
+    // Returns a copy at target_rate, passing everything below 45% of that rate. Asking
+    // for a rate at or above the current one returns the recording unchanged.
+    pub fn decimated(&self, target_rate: u32) -> HqRecording {
+        if target_rate >= self.sample_rate {
+            return HqRecording {
+                sample_rate: self.sample_rate,
+                samples: self.samples.clone(),
+            };
+        }
+        let ratio = self.sample_rate as f64 / target_rate as f64;
+        let cutoff = 0.45 / ratio;
+        // Transition band of 5% of the target rate; a Blackman window needs about 4 / width taps
+        let half_width = (40.0 * ratio).ceil() as i64;
+        let kernels = decimation_kernels(cutoff, half_width);
+
+        let output_length = (self.samples.len() as f64 / ratio) as usize;
+        let mut output = Vec::with_capacity(output_length);
+        for n in 0 .. output_length {
+            let position = n as f64 * ratio;
+            let mut center = position.floor() as i64;
+            let mut phase = ((position - center as f64) * KERNEL_PHASES as f64).round() as usize;
+            if phase == KERNEL_PHASES {
+                center += 1;
+                phase = 0;
+            }
+            let kernel = &kernels[phase];
+            let first = center - half_width;
+            let mut sum = 0.0f64;
+            for (k, weight) in kernel.iter().enumerate() {
+                let index = first + k as i64;
+                if index >= 0 && (index as usize) < self.samples.len() {
+                    sum += self.samples[index as usize] as f64 * weight;
+                }
+            }
+            output.push(sum as f32);
+        }
+
+        return HqRecording {
+            sample_rate: target_rate,
+            samples: output,
+        };
+    }
+
+    // 32-bit float mono WAV, with the recording's own rate in the header
+    pub fn wav_file(&self) -> Vec<u8> {
+        let data_size = (self.samples.len() * 4) as u32;
+        let mut data = Vec::with_capacity(58 + self.samples.len() * 4);
+        data.extend_from_slice(b"RIFF");
+        data.extend_from_slice(&(50 + data_size).to_le_bytes());
+        data.extend_from_slice(b"WAVE");
+        data.extend_from_slice(b"fmt ");
+        data.extend_from_slice(&18u32.to_le_bytes());
+        data.extend_from_slice(&3u16.to_le_bytes()); // IEEE float
+        data.extend_from_slice(&1u16.to_le_bytes()); // Mono
+        data.extend_from_slice(&self.sample_rate.to_le_bytes());
+        data.extend_from_slice(&(self.sample_rate * 4).to_le_bytes());
+        data.extend_from_slice(&4u16.to_le_bytes());
+        data.extend_from_slice(&32u16.to_le_bytes());
+        data.extend_from_slice(&0u16.to_le_bytes()); // No extension
+        // Required for anything other than integer PCM
+        data.extend_from_slice(b"fact");
+        data.extend_from_slice(&4u32.to_le_bytes());
+        data.extend_from_slice(&(self.samples.len() as u32).to_le_bytes());
+        data.extend_from_slice(b"data");
+        data.extend_from_slice(&data_size.to_le_bytes());
+        for sample in &self.samples {
+            data.extend_from_slice(&sample.to_le_bytes());
+        }
+        return data;
+    }
+}
+
+// One Blackman windowed sinc per phase, each normalized to unity gain. Tap k of phase p
+// weighs the input sample (k - half_width) cycles from the output's integer position.
+fn decimation_kernels(cutoff: f64, half_width: i64) -> Vec<Vec<f64>> {
+    let mut kernels = Vec::with_capacity(KERNEL_PHASES);
+    for phase in 0 .. KERNEL_PHASES {
+        let fraction = phase as f64 / KERNEL_PHASES as f64;
+        let mut kernel: Vec<f64> = (0 ..= 2 * half_width).map(|k| {
+            let t = (k - half_width) as f64 - fraction;
+            if t.abs() > half_width as f64 {
+                return 0.0;
+            }
+            let sinc = if t == 0.0 {2.0 * cutoff} else {(2.0 * PI * cutoff * t).sin() / (PI * t)};
+            let window = 0.42 + 0.5 * (PI * t / half_width as f64).cos() + 0.08 * (2.0 * PI * t / half_width as f64).cos();
+            return sinc * window;
+        }).collect();
+        let sum: f64 = kernel.iter().sum();
+        for weight in kernel.iter_mut() {
+            *weight /= sum;
+        }
+        kernels.push(kernel);
+    }
+    return kernels;
+}
@@ -221,6 +221,15 @@ impl INesHeader {
         return Mirroring::Horizontal;
     }
 
+    // The raw flags behind mirroring(), for boards which give them their own meaning
+    pub fn alternative_nametables(&self) -> bool {
+        return self.raw_bytes[INES_FLAGS_6] & 0b0000_1000 != 0;
+    }
+
+    pub fn vertical_arrangement_bit(&self) -> bool {
+        return self.raw_bytes[INES_FLAGS_6] & 0b0000_0001 != 0;
+    }
+
     pub fn has_sram(&self) -> bool {
         return self.raw_bytes[INES_FLAGS_6] & 0b0000_0010 != 0;
     }
@@ -438,6 +447,17 @@ impl INesCartridge {
         return Ok(blocks[0].clone());
     }
 
+    // As prg_ram_block, for mappers which can only address so much PRG RAM. Headers asking
+    // for more are rejected rather than silently truncated.
+    pub fn prg_ram_block_up_to(&self, max_size: usize) -> Result<MemoryBlock, String> {
+        let block = self.prg_ram_block()?;
+        if block.len() > max_size {
+            return Err(format!("Mapper number {} supports at most {}k of PRG RAM, but the header specifies {}k",
+                self.header.mapper_number(), max_size / 1024, block.len() / 1024));
+        }
+        return Ok(block);
+    }
+
     pub fn chr_block(&self) -> Result<MemoryBlock, String> {
         let blocks = self.chr_blocks();
         if blocks.len() != 1 {
@@ -446,3 +466,24 @@ impl INesCartridge {
         return Ok(blocks[0].clone());
     }
 }
+
+// Blank iNES 1.0 images for mapper tests. Every byte of PRG holds the number of the 8k bank
+// it sits in, and every byte of CHR the number of its 1k bank, so reads show the mapping.
+#[cfg(test)]
+impl INesCartridge {
+    pub fn test_image(mapper_number: u8, prg_16k_banks: u8, chr_8k_banks: u8) -> INesCartridge {
+        let mut header_bytes = [0u8; 16];
+        header_bytes[0 .. 4].copy_from_slice(&[0x4E, 0x45, 0x53, 0x1A]);
+        header_bytes[INES_PRG_ROM_LSB] = prg_16k_banks;
+        header_bytes[INES_CHR_ROM_LSB] = chr_8k_banks;
+        header_bytes[INES_FLAGS_6] = (mapper_number & 0x0F) << 4;
+        header_bytes[INES_FLAGS_7] = mapper_number & 0xF0;
+        return INesCartridge {
+            header: INesHeader::from(&header_bytes),
+            trainer: Vec::new(),
+            prg: (0 .. prg_16k_banks as usize * 0x4000).map(|i| (i / 0x2000) as u8).collect(),
+            chr: (0 .. chr_8k_banks as usize * 0x2000).map(|i| (i / 0x400) as u8).collect(),
+            misc_rom: Vec::new(),
+        };
+    }
+}
@@ -0,0 +1,117 @@
+// Short, frame-indexed button sequences which can be injected on demand. Unlike a movie,
+// a macro is relative: frame 0 is whichever frame it was started on, and its buttons are
+// combined with whatever the player is already holding.
+
+use nes::NesState;
+
+// Standard controller bits, as they appear in p1_input / p2_input
+pub const BUTTON_A: u8      = 1 << 0;
+pub const BUTTON_B: u8      = 1 << 1;
+pub const BUTTON_SELECT: u8 = 1 << 2;
+pub const BUTTON_START: u8  = 1 << 3;
+pub const BUTTON_UP: u8     = 1 << 4;
+pub const BUTTON_DOWN: u8   = 1 << 5;
+pub const BUTTON_LEFT: u8   = 1 << 6;
+pub const BUTTON_RIGHT: u8  = 1 << 7;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct MacroFrame {
+    pub p1: u8,
+    pub p2: u8,
+}
+
+#[derive(Clone)]
+pub struct InputMacro {
+    pub name: String,
+    pub frames: Vec<MacroFrame>,
+}
+
+impl InputMacro {
+    pub fn new(name: &str) -> InputMacro {
+        return InputMacro {
+            name: String::from(name),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn hold(mut self, p1: u8, p2: u8, frame_count: usize) -> InputMacro {
+        for _ in 0 .. frame_count {
+            self.frames.push(MacroFrame{p1: p1, p2: p2});
+        }
+        return self;
+    }
+
+    pub fn press(self, p1: u8, frame_count: usize) -> InputMacro {
+        return self.hold(p1, 0, frame_count);
+    }
+
+    pub fn wait(self, frame_count: usize) -> InputMacro {
+        return self.hold(0, 0, frame_count);
+    }
+
+    pub fn len(&self) -> usize {
+        return self.frames.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.frames.is_empty();
+    }
+}
+
+pub struct ActiveMacro {
+    pub input_macro: InputMacro,
+    pub position: usize,
+}
+
+pub struct MacroPlayer {
+    pub active: Vec<ActiveMacro>,
+}
+
+impl MacroPlayer {
+    pub fn new() -> MacroPlayer {
+        return MacroPlayer {
+            active: Vec::new(),
+        }
+    }
+
+    pub fn start(&mut self, input_macro: InputMacro) {
+        self.active.push(ActiveMacro {
+            input_macro: input_macro,
+            position: 0,
+        });
+    }
+
+    pub fn stop_all(&mut self) {
+        self.active.clear();
+    }
+
+    pub fn playing(&self) -> bool {
+        return !self.active.is_empty();
+    }
+
+    // Returns the combined buttons for the upcoming frame and advances every active
+    // macro by one frame, discarding any that have finished.
+    pub fn next_frame(&mut self) -> MacroFrame {
+        let mut combined = MacroFrame{p1: 0, p2: 0};
+        for active_macro in self.active.iter_mut() {
+            if let Some(frame) = active_macro.input_macro.frames.get(active_macro.position) {
+                combined.p1 |= frame.p1;
+                combined.p2 |= frame.p2;
+            }
+            active_macro.position += 1;
+        }
+        self.active.retain(|active_macro| active_macro.position < active_macro.input_macro.frames.len());
+        return combined;
+    }
+
+    // Call once per frame after the frontend has written live input and before running
+    // the frame.
+    pub fn apply(&mut self, nes: &mut NesState) {
+        if !self.playing() {
+            return;
+        }
+        let frame = self.next_frame();
+        nes.p1_input |= frame.p1;
+        nes.p2_input |= frame.p2;
+    }
+}
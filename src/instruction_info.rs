@@ -0,0 +1,77 @@
+// A record of one executed instruction, for external tools (coverage, fuzzers and the
+// like) that drive the core one instruction at a time and would otherwise have to parse
+// a trace log. See NesState::step_with_info and NesState::instructions.
+
+use memory;
+use nes::NesState;
+use opcode_info;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BusAccess {
+    pub address: u16,
+    pub data: u8,
+    pub write: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct InstructionInfo {
+    pub pc: u16,
+    pub opcode: u8,
+    pub operand: Vec<u8>,
+    // The memory operand, or the destination of a jump or branch. None for implied and
+    // immediate instructions.
+    pub effective_address: Option<u16>,
+    // Every bus access made while the step ran, in order. This includes the opcode and
+    // operand fetches, dummy reads, and any DMA that stalled the instruction.
+    pub reads: Vec<BusAccess>,
+    pub writes: Vec<BusAccess>,
+    pub cycles: u64,
+    // True if this step serviced an NMI or IRQ instead of running the instruction at pc
+    pub interrupt: bool,
+}
+
+// Works out the effective address before the instruction runs, from the current registers.
+// Pointers are fetched with debug reads, so this has no side effects.
+pub fn effective_address(nes: &NesState, opcode: u8, operand: &[u8]) -> Option<u16> {
+    let byte = |index: usize| operand.get(index).cloned().unwrap_or(0) as u16;
+    let zero_page_word = |address: u16| {
+        let low = memory::debug_read_byte(nes, address & 0xFF) as u16;
+        let high = memory::debug_read_byte(nes, (address + 1) & 0xFF) as u16;
+        (high << 8) | low
+    };
+    let absolute = byte(0) | (byte(1) << 8);
+    let x = nes.registers.x as u16;
+    let y = nes.registers.y as u16;
+    return match opcode_info::addressing_mode(opcode) {
+        "d" => Some(byte(0)),
+        "d, x" => Some((byte(0) + x) & 0xFF),
+        "d, y" => Some((byte(0) + y) & 0xFF),
+        "a" => Some(absolute),
+        "a, x" => Some(absolute.wrapping_add(x)),
+        "a, y" => Some(absolute.wrapping_add(y)),
+        "(d, x)" => Some(zero_page_word(byte(0) + x)),
+        "(d), y" => Some(zero_page_word(byte(0)).wrapping_add(y)),
+        "(a)" => {
+            // The pointer's high byte never carries into the next page
+            let high_address = (absolute & 0xFF00) | (absolute.wrapping_add(1) & 0x00FF);
+            let low = memory::debug_read_byte(nes, absolute) as u16;
+            let high = memory::debug_read_byte(nes, high_address) as u16;
+            Some((high << 8) | low)
+        },
+        "r" => Some(nes.registers.pc.wrapping_add(2).wrapping_add(byte(0) as i8 as u16)),
+        _ => None
+    };
+}
+
+// Iterates forever; use take_while or similar to stop it
+pub struct ExecutedInstructions<'a> {
+    pub nes: &'a mut NesState,
+}
+
+impl<'a> Iterator for ExecutedInstructions<'a> {
+    type Item = InstructionInfo;
+
+    fn next(&mut self) -> Option<InstructionInfo> {
+        return Some(self.nes.step_with_info());
+    }
+}
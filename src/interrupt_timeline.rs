@@ -0,0 +1,109 @@
+// Debug: a record of every interrupt the CPU services, for profiling handlers. Each entry
+// notes when the interrupt line was asserted, when the CPU acknowledged it by fetching a
+// vector, and how many cycles elapsed before the matching RTI. Disabled by default, as
+// it's only interesting to developers.
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InterruptSource {
+    Nmi,
+    Irq,
+    Brk,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct InterruptRecord {
+    pub source: InterruptSource,
+    // CPU cycles since power on. BRK has no line to assert, so it's asserted on acknowledge.
+    pub assert_cycle: u64,
+    pub acknowledge_cycle: u64,
+    pub vector: u16,
+    // Where the PPU was when the CPU acknowledged, to relate NMI time to vblank
+    pub scanline: u16,
+    pub scanline_cycle: u16,
+    // None until the handler returns; handlers that never RTI stay open
+    pub handler_cycles: Option<u64>,
+}
+
+// Oldest records are discarded past this point, so a forgotten timeline can't grow forever
+pub const MAX_INTERRUPT_RECORDS: usize = 4096;
+
+pub struct InterruptTimeline {
+    pub enabled: bool,
+    pub records: Vec<InterruptRecord>,
+    // Indices into records for handlers which haven't returned yet, innermost last
+    pub open_handlers: Vec<usize>,
+    pub nmi_asserted_at: Option<u64>,
+    pub irq_asserted_at: Option<u64>,
+}
+
+impl InterruptTimeline {
+    pub fn new() -> InterruptTimeline {
+        return InterruptTimeline {
+            enabled: false,
+            records: Vec::new(),
+            open_handlers: Vec::new(),
+            nmi_asserted_at: None,
+            irq_asserted_at: None,
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+        self.open_handlers.clear();
+        self.nmi_asserted_at = None;
+        self.irq_asserted_at = None;
+    }
+
+    // Returns every record collected so far, leaving handlers still in progress open
+    pub fn take_records(&mut self) -> Vec<InterruptRecord> {
+        let open: Vec<InterruptRecord> = self.open_handlers.iter().map(|&index| self.records[index]).collect();
+        let finished = std::mem::replace(&mut self.records, open);
+        self.open_handlers = (0 .. self.records.len()).collect();
+        return finished;
+    }
+
+    pub fn nmi_edge(&mut self, cycle: u64) {
+        self.nmi_asserted_at = Some(cycle);
+    }
+
+    // IRQ is level triggered; only the start of each assertion is interesting
+    pub fn irq_line(&mut self, asserted: bool, cycle: u64) {
+        if !asserted {
+            self.irq_asserted_at = None;
+        } else if self.irq_asserted_at.is_none() {
+            self.irq_asserted_at = Some(cycle);
+        }
+    }
+
+    pub fn acknowledge(&mut self, source: InterruptSource, vector: u16, cycle: u64, scanline: u16, scanline_cycle: u16) {
+        let assert_cycle = match source {
+            InterruptSource::Nmi => self.nmi_asserted_at.take(),
+            InterruptSource::Irq => self.irq_asserted_at,
+            InterruptSource::Brk => None,
+        }.unwrap_or(cycle);
+        if self.records.len() >= MAX_INTERRUPT_RECORDS {
+            self.records.remove(0);
+            self.open_handlers.retain(|&index| index > 0);
+            for index in &mut self.open_handlers {
+                *index -= 1;
+            }
+        }
+        self.records.push(InterruptRecord {
+            source: source,
+            assert_cycle: assert_cycle,
+            acknowledge_cycle: cycle,
+            vector: vector,
+            scanline: scanline,
+            scanline_cycle: scanline_cycle,
+            handler_cycles: None,
+        });
+        self.open_handlers.push(self.records.len() - 1);
+    }
+
+    pub fn return_from_interrupt(&mut self, cycle: u64) {
+        if let Some(index) = self.open_handlers.pop() {
+            let record = &mut self.records[index];
+            record.handler_cycles = Some(cycle - record.acknowledge_cycle);
+        }
+    }
+}
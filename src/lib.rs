@@ -1,18 +1,38 @@
 pub mod addressing;
 pub mod apu;
 pub mod asm;
+pub mod audio_activity;
+pub mod blargg;
 pub mod cartridge;
+pub mod chr_dump;
 pub mod cycle_cpu;
+pub mod debug_port;
+pub mod display;
+pub mod dpcm_dump;
 pub mod fds;
+pub mod hq_recording;
 pub mod tracked_events;
 pub mod ines;
+pub mod input_macro;
+pub mod instruction_info;
+pub mod interrupt_timeline;
 pub mod memory;
 pub mod memoryblock;
+pub mod memory_search;
 pub mod mmc;
+pub mod movie;
 pub mod nes;
 pub mod nsf;
 pub mod opcodes;
 pub mod opcode_info;
+pub mod palette_generator;
 pub mod palettes;
+pub mod patch;
+pub mod pitch_history;
+pub mod poke;
 pub mod ppu;
+pub mod profiler;
+pub mod region;
+pub mod scroll_splits;
+pub mod shift_jis;
 pub mod unofficial_opcodes;
\ No newline at end of file
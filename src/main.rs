@@ -0,0 +1,497 @@
+// Headless command line frontend. This exists mostly to exercise the core from scripts
+// and test harnesses; the interactive frontends live in their own repositories.
+
+extern crate rusticnes_core;
+
+use rusticnes_core::blargg;
+use rusticnes_core::cartridge;
+use rusticnes_core::chr_dump;
+use rusticnes_core::chr_dump::TileSheet;
+use rusticnes_core::dpcm_dump::DpcmSampleLibrary;
+use rusticnes_core::dpcm_dump::DumpedSample;
+use rusticnes_core::hq_recording::HqRecording;
+use rusticnes_core::memory;
+use rusticnes_core::mmc::mapper::Mirroring;
+use rusticnes_core::movie::MergePolicy;
+use rusticnes_core::movie::Movie;
+use rusticnes_core::movie::MoviePlayer;
+use rusticnes_core::nes::NesState;
+use rusticnes_core::opcode_info;
+use rusticnes_core::palettes::NTSC_PAL;
+
+use std::env;
+use std::fs;
+use std::fs::File;
+use std::io::prelude::*;
+use std::path::PathBuf;
+use std::process;
+
+fn usage() {
+    println!("Usage: rusticnes-core <command> [options]");
+    println!();
+    println!("Commands:");
+    println!("  run ROM [--frames N] [--debug-port HEX]");
+    println!("  trace ROM [--frames N]");
+    println!("  screenshot ROM [--frame N] [--out FILE.ppm]");
+    println!("  verify-movie ROM MOVIE.fm2 [--expect HASH]");
+    println!("  play-nsf FILE.nsf [--track N] [--seek N] [--seconds N] [--wav OUT.wav] [--integer-mix]");
+    println!("  record-hq FILE.nsf [--track N] [--seek N] [--seconds N] [--rate N] [--wav OUT.wav]");
+    println!("  test-rom ROM [--max-frames N]");
+    println!("  test-suite DIR [--max-frames N]");
+    println!("  dump-dpcm ROM [--frames N] [--out DIR]");
+    println!("  dump-chr ROM [--frame N] [--out FILE.chr] [--sheet FILE.ppm] [--palette N]");
+    println!("  cpu-test BINARY [--load HEX] [--start HEX] [--success HEX] [--max-cycles N]");
+    println!();
+    println!("Commands that load a ROM or NSF file accept --patch FILE.ips|FILE.bps to soft-patch it; cpu-test and --prg dumps don't.");
+    println!("run, trace, screenshot and test-rom accept a headerless dump in place of ROM: --prg FILE.prg [--chr FILE.chr] [--vertical]");
+}
+
+fn option_value(args: &[String], name: &str) -> Option<String> {
+    for i in 0 .. args.len() {
+        if args[i] == name && i + 1 < args.len() {
+            return Some(args[i + 1].clone());
+        }
+    }
+    return None;
+}
+
+fn numeric_option(args: &[String], name: &str, default: u32) -> Result<u32, String> {
+    return match option_value(args, name) {
+        Some(value) => value.parse::<u32>().map_err(|_| format!("Invalid value for {}: {}", name, value)),
+        None => Ok(default)
+    }
+}
+
+fn hex_option(args: &[String], name: &str, default: u16) -> Result<u16, String> {
+    return match option_value(args, name) {
+        Some(value) => u16::from_str_radix(value.trim_start_matches('$').trim_start_matches("0x"), 16)
+            .map_err(|_| format!("Invalid hex value for {}: {}", name, value)),
+        None => Ok(default)
+    }
+}
+
+// --track, checked against the number of songs the NSF header declares
+fn track_option(args: &[String], nes: &NesState) -> Result<u8, String> {
+    let track = numeric_option(args, "--track", 1)?;
+    let total_songs = nes.mapper.nsf_total_songs() as u32;
+    if track < 1 || track > total_songs {
+        return Err(format!("Invalid value for --track: {} (this NSF has tracks 1-{})", track, total_songs));
+    }
+    return Ok(track as u8);
+}
+
+fn positional(args: &[String], index: usize, description: &str) -> Result<String, String> {
+    return match args.get(index) {
+        Some(value) => Ok(value.clone()),
+        None => Err(format!("Missing argument: {}", description))
+    }
+}
+
+fn read_file(filename: &str) -> Result<Vec<u8>, String> {
+    let mut file = File::open(filename).map_err(|e| format!("Could not open {}: {}", filename, e))?;
+    let mut data = Vec::new();
+    file.read_to_end(&mut data).map_err(|e| format!("Could not read {}: {}", filename, e))?;
+    return Ok(data);
+}
+
+fn load_nes(filename: &str, args: &[String]) -> Result<NesState, String> {
+    if let Some(prg_filename) = option_value(args, "--prg") {
+        if option_value(args, "--patch").is_some() {
+            return Err(String::from("--patch only applies to iNES and NSF files, not --prg dumps"));
+        }
+        let chr = match option_value(args, "--chr") {
+            Some(chr_filename) => read_file(&chr_filename)?,
+            None => Vec::new()
+        };
+        let mirroring = if args.iter().any(|arg| arg == "--vertical") {Mirroring::Vertical} else {Mirroring::Horizontal};
+        let mut nes = NesState::new(cartridge::mapper_from_raw_dumps(&read_file(&prg_filename)?, &chr, mirroring)?);
+        nes.power_on();
+        return Ok(nes);
+    }
+    let data = read_file(filename)?;
+    let mapper = match option_value(args, "--patch") {
+        Some(patch_filename) => cartridge::mapper_from_patched_file(&data, &read_file(&patch_filename)?)?,
+        None => cartridge::mapper_from_file(&data)?
+    };
+    let mut nes = NesState::new(mapper);
+    nes.power_on();
+    return Ok(nes);
+}
+
+// FNV-1a, used to summarize framebuffer and RAM contents for comparison between runs
+fn fnv1a_hash(data: &[u8], initial: u64) -> u64 {
+    let mut hash = initial;
+    for byte in data {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    return hash;
+}
+
+fn state_hash(nes: &NesState) -> u64 {
+    let mut screen_bytes = Vec::with_capacity(nes.ppu.screen.len() * 2);
+    for pixel in &nes.ppu.screen {
+        screen_bytes.push((*pixel & 0xFF) as u8);
+        screen_bytes.push((*pixel >> 8) as u8);
+    }
+    let hash = fnv1a_hash(&screen_bytes, 0xcbf29ce484222325);
+    return fnv1a_hash(&nes.memory.iram_raw, hash);
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let rom = positional(args, 0, "ROM")?;
+    let frames = numeric_option(args, "--frames", 60)?;
+    let mut nes = load_nes(&rom, args)?;
+    if option_value(args, "--debug-port").is_some() {
+        nes.debug_port.enable(hex_option(args, "--debug-port", 0x4018)?);
+    }
+    for _ in 0 .. frames {
+        nes.run_until_vblank();
+        print!("{}", nes.debug_port.take_text());
+    }
+    println!("Ran {} frames, state hash: {:016x}", frames, state_hash(&nes));
+    return Ok(());
+}
+
+fn trace(args: &[String]) -> Result<(), String> {
+    let rom = positional(args, 0, "ROM")?;
+    let frames = numeric_option(args, "--frames", 1)?;
+    let mut nes = load_nes(&rom, args)?;
+    let target_frame = nes.ppu.current_frame + frames;
+    while nes.ppu.current_frame < target_frame {
+        let pc = nes.registers.pc;
+        let opcode = memory::debug_read_byte(&nes, pc);
+        let data1 = memory::debug_read_byte(&nes, pc.wrapping_add(1));
+        let data2 = memory::debug_read_byte(&nes, pc.wrapping_add(2));
+        let (instruction, data_bytes) = opcode_info::disassemble_instruction(opcode, data1, data2);
+        let operand = match data_bytes {
+            1 => format!("{:02X}", data1),
+            2 => format!("{:02X}{:02X}", data2, data1),
+            _ => String::from(""),
+        };
+        let bank_offset = match nes.mapper.resolve_prg_offset(pc) {
+            Some((bank, rom_offset)) => format!("{:02X}:{:05X}", bank, rom_offset),
+            None => String::from("--:-----"),
+        };
+        println!("{} {:04X}  {:02X}  {:<12} {:<6} A:{:02X} X:{:02X} Y:{:02X} P:{:02X} SP:{:02X} SL:{} CYC:{}",
+            bank_offset, pc, opcode, instruction, operand,
+            nes.registers.a, nes.registers.x, nes.registers.y,
+            nes.registers.status_as_byte(false), nes.registers.s,
+            nes.ppu.current_scanline, nes.ppu.current_scanline_cycle);
+        nes.step();
+    }
+    return Ok(());
+}
+
+fn write_ppm(nes: &NesState, filename: &str) -> Result<(), String> {
+    let mut file = File::create(filename).map_err(|e| format!("Could not create {}: {}", filename, e))?;
+    let mut data = Vec::new();
+    data.extend_from_slice(b"P6\n256 240\n255\n");
+    for pixel in &nes.ppu.screen {
+        let palette_index = ((*pixel as usize) & 0x1FF) * 3;
+        data.push(NTSC_PAL[palette_index]);
+        data.push(NTSC_PAL[palette_index + 1]);
+        data.push(NTSC_PAL[palette_index + 2]);
+    }
+    file.write_all(&data).map_err(|e| format!("Could not write {}: {}", filename, e))?;
+    return Ok(());
+}
+
+fn screenshot(args: &[String]) -> Result<(), String> {
+    let rom = positional(args, 0, "ROM")?;
+    let frame = numeric_option(args, "--frame", 60)?;
+    let out = option_value(args, "--out").unwrap_or(String::from("screenshot.ppm"));
+    let mut nes = load_nes(&rom, args)?;
+    for _ in 0 .. frame {
+        nes.run_until_vblank();
+    }
+    write_ppm(&nes, &out)?;
+    println!("Wrote frame {} to {}", frame, out);
+    return Ok(());
+}
+
+fn verify_movie(args: &[String]) -> Result<(), String> {
+    let rom = positional(args, 0, "ROM")?;
+    let movie = positional(args, 1, "MOVIE")?;
+    let movie_data = read_file(&movie)?;
+    let movie = Movie::from_fm2(&String::from_utf8_lossy(&movie_data))?;
+    let frame_count = movie.len();
+    let mut player = MoviePlayer::new(movie, MergePolicy::MovieOnly);
+    let mut nes = load_nes(&rom, args)?;
+    player.begin(&mut nes, |_, _| Err(String::from("This movie starts from a savestate, which the headless frontend can't load")))?;
+    while !player.finished() {
+        player.apply(&mut nes);
+        nes.run_until_vblank();
+    }
+    let hash = format!("{:016x}", state_hash(&nes));
+    println!("Played {} frames, state hash: {}", frame_count, hash);
+    return match option_value(args, "--expect") {
+        Some(expected) => {
+            if expected.to_lowercase() == hash {
+                println!("Movie verified.");
+                Ok(())
+            } else {
+                Err(format!("Hash mismatch! Expected {}, got {}", expected, hash))
+            }
+        },
+        None => Ok(())
+    }
+}
+
+fn write_wav(samples: &[i16], sample_rate: u32, filename: &str) -> Result<(), String> {
+    let mut file = File::create(filename).map_err(|e| format!("Could not create {}: {}", filename, e))?;
+    let data_size = (samples.len() * 2) as u32;
+    let mut data = Vec::new();
+    data.extend_from_slice(b"RIFF");
+    data.extend_from_slice(&(36 + data_size).to_le_bytes());
+    data.extend_from_slice(b"WAVE");
+    data.extend_from_slice(b"fmt ");
+    data.extend_from_slice(&16u32.to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    data.extend_from_slice(&1u16.to_le_bytes()); // Mono
+    data.extend_from_slice(&sample_rate.to_le_bytes());
+    data.extend_from_slice(&(sample_rate * 2).to_le_bytes());
+    data.extend_from_slice(&2u16.to_le_bytes());
+    data.extend_from_slice(&16u16.to_le_bytes());
+    data.extend_from_slice(b"data");
+    data.extend_from_slice(&data_size.to_le_bytes());
+    for sample in samples {
+        data.extend_from_slice(&sample.to_le_bytes());
+    }
+    file.write_all(&data).map_err(|e| format!("Could not write {}: {}", filename, e))?;
+    return Ok(());
+}
+
+fn play_nsf(args: &[String]) -> Result<(), String> {
+    let nsf = positional(args, 0, "NSF")?;
+    let seconds = numeric_option(args, "--seconds", 30)?;
+    let out = option_value(args, "--wav").unwrap_or(String::from("output.wav"));
+    let mut nes = load_nes(&nsf, args)?;
+    let track = track_option(args, &nes)?;
+    nes.apu.set_integer_mixing(args.iter().any(|arg| arg == "--integer-mix"));
+    nes.mapper.nsf_set_track(track);
+    let seek = numeric_option(args, "--seek", 0)?;
+    if seek > 0 {
+        nes.mapper.nsf_seek(seek);
+    }
+    let sample_rate = nes.apu.sample_rate;
+    let total_samples = (sample_rate * seconds as u64) as usize;
+    let mut samples: Vec<i16> = Vec::with_capacity(total_samples);
+    while samples.len() < total_samples {
+        nes.run_until_vblank();
+        samples.extend(nes.apu.consume_samples());
+    }
+    samples.truncate(total_samples);
+    write_wav(&samples, sample_rate as u32, &out)?;
+    println!("Wrote {} seconds of track {} to {}", seconds, track, out);
+    // Compare between machines to check that audio output is deterministic
+    let mut sample_bytes = Vec::with_capacity(samples.len() * 2);
+    for sample in &samples {
+        sample_bytes.extend_from_slice(&sample.to_le_bytes());
+    }
+    println!("Sample hash: {:016x}", fnv1a_hash(&sample_bytes, 0xcbf29ce484222325));
+    return Ok(());
+}
+
+// Records the unfiltered mix at the CPU rate as 32-bit float. --rate decimates it to a
+// more manageable rate first; 0 keeps the native rate.
+fn record_hq(args: &[String]) -> Result<(), String> {
+    let nsf = positional(args, 0, "NSF")?;
+    let seconds = numeric_option(args, "--seconds", 30)?;
+    let rate = numeric_option(args, "--rate", 96000)?;
+    let out = option_value(args, "--wav").unwrap_or(String::from("output_hq.wav"));
+    let mut nes = load_nes(&nsf, args)?;
+    let track = track_option(args, &nes)?;
+    nes.mapper.nsf_set_track(track);
+    let seek = numeric_option(args, "--seek", 0)?;
+    if seek > 0 {
+        nes.mapper.nsf_seek(seek);
+    }
+    let mut recording = HqRecording::start(&mut nes.apu);
+    let total_samples = recording.sample_rate as usize * seconds as usize;
+    while recording.samples.len() < total_samples {
+        nes.run_until_vblank();
+        recording.append_from(&mut nes.apu);
+    }
+    recording.samples.truncate(total_samples);
+    if rate > 0 {
+        recording = recording.decimated(rate);
+    }
+    let mut file = File::create(&out).map_err(|e| format!("Could not create {}: {}", out, e))?;
+    file.write_all(&recording.wav_file()).map_err(|e| format!("Could not write {}: {}", out, e))?;
+    println!("Wrote {} seconds of track {} to {} at {} Hz", seconds, track, out, recording.sample_rate);
+    return Ok(());
+}
+
+// Plays the ROM with no input for a while, and writes every DMC
+// sample heard as .dmc and .wav files
+fn dump_dpcm(args: &[String]) -> Result<(), String> {
+    let rom = positional(args, 0, "ROM")?;
+    let frames = numeric_option(args, "--frames", 60 * 60)?;
+    let out = option_value(args, "--out").unwrap_or(String::from("."));
+    let mut nes = load_nes(&rom, args)?;
+    let mut library = DpcmSampleLibrary::new();
+    DpcmSampleLibrary::start_capture(&mut nes);
+    for _ in 0 .. frames {
+        nes.run_until_vblank();
+        let _ = nes.apu.consume_samples();
+        library.collect(&mut nes);
+    }
+    for sample in &library.samples {
+        let stem = format!("{}/{}", out, sample.file_stem());
+        let rates: Vec<u32> = sample.period_indices.iter().map(|&index| DumpedSample::playback_rate(&nes, index)).collect();
+        File::create(format!("{}.dmc", stem)).and_then(|mut file| file.write_all(&sample.dmc_file()))
+            .map_err(|e| format!("Could not write {}.dmc: {}", stem, e))?;
+        File::create(format!("{}.wav", stem)).and_then(|mut file| file.write_all(&sample.wav_file(rates[0])))
+            .map_err(|e| format!("Could not write {}.wav: {}", stem, e))?;
+        println!("{}: {} bytes, played {} times at {:?} Hz{}", stem, sample.raw_bytes.len(), sample.play_count, rates,
+            if sample.looping {", looping"} else {""});
+    }
+    println!("Dumped {} samples", library.samples.len());
+    return Ok(());
+}
+
+// Saves the pattern tables as the PPU sees them after N frames, optionally along with a
+// tile sheet drawn in one of the game's palettes (grayscale if none is given)
+fn dump_chr(args: &[String]) -> Result<(), String> {
+    let rom = positional(args, 0, "ROM")?;
+    let frame = numeric_option(args, "--frame", 60)?;
+    let out = option_value(args, "--out").unwrap_or(String::from("pattern_tables.chr"));
+    let mut nes = load_nes(&rom, args)?;
+    for _ in 0 .. frame {
+        nes.run_until_vblank();
+    }
+    let chr = chr_dump::banked_chr(&*nes.mapper);
+    File::create(&out).and_then(|mut file| file.write_all(&chr))
+        .map_err(|e| format!("Could not write {}: {}", out, e))?;
+    println!("Wrote {} bytes of CHR to {}", chr.len(), out);
+    if let Some(sheet_filename) = option_value(args, "--sheet") {
+        let colors = match option_value(args, "--palette") {
+            Some(_) => chr_dump::palette_colors(&nes.ppu, &*nes.mapper, numeric_option(args, "--palette", 0)? as u8),
+            None => chr_dump::GRAYSCALE,
+        };
+        let sheet = TileSheet::new(&chr, 16);
+        let mut data = format!("P6\n{} {}\n255\n", sheet.width, sheet.height).into_bytes();
+        for pixel in sheet.rgba(&colors).chunks(4) {
+            data.extend_from_slice(&pixel[0 .. 3]);
+        }
+        File::create(&sheet_filename).and_then(|mut file| file.write_all(&data))
+            .map_err(|e| format!("Could not write {}: {}", sheet_filename, e))?;
+        println!("Wrote {}x{} tile sheet to {}", sheet.width, sheet.height, sheet_filename);
+    }
+    return Ok(());
+}
+
+fn test_rom(args: &[String]) -> Result<(), String> {
+    let rom = positional(args, 0, "ROM")?;
+    let max_frames = numeric_option(args, "--max-frames", 60 * 60)?;
+    let mut nes = load_nes(&rom, args)?;
+    let message = blargg::run_test_rom(&mut nes, max_frames)?;
+    println!("{}", message);
+    println!("Passed.");
+    return Ok(());
+}
+
+// Runs every .nes file in a directory as a test ROM, in filename order, so a whole suite
+// (cpu_interrupts_v2, apu_test, and so on) can be checked in one go
+fn test_suite(args: &[String]) -> Result<(), String> {
+    let directory = positional(args, 0, "DIR")?;
+    let max_frames = numeric_option(args, "--max-frames", 60 * 60)?;
+    let entries = fs::read_dir(&directory).map_err(|e| format!("Couldn't read {}: {}", directory, e))?;
+    let mut roms: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().map(|extension| extension == "nes").unwrap_or(false))
+        .collect();
+    roms.sort();
+    if roms.is_empty() {
+        return Err(format!("No .nes files found in {}", directory));
+    }
+    let mut failures = 0;
+    for path in &roms {
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let result = load_nes(&path.to_string_lossy(), args).and_then(|mut nes| blargg::run_test_rom(&mut nes, max_frames));
+        match result {
+            Ok(_) => println!("PASS {}", name),
+            Err(why) => {
+                failures += 1;
+                println!("FAIL {}: {}", name, why.lines().last().unwrap_or(""));
+            }
+        }
+    }
+    println!("{} of {} passed", roms.len() - failures, roms.len());
+    return match failures {
+        0 => Ok(()),
+        _ => Err(format!("{} test(s) failed", failures))
+    }
+}
+
+// Runs a flat 6502 binary until the CPU traps (an instruction which jumps or branches to
+// itself, the usual way test suites report where they stopped), then compares that address
+// against the expected success trap.
+fn cpu_test(args: &[String]) -> Result<(), String> {
+    let binary = positional(args, 0, "BINARY")?;
+    let load_address = hex_option(args, "--load", 0x0000)?;
+    let max_cycles = numeric_option(args, "--max-cycles", 100_000_000)? as u64;
+    let mut nes = NesState::new(cartridge::mapper_from_flat_binary(&read_file(&binary)?, load_address)?);
+    nes.power_on();
+    if option_value(args, "--start").is_some() {
+        nes.registers.pc = hex_option(args, "--start", 0)?;
+    }
+    loop {
+        let pc = nes.registers.pc;
+        nes.step();
+        if nes.registers.pc == pc {
+            break;
+        }
+        if nes.cpu_cycle() >= max_cycles {
+            return Err(format!("No trap after {} cycles, PC: {:04X}", max_cycles, nes.registers.pc));
+        }
+    }
+    let trap = nes.registers.pc;
+    println!("Trapped at {:04X} after {} cycles", trap, nes.cpu_cycle());
+    return match option_value(args, "--success") {
+        Some(_) => {
+            let success = hex_option(args, "--success", 0)?;
+            if trap == success {
+                println!("Passed.");
+                Ok(())
+            } else {
+                Err(format!("Failed! Expected trap at {:04X}", success))
+            }
+        },
+        None => Ok(())
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    if args.len() < 2 {
+        usage();
+        process::exit(1);
+    }
+    let command_args = &args[2..];
+    let result = match args[1].as_str() {
+        "run" => run(command_args),
+        "trace" => trace(command_args),
+        "screenshot" => screenshot(command_args),
+        "verify-movie" => verify_movie(command_args),
+        "play-nsf" => play_nsf(command_args),
+        "record-hq" => record_hq(command_args),
+        "test-rom" => test_rom(command_args),
+        "test-suite" => test_suite(command_args),
+        "dump-dpcm" => dump_dpcm(command_args),
+        "dump-chr" => dump_chr(command_args),
+        "cpu-test" => cpu_test(command_args),
+        _ => {
+            usage();
+            process::exit(1);
+        }
+    };
+    match result {
+        Ok(_) => {},
+        Err(why) => {
+            println!("Error: {}", why);
+            process::exit(1);
+        }
+    }
+}
@@ -1,3 +1,4 @@
+use instruction_info::BusAccess;
 use nes::NesState;
 
 pub struct CpuMemory {
@@ -5,7 +6,9 @@ pub struct CpuMemory {
 
     pub recent_reads: Vec<u16>,
     pub recent_writes: Vec<u16>,
-    pub open_bus: u8
+    pub open_bus: u8,
+    // While Some, every CPU read and write is appended here; see NesState::step_with_info
+    pub bus_log: Option<Vec<BusAccess>>,
 }
 
 impl CpuMemory {
@@ -15,11 +18,15 @@ impl CpuMemory {
             recent_reads: Vec::new(),
             recent_writes: Vec::new(),
             open_bus: 0,
+            bus_log: None,
         }
     }
 }
 
 pub fn debug_read_byte(nes: &NesState, address: u16) -> u8 {
+    if nes.mapper.flat_address_space() {
+        return nes.mapper.debug_read_cpu(address).unwrap_or(nes.memory.open_bus);
+    }
     // Handle a few special cases for debug reads
     match address {
         0x2000 ..= 0x3FFF => {
@@ -44,7 +51,20 @@ pub fn debug_read_byte(nes: &NesState, address: u16) -> u8 {
 }
 
 pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
+    let data = read_bus_byte(nes, address);
+    if let Some(ref mut bus_log) = nes.memory.bus_log {
+        bus_log.push(BusAccess{address: address, data: data, write: false});
+    }
+    return data;
+}
+
+fn read_bus_byte(nes: &mut NesState, address: u16) -> u8 {
     let mapped_byte = nes.mapper.read_cpu(address).unwrap_or(nes.memory.open_bus);
+    if nes.mapper.flat_address_space() {
+        nes.memory.open_bus = mapped_byte;
+        nes.event_tracker.snoop_cpu_read(nes.registers.pc, address, mapped_byte);
+        return mapped_byte;
+    }
 
     // This is a live read, handle any side effects
     match address {
@@ -98,6 +118,7 @@ pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
             return apu_byte;
         },
         0x4016 => {
+            nes.input_polled = true;
             if nes.input_latch {
                 // strobe register is high, so copy input data to latch (probably bad if this
                 // actually occurs here, but it matches what real hardware would do)
@@ -110,6 +131,7 @@ pub fn read_byte(nes: &mut NesState, address: u16) -> u8 {
             return result;
         },
         0x4017 => {
+            nes.input_polled = true;
             if nes.input_latch {
                 // strobe register is high, so copy input data to latch (probably bad if this
                 // actually occurs here, but it matches what real hardware would do)
@@ -182,10 +204,22 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
     // Track every byte written, unconditionally
     // (filtering is done inside the tracker)
     nes.event_tracker.snoop_cpu_write(nes.registers.pc, address, data);
+    nes.debug_port.watch_write(address, data);
+    if let Some(ref mut bus_log) = nes.memory.bus_log {
+        bus_log.push(BusAccess{address: address, data: data, write: true});
+    }
+    write_byte_untracked(nes, address, data);
+}
 
+// The bus side of a CPU write, with every side effect except for the debug trackers noticing.
+// For the CPU itself, use write_byte.
+pub fn write_byte_untracked(nes: &mut NesState, address: u16, data: u8) {
     // The mapper *always* sees the write. Even to RAM, and even to internal registers.
     // Most mappers ignore writes to addresses below 0x6000. Some (notably MMC5) do not.
     nes.mapper.write_cpu(address, data);
+    if nes.mapper.flat_address_space() {
+        return;
+    }
     match address {
         0x0000 ..= 0x1FFF => nes.memory.iram_raw[(address & 0x7FF) as usize] = data,
         0x2000 ..= 0x3FFF => {
@@ -203,7 +237,7 @@ pub fn write_byte(nes: &mut NesState, address: u16, data: u8) {
                 },
                 // PPU MASK
                 1 => {
-                    nes.ppu.mask = data;
+                    nes.ppu.write_mask(data);
                 },
                 // PPUSTATUS is read-only
                 // OAM ADDRESS
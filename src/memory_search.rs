@@ -0,0 +1,193 @@
+// RAM search, the engine behind cheat finders. Start a search to capture every address in a
+// region, let the game run, then repeatedly narrow the candidates by comparing each
+// address's current value against the value seen at the previous step (or a constant),
+// until only a handful of addresses remain.
+
+use memory;
+use nes::NesState;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchRegion {
+    CpuRam,
+    PrgRam,
+}
+
+impl SearchRegion {
+    pub fn base_address(&self) -> u16 {
+        return match self {
+            SearchRegion::CpuRam => 0x0000,
+            SearchRegion::PrgRam => 0x6000,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        return match self {
+            SearchRegion::CpuRam => 0x800,
+            SearchRegion::PrgRam => 0x2000,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Greater,
+    Less,
+    GreaterOrEqual,
+    LessOrEqual,
+    // current == reference + delta, with 8-bit wraparound
+    ChangedBy(i16),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchTarget {
+    // The value each address held when it was last checked
+    Previous,
+    Value(u8),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SearchResult {
+    pub address: u16,
+    pub previous: u8,
+    pub current: u8,
+}
+
+pub struct MemorySearch {
+    pub region: SearchRegion,
+    // Indexed by offset into the region
+    pub snapshot: Vec<u8>,
+    // The snapshot as it stood before the last filter, which results report as the
+    // previous values
+    pub last_values: Vec<u8>,
+    pub candidates: Vec<u16>,
+}
+
+fn matches(comparison: Comparison, current: u8, reference: u8) -> bool {
+    return match comparison {
+        Comparison::Equal => current == reference,
+        Comparison::NotEqual => current != reference,
+        Comparison::Greater => current > reference,
+        Comparison::Less => current < reference,
+        Comparison::GreaterOrEqual => current >= reference,
+        Comparison::LessOrEqual => current <= reference,
+        Comparison::ChangedBy(delta) => current == (reference as i16).wrapping_add(delta) as u8,
+    }
+}
+
+impl MemorySearch {
+    pub fn new(region: SearchRegion) -> MemorySearch {
+        return MemorySearch {
+            region: region,
+            snapshot: vec!(0u8; region.size()),
+            last_values: vec!(0u8; region.size()),
+            candidates: Vec::new(),
+        }
+    }
+
+    fn read(&self, nes: &NesState, address: u16) -> u8 {
+        return memory::debug_read_byte(nes, address);
+    }
+
+    // Every address in the region becomes a candidate again
+    pub fn start(&mut self, nes: &NesState) {
+        let base = self.region.base_address();
+        self.candidates = (0 .. self.region.size()).map(|offset| base + offset as u16).collect();
+        self.take_snapshot(nes);
+        self.last_values = self.snapshot.clone();
+    }
+
+    // Refreshes the remembered values without discarding any candidates
+    pub fn take_snapshot(&mut self, nes: &NesState) {
+        let base = self.region.base_address();
+        for offset in 0 .. self.snapshot.len() {
+            self.snapshot[offset] = self.read(nes, base + offset as u16);
+        }
+    }
+
+    fn region_offset(&self, address: u16) -> Option<usize> {
+        let offset = address.checked_sub(self.region.base_address())? as usize;
+        if offset >= self.region.size() {
+            return None;
+        }
+        return Some(offset);
+    }
+
+    // The value compared against by the next SearchTarget::Previous filter; None outside
+    // the region
+    pub fn previous_value(&self, address: u16) -> Option<u8> {
+        return self.region_offset(address).map(|offset| self.snapshot[offset]);
+    }
+
+    // Discards every candidate that fails the comparison, then snapshots, so the next
+    // step compares against the values seen now. Returns the remaining candidate count.
+    pub fn filter(&mut self, nes: &NesState, comparison: Comparison, target: SearchTarget) -> usize {
+        let mut remaining = Vec::new();
+        for &address in &self.candidates {
+            let current = self.read(nes, address);
+            let reference = match target {
+                SearchTarget::Previous => self.previous_value(address).unwrap_or(current),
+                SearchTarget::Value(value) => value,
+            };
+            if matches(comparison, current, reference) {
+                remaining.push(address);
+            }
+        }
+        self.candidates = remaining;
+        self.last_values = self.snapshot.clone();
+        self.take_snapshot(nes);
+        return self.candidates.len();
+    }
+
+    pub fn remove(&mut self, address: u16) {
+        self.candidates.retain(|&candidate| candidate != address);
+    }
+
+    pub fn clear(&mut self) {
+        self.candidates.clear();
+    }
+
+    // Each remaining candidate, with the value it held before the last filter
+    pub fn results(&self, nes: &NesState) -> Vec<SearchResult> {
+        return self.candidates.iter().map(|&address| SearchResult {
+            address: address,
+            previous: self.region_offset(address).map_or(0, |offset| self.last_values[offset]),
+            current: self.read(nes, address),
+        }).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ines::INesCartridge;
+    use mmc::nrom::Nrom;
+
+    fn test_nes() -> NesState {
+        return NesState::new(Box::new(Nrom::from_ines(INesCartridge::test_image(0, 2, 1)).unwrap()));
+    }
+
+    #[test]
+    fn results_show_values_from_before_the_filter() {
+        let mut nes = test_nes();
+        let mut search = MemorySearch::new(SearchRegion::CpuRam);
+        nes.memory.iram_raw[0x10] = 3;
+        search.start(&nes);
+        nes.memory.iram_raw[0x10] = 2;
+        search.filter(&nes, Comparison::ChangedBy(-1), SearchTarget::Previous);
+        assert_eq!(search.results(&nes), vec![SearchResult {address: 0x10, previous: 3, current: 2}]);
+        // The next step compares against what the filter saw
+        assert_eq!(search.previous_value(0x10), Some(2));
+    }
+
+    #[test]
+    fn previous_value_outside_the_region_is_none() {
+        let nes = test_nes();
+        let mut search = MemorySearch::new(SearchRegion::PrgRam);
+        search.start(&nes);
+        assert_eq!(search.previous_value(0x5FFF), None);
+        assert_eq!(search.previous_value(0x8000), None);
+        assert!(search.previous_value(0x7FFF).is_some());
+    }
+}
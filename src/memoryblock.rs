@@ -7,7 +7,8 @@
 pub struct MemoryBlock {
     bytes: Vec<u8>,
     readonly: bool,
-    volatile: bool
+    volatile: bool,
+    bank_wrap: BankWrap,
 }
 
 #[derive(PartialEq)]
@@ -17,15 +18,40 @@ pub enum MemoryType {
     NvRam,
 }
 
+/// What a banked access does when the selected bank lies past the end of the chip.
+/// Real boards differ here, so mappers declare which one they are with set_bank_wrap.
+///
+/// Wrap is the default on purpose, and every mapper that doesn't call set_bank_wrap relies
+/// on it: their bank registers are wider than the chips fitted to them, and the unused
+/// upper lines simply aren't connected. That includes the banked PRG RAM on MMC1 (SOROM,
+/// SXROM), FME-7 and Rainbow. Only MMC5 with 32k of PRG RAM, where half the bank numbers
+/// select an empty socket, opts into OpenBus.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum BankWrap {
+    /// The upper bank lines aren't connected, so banks mirror the chip.
+    Wrap,
+    /// Banks past the end select nothing: reads are open bus and writes are ignored.
+    OpenBus,
+}
+
 impl MemoryBlock {
     pub fn new(data: &[u8], memory_type: MemoryType) -> MemoryBlock {
         return MemoryBlock {
             bytes: data.to_vec(),
             readonly: memory_type == MemoryType::Rom,
             volatile: memory_type != MemoryType::NvRam,
+            bank_wrap: BankWrap::Wrap,
         }
     }
 
+    pub fn set_bank_wrap(&mut self, bank_wrap: BankWrap) {
+        self.bank_wrap = bank_wrap;
+    }
+
+    pub fn bank_wrap(&self) -> BankWrap {
+        return self.bank_wrap;
+    }
+
     pub fn len(&self) -> usize {
         return self.bytes.len();
     }
@@ -68,13 +94,83 @@ impl MemoryBlock {
     }
 
     pub fn banked_read(&self, bank_size: usize, bank_index: usize, offset: usize) -> Option<u8> {
-        let effective_address = (bank_size * bank_index) + (offset % bank_size);
-        return self.wrapping_read(effective_address);
+        return self.banked_offset(bank_size, bank_index, offset).map(|address| self.bytes[address]);
     }
 
     pub fn banked_write(&mut self, bank_size: usize, bank_index: usize, offset: usize, data: u8) {
-        let effective_address = (bank_size * bank_index) + (offset % bank_size);
-        self.wrapping_write(effective_address, data);
+        if self.readonly {
+            return;
+        }
+        if let Some(address) = self.banked_offset(bank_size, bank_index, offset) {
+            self.bytes[address] = data;
+        }
+    }
+
+    /// Like wrapping_write, but reports whether the byte landed: false for an empty or
+    /// read only block.
+    pub fn wrapping_poke(&mut self, address: usize, data: u8) -> bool {
+        if self.readonly {
+            return false;
+        }
+        return match self.wrapping_offset(address) {
+            Some(offset) => {self.bytes[offset] = data; true},
+            None => false
+        };
+    }
+
+    /// Like banked_write, but reports whether the byte landed: false for open bus or a
+    /// read only block.
+    pub fn banked_poke(&mut self, bank_size: usize, bank_index: usize, offset: usize, data: u8) -> bool {
+        if self.readonly {
+            return false;
+        }
+        return match self.banked_offset(bank_size, bank_index, offset) {
+            Some(address) => {self.bytes[address] = data; true},
+            None => false
+        };
+    }
+
+    /// Returns the offset into this block that a wrapping read of the given address
+    /// would access, or None if the block is empty.
+    pub fn wrapping_offset(&self, address: usize) -> Option<usize> {
+        if self.bytes.len() == 0 {
+            return None;
+        }
+        return Some(address % self.len());
+    }
+
+    /// Returns the offset into this block that a banked access would reach, or None
+    /// for open bus. Never panics, whatever the bank registers happen to hold.
+    pub fn banked_offset(&self, bank_size: usize, bank_index: usize, offset: usize) -> Option<usize> {
+        let len = self.len();
+        if len == 0 || bank_size == 0 {
+            return None;
+        }
+        let offset = offset % bank_size;
+        return match self.bank_wrap {
+            BankWrap::Wrap => {
+                // Same as (bank_size * bank_index + offset) % len, reduced first so that
+                // absurd bank numbers can't overflow (usize may be 32 bits on wasm)
+                let len = len as u64;
+                let base = ((bank_index as u64 % len) * (bank_size as u64 % len)) % len;
+                Some(((base + offset as u64) % len) as usize)
+            },
+            BankWrap::OpenBus => bank_index.checked_mul(bank_size)
+                .and_then(|base| base.checked_add(offset))
+                .filter(|&address| address < len),
+        }
+    }
+
+    /// Replaces the contents of this block, keeping its size. Data beyond the end is
+    /// dropped and any shortfall is zero filled, so a save file of the wrong size can't
+    /// resize the chip.
+    pub fn load(&mut self, data: &[u8]) {
+        let len = self.len();
+        let copy_len = len.min(data.len());
+        self.bytes[.. copy_len].copy_from_slice(&data[.. copy_len]);
+        for byte in self.bytes[copy_len ..].iter_mut() {
+            *byte = 0;
+        }
     }
 
     pub fn as_vec(&self) -> &Vec<u8> {
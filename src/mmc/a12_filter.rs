@@ -0,0 +1,47 @@
+// Scanline counters in the MMC3 family clock on rising edges of PPU A12. Sprite fetches
+// raise A12 several times per line, so the edge is filtered: A12 has to stay low for a
+// few M2 (CPU) cycles before another rise counts.
+// https://wiki.nesdev.com/w/index.php/MMC3#IRQ_Specifics
+
+pub struct A12Filter {
+    pub last_a12: u8,
+    pub filtered_a12: u8,
+    pub low_a12_counter: u8,
+}
+
+impl A12Filter {
+    pub fn new() -> A12Filter {
+        return A12Filter {
+            last_a12: 0,
+            filtered_a12: 0,
+            low_a12_counter: 0,
+        }
+    }
+
+    // Call with every address the PPU puts on the bus; returns true on a filtered rising
+    // edge, which is when the scanline counter should be clocked
+    pub fn snoop_ppu(&mut self, address: u16) -> bool {
+        let current_a12 = ((address & 0b0001_0000_0000_0000) >> 12) as u8;
+        let last_filtered_a12 = self.filtered_a12;
+
+        if current_a12 == 1 {
+            self.filtered_a12 = 1;
+            self.low_a12_counter = 0;
+        }
+
+        // Caching this value so the M2 counter can see it
+        self.last_a12 = current_a12;
+
+        return (self.filtered_a12 == 1) && (last_filtered_a12 == 0);
+    }
+
+    // Call once per CPU cycle
+    pub fn snoop_cpu_m2(&mut self) {
+        if self.low_a12_counter < 255 && self.last_a12 == 0 {
+            self.low_a12_counter += 1;
+        }
+        if self.low_a12_counter >= 3 {
+            self.filtered_a12 = 0;
+        }
+    }
+}
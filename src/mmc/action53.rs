@@ -1,8 +1,12 @@
-// A very simple Mapper with no esoteric features or bank switching.
-// Reference capabilities: https://wiki.nesdev.com/w/index.php/NROM
+// Action 53, the multicart board behind the NESdev compo and Action 53 volumes. A register
+// select port at $5000 picks which of four registers writes to $8000-$FFFF reach. The outer
+// bank picks a game, and its size decides how many of the inner bank's bits are used,
+// so each game sees a plain NROM, CNROM, UNROM or AOROM style board of its own.
+// Reference capabilities: https://wiki.nesdev.com/w/index.php/Action_53_mapper
 
 use ines::INesCartridge;
 use memoryblock::MemoryBlock;
+use memoryblock::MemoryType;
 
 use mmc::mapper::*;
 use mmc::mirroring;
@@ -25,8 +29,13 @@ pub struct Action53 {
 impl Action53 {
     pub fn from_ines(ines: INesCartridge) -> Result<Action53, String> {
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
-        let chr_block = ines.chr_block()?;
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
+        let mut chr_block = ines.chr_block()?;
+        // The board has 32k of CHR RAM, which iNES 1.0 can't describe; without it the CHR
+        // bank bits would have nothing to select
+        if ines.header.version() != 2 && ines.chr.is_empty() {
+            chr_block = MemoryBlock::new(&[0u8; 0x8000], MemoryType::Ram);
+        }
 
         return Ok(Action53 {
             prg_rom: prg_rom_block.clone(),
@@ -86,6 +95,23 @@ impl Action53 {
 }
 
 impl Mapper for Action53 {
+    fn print_debug_status(&self) {
+        println!("======= Action 53 =======");
+        println!("Register Select: 0x{:02X}, PRG Mode: {}, Outer Bank Size: {}", self.register_select, self.prg_mode, self.prg_outer_bank_size);
+        println!("PRG Outer Bank: {}, PRG Inner Bank: {}, CHR Bank: {}", self.prg_outer_bank, self.prg_inner_bank, self.chr_ram_a13_a14);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring()));
+        println!("====================");
+    }
+
+    fn reset(&mut self) {
+        // Return to the power-on outer bank, which maps the last 32k (and the menu's
+        // reset vector) back in, so pressing reset always exits to the menu
+        self.prg_inner_bank = 0xFF;
+        self.prg_outer_bank = 0xFF;
+        self.prg_mode = 0;
+        self.prg_outer_bank_size = 0;
+    }
+
     fn mirroring(&self) -> Mirroring {
         match self.mirroring_mode {
             0 => Mirroring::OneScreenLower,
@@ -141,6 +167,13 @@ impl Mapper for Action53 {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
         match address {
             0x0000 ..= 0x1FFF => {return self.chr.wrapping_read(self.chr_address(address))},
@@ -168,4 +201,63 @@ impl Mapper for Action53 {
             _ => {}
         }
     }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => {
+                let rom_offset = self.prg_rom.wrapping_offset(self.prg_address(cpu_address))?;
+                Some((rom_offset / 0x4000, rom_offset))
+            },
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_offset(self.chr_address(ppu_address)),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reset_returns_to_the_menu() {
+        // 256k of PRG, so the menu lives in 8k banks 28-31
+        let mut mapper = Action53::from_ines(INesCartridge::test_image(28, 16, 0)).unwrap();
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(28));
+        assert_eq!(mapper.debug_read_cpu(0xFFFF), Some(31));
+
+        // The menu launches a UNROM style game from the first 128k, with the outer bank on
+        // its last 32k so that's what ends up fixed at $C000
+        mapper.write_cpu(0x5000, 0x80);
+        mapper.write_cpu(0x8000, 0b0010_1100);
+        mapper.write_cpu(0x5000, 0x81);
+        mapper.write_cpu(0x8000, 0x03);
+        mapper.write_cpu(0x5000, 0x01);
+        mapper.write_cpu(0x8000, 0x02);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(4));
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(14));
+
+        mapper.reset();
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(28));
+        assert_eq!(mapper.debug_read_cpu(0xA000), Some(29));
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(30));
+        assert_eq!(mapper.debug_read_cpu(0xFFFF), Some(31));
+    }
 }
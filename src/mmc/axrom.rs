@@ -85,4 +85,30 @@ impl Mapper for AxRom {
             _ => {}
         }
     }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x8000, self.prg_bank, (cpu_address - 0x8000) as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_offset(ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
 }
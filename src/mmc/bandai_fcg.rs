@@ -0,0 +1,347 @@
+// Bandai FCG family (mappers 16, 153, 157 and 159): 1k CHR banking, 16k PRG banking and a 16-bit
+// CPU cycle IRQ counter. The LZ93D50 boards save to a serial EEPROM rather than battery
+// backed RAM: a 256 byte 24C02 on mapper 16, or a 128 byte X24C01 on mapper 159.
+//
+// The older FCG-1 and FCG-2 chips (mapper 16 submapper 4) have no EEPROM, decode their
+// registers at $6000-$7FFF instead, and write the IRQ counter directly rather than through
+// a latch. LZ93D50 boards (submapper 5, and mapper 159) decode only $8000-$FFFF. Without a
+// submapper, both ranges are decoded; games for either chip work with the latch, since
+// they write the counter before enabling it.
+//
+// Two LZ93D50 boards swap the EEPROM for something else. Mapper 153 has 8k of battery
+// backed WRAM, enabled through the EEPROM register's clock bit, and uses bit 0 of the CHR
+// registers as an outer 256k PRG bank. Mapper 157, the Datach Joint ROM System, keeps the
+// 24C02 in the base unit and adds an X24C01 in some game carts, sharing the data line. Both
+// have 8k of unbanked CHR RAM. The Datach's barcode reader answers in bit 3 of the same
+// reads as the EEPROM data line.
+// https://wiki.nesdev.com/w/index.php/Bandai_FCG_board
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+use memoryblock::MemoryType;
+
+use mmc::barcode::BarcodeReader;
+use mmc::eeprom::EepromType;
+use mmc::eeprom::SerialEeprom;
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct BandaiFcg {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub mapper_number: u16,
+    pub chr_banks: Vec<usize>,
+    pub prg_bank: usize,
+    pub mirroring: Mirroring,
+    pub eeprom: Option<SerialEeprom>,
+    // Datach game carts only; clocked by bit 3 of the EEPROM register
+    pub external_eeprom: Option<SerialEeprom>,
+    pub prg_ram: MemoryBlock,
+    pub prg_ram_enabled: bool,
+    pub outer_prg_bank: usize,
+    pub barcode: Option<BarcodeReader>,
+    pub registers_at_6000: bool,
+    pub registers_at_8000: bool,
+    // FCG-1/2: counter writes take effect immediately, with no latch
+    pub fcg: bool,
+
+    pub irq_enabled: bool,
+    pub irq_counter: u16,
+    pub irq_latch: u16,
+    pub irq_pending: bool,
+}
+
+impl BandaiFcg {
+    pub fn from_ines(ines: INesCartridge) -> Result<BandaiFcg, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+        let mapper_number = ines.header.mapper_number();
+        let submapper = if mapper_number == 16 {ines.header.submapper_number()} else {5};
+        let fcg = submapper == 4;
+        let eeprom_type = if mapper_number == 159 {EepromType::X24C01} else {EepromType::X24C02};
+        let mut prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
+        if mapper_number == 153 && prg_ram_block.len() == 0 {
+            prg_ram_block = MemoryBlock::new(&[0u8; 0x2000], MemoryType::NvRam);
+        }
+
+        return Ok(BandaiFcg {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            mapper_number: mapper_number,
+            chr_banks: vec![0usize; 8],
+            prg_bank: 0,
+            mirroring: Mirroring::Vertical,
+            eeprom: if fcg || mapper_number == 153 {None} else {Some(SerialEeprom::new(eeprom_type))},
+            external_eeprom: if mapper_number == 157 {Some(SerialEeprom::new(EepromType::X24C01))} else {None},
+            prg_ram: prg_ram_block.clone(),
+            prg_ram_enabled: false,
+            outer_prg_bank: 0,
+            barcode: if mapper_number == 157 {Some(BarcodeReader::new())} else {None},
+            registers_at_6000: submapper != 5,
+            registers_at_8000: !fcg,
+            fcg: fcg,
+
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_latch: 0,
+            irq_pending: false,
+        })
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return match self.mapper_number {
+            153 | 157 => (address / 0x400) as usize,
+            _ => self.chr_banks[(address / 0x400) as usize]
+        };
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        if self.mapper_number == 153 {
+            let inner_bank = if address < 0xC000 {self.prg_bank} else {0x0F};
+            return (self.outer_prg_bank << 4) | inner_bank;
+        }
+        return if address < 0xC000 {self.prg_bank} else {0xFF};
+    }
+
+    // The EEPROM data line shows up in bit 4, low if either chip pulls it low, and the
+    // barcode reader in bit 3. The rest is open bus.
+    fn eeprom_data_line(&self) -> Option<u8> {
+        if self.eeprom.is_none() && self.external_eeprom.is_none() {
+            return None;
+        }
+        let sda = self.eeprom.iter().chain(self.external_eeprom.iter()).all(|eeprom| eeprom.read_sda());
+        let barcode = self.barcode.as_ref().map_or(0, |reader| reader.read());
+        return Some(((sda as u8) << 4) | barcode);
+    }
+
+    fn write_register(&mut self, register: u16, data: u8) {
+        match register {
+            0x0 ..= 0x7 => {
+                self.chr_banks[register as usize] = data as usize;
+                if self.mapper_number == 153 && register < 4 {
+                    self.outer_prg_bank = data as usize & 0b1;
+                }
+            },
+            0x8 => {self.prg_bank = data as usize & 0x0F;},
+            0x9 => {
+                self.mirroring = match data & 0b11 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+            },
+            0xA => {
+                // The LZ93D50 loads its counter from the latch when enabled
+                self.irq_enabled = (data & 0b1) != 0;
+                if !self.fcg {
+                    self.irq_counter = self.irq_latch;
+                }
+                self.irq_pending = false;
+            },
+            0xB => {
+                self.irq_latch = (self.irq_latch & 0xFF00) | (data as u16);
+                if self.fcg {
+                    self.irq_counter = self.irq_latch;
+                }
+            },
+            0xC => {
+                self.irq_latch = (self.irq_latch & 0x00FF) | ((data as u16) << 8);
+                if self.fcg {
+                    self.irq_counter = self.irq_latch;
+                }
+            },
+            0xD => {
+                if self.mapper_number == 153 {
+                    self.prg_ram_enabled = (data & 0b0010_0000) != 0;
+                }
+                let sda = (data & 0b0100_0000) != 0;
+                if let Some(ref mut eeprom) = self.eeprom {
+                    eeprom.write_lines((data & 0b0010_0000) != 0, sda);
+                }
+                if let Some(ref mut eeprom) = self.external_eeprom {
+                    eeprom.write_lines((data & 0b0000_1000) != 0, sda);
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for BandaiFcg {
+    fn print_debug_status(&self) {
+        println!("======= Bandai FCG =======");
+        println!("Mapper: {}, {}, PRG Bank: {}, CHR: {:?}", self.mapper_number, if self.fcg {"FCG-1/2"} else {"LZ93D50"}, self.prg_bank, self.chr_banks);
+        if self.mapper_number == 153 {
+            println!("Outer PRG Bank: {}, PRG RAM Enabled: {}", self.outer_prg_bank, self.prg_ram_enabled);
+        }
+        println!("IRQ: Counter: {:04X}, Latch: {:04X}, Enabled: {}", self.irq_counter, self.irq_latch, self.irq_enabled);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn clock_cpu(&mut self) {
+        if let Some(ref mut reader) = self.barcode {
+            reader.clock();
+        }
+        if self.irq_enabled {
+            if self.irq_counter == 0 {
+                self.irq_pending = true;
+            }
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+        }
+    }
+
+    fn irq_flag(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        return Some(MapperIrqState {
+            counter: self.irq_counter as u32,
+            latch: self.irq_latch as u32,
+            enabled: self.irq_enabled,
+            pending: self.irq_pending,
+            unit: IrqCounterUnit::CpuCycles,
+            clocks_until_irq: if self.irq_enabled {Some(self.irq_counter as u32 + 1)} else {None},
+        });
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF if self.mapper_number == 153 => {
+                if self.prg_ram_enabled {
+                    self.prg_ram.wrapping_read(address as usize - 0x6000)
+                } else {
+                    None
+                }
+            },
+            0x6000 ..= 0x7FFF => self.eeprom_data_line(),
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, self.prg_bank(address), address as usize & 0x3FFF),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        let decoded = match address {
+            0x6000 ..= 0x7FFF => self.registers_at_6000,
+            0x8000 ..= 0xFFFF => self.registers_at_8000,
+            _ => false
+        };
+        if decoded {
+            self.write_register(address & 0x000F, data);
+        }
+        if self.mapper_number == 153 && self.prg_ram_enabled && (0x6000 ..= 0x7FFF).contains(&address) {
+            self.prg_ram.wrapping_write(address as usize - 0x6000, data);
+        }
+    }
+
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF if self.mapper_number == 153 => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x400, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+                Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x400, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn scan_barcode(&mut self, code: &str) -> Result<(), String> {
+        return match self.barcode {
+            Some(ref mut reader) => reader.scan(code),
+            None => Err(String::from("This cartridge has no barcode reader"))
+        };
+    }
+
+    // EEPROM contents are saved just like battery backed RAM. With two EEPROMs, the base
+    // unit's comes first.
+    fn has_sram(&self) -> bool {
+        return self.eeprom.is_some() || self.mapper_number == 153;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        if self.mapper_number == 153 {
+            return self.prg_ram.as_vec().clone();
+        }
+        let mut sram_data = Vec::new();
+        for eeprom in self.eeprom.iter().chain(self.external_eeprom.iter()) {
+            sram_data.extend_from_slice(&eeprom.data);
+        }
+        return sram_data;
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        if self.mapper_number == 153 {
+            self.prg_ram.load(&sram_data);
+            return;
+        }
+        let mut remaining = &sram_data[..];
+        for eeprom in self.eeprom.iter_mut().chain(self.external_eeprom.iter_mut()) {
+            let size = eeprom.size();
+            let length = size.min(remaining.len());
+            eeprom.data = remaining[.. length].to_vec();
+            eeprom.data.resize(size, 0);
+            remaining = &remaining[length ..];
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank(cpu_address), cpu_address as usize & 0x3FFF),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x400, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
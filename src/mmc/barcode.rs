@@ -0,0 +1,110 @@
+// The Datach barcode reader. Swiping a card produces a serial stream on one data line:
+// spaces read as the line being high, bars as low, with every module (the narrowest bar
+// width) lasting a fixed number of CPU cycles. Games sample the line and decode the code
+// themselves, so all that needs emulating is the stream.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_157
+
+// How long each module is held on the data line, in CPU cycles
+const CYCLES_PER_MODULE: u32 = 1000;
+// Blank spaces either side of the code, like the card's margins
+const QUIET_ZONE_MODULES: usize = 32;
+
+// EAN module patterns for each digit, with 1 for a bar. The right half uses the R codes;
+// the left half mixes L and G codes, and for EAN-13 that mix encodes the first digit.
+const L_CODES: [u8; 10] = [0b0001101, 0b0011001, 0b0010011, 0b0111101, 0b0100011, 0b0110001, 0b0101111, 0b0111011, 0b0110111, 0b0001011];
+const G_CODES: [u8; 10] = [0b0100111, 0b0110011, 0b0011011, 0b0100001, 0b0011101, 0b0111001, 0b0000101, 0b0010001, 0b0001001, 0b0010111];
+const R_CODES: [u8; 10] = [0b1110010, 0b1100110, 0b1101100, 0b1000010, 0b1011100, 0b1001110, 0b1010000, 0b1000100, 0b1001000, 0b1110100];
+// Bit 5 is the second digit, down to bit 0 for the seventh; set bits use G codes
+const FIRST_DIGIT_PARITY: [u8; 10] = [0b000000, 0b001011, 0b001101, 0b001110, 0b010011, 0b011001, 0b011100, 0b010101, 0b010110, 0b011010];
+
+// The last digit of an EAN code, from all the others
+pub fn ean_check_digit(digits: &[u8]) -> u8 {
+    // Weights alternate 3, 1, ... counting back from the digit before the check digit
+    let sum: u32 = digits.iter().rev().enumerate()
+        .map(|(i, &digit)| digit as u32 * if i % 2 == 0 {3} else {1})
+        .sum();
+    return ((10 - (sum % 10)) % 10) as u8;
+}
+
+fn push_pattern(modules: &mut Vec<bool>, pattern: u8, width: usize) {
+    for i in (0 .. width).rev() {
+        modules.push((pattern >> i) & 0b1 != 0);
+    }
+}
+
+// Encodes an EAN-13 or EAN-8 code as modules, with true for a bar. Codes may leave off
+// the check digit (12 or 7 digits), in which case it is calculated.
+pub fn ean_modules(code: &str) -> Result<Vec<bool>, String> {
+    let mut digits: Vec<u8> = Vec::new();
+    for c in code.trim().chars() {
+        match c.to_digit(10) {
+            Some(digit) => digits.push(digit as u8),
+            None => return Err(format!("Invalid barcode digit: {}", c)),
+        }
+    }
+    if digits.len() == 12 || digits.len() == 7 {
+        let check_digit = ean_check_digit(&digits);
+        digits.push(check_digit);
+    }
+    let (left, right, parity) = match digits.len() {
+        13 => (&digits[1 .. 7], &digits[7 .. 13], FIRST_DIGIT_PARITY[digits[0] as usize]),
+        8 => (&digits[0 .. 4], &digits[4 .. 8], 0),
+        _ => return Err(format!("Barcodes must have 13 or 8 digits, got {}", digits.len())),
+    };
+
+    let mut modules = vec![false; QUIET_ZONE_MODULES];
+    push_pattern(&mut modules, 0b101, 3);
+    for (i, &digit) in left.iter().enumerate() {
+        let even_parity = (parity >> (left.len() - 1 - i)) & 0b1 != 0;
+        let pattern = if even_parity {G_CODES[digit as usize]} else {L_CODES[digit as usize]};
+        push_pattern(&mut modules, pattern, 7);
+    }
+    push_pattern(&mut modules, 0b01010, 5);
+    for &digit in right {
+        push_pattern(&mut modules, R_CODES[digit as usize], 7);
+    }
+    push_pattern(&mut modules, 0b101, 3);
+    modules.extend_from_slice(&[false; QUIET_ZONE_MODULES]);
+    return Ok(modules);
+}
+
+pub struct BarcodeReader {
+    pub modules: Vec<bool>,
+    pub cycles: u32,
+}
+
+impl BarcodeReader {
+    pub fn new() -> BarcodeReader {
+        return BarcodeReader {
+            modules: Vec::new(),
+            cycles: 0,
+        }
+    }
+
+    // Starts a swipe of the given code, replacing any swipe in progress
+    pub fn scan(&mut self, code: &str) -> Result<(), String> {
+        self.modules = ean_modules(code)?;
+        self.cycles = 0;
+        return Ok(());
+    }
+
+    pub fn scanning(&self) -> bool {
+        return (self.cycles / CYCLES_PER_MODULE) < self.modules.len() as u32;
+    }
+
+    pub fn clock(&mut self) {
+        if self.scanning() {
+            self.cycles += 1;
+        }
+    }
+
+    // The data line as it appears in bit 3 of a read. It sits low when no card is passing
+    // through the reader.
+    pub fn read(&self) -> u8 {
+        let module = (self.cycles / CYCLES_PER_MODULE) as usize;
+        return match self.modules.get(module) {
+            Some(&bar) => if bar {0} else {0b0000_1000},
+            None => 0
+        };
+    }
+}
@@ -0,0 +1,129 @@
+// Camerica BF9093/BF9097, used by most of the Codemasters games: UxROM style banking
+// with the register at $C000-$FFFF instead. The BF9097 on Fire Hawk (submapper 1) adds
+// one-screen mirroring control at $8000-$9FFF.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_071
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Bf9093 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mirroring: Mirroring,
+    pub fire_hawk: bool,
+    pub prg_bank: usize,
+    pub vram: Vec<u8>,
+}
+
+impl Bf9093 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Bf9093, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+        let fire_hawk = ines.header.submapper_number() == 1;
+
+        return Ok(Bf9093 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            // Fire Hawk sets its own mirroring; the header's is meaningless there
+            mirroring: if fire_hawk {Mirroring::OneScreenLower} else {ines.header.mirroring()},
+            fire_hawk: fire_hawk,
+            prg_bank: 0,
+            vram: vec![0u8; 0x1000],
+        })
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            Mirroring::OneScreenLower => Some(mirroring::one_screen_lower(address) as usize),
+            Mirroring::OneScreenUpper => Some(mirroring::one_screen_upper(address) as usize),
+            _ => None
+        };
+    }
+}
+
+impl Mapper for Bf9093 {
+    fn print_debug_status(&self) {
+        println!("======= BF9093 =======");
+        println!("PRG Bank: {}, Fire Hawk: {}", self.prg_bank, self.fire_hawk);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, 0xFF, address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x8000 ..= 0x9FFF => {
+                if self.fire_hawk {
+                    self.mirroring = if (data & 0b0001_0000) == 0 {Mirroring::OneScreenLower} else {Mirroring::OneScreenUpper};
+                }
+            },
+            0xC000 ..= 0xFFFF => {
+                self.prg_bank = data as usize & 0b0000_1111;
+            },
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_read(address as usize),
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|vram_address| self.vram[vram_address]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_write(address as usize, data),
+            0x2000 ..= 0x3FFF => {
+                if let Some(vram_address) = self.nametable_address(address) {
+                    self.vram[vram_address] = data;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank, cpu_address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x4000, 0xFF, cpu_address as usize - 0xC000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_offset(ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
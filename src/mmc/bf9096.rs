@@ -0,0 +1,132 @@
+// Camerica BF9096, used by the Quattro multicarts: UxROM style banking within one of
+// four 64k blocks. The Aladdin Deck Enhancer (submapper 1) wires the two block bits in
+// the opposite order.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_232
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Bf9096 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mirroring: Mirroring,
+    pub aladdin: bool,
+    pub prg_block: usize,
+    pub prg_bank: usize,
+    pub vram: Vec<u8>,
+}
+
+impl Bf9096 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Bf9096, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Bf9096 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            mirroring: ines.header.mirroring(),
+            aladdin: ines.header.submapper_number() == 1,
+            prg_block: 0,
+            prg_bank: 0,
+            vram: vec![0u8; 0x1000],
+        })
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match address {
+            0x8000 ..= 0xBFFF => (self.prg_block << 2) | self.prg_bank,
+            _ => (self.prg_block << 2) | 0b11,
+        }
+    }
+}
+
+impl Mapper for Bf9096 {
+    fn print_debug_status(&self) {
+        println!("======= BF9096 =======");
+        println!("PRG Block: {}, PRG Bank: {}, Aladdin: {}", self.prg_block, self.prg_bank, self.aladdin);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            // Block select; games write to $8000-$9FFF, but the chip decodes $A000-$BFFF too
+            0x8000 ..= 0xBFFF => {
+                let block = (data as usize & 0b0001_1000) >> 3;
+                self.prg_block = if self.aladdin {
+                    ((block & 0b01) << 1) | ((block & 0b10) >> 1)
+                } else {
+                    block
+                };
+            },
+            0xC000 ..= 0xFFFF => {
+                self.prg_bank = data as usize & 0b11;
+            },
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_read(address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_write(address as usize, data),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_offset(ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
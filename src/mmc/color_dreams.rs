@@ -0,0 +1,112 @@
+// Color Dreams (mapper 11), the unlicensed cousin of GxROM: a single register selects
+// a 32k PRG bank and an 8k CHR bank, with the fields in the opposite nybbles. The board
+// has no bus conflict prevention, so writes are ANDed with the ROM byte underneath.
+// https://wiki.nesdev.com/w/index.php/Color_Dreams
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct ColorDreams {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mirroring: Mirroring,
+    pub prg_bank: usize,
+    pub chr_bank: usize,
+    pub vram: Vec<u8>,
+}
+
+impl ColorDreams {
+    pub fn from_ines(ines: INesCartridge) -> Result<ColorDreams, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(ColorDreams {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            mirroring: ines.header.mirroring(),
+            prg_bank: 0x00,
+            chr_bank: 0x00,
+            vram: vec![0u8; 0x1000],
+        });
+    }
+}
+
+impl Mapper for ColorDreams {
+    fn print_debug_status(&self) {
+        println!("======= Color Dreams =======");
+        println!("PRG Bank: {}, CHR Bank: {}, Mirroring Mode: {}", self.prg_bank, self.chr_bank, mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => {self.prg_rom.banked_read(0x8000, self.prg_bank, (address - 0x8000) as usize)},
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        if address >= 0x8000 {
+            let data = data & self.debug_read_cpu(address).unwrap_or(0xFF);
+            self.prg_bank =  (data & 0b0000_0011) as usize;
+            self.chr_bank = ((data & 0b1111_0000) >> 4) as usize;
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x2000, self.chr_bank, address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_write(0x2000, self.chr_bank, address as usize, data),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x8000, self.prg_bank, (cpu_address - 0x8000) as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x2000, self.chr_bank, ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
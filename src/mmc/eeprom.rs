@@ -0,0 +1,361 @@
+// Bit banged serial EEPROMs, used by Bandai boards in place of battery backed RAM. The
+// mapper passes along the clock (SCL) and data (SDA) lines whenever the game writes them,
+// and reads the chip's data output back.
+//
+// Two devices are supported. The 24C02 (256 bytes) follows the usual I²C protocol: a
+// device address byte, then a word address, both MSB first. The older X24C01 (128 bytes)
+// predates it: the byte after a start condition carries a 7-bit word address and the
+// read/write bit, and everything is sent LSB first.
+// https://wiki.nesdev.com/w/index.php/Bandai_FCG_board#Serial_EEPROM
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EepromType {
+    X24C01,
+    X24C02,
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum EepromMode {
+    Idle,
+    DeviceAddress,
+    WordAddress,
+    Write,
+    Read,
+}
+
+pub struct SerialEeprom {
+    pub eeprom_type: EepromType,
+    pub data: Vec<u8>,
+    pub mode: EepromMode,
+    pub next_mode: EepromMode,
+    pub address: u8,
+    // Bits of the current byte clocked so far; 8 is the acknowledge clock
+    pub bit: u8,
+    pub shift_register: u8,
+    pub acknowledge: bool,
+    pub scl: bool,
+    pub sda: bool,
+    // The chip's side of the open drain data line; true when released (high)
+    pub output: bool,
+}
+
+impl SerialEeprom {
+    pub fn new(eeprom_type: EepromType) -> SerialEeprom {
+        let size = match eeprom_type {
+            EepromType::X24C01 => 128,
+            EepromType::X24C02 => 256,
+        };
+        return SerialEeprom {
+            eeprom_type: eeprom_type,
+            data: vec![0u8; size],
+            mode: EepromMode::Idle,
+            next_mode: EepromMode::Idle,
+            address: 0,
+            bit: 0,
+            shift_register: 0,
+            acknowledge: false,
+            scl: false,
+            sda: false,
+            output: true,
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        return self.data.len();
+    }
+
+    // The level a read of the data line sees: low if either side pulls it low
+    pub fn read_sda(&self) -> bool {
+        return self.output && self.sda;
+    }
+
+    pub fn write_lines(&mut self, scl: bool, sda: bool) {
+        let last_scl = self.scl;
+        let last_sda = self.sda;
+        self.scl = scl;
+        self.sda = sda;
+
+        if last_scl && scl {
+            // With the clock high, a change on the data line is a start or stop condition
+            if last_sda && !sda {
+                self.start();
+            } else if !last_sda && sda {
+                self.stop();
+            }
+        } else if !last_scl && scl {
+            self.clock_rising(sda);
+        } else if last_scl && !scl {
+            self.clock_falling();
+        }
+    }
+
+    fn start(&mut self) {
+        self.mode = match self.eeprom_type {
+            EepromType::X24C01 => EepromMode::WordAddress,
+            EepromType::X24C02 => EepromMode::DeviceAddress,
+        };
+        self.bit = 0;
+        self.shift_register = 0;
+        self.output = true;
+    }
+
+    fn stop(&mut self) {
+        self.mode = EepromMode::Idle;
+        self.output = true;
+    }
+
+    fn lsb_first(&self) -> bool {
+        return self.eeprom_type == EepromType::X24C01;
+    }
+
+    fn clock_rising(&mut self, sda: bool) {
+        match self.mode {
+            EepromMode::Idle => {},
+            EepromMode::Read => {
+                if self.bit < 8 {
+                    self.bit += 1;
+                } else {
+                    // The controller acknowledges each byte it wants more after; without
+                    // that, the chip waits for a stop condition
+                    self.bit = 0;
+                    if sda {
+                        self.mode = EepromMode::Idle;
+                    } else {
+                        self.advance_address();
+                    }
+                }
+            },
+            _ => {
+                if self.bit < 8 {
+                    let bit = sda as u8;
+                    self.shift_register = if self.lsb_first() {
+                        (self.shift_register >> 1) | (bit << 7)
+                    } else {
+                        (self.shift_register << 1) | bit
+                    };
+                    self.bit += 1;
+                    if self.bit == 8 {
+                        let byte = self.shift_register;
+                        self.receive_byte(byte);
+                    }
+                } else {
+                    self.bit = 0;
+                    self.shift_register = 0;
+                    self.mode = self.next_mode;
+                }
+            }
+        }
+    }
+
+    // The chip changes its output while the clock is low, so that it's stable by the time
+    // the controller samples it
+    fn clock_falling(&mut self) {
+        self.output = match self.mode {
+            EepromMode::Idle => true,
+            EepromMode::Read => {
+                if self.bit < 8 {
+                    let byte = self.data[self.address as usize % self.data.len()];
+                    let shift = if self.lsb_first() {self.bit} else {7 - self.bit};
+                    ((byte >> shift) & 0b1) != 0
+                } else {
+                    true
+                }
+            },
+            _ => !(self.bit == 8 && self.acknowledge),
+        };
+    }
+
+    fn receive_byte(&mut self, byte: u8) {
+        self.acknowledge = true;
+        match (self.eeprom_type, self.mode) {
+            (EepromType::X24C01, EepromMode::WordAddress) => {
+                self.address = byte & 0x7F;
+                self.next_mode = if (byte & 0x80) != 0 {EepromMode::Read} else {EepromMode::Write};
+            },
+            (EepromType::X24C02, EepromMode::DeviceAddress) => {
+                if (byte & 0xF0) == 0xA0 {
+                    self.next_mode = if (byte & 0x01) != 0 {EepromMode::Read} else {EepromMode::WordAddress};
+                } else {
+                    self.acknowledge = false;
+                    self.next_mode = EepromMode::Idle;
+                }
+            },
+            (EepromType::X24C02, EepromMode::WordAddress) => {
+                self.address = byte;
+                self.next_mode = EepromMode::Write;
+            },
+            (_, EepromMode::Write) => {
+                let index = self.address as usize % self.data.len();
+                self.data[index] = byte;
+                // Writes wrap within a page: 4 bytes on the X24C01, 8 on the 24C02
+                let page_mask = match self.eeprom_type {
+                    EepromType::X24C01 => 0x03,
+                    EepromType::X24C02 => 0x07,
+                };
+                self.address = (self.address & !page_mask) | (self.address.wrapping_add(1) & page_mask);
+                self.next_mode = EepromMode::Write;
+            },
+            _ => {}
+        }
+    }
+
+    // Sequential reads run through the whole chip
+    fn advance_address(&mut self) {
+        self.address = ((self.address as usize + 1) % self.data.len()) as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Drives both lines the way a mapper's register writes would, one edge at a time
+    struct Controller {
+        eeprom: SerialEeprom,
+        lsb_first: bool,
+    }
+
+    impl Controller {
+        fn new(eeprom_type: EepromType) -> Controller {
+            return Controller {
+                eeprom: SerialEeprom::new(eeprom_type),
+                lsb_first: eeprom_type == EepromType::X24C01,
+            }
+        }
+
+        fn start(&mut self) {
+            self.eeprom.write_lines(false, true);
+            self.eeprom.write_lines(true, true);
+            self.eeprom.write_lines(true, false);
+            self.eeprom.write_lines(false, false);
+        }
+
+        fn stop(&mut self) {
+            self.eeprom.write_lines(false, false);
+            self.eeprom.write_lines(true, false);
+            self.eeprom.write_lines(true, true);
+        }
+
+        fn clock_bit(&mut self, sda: bool) {
+            self.eeprom.write_lines(false, sda);
+            self.eeprom.write_lines(true, sda);
+            self.eeprom.write_lines(false, sda);
+        }
+
+        // Returns whether the chip acknowledged the byte
+        fn send_byte(&mut self, byte: u8) -> bool {
+            for i in 0 .. 8 {
+                let shift = if self.lsb_first {i} else {7 - i};
+                self.clock_bit(((byte >> shift) & 0b1) != 0);
+            }
+            self.eeprom.write_lines(false, true);
+            let acknowledged = !self.eeprom.read_sda();
+            self.clock_bit(true);
+            return acknowledged;
+        }
+
+        fn read_byte(&mut self, acknowledge: bool) -> u8 {
+            let mut byte = 0;
+            for i in 0 .. 8 {
+                self.eeprom.write_lines(false, true);
+                let shift = if self.lsb_first {i} else {7 - i};
+                byte |= (self.eeprom.read_sda() as u8) << shift;
+                self.clock_bit(true);
+            }
+            self.clock_bit(!acknowledge);
+            return byte;
+        }
+    }
+
+    #[test]
+    fn x24c01_write_and_random_read() {
+        let mut controller = Controller::new(EepromType::X24C01);
+        controller.start();
+        // 7-bit word address, R/W clear
+        assert!(controller.send_byte(0x05));
+        assert!(controller.send_byte(0x12));
+        controller.stop();
+        assert_eq!(controller.eeprom.data[0x05], 0x12);
+
+        controller.start();
+        assert!(controller.send_byte(0x80 | 0x05));
+        assert_eq!(controller.read_byte(false), 0x12);
+        controller.stop();
+    }
+
+    #[test]
+    fn x24c01_page_write_wraps_and_sequential_read_does_not() {
+        let mut controller = Controller::new(EepromType::X24C01);
+        controller.start();
+        assert!(controller.send_byte(0x06));
+        for byte in [0xA0, 0xA1, 0xA2, 0xA3, 0xA4].iter() {
+            assert!(controller.send_byte(*byte));
+        }
+        controller.stop();
+        // 4 byte pages: $06, $07, then back around to $04
+        assert_eq!(&controller.eeprom.data[0x04 .. 0x08], &[0xA2, 0xA3, 0xA4, 0xA1]);
+
+        controller.eeprom.data[0x08] = 0x55;
+        controller.start();
+        assert!(controller.send_byte(0x80 | 0x06));
+        assert_eq!(controller.read_byte(true), 0xA4);
+        assert_eq!(controller.read_byte(true), 0xA1);
+        assert_eq!(controller.read_byte(false), 0x55);
+        controller.stop();
+    }
+
+    #[test]
+    fn x24c02_write_and_random_read() {
+        let mut controller = Controller::new(EepromType::X24C02);
+        controller.start();
+        assert!(controller.send_byte(0xA0));
+        assert!(controller.send_byte(0x42));
+        assert!(controller.send_byte(0x99));
+        controller.stop();
+        assert_eq!(controller.eeprom.data[0x42], 0x99);
+
+        // Random read: a dummy write sets the address, then a repeated start reads
+        controller.start();
+        assert!(controller.send_byte(0xA0));
+        assert!(controller.send_byte(0x42));
+        controller.start();
+        assert!(controller.send_byte(0xA1));
+        assert_eq!(controller.read_byte(false), 0x99);
+        controller.stop();
+    }
+
+    #[test]
+    fn x24c02_page_write_wraps_and_sequential_read_does_not() {
+        let mut controller = Controller::new(EepromType::X24C02);
+        controller.start();
+        assert!(controller.send_byte(0xA0));
+        assert!(controller.send_byte(0x0E));
+        for byte in 0 .. 9u8 {
+            assert!(controller.send_byte(0x10 + byte));
+        }
+        controller.stop();
+        // 8 byte pages: $0E, $0F, then $08 through $0E again
+        assert_eq!(&controller.eeprom.data[0x08 .. 0x10], &[0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x11]);
+
+        controller.eeprom.data[0x10] = 0x55;
+        controller.start();
+        assert!(controller.send_byte(0xA0));
+        assert!(controller.send_byte(0x0E));
+        controller.start();
+        assert!(controller.send_byte(0xA1));
+        assert_eq!(controller.read_byte(true), 0x18);
+        assert_eq!(controller.read_byte(true), 0x11);
+        assert_eq!(controller.read_byte(false), 0x55);
+        controller.stop();
+    }
+
+    #[test]
+    fn x24c02_ignores_other_devices() {
+        let mut controller = Controller::new(EepromType::X24C02);
+        controller.start();
+        assert!(!controller.send_byte(0xB0));
+        assert!(!controller.send_byte(0x00));
+        controller.stop();
+        assert!(controller.eeprom.data.iter().all(|&byte| byte == 0));
+    }
+}
@@ -310,6 +310,10 @@ impl Mapper for FdsMapper {
             nes_sample;
     }
 
+    fn has_irq(&self) -> bool {
+        return true;
+    }
+
     fn irq_flag(&self) -> bool {
         return self.timer_pending || self.disk_irq_pending;
     }
@@ -447,6 +451,13 @@ impl Mapper for FdsMapper {
         self.audio.write_cpu(address, data);
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => {self.prg_ram[address as usize - 0x6000] = data; true},
+            _ => false
+        };
+    }
+
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
         match address {
             0x0000 ..= 0x1FFF => return Some(self.chr[address as usize]),
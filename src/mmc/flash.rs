@@ -0,0 +1,102 @@
+// SST39SF010A/020A/040 parallel flash, used as self-writable PRG ROM by several homebrew
+// boards. Programming and erasing are unlocked by writing a fixed sequence of bytes to
+// $5555 and $2AAA (in the chip's own address space), then issuing the command. Programs
+// can only clear bits; erasing sets a 4k sector (or the whole chip) back to $FF. Both
+// complete instantly here, so the status polling games do always reads back done.
+// http://ww1.microchip.com/downloads/en/DeviceDoc/20005022C.pdf
+
+use memoryblock::MemoryBlock;
+
+const MANUFACTURER_ID: u8 = 0xBF;
+const SECTOR_SIZE: usize = 0x1000;
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum FlashMode {
+    Ready,
+    Unlock1,
+    Unlock2,
+    ByteProgram,
+    EraseUnlock1,
+    EraseUnlock2,
+    EraseCommand,
+}
+
+pub struct SstFlash {
+    pub mode: FlashMode,
+    // Software ID mode; reads return the manufacturer and device IDs instead of data
+    pub id_mode: bool,
+}
+
+impl SstFlash {
+    pub fn new() -> SstFlash {
+        return SstFlash {
+            mode: FlashMode::Ready,
+            id_mode: false,
+        }
+    }
+
+    fn device_id(chip: &MemoryBlock) -> u8 {
+        return match chip.len() {
+            0 ..= 0x20000 => 0xB5,
+            0x20001 ..= 0x40000 => 0xB6,
+            _ => 0xB7,
+        };
+    }
+
+    pub fn read(&self, chip: &MemoryBlock, address: usize) -> Option<u8> {
+        if self.id_mode {
+            return Some(if (address & 0b1) == 0 {MANUFACTURER_ID} else {SstFlash::device_id(chip)});
+        }
+        return chip.wrapping_read(address);
+    }
+
+    pub fn write(&mut self, chip: &mut MemoryBlock, address: usize, data: u8) {
+        // Only A0-A14 take part in command decoding
+        let command_address = address & 0x7FFF;
+        self.mode = match (self.mode, command_address, data) {
+            (FlashMode::Ready,        0x5555, 0xAA) => FlashMode::Unlock1,
+            (FlashMode::Ready,        _,      0xF0) => {self.id_mode = false; FlashMode::Ready},
+            (FlashMode::Unlock1,      0x2AAA, 0x55) => FlashMode::Unlock2,
+            (FlashMode::Unlock2,      0x5555, 0xA0) => FlashMode::ByteProgram,
+            (FlashMode::Unlock2,      0x5555, 0x80) => FlashMode::EraseUnlock1,
+            (FlashMode::Unlock2,      0x5555, 0x90) => {self.id_mode = true; FlashMode::Ready},
+            (FlashMode::Unlock2,      0x5555, 0xF0) => {self.id_mode = false; FlashMode::Ready},
+            (FlashMode::ByteProgram,  _,      _)    => {
+                self.program_byte(chip, address, data);
+                FlashMode::Ready
+            },
+            (FlashMode::EraseUnlock1, 0x5555, 0xAA) => FlashMode::EraseUnlock2,
+            (FlashMode::EraseUnlock2, 0x2AAA, 0x55) => FlashMode::EraseCommand,
+            (FlashMode::EraseCommand, 0x5555, 0x10) => {
+                self.erase(chip, 0, chip.len());
+                FlashMode::Ready
+            },
+            (FlashMode::EraseCommand, _,      0x30) => {
+                if chip.len() > 0 {
+                    let sector_start = (address % chip.len()) & !(SECTOR_SIZE - 1);
+                    self.erase(chip, sector_start, sector_start + SECTOR_SIZE);
+                }
+                FlashMode::Ready
+            },
+            // Anything out of sequence aborts the command
+            _ => FlashMode::Ready,
+        };
+    }
+
+    fn program_byte(&self, chip: &mut MemoryBlock, address: usize, data: u8) {
+        let len = chip.len();
+        if len == 0 {
+            return;
+        }
+        let bytes = chip.as_mut_vec();
+        bytes[address % len] &= data;
+    }
+
+    fn erase(&self, chip: &mut MemoryBlock, start: usize, end: usize) {
+        let bytes = chip.as_mut_vec();
+        let end = end.min(bytes.len());
+        for byte in bytes[start.min(end) .. end].iter_mut() {
+            *byte = 0xFF;
+        }
+    }
+}
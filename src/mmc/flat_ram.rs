@@ -0,0 +1,61 @@
+// Not a real cartridge: 64k of RAM covering the CPU's entire address space, for running
+// flat 6502 test binaries (Klaus Dormann's functional tests, for instance). While this
+// mapper is loaded, the core routes every CPU access here instead of to internal RAM,
+// the PPU, or the APU, which still run but are unreachable from software.
+
+use mmc::mapper::*;
+
+pub struct FlatRam {
+    pub ram: Vec<u8>,
+    vram: Vec<u8>,
+}
+
+impl FlatRam {
+    // Copies the binary into RAM at load_address; anything that would run past $FFFF is
+    // an error, rather than silently wrapping around into zero page.
+    pub fn new(binary: &[u8], load_address: u16) -> Result<FlatRam, String> {
+        let start = load_address as usize;
+        if start + binary.len() > 0x10000 {
+            return Err(format!("Binary of {} bytes does not fit at ${:04X}", binary.len(), load_address));
+        }
+        let mut ram = vec!(0u8; 0x10000);
+        ram[start .. start + binary.len()].copy_from_slice(binary);
+        return Ok(FlatRam {
+            ram: ram,
+            vram: vec!(0u8; 0x4000),
+        });
+    }
+}
+
+impl Mapper for FlatRam {
+    fn print_debug_status(&self) {
+        println!("======= Flat RAM =======");
+        println!("64k CPU RAM, no I/O");
+        println!("========================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return Mirroring::FourScreen;
+    }
+
+    fn flat_address_space(&self) -> bool {
+        return true;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        return Some(self.ram[address as usize]);
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        self.ram[address as usize] = data;
+    }
+
+    // The PPU still renders, so give it something harmless to look at
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        return Some(self.vram[(address & 0x3FFF) as usize]);
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        self.vram[(address & 0x3FFF) as usize] = data;
+    }
+}
@@ -37,7 +37,7 @@ pub struct Fme7 {
 impl Fme7 {
     pub fn from_ines(ines: INesCartridge) -> Result<Fme7, String> {
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
+        let prg_ram_block = ines.prg_ram_block_up_to(0x80000)?;
         let chr_block = ines.chr_block()?;
 
         return Ok(Fme7 {
@@ -183,6 +183,13 @@ impl Mapper for Fme7 {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF if self.prg_ram_selected => self.prg_ram.banked_poke(0x2000, self.prg_banks[0], (address - 0x6000) as usize, data),
+            _ => false
+        };
+    }
+
     fn write_ppu(&mut self, address: u16, data: u8) {
         match address {
             0x2000 ..= 0x3FFF => match self.mirroring {
@@ -200,10 +207,27 @@ impl Mapper for Fme7 {
         return self.irq_enabled && self.irq_pending;
     }
 
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        // The IRQ is raised as the counter wraps from $0000 to $FFFF
+        let will_fire = self.irq_enabled && self.irq_counter_enabled;
+        return Some(MapperIrqState {
+            counter: self.irq_counter as u32,
+            latch: 0,
+            enabled: self.irq_enabled,
+            pending: self.irq_pending,
+            unit: IrqCounterUnit::CpuCycles,
+            clocks_until_irq: if will_fire {Some(self.irq_counter as u32 + 1)} else {None},
+        });
+    }
+
     fn mix_expansion_audio(&self, nes_sample: f32) -> f32 {
         return (self.expansion_audio_chip.output() - 0.5) * 1.06 - nes_sample;
     }
 
+    fn expansion_chip_outputs(&self, _nes_sample: f32) -> Vec<(String, f32)> {
+        return vec![(String::from("YM2149F"), (self.expansion_audio_chip.output() - 0.5) * 1.06)];
+    }
+
     fn channels(&self) ->  Vec<& dyn AudioChannelState> {
         let mut channels: Vec<& dyn AudioChannelState> = Vec::new();
         channels.push(&self.expansion_audio_chip.channel_a);
@@ -223,6 +247,42 @@ impl Mapper for Fme7 {
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {
         self.expansion_audio_chip.record_output();
     }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x6000 ..= 0x7FFF => {
+                if self.prg_ram_selected {
+                    None
+                } else {
+                    banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[0], cpu_address as usize)
+                }
+            },
+            0x8000 ..= 0x9FFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[1], cpu_address as usize),
+            0xA000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[2], cpu_address as usize),
+            0xC000 ..= 0xDFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[3], cpu_address as usize),
+            0xE000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, 0xFF, cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr_rom.banked_offset(0x400, self.chr_banks[(ppu_address / 0x400) as usize], ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr_rom);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr_rom);
+    }
 }
 
 pub struct ToneGenerator {
@@ -482,6 +542,12 @@ impl AudioChannelState for YmChannel {
         self.muted = false;
     }
 
+    fn has_edges(&self) -> bool {
+        // Noise-only channels still clock their tone generator, but its edges don't
+        // correspond to anything audible
+        return self.tone_enabled;
+    }
+
     fn playing(&self) -> bool {
         return             
             self.tone_enabled &&
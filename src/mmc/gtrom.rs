@@ -0,0 +1,124 @@
+// GTROM, also known as Cheapocabra (mapper 111): a homebrew board by membler, with 32k
+// PRG banking, 16k of CHR RAM in two 8k banks, and 8k of nametable RAM providing two
+// sets of four screen nametables. The PRG chip is flash, and games may rewrite it for
+// saves; that isn't emulated yet, so writes to $8000-$FFFF are ignored.
+// https://wiki.nesdev.com/w/index.php/GTROM
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+use memoryblock::MemoryType;
+
+use mmc::mapper::*;
+
+pub struct Gtrom {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub nametable_ram: Vec<u8>,
+    pub prg_bank: usize,
+    pub chr_bank: usize,
+    pub nametable_bank: usize,
+    // Two LEDs on the board, which some games use for debugging
+    pub red_led: bool,
+    pub green_led: bool,
+}
+
+impl Gtrom {
+    pub fn from_ines(ines: INesCartridge) -> Result<Gtrom, String> {
+        let prg_rom_block = ines.prg_rom_block();
+
+        return Ok(Gtrom {
+            prg_rom: prg_rom_block.clone(),
+            // The RAM is soldered on, whatever the header claims
+            chr: MemoryBlock::new(&[0u8; 0x4000], MemoryType::Ram),
+            nametable_ram: vec![0u8; 0x2000],
+            prg_bank: 0,
+            chr_bank: 0,
+            nametable_bank: 0,
+            red_led: false,
+            green_led: false,
+        })
+    }
+
+    fn nametable_address(&self, address: u16) -> usize {
+        return (self.nametable_bank * 0x1000) + (address as usize & 0x0FFF);
+    }
+}
+
+impl Mapper for Gtrom {
+    fn print_debug_status(&self) {
+        println!("======= GTROM =======");
+        println!("PRG Bank: {}, CHR Bank: {}, Nametable Bank: {}", self.prg_bank, self.chr_bank, self.nametable_bank);
+        println!("LEDs: Red: {}, Green: {}", self.red_led, self.green_led);
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return Mirroring::FourScreen;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x8000, self.prg_bank, address as usize - 0x8000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            // The register is decoded in both places
+            0x5000 ..= 0x5FFF | 0x7000 ..= 0x7FFF => {
+                self.prg_bank = (data & 0b0000_1111) as usize;
+                self.chr_bank = ((data & 0b0001_0000) >> 4) as usize;
+                self.nametable_bank = ((data & 0b0010_0000) >> 5) as usize;
+                self.green_led = (data & 0b0100_0000) != 0;
+                self.red_led = (data & 0b1000_0000) != 0;
+            },
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x2000, self.chr_bank, address as usize),
+            0x2000 ..= 0x3FFF => Some(self.nametable_ram[self.nametable_address(address)]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_write(0x2000, self.chr_bank, address as usize, data),
+            0x2000 ..= 0x3FFF => {
+                let nametable_address = self.nametable_address(address);
+                self.nametable_ram[nametable_address] = data;
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x8000, self.prg_bank, cpu_address as usize - 0x8000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x2000, self.chr_bank, ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
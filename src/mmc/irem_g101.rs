@@ -0,0 +1,148 @@
+// Irem G-101 (mapper 32): two switchable 8k PRG banks, one of which can trade places with
+// the fixed second-to-last bank, and eight 1k CHR banks. Major League (submapper 1) wires
+// the board for one screen mirroring and leaves the PRG mode fixed.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_032
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct IremG101 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub prg_banks: Vec<usize>,
+    pub chr_banks: Vec<usize>,
+    // When set, $8000 is fixed to the second-to-last bank and the first register moves
+    // to $C000
+    pub prg_mode: bool,
+    pub mirroring: Mirroring,
+    pub major_league: bool,
+}
+
+impl IremG101 {
+    pub fn from_ines(ines: INesCartridge) -> Result<IremG101, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+        let major_league = ines.header.submapper_number() == 1;
+
+        return Ok(IremG101 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            prg_banks: vec![0usize; 2],
+            chr_banks: vec![0usize; 8],
+            prg_mode: false,
+            mirroring: if major_league {Mirroring::OneScreenLower} else {Mirroring::Vertical},
+            major_league: major_league,
+        })
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match (address, self.prg_mode) {
+            (0x8000 ..= 0x9FFF, false) => self.prg_banks[0],
+            (0x8000 ..= 0x9FFF, true) => 0xFE,
+            (0xA000 ..= 0xBFFF, _) => self.prg_banks[1],
+            (0xC000 ..= 0xDFFF, false) => 0xFE,
+            (0xC000 ..= 0xDFFF, true) => self.prg_banks[0],
+            _ => 0xFF,
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address / 0x400) as usize];
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            Mirroring::OneScreenLower => Some(mirroring::one_screen_lower(address) as usize),
+            _ => None
+        }
+    }
+}
+
+impl Mapper for IremG101 {
+    fn print_debug_status(&self) {
+        println!("======= Irem G-101 =======");
+        println!("PRG: {:?}, Mode: {}, CHR: {:?}", self.prg_banks, self.prg_mode as u8, self.chr_banks);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x8000 ..= 0x8FFF => {self.prg_banks[0] = data as usize & 0x1F;},
+            0x9000 ..= 0x9FFF if !self.major_league => {
+                self.prg_mode = (data & 0b10) != 0;
+                self.mirroring = if (data & 0b01) == 0 {Mirroring::Vertical} else {Mirroring::Horizontal};
+            },
+            0xA000 ..= 0xAFFF => {self.prg_banks[1] = data as usize & 0x1F;},
+            0xB000 ..= 0xBFFF => {self.chr_banks[(address & 0x7) as usize] = data as usize;},
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x400, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|index| self.vram[index]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x400, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => {
+                if let Some(index) = self.nametable_address(address) {
+                    self.vram[index] = data;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x400, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
@@ -0,0 +1,240 @@
+// Irem H3001 (mapper 65): three switchable 8k PRG banks, eight 1k CHR banks, and a 16-bit
+// IRQ counter which counts CPU cycles down to zero and then stops.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_065
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct IremH3001 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub prg_banks: Vec<usize>,
+    pub chr_banks: Vec<usize>,
+    // Swaps the banks at $8000 and $C000
+    pub prg_swap: bool,
+    pub mirroring: Mirroring,
+
+    pub irq_reload: u16,
+    pub irq_counter: u16,
+    pub irq_enabled: bool,
+    pub irq_pending: bool,
+}
+
+impl IremH3001 {
+    pub fn from_ines(ines: INesCartridge) -> Result<IremH3001, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(IremH3001 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            prg_banks: vec![0, 1, 0xFE],
+            chr_banks: vec![0usize; 8],
+            prg_swap: false,
+            mirroring: Mirroring::Vertical,
+
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_enabled: false,
+            irq_pending: false,
+        })
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match (address, self.prg_swap) {
+            (0x8000 ..= 0x9FFF, false) => self.prg_banks[0],
+            (0x8000 ..= 0x9FFF, true) => self.prg_banks[2],
+            (0xA000 ..= 0xBFFF, _) => self.prg_banks[1],
+            (0xC000 ..= 0xDFFF, false) => self.prg_banks[2],
+            (0xC000 ..= 0xDFFF, true) => self.prg_banks[0],
+            _ => 0xFF,
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address / 0x400) as usize];
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            _ => None
+        }
+    }
+}
+
+impl Mapper for IremH3001 {
+    fn print_debug_status(&self) {
+        println!("======= Irem H3001 =======");
+        println!("PRG: {:?}, Swapped: {}, CHR: {:?}", self.prg_banks, self.prg_swap, self.chr_banks);
+        println!("IRQ: Counter: {:04X}, Reload: {:04X}, Enabled: {}", self.irq_counter, self.irq_reload, self.irq_enabled);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn clock_cpu(&mut self) {
+        if self.irq_enabled && self.irq_counter > 0 {
+            self.irq_counter -= 1;
+            if self.irq_counter == 0 {
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn irq_flag(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        return Some(MapperIrqState {
+            counter: self.irq_counter as u32,
+            latch: self.irq_reload as u32,
+            enabled: self.irq_enabled,
+            pending: self.irq_pending,
+            unit: IrqCounterUnit::CpuCycles,
+            clocks_until_irq: if self.irq_enabled && self.irq_counter > 0 {Some(self.irq_counter as u32)} else {None},
+        });
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x8000 => {self.prg_banks[0] = data as usize;},
+            0x9000 => {self.prg_swap = (data & 0b1000_0000) != 0;},
+            0x9001 => {
+                self.mirroring = if (data & 0b1000_0000) == 0 {Mirroring::Vertical} else {Mirroring::Horizontal};
+            },
+            0x9003 => {
+                self.irq_enabled = (data & 0b1000_0000) != 0;
+                self.irq_pending = false;
+            },
+            0x9004 => {
+                self.irq_counter = self.irq_reload;
+                self.irq_pending = false;
+            },
+            0x9005 => {self.irq_reload = (self.irq_reload & 0x00FF) | ((data as u16) << 8);},
+            0x9006 => {self.irq_reload = (self.irq_reload & 0xFF00) | (data as u16);},
+            0xA000 => {self.prg_banks[1] = data as usize;},
+            0xB000 ..= 0xB007 => {self.chr_banks[(address - 0xB000) as usize] = data as usize;},
+            0xC000 => {self.prg_banks[2] = data as usize;},
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x400, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|index| self.vram[index]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x400, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => {
+                if let Some(index) = self.nametable_address(address) {
+                    self.vram[index] = data;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x400, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prg_banking_and_swap() {
+        let mut mapper = IremH3001::from_ines(INesCartridge::test_image(65, 8, 1)).unwrap();
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(14));
+        mapper.write_cpu(0x8000, 3);
+        mapper.write_cpu(0xA000, 4);
+        mapper.write_cpu(0xC000, 5);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(3));
+        assert_eq!(mapper.debug_read_cpu(0xA000), Some(4));
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(5));
+        assert_eq!(mapper.debug_read_cpu(0xE000), Some(15));
+        mapper.write_cpu(0x9000, 0x80);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(5));
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(3));
+    }
+
+    #[test]
+    fn irq_fires_once_and_stops_at_zero() {
+        let mut mapper = IremH3001::from_ines(INesCartridge::test_image(65, 8, 1)).unwrap();
+        mapper.write_cpu(0x9005, 0x01);
+        mapper.write_cpu(0x9006, 0x00);
+        mapper.write_cpu(0x9004, 0);
+        mapper.write_cpu(0x9003, 0x80);
+        assert_eq!(mapper.irq_state().unwrap().clocks_until_irq, Some(0x100));
+        for _ in 0 .. 0xFF {
+            mapper.clock_cpu();
+        }
+        assert!(!mapper.irq_flag());
+        mapper.clock_cpu();
+        assert!(mapper.irq_flag());
+
+        // Acknowledged and still enabled, but the counter doesn't wrap around again
+        mapper.write_cpu(0x9003, 0x80);
+        for _ in 0 .. 0x20000 {
+            mapper.clock_cpu();
+        }
+        assert!(!mapper.irq_flag());
+        assert_eq!(mapper.irq_counter, 0);
+
+        // Until it's reloaded
+        mapper.write_cpu(0x9004, 0);
+        for _ in 0 .. 0x100 {
+            mapper.clock_cpu();
+        }
+        assert!(mapper.irq_flag());
+    }
+}
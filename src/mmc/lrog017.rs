@@ -0,0 +1,109 @@
+// Irem LROG017 (mapper 77), used only by Napoleon Senki. The first 2k of the pattern
+// tables is switchable CHR ROM; the board's 8k of RAM fills in the other 6k, and its
+// remaining 2k provides the extra nametables for four screen mirroring.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_077
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+use memoryblock::MemoryType;
+
+use mmc::mapper::*;
+
+pub struct Lrog017 {
+    pub prg_rom: MemoryBlock,
+    pub chr_rom: MemoryBlock,
+    // $0000-$07FF: nametables at $2800-$2FFF, $0800-$1FFF: pattern tables at $0800-$1FFF
+    pub chr_ram: MemoryBlock,
+    // The console's own 2k, for nametables at $2000-$27FF
+    pub vram: Vec<u8>,
+    pub prg_bank: usize,
+    pub chr_bank: usize,
+}
+
+impl Lrog017 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Lrog017, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Lrog017 {
+            prg_rom: prg_rom_block.clone(),
+            chr_rom: chr_block.clone(),
+            chr_ram: MemoryBlock::new(&[0u8; 0x2000], MemoryType::Ram),
+            vram: vec![0u8; 0x800],
+            prg_bank: 0,
+            chr_bank: 0,
+        })
+    }
+}
+
+impl Mapper for Lrog017 {
+    fn print_debug_status(&self) {
+        println!("======= LROG017 =======");
+        println!("PRG Bank: {}, CHR Bank: {}", self.prg_bank, self.chr_bank);
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return Mirroring::FourScreen;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x8000, self.prg_bank, address as usize - 0x8000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        if address >= 0x8000 {
+            self.prg_bank = (data & 0b0000_1111) as usize;
+            self.chr_bank = ((data & 0b1111_0000) >> 4) as usize;
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x07FF => self.chr_rom.banked_read(0x800, self.chr_bank, address as usize),
+            0x0800 ..= 0x1FFF => self.chr_ram.bounded_read(address as usize),
+            0x2000 ..= 0x3FFF => match address & 0x0FFF {
+                0x000 ..= 0x7FF => Some(self.vram[(address & 0x7FF) as usize]),
+                _ => self.chr_ram.bounded_read((address & 0x7FF) as usize),
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0800 ..= 0x1FFF => self.chr_ram.bounded_write(address as usize, data),
+            0x2000 ..= 0x3FFF => match address & 0x0FFF {
+                0x000 ..= 0x7FF => self.vram[(address & 0x7FF) as usize] = data,
+                _ => self.chr_ram.bounded_write((address & 0x7FF) as usize, data),
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x8000, self.prg_bank, cpu_address as usize - 0x8000),
+            _ => None
+        };
+    }
+
+    // Only the CHR ROM window; the RAM behind $0800-$1FFF isn't part of chr()
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x07FF => self.chr_rom.banked_offset(0x800, self.chr_bank, ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr_rom);
+    }
+}
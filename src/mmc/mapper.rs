@@ -1,4 +1,7 @@
 use apu::AudioChannelState;
+use apu::from_fixed_sample;
+use apu::to_fixed_sample;
+use memoryblock::MemoryBlock;
 
 #[derive(Copy, Clone, PartialEq)]
 pub enum Mirroring {
@@ -19,29 +22,195 @@ pub fn mirroring_mode_name(mode: Mirroring) -> &'static str {
     }
 }
 
+#[derive(Copy, Clone, PartialEq)]
+pub enum IrqCounterUnit {
+    Scanlines,
+    CpuCycles,
+}
+
+// Snapshot of a mapper's IRQ counter, for raster debugging views. clocks_until_irq is
+// measured in counter clocks of the given unit, and is None if the IRQ will not assert.
+#[derive(Copy, Clone)]
+pub struct MapperIrqState {
+    pub counter: u32,
+    pub latch: u32,
+    pub enabled: bool,
+    pub pending: bool,
+    pub unit: IrqCounterUnit,
+    pub clocks_until_irq: Option<u32>,
+}
+
+// What a mapper can do, so generic frontend code (mixers, IRQ panels, save managers)
+// can adapt without trial calls.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MapperCapabilities {
+    pub bits: u8,
+}
+
+impl MapperCapabilities {
+    pub const AUDIO: u8     = 0b0000_0001;
+    pub const IRQ: u8       = 0b0000_0010;
+    pub const SRAM: u8      = 0b0000_0100;
+    pub const CHR_RAM: u8   = 0b0000_1000;
+    pub const SAVESTATE: u8 = 0b0001_0000;
+
+    pub fn contains(&self, flags: u8) -> bool {
+        return (self.bits & flags) == flags;
+    }
+
+    pub fn has_audio(&self) -> bool {return self.contains(MapperCapabilities::AUDIO);}
+    pub fn has_irq(&self) -> bool {return self.contains(MapperCapabilities::IRQ);}
+    pub fn has_sram(&self) -> bool {return self.contains(MapperCapabilities::SRAM);}
+    pub fn has_chr_ram(&self) -> bool {return self.contains(MapperCapabilities::CHR_RAM);}
+    pub fn supports_savestate(&self) -> bool {return self.contains(MapperCapabilities::SAVESTATE);}
+}
+
+// A per-tile replacement some mappers make to background fetches (MMC5 extended
+// attributes, for instance), so nametable viewers can draw what the PPU actually sees
+// instead of what the nametable and attribute bytes alone would suggest.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct TileOverride {
+    pub palette: u8,
+    // The tile's 16 bytes of pattern data: 8 rows of the low plane, then 8 of the high plane
+    pub pattern: [u8; 16],
+}
+
+// VRC4, VRC6 and VRC7 share the same 8-bit up counter, with an optional prescaler that
+// approximates scanlines by counting 113.667 CPU cycles. Prediction is reported in CPU
+// cycles for both modes, since the prescaler isn't synchronized with the PPU.
+pub fn vrc_irq_state(counter: u8, latch: u8, enabled: bool, pending: bool, scanline_mode: bool, prescaler: i16) -> MapperIrqState {
+    let counter_clocks = (0xFF - counter as u32) + 1;
+    let cycles_until_irq = if scanline_mode {
+        let first_clock = (prescaler.max(1) as u32).div_ceil(3);
+        first_clock + ((counter_clocks - 1) * 341) / 3
+    } else {
+        counter_clocks
+    };
+    return MapperIrqState {
+        counter: counter as u32,
+        latch: latch as u32,
+        enabled: enabled,
+        pending: pending,
+        unit: IrqCounterUnit::CpuCycles,
+        clocks_until_irq: if enabled {Some(cycles_until_irq)} else {None},
+    };
+}
+
+// Resolves a banked access to (bank number, offset into PRG ROM), using the same wrapping
+// rules as MemoryBlock::banked_read. The bank number is in units of bank_size.
+pub fn banked_prg_offset(prg_rom: &MemoryBlock, bank_size: usize, bank_index: usize, offset: usize) -> Option<(usize, usize)> {
+    let rom_offset = prg_rom.banked_offset(bank_size, bank_index, offset)?;
+    return Some((rom_offset / bank_size, rom_offset));
+}
+
 pub trait Mapper: Send {
     fn read_cpu(&mut self, address: u16) -> Option<u8> {return self.debug_read_cpu(address);}
+    // Sees every CPU write, including both writes of a read-modify-write instruction: the
+    // unmodified value, then the result, on consecutive cycles.
     fn write_cpu(&mut self, address: u16, data: u8);
     fn access_ppu(&mut self, _address: u16) {}
     fn read_ppu(&mut self, address: u16) -> Option<u8> {return self.debug_read_ppu(address);}
     fn write_ppu(&mut self, address: u16, data: u8);
     fn debug_read_cpu(&self, address: u16) -> Option<u8>;
     fn debug_read_ppu(&self, address: u16) -> Option<u8>;
+    // Stores a byte straight into whatever RAM the CPU sees at $6000-$7FFF, ignoring the
+    // board's write protection and never reaching a register. Returns false if nothing
+    // there is RAM right now (unmapped, ROM, or a register).
+    fn poke_prg_ram(&mut self, _address: u16, _data: u8) -> bool {return false;}
     fn print_debug_status(&self) {}
     fn mirroring(&self) -> Mirroring;
     fn has_sram(&self) -> bool {return false;}
     fn get_sram(&self) -> Vec<u8> {return vec![0u8; 0];}
     fn load_sram(&mut self, _: Vec<u8>) {}
     fn irq_flag(&self) -> bool {return false;}
+    // Cartridges can't normally drive /NMI, but the NSF player uses it to regain control
+    fn nmi_flag(&self) -> bool {return false;}
+    fn irq_state(&self) -> Option<MapperIrqState> {return None;}
     fn clock_cpu(&mut self) {}
+    // Called by the PPU at dot 0 of every scanline (0-239 visible, 240 post-render, 241-260
+    // vblank, 261 pre-render), before any fetches that dot. When the pre-render line is cut
+    // short on odd frames, scanline 0 starts in place of its dot 340 instead. Unlike A12
+    // snooping, this fires whether or not rendering is enabled.
+    fn start_scanline(&mut self, _scanline: u16) {}
+    // Called at dot 1 of scanline 241, the dot the PPU sets its VBlank flag
+    fn start_vblank(&mut self) {}
+    // Called when the console's reset button is pressed. Mappers which react to the CPU
+    // reset line (or emulate the way a multicart returns to its menu) should restore
+    // the relevant latches here.
+    fn reset(&mut self) {}
     fn mix_expansion_audio(&self, nes_sample: f32) -> f32 {return nes_sample;}
+    // Fixed point (MIX_FIXED_ONE = 1.0) equivalent of mix_expansion_audio. Only MMC5 and
+    // VRC6 implement a true integer mixer; everything else (N163, FDS, VRC7, 5B) falls back
+    // to quantizing the floating point mix, so its output is not bit exact across hosts.
+    fn mix_expansion_audio_fixed(&self, nes_sample: i32) -> i32 {
+        return to_fixed_sample(self.mix_expansion_audio(from_fixed_sample(nes_sample)));
+    }
     fn channels(&self) ->  Vec<& dyn AudioChannelState> {return Vec::new();}
     fn channels_mut(&mut self) ->  Vec<&mut dyn AudioChannelState> {return Vec::new();}
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {}
+    // Each expansion chip's contribution to the mix, before filtering. The default assumes a
+    // single chip whose output is added to the 2A03; mappers that mix differently (or carry
+    // several chips, like NSF) should report each chip individually.
+    fn expansion_chip_outputs(&self, nes_sample: f32) -> Vec<(String, f32)> {
+        let channels = self.channels();
+        if channels.is_empty() {
+            return Vec::new();
+        }
+        return vec![(channels[0].chip(), self.mix_expansion_audio(nes_sample) - nes_sample)];
+    }
+    // Tracks are numbered from 1 up to nsf_total_songs; 0 for anything that isn't an NSF
+    fn nsf_total_songs(&self) -> u8 {return 0;}
     fn nsf_set_track(&mut self, _track_index: u8) {}
     fn nsf_manual_mode(&mut self) {}
+    fn nsf_restart_track(&mut self) {}
+    // Skips ahead in the current track; playback resumes once NesState has fast forwarded
+    fn nsf_seek(&mut self, _seconds: u32) {}
+    // Repeats (start, end) of the current track, in seconds. None clears the loop.
+    fn nsf_set_loop(&mut self, _region_seconds: Option<(u32, u32)>) {}
+    fn nsf_seeking(&self) -> bool {return false;}
+    // Longest INIT and PLAY calls allowed, in CPU cycles, before the player gives up on them
+    // and carries on. 0 disables that watchdog.
+    fn nsf_set_timeouts(&mut self, _init_cycles: u64, _play_cycles: u64) {}
     fn audio_multiplexing(&mut self, _emulate: bool) {}
     fn needs_bios(&self) -> bool {return false;}
     fn load_bios(&mut self, _: Vec<u8>) {}
     fn switch_disk(&mut self, _: usize) {}
+    // Writes a program straight into a RAM backed cartridge (see mmc::ram_cart), bypassing
+    // any write protection. Reset the console afterwards to run it.
+    fn upload_prg(&mut self, _address: u16, _data: &[u8]) -> Result<(), String> {return Err(String::from("This cartridge can't be reprogrammed"));}
+    fn upload_chr(&mut self, _address: u16, _data: &[u8]) -> Result<(), String> {return Err(String::from("This cartridge can't be reprogrammed"));}
+    // Swipes a barcode (EAN-13 or EAN-8 digits) through the cartridge's reader, if any
+    fn scan_barcode(&mut self, _code: &str) -> Result<(), String> {return Err(String::from("This cartridge has no barcode reader"));}
+    // Direct access to the cartridge's memory chips, for tools which need to inspect or
+    // replace ROM contents. Mappers without a simple PRG / CHR layout return None.
+    fn prg_rom(&self) -> Option<&MemoryBlock> {return None;}
+    // For trace logs and debuggers: which PRG ROM bank, and which byte within the whole
+    // ROM, the CPU would see at this address. None for RAM, registers and open bus.
+    fn resolve_prg_offset(&self, _cpu_address: u16) -> Option<(usize, usize)> {return None;}
+    // Likewise for the PPU: which byte of CHR (ROM or RAM) pattern table reads at this
+    // address currently land on. None outside $0000-$1FFF, or for mappers which don't say.
+    fn resolve_chr_offset(&self, _ppu_address: u16) -> Option<usize> {return None;}
+    fn chr(&self) -> Option<&MemoryBlock> {return None;}
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {return None;}
+    // Extra CPU-visible RAM a mapper provides beyond PRG RAM (MMC5 ExRAM, say), along with
+    // the mode it is currently operating in, in the mapper's own numbering
+    fn debug_exram(&self) -> Option<(&[u8], u8)> {return None;}
+    fn debug_tile_override(&self, _nametable_address: u16) -> Option<TileOverride> {return None;}
+    // True only for test harness mappers which replace the whole CPU address space,
+    // internal RAM and I/O registers included
+    fn flat_address_space(&self) -> bool {return false;}
+    // Mappers which can raise IRQs, but don't report a counter through irq_state, should
+    // override this
+    fn has_irq(&self) -> bool {return self.irq_state().is_some();}
+    fn capabilities(&self) -> MapperCapabilities {
+        let mut bits = 0;
+        if !self.channels().is_empty() {bits |= MapperCapabilities::AUDIO;}
+        if self.has_irq() {bits |= MapperCapabilities::IRQ;}
+        if self.has_sram() {bits |= MapperCapabilities::SRAM;}
+        if let Some(chr) = self.chr() {
+            if !chr.is_readonly() {bits |= MapperCapabilities::CHR_RAM;}
+        }
+        // No mapper serializes its state yet
+        return MapperCapabilities{bits: bits};
+    }
 }
@@ -0,0 +1,159 @@
+// AVE Maxi 15 (mapper 234), a multicart whose registers live at the very top of ROM.
+// They latch the data bus on any access, so reading the register (as a game does to get
+// past a lockout check) switches banks just like writing it. Writes are subject to bus
+// conflicts with the ROM byte underneath.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_234
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Maxi15 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    // MO.. BBBB: mirroring, mode, outer block. Locked once nonzero, until reset.
+    pub outer_bank: u8,
+    // .CCC ...P: inner CHR and PRG banks
+    pub inner_bank: u8,
+}
+
+impl Maxi15 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Maxi15, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Maxi15 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            outer_bank: 0,
+            inner_bank: 0,
+        })
+    }
+
+    // In mode 1, the outer block selects a pair of 32k banks and the inner register picks
+    // between them; in mode 0 the outer block alone selects the PRG bank
+    fn prg_bank(&self) -> usize {
+        if self.outer_bank & 0b0100_0000 != 0 {
+            return ((self.outer_bank & 0b0000_1110) | (self.inner_bank & 0b0000_0001)) as usize;
+        }
+        return (self.outer_bank & 0b0000_1111) as usize;
+    }
+
+    fn chr_bank(&self) -> usize {
+        if self.outer_bank & 0b0100_0000 != 0 {
+            return (((self.outer_bank & 0b0000_1110) as usize) << 2) | ((self.inner_bank >> 4) & 0b111) as usize;
+        }
+        return (((self.outer_bank & 0b0000_1111) as usize) << 2) | ((self.inner_bank >> 4) & 0b011) as usize;
+    }
+
+    fn latch(&mut self, address: u16, data: u8) {
+        match address {
+            0xFF80 ..= 0xFF9F if self.outer_bank == 0 => {
+                self.outer_bank = data;
+            },
+            0xFFE8 ..= 0xFFF7 => {
+                self.inner_bank = data;
+            },
+            _ => {}
+        }
+    }
+}
+
+impl Mapper for Maxi15 {
+    fn print_debug_status(&self) {
+        println!("======= Maxi 15 =======");
+        println!("Outer: {:02X}, Inner: {:02X}, PRG Bank: {}, CHR Bank: {}", self.outer_bank, self.inner_bank, self.prg_bank(), self.chr_bank());
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring()));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        if self.outer_bank & 0b1000_0000 == 0 {
+            return Mirroring::Vertical;
+        }
+        return Mirroring::Horizontal;
+    }
+
+    fn reset(&mut self) {
+        self.outer_bank = 0;
+        self.inner_bank = 0;
+    }
+
+    fn read_cpu(&mut self, address: u16) -> Option<u8> {
+        let data = self.debug_read_cpu(address);
+        if let Some(byte) = data {
+            self.latch(address, byte);
+        }
+        return data;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x8000, self.prg_bank(), address as usize - 0x8000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        if let Some(rom_byte) = self.debug_read_cpu(address) {
+            self.latch(address, data & rom_byte);
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x2000, self.chr_bank(), address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring() {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank();
+                self.chr.banked_write(0x2000, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring() {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x8000, self.prg_bank(), cpu_address as usize - 0x8000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x2000, self.chr_bank(), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
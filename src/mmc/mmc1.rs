@@ -26,13 +26,14 @@ pub struct Mmc1 {
     pub control: u8,
 
     pub mirroring: Mirroring,
-    pub last_write: bool,
+    // Nonzero while a write on this CPU cycle would immediately follow another write
+    pub write_cooldown: u8,
 }
 
 impl Mmc1 {
     pub fn from_ines(ines: INesCartridge) -> Result<Mmc1, String> {
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
+        let prg_ram_block = ines.prg_ram_block_up_to(0x8000)?;
         let chr_block = ines.chr_block()?;
 
         return Ok(Mmc1 {
@@ -53,7 +54,7 @@ impl Mmc1 {
             // (Real hardware might not do this consistently?)
             control: 0x0C,
             mirroring: Mirroring::Vertical,
-            last_write: false,
+            write_cooldown: 0,
         })
     }
 }
@@ -76,9 +77,8 @@ impl Mapper for Mmc1 {
         return self.mirroring;
     }
 
-    fn read_cpu(&mut self, address: u16) -> Option<u8> {
-        self.last_write = false;
-        return self.debug_read_cpu(address);
+    fn clock_cpu(&mut self) {
+        self.write_cooldown = self.write_cooldown.saturating_sub(1);
     }
 
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
@@ -151,12 +151,16 @@ impl Mapper for Mmc1 {
             },
             // Control Registers
             0x8000 ..= 0xFFFF => {
-                if self.last_write {
-                    // Ignore this write! MMC1 ignores successive writes, and will clear this flag
-                    // on the next read cycle.
+                // MMC1 ignores a write on the cycle right after another one. This is what
+                // read-modify-write instructions do (INC $8000 writes the old value, then the
+                // new one) and some games rely on it to reset the shift register with only
+                // the first write landing. clock_cpu runs after this cycle's access, so the
+                // cooldown is still nonzero on the next cycle.
+                let consecutive = self.write_cooldown > 0;
+                self.write_cooldown = 2;
+                if consecutive {
                     return;
                 }
-                self.last_write = true;
                 
                 if data & 0x80 != 0 {
                     // Shift / Control Reset!
@@ -211,6 +215,13 @@ impl Mapper for Mmc1 {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.banked_poke(0x2000, self.prg_ram_bank, address as usize, data),
+            _ => false
+        };
+    }
+
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
         match address {
             // CHR Bank 0
@@ -290,6 +301,84 @@ impl Mapper for Mmc1 {
     }
 
     fn load_sram(&mut self, sram_data: Vec<u8>) {
-        *self.prg_ram.as_mut_vec() = sram_data;
+        self.prg_ram.load(&sram_data);
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        let prg_mode = (self.control >> 2) & 0x3;
+        let bank = match cpu_address {
+            0x8000 ..= 0xBFFF => match prg_mode {
+                0 | 1 => self.prg_bank & 0xFFFE,
+                2 => 0,
+                _ => self.prg_bank,
+            },
+            0xC000 ..= 0xFFFF => match prg_mode {
+                0 | 1 => self.prg_bank | 0x0001,
+                2 => self.prg_bank,
+                _ => 0xFF,
+            },
+            _ => return None
+        };
+        return banked_prg_offset(&self.prg_rom, 0x4000, bank, (cpu_address - 0x8000) as usize);
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        let bank = match ppu_address {
+            0x0000 ..= 0x0FFF => if self.control & 0x10 == 0 {self.chr_bank_0 & 0xFFFE} else {self.chr_bank_0},
+            0x1000 ..= 0x1FFF => if self.control & 0x10 == 0 {self.chr_bank_0 | 0x0001} else {self.chr_bank_1},
+            _ => return None
+        };
+        return self.chr.banked_offset(0x1000, bank, ppu_address as usize);
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Writes happen during a CPU cycle, and clock_cpu runs at the end of it
+    fn write_and_clock(mapper: &mut Mmc1, address: u16, data: u8) {
+        mapper.write_cpu(address, data);
+        mapper.clock_cpu();
+    }
+
+    #[test]
+    fn back_to_back_writes_only_land_once() {
+        let mut mapper = Mmc1::from_ines(INesCartridge::test_image(1, 8, 1)).unwrap();
+        // As INC $8000 would: the old value, then the new one on the very next cycle
+        write_and_clock(&mut mapper, 0x8000, 0x01);
+        write_and_clock(&mut mapper, 0x8000, 0x00);
+        assert_eq!(mapper.shift_counter, 1);
+        assert_eq!(mapper.shift_data, 0b1_0000);
+    }
+
+    #[test]
+    fn spaced_out_writes_all_land() {
+        let mut mapper = Mmc1::from_ines(INesCartridge::test_image(1, 8, 1)).unwrap();
+        write_and_clock(&mut mapper, 0x8000, 0x01);
+        mapper.clock_cpu();
+        write_and_clock(&mut mapper, 0x8000, 0x00);
+        assert_eq!(mapper.shift_counter, 2);
+        assert_eq!(mapper.shift_data, 0b0_1000);
+    }
+
+    #[test]
+    fn consecutive_reset_does_not_land() {
+        let mut mapper = Mmc1::from_ines(INesCartridge::test_image(1, 8, 1)).unwrap();
+        write_and_clock(&mut mapper, 0x8000, 0x01);
+        write_and_clock(&mut mapper, 0x8000, 0x80);
+        assert_eq!(mapper.shift_counter, 1);
     }
 }
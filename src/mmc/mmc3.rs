@@ -4,6 +4,7 @@
 use ines::INesCartridge;
 use memoryblock::MemoryBlock;
 
+use mmc::a12_filter::A12Filter;
 use mmc::mapper::*;
 use mmc::mirroring;
 
@@ -34,27 +35,30 @@ pub struct Mmc3 {
     pub irq_enabled: bool,
     pub irq_flag: bool,
 
-    pub last_a12: u8,
-    pub filtered_a12: u8,
-    pub low_a12_counter: u8,
+    pub a12_filter: A12Filter,
 
     // Debug
     pub last_chr_read: u16,
 
     pub mirroring: Mirroring,
+    // Boards such as TVROM (Rad Racer II) wire extra nametable RAM directly to the PPU,
+    // and the $A000 mirroring register has no effect
+    pub hardwired_mirroring: bool,
 }
 
 impl Mmc3 {
     pub fn from_ines(ines: INesCartridge) -> Result<Mmc3, String> {
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
         let chr_block = ines.chr_block()?;
+        let mirroring = ines.header.mirroring();
 
         return Ok(Mmc3 {
             prg_rom: prg_rom_block.clone(),
             prg_ram: prg_ram_block.clone(),
             chr: chr_block.clone(),
-            vram: vec![0u8; 0x2000],
+            // Large enough for all four nametables, in case the board provides them
+            vram: vec![0u8; 0x1000],
             // Note: On real MMC3-based hardware, many of these values are random on startup, so
             // the defaults presented below are arbitrary.
             chr2_bank_0: 0,
@@ -78,42 +82,19 @@ impl Mmc3 {
             irq_enabled: false,
             irq_flag: false,
 
-            last_a12: 0,
-            filtered_a12: 0,
+            a12_filter: A12Filter::new(),
             last_chr_read: 0,
-            low_a12_counter: 0,
 
-            mirroring: ines.header.mirroring(),
+            mirroring: mirroring,
+            hardwired_mirroring: mirroring == Mirroring::FourScreen,
         })
     }
 
     fn snoop_ppu_a12(&mut self, address: u16) {
         self.last_chr_read = address;
-        let current_a12 = ((address & 0b0001_0000_0000_0000) >> 12) as u8;
-        
-        let last_filtered_a12 = self.filtered_a12;
-
-        if current_a12 == 1 {     
-            self.filtered_a12 = 1;
-            self.low_a12_counter = 0;
-        }
-
-        let filtered_a12_rising_edge = (self.filtered_a12 == 1) && (last_filtered_a12 == 0);
-        if filtered_a12_rising_edge {
+        if self.a12_filter.snoop_ppu(address) {
             self.clock_irq_counter();
         }
-
-        // Caching this value so the M2 counter can see it
-        self.last_a12 = current_a12;
-    }
-
-    fn snoop_cpu_m2(&mut self) {
-        if self.low_a12_counter < 255 && self.last_a12 == 0 {
-            self.low_a12_counter += 1;
-        }
-        if self.low_a12_counter >= 3 {            
-            self.filtered_a12 = 0;
-        }
     }
 
     fn clock_irq_counter(&mut self) {
@@ -128,6 +109,21 @@ impl Mmc3 {
         }
     }
 
+    // (bank size, bank number) of the CHR window covering this address; mirrors the
+    // layout used by _read_ppu and write_ppu
+    fn chr_bank_for(&self, address: u16) -> Option<(usize, usize)> {
+        let window = if self.switch_chr_banks {address ^ 0x1000} else {address};
+        return match window {
+            0x0000 ..= 0x07FF => Some((0x800, self.chr2_bank_0 >> 1)),
+            0x0800 ..= 0x0FFF => Some((0x800, self.chr2_bank_1 >> 1)),
+            0x1000 ..= 0x13FF => Some((0x400, self.chr1_bank_2)),
+            0x1400 ..= 0x17FF => Some((0x400, self.chr1_bank_3)),
+            0x1800 ..= 0x1BFF => Some((0x400, self.chr1_bank_4)),
+            0x1C00 ..= 0x1FFF => Some((0x400, self.chr1_bank_5)),
+            _ => None
+        };
+    }
+
     fn _read_ppu(&self, address: u16) -> Option<u8> {
         match address {
             // CHR
@@ -157,8 +153,9 @@ impl Mmc3 {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
                 Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+                Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
                 Mirroring::FourScreen => Some(self.vram[mirroring::four_banks(address) as usize]),
-                _ => None
             },
             _ => None
         }
@@ -169,7 +166,7 @@ impl Mapper for Mmc3 {
     fn print_debug_status(&self) {
         println!("======= MMC3 =======");
         println!("IRQ: Current: {}, Reload: {}", self.irq_counter, self.irq_reload);
-        println!("Last A12: {}, Last CHR Read: 0x{:04X}", self.last_a12, self.last_chr_read);
+        println!("Last A12: {}, Last CHR Read: 0x{:04X}", self.a12_filter.last_a12, self.last_chr_read);
         println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
         println!("====================");
     }
@@ -182,8 +179,25 @@ impl Mapper for Mmc3 {
         return self.irq_flag;
     }
 
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        let clocks_until_irq = if self.irq_counter == 0 || self.irq_reload_requested {
+            // The next clock reloads the counter; a latch of 0 fires immediately
+            if self.irq_reload == 0 {1} else {self.irq_reload as u32 + 1}
+        } else {
+            self.irq_counter as u32
+        };
+        return Some(MapperIrqState {
+            counter: self.irq_counter as u32,
+            latch: self.irq_reload as u32,
+            enabled: self.irq_enabled,
+            pending: self.irq_flag,
+            unit: IrqCounterUnit::Scanlines,
+            clocks_until_irq: if self.irq_enabled {Some(clocks_until_irq)} else {None},
+        });
+    }
+
     fn clock_cpu(&mut self) {
-        self.snoop_cpu_m2();
+        self.a12_filter.snoop_cpu_m2();
     }
 
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
@@ -236,7 +250,7 @@ impl Mapper for Mmc3 {
                             self.switch_chr_banks = (data & 0b1000_0000) != 0;
                         },
                         0xA000 ..= 0xBFFF => {
-                            if self.mirroring != Mirroring::FourScreen {
+                            if !self.hardwired_mirroring {
                                 if data & 0b1 == 0 {
                                     self.mirroring = Mirroring::Vertical;
                                 } else {
@@ -289,11 +303,20 @@ impl Mapper for Mmc3 {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
     fn read_ppu(&mut self, address: u16) -> Option<u8> {
         self.snoop_ppu_a12(address);
         return self._read_ppu(address);
     }
 
+    // With rendering disabled, the PPU drives v onto the bus, so games can clock the IRQ
+    // counter by hand through $2006 and $2007. The core reports those changes here.
     fn access_ppu(&mut self, address: u16) {
         self.snoop_ppu_a12(address);
     }    
@@ -307,20 +330,7 @@ impl Mapper for Mmc3 {
         match address {
             // CHR RAM (if enabled)
             0x0000 ..= 0x1FFF => {
-                self.last_chr_read = address;
-                let current_a12 = ((address & 0b0001_0000_0000_0000) >> 12) as u8;
-                if current_a12 == 1 && self.last_a12 == 0 {
-                    if self.irq_counter == 0 || self.irq_reload_requested {
-                        self.irq_counter = self.irq_reload;
-                        self.irq_reload_requested = false;
-                    } else {
-                        self.irq_counter -= 1;                        
-                    }
-                    if self.irq_counter == 0 && self.irq_enabled {
-                        self.irq_flag = true;                        
-                    }
-                }
-                self.last_a12 = current_a12;
+                // A12 was already seen by snoop_ppu_a12 above, which applies the M2 filter
                 if self.switch_chr_banks {
                     match address {
                         0x0000 ..= 0x03FF => self.chr.banked_write(0x400, self.chr1_bank_2, address as usize -  0x000, data),
@@ -346,8 +356,9 @@ impl Mapper for Mmc3 {
             0x2000 ..= 0x3FFF => match self.mirroring {
                 Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
                 Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
                 Mirroring::FourScreen => self.vram[mirroring::four_banks(address) as usize] = data,
-                _ => {}
             },
             _ => (),
         }
@@ -362,6 +373,37 @@ impl Mapper for Mmc3 {
     }
 
     fn load_sram(&mut self, sram_data: Vec<u8>) {
-        *self.prg_ram.as_mut_vec() = sram_data;
+        self.prg_ram.load(&sram_data);
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        let (bank_6_slot, bank_fe_slot) = if self.switch_prg_banks {(0xC000, 0x8000)} else {(0x8000, 0xC000)};
+        let bank = match cpu_address {
+            0x8000 ..= 0xFFFF => match cpu_address & 0xE000 {
+                slot if slot == bank_6_slot => self.prg_bank_6,
+                slot if slot == bank_fe_slot => 0xFE,
+                0xA000 => self.prg_bank_7,
+                _ => 0xFF,
+            },
+            _ => return None
+        };
+        return banked_prg_offset(&self.prg_rom, 0x2000, bank, cpu_address as usize);
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        let (bank_size, bank) = self.chr_bank_for(ppu_address)?;
+        return self.chr.banked_offset(bank_size, bank, ppu_address as usize);
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
     }
 }
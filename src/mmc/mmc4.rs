@@ -0,0 +1,187 @@
+// MMC4, MMC2's sibling: 16k PRG banking, battery backed PRG RAM, and CHR latches which
+// respond to a range of addresses in both pattern tables
+// https://wiki.nesdev.com/w/index.php/MMC4
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Mmc4 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mirroring: Mirroring,
+    pub chr_0_latch: u8,
+    pub chr_0_fd_bank: usize,
+    pub chr_0_fe_bank: usize,
+    pub chr_1_latch: u8,
+    pub chr_1_fd_bank: usize,
+    pub chr_1_fe_bank: usize,
+    pub prg_bank: usize,
+    pub vram: Vec<u8>,
+}
+
+impl Mmc4 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Mmc4, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Mmc4 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            mirroring: Mirroring::Vertical,
+            chr_0_latch: 0,
+            chr_0_fd_bank: 0,
+            chr_0_fe_bank: 0,
+            chr_1_latch: 0,
+            chr_1_fd_bank: 0,
+            chr_1_fe_bank: 0,
+            prg_bank: 0,
+            vram: vec![0u8; 0x1000],
+        })
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        let (latch, fd_bank, fe_bank) = match address {
+            0x0000 ..= 0x0FFF => (self.chr_0_latch, self.chr_0_fd_bank, self.chr_0_fe_bank),
+            _ => (self.chr_1_latch, self.chr_1_fd_bank, self.chr_1_fe_bank),
+        };
+        return match latch {
+            0 => fd_bank,
+            _ => fe_bank
+        };
+    }
+}
+
+impl Mapper for Mmc4 {
+    fn print_debug_status(&self) {
+        println!("======= MMC4 =======");
+        println!("PRG Bank: {}, ", self.prg_bank);
+        println!("CHR0 0xFD Bank: {}. CHR0 0xFE Bank: {}", self.chr_0_fd_bank, self.chr_0_fe_bank);
+        println!("CHR1 0xFD Bank: {}. CHR1 0xFE Bank: {}", self.chr_1_fd_bank, self.chr_1_fe_bank);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read((address - 0x6000) as usize),
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, 0xFF,          address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_write((address - 0x6000) as usize, data),
+            0xA000 ..= 0xAFFF => { self.prg_bank = (data & 0b0000_1111) as usize; },
+            0xB000 ..= 0xBFFF => { self.chr_0_fd_bank = (data & 0b0001_1111) as usize; },
+            0xC000 ..= 0xCFFF => { self.chr_0_fe_bank = (data & 0b0001_1111) as usize; },
+            0xD000 ..= 0xDFFF => { self.chr_1_fd_bank = (data & 0b0001_1111) as usize; },
+            0xE000 ..= 0xEFFF => { self.chr_1_fe_bank = (data & 0b0001_1111) as usize; },
+            0xF000 ..= 0xFFFF => {
+                if data & 0b1 == 0 {
+                    self.mirroring = Mirroring::Vertical;
+                } else {
+                    self.mirroring = Mirroring::Horizontal;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
+    fn read_ppu(&mut self, address: u16) -> Option<u8> {
+        // As with MMC2, the triggering fetch still reads from the old bank
+        let data = self.debug_read_ppu(address);
+        match address {
+            0x0FD8 ..= 0x0FDF => {self.chr_0_latch = 0;},
+            0x0FE8 ..= 0x0FEF => {self.chr_0_latch = 1;},
+            0x1FD8 ..= 0x1FDF => {self.chr_1_latch = 0;},
+            0x1FE8 ..= 0x1FEF => {self.chr_1_latch = 1;},
+            _ => {}
+        }
+        return data;
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x1000, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x1000, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return true;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        self.prg_ram.load(&sram_data);
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank, cpu_address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x4000, 0xFF,          cpu_address as usize - 0xC000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x1000, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
@@ -5,9 +5,11 @@
 
 use ines::INesCartridge;
 use memoryblock::MemoryBlock;
+use memoryblock::BankWrap;
 
 use mmc::mapper::*;
 use apu::PulseChannelState;
+use apu::MIX_FIXED_ONE;
 
 use apu::AudioChannelState;
 use apu::RingBuffer;
@@ -94,6 +96,10 @@ impl AudioChannelState for Mmc5PcmChannel {
         self.muted = false;
     }
 
+    fn has_edges(&self) -> bool {
+        return false;
+    }
+
 
     fn playing(&self) -> bool {
         return true;
@@ -101,7 +107,7 @@ impl AudioChannelState for Mmc5PcmChannel {
 
     fn amplitude(&self) -> f32 {
         let buffer = self.output_buffer.buffer();
-        let mut index = (self.output_buffer.index() - 256) % buffer.len();
+        let mut index = (self.output_buffer.index() + buffer.len() - 256) % buffer.len();
         let mut max = buffer[index];
         let mut min = buffer[index];
         for _i in 0 .. 256 {
@@ -114,6 +120,15 @@ impl AudioChannelState for Mmc5PcmChannel {
     }
 }
 
+pub fn exram_mode_name(mode: u8) -> &'static str {
+    return match mode {
+        0 => "Nametable",
+        1 => "Extended Attributes",
+        2 => "RAM",
+        _ => "Read-only RAM",
+    }
+}
+
 pub struct Mmc5 {
     pub prg_rom: MemoryBlock,
     pub prg_ram: MemoryBlock,
@@ -165,7 +180,13 @@ pub struct Mmc5 {
 impl Mmc5 {
     pub fn from_ines(ines: INesCartridge) -> Result<Mmc5, String> {
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
+        let mut prg_ram_block = ines.prg_ram_block_up_to(0x20000)?;
+        // Only 3 bits of a RAM bank number reach the board, and bit 2 picks between two
+        // chip selects. With 32k (EWROM) the second socket is empty, so its banks are
+        // open bus. The 8k and 16k boards mirror in ways plain wrapping approximates.
+        if prg_ram_block.len() >= 0x8000 {
+            prg_ram_block.set_bank_wrap(BankWrap::OpenBus);
+        }
         let chr_block = ines.chr_block()?;
 
         let mut pulse1 = PulseChannelState::new("Pulse 1", "MMC5", 1_789_773, false);
@@ -303,7 +324,7 @@ impl Mmc5 {
         let (datastore, bank_number, bank_size) = match address {
             0x6000 ..= 0x7FFF => (&self.prg_ram, self.prg_ram_bank, 8 * 1024),
             0x8000 ..= 0xBFFF => match self.prg_bank_b_isram {
-                true  => (&self.prg_ram, self.prg_bank_b >> 1, 16 * 1024),
+                true  => (&self.prg_ram, (self.prg_bank_b & 0b111) >> 1, 16 * 1024),
                 false => (&self.prg_rom, self.prg_bank_b >> 1, 16 * 1024)
             },
             0xC000 ..= 0xFFFF => (&self.prg_rom, self.prg_bank_d >> 1, 16 * 1024),
@@ -317,11 +338,11 @@ impl Mmc5 {
         let (datastore, bank_number, bank_size) = match address {
             0x6000 ..= 0x7FFF => (&self.prg_ram, self.prg_ram_bank, 8 * 1024),
             0x8000 ..= 0xBFFF => match self.prg_bank_b_isram {
-                true  => (&self.prg_ram, self.prg_bank_b >> 1, 16 * 1024),
+                true  => (&self.prg_ram, (self.prg_bank_b & 0b111) >> 1, 16 * 1024),
                 false => (&self.prg_rom, self.prg_bank_b >> 1, 16 * 1024)
             },
             0xC000 ..= 0xDFFF => match self.prg_bank_c_isram {
-                true  => (&self.prg_ram, self.prg_bank_c, 8 * 1024),
+                true  => (&self.prg_ram, self.prg_bank_c & 0b111, 8 * 1024),
                 false => (&self.prg_rom, self.prg_bank_c, 8 * 1024)
             },
             0xE000 ..= 0xFFFF => (&self.prg_rom, self.prg_bank_d, 8 * 1024),
@@ -335,15 +356,15 @@ impl Mmc5 {
         let (datastore, bank_number, bank_size) = match address {
             0x6000 ..= 0x7FFF => (&self.prg_ram, self.prg_ram_bank, 8 * 1024),
             0x8000 ..= 0x9FFF => match self.prg_bank_a_isram {
-                true  => (&self.prg_ram, self.prg_bank_a, 8 * 1024),
+                true  => (&self.prg_ram, self.prg_bank_a & 0b111, 8 * 1024),
                 false => (&self.prg_rom, self.prg_bank_a, 8 * 1024)
             },
             0xA000 ..= 0xBFFF => match self.prg_bank_b_isram {
-                true  => (&self.prg_ram, self.prg_bank_b, 8 * 1024),
+                true  => (&self.prg_ram, self.prg_bank_b & 0b111, 8 * 1024),
                 false => (&self.prg_rom, self.prg_bank_b, 8 * 1024)
             },
             0xC000 ..= 0xDFFF => match self.prg_bank_c_isram {
-                true  => (&self.prg_ram, self.prg_bank_c, 8 * 1024),
+                true  => (&self.prg_ram, self.prg_bank_c & 0b111, 8 * 1024),
                 false => (&self.prg_rom, self.prg_bank_c, 8 * 1024)
             },
             0xE000 ..= 0xFFFF => (&self.prg_rom, self.prg_bank_d, 8 * 1024),
@@ -376,7 +397,7 @@ impl Mmc5 {
         let (bank_number, bank_size) = match address {
             0x6000 ..= 0x7FFF => (self.prg_ram_bank, 8 * 1024),
             0x8000 ..= 0xBFFF => match self.prg_bank_b_isram {
-                true  => (self.prg_bank_b >> 1, 16 * 1024),
+                true  => ((self.prg_bank_b & 0b111) >> 1, 16 * 1024),
                 false => {return}
             },
             _ => {return}
@@ -389,11 +410,11 @@ impl Mmc5 {
         let (bank_number, bank_size) = match address {
             0x6000 ..= 0x7FFF => (self.prg_ram_bank, 8 * 1024),
             0x8000 ..= 0xBFFF => match self.prg_bank_b_isram {
-                true  => (self.prg_bank_b >> 1, 16 * 1024),
+                true  => ((self.prg_bank_b & 0b111) >> 1, 16 * 1024),
                 false => {return}
             },
             0xC000 ..= 0xDFFF => match self.prg_bank_c_isram {
-                true  => (self.prg_bank_c, 8 * 1024),
+                true  => (self.prg_bank_c & 0b111, 8 * 1024),
                 false => {return}
             },
             _ => {return}
@@ -406,15 +427,15 @@ impl Mmc5 {
         let (bank_number, bank_size) = match address {
             0x6000 ..= 0x7FFF => (self.prg_ram_bank, 8 * 1024),
             0x8000 ..= 0x9FFF => match self.prg_bank_a_isram {
-                true  => (self.prg_bank_a, 8 * 1024),
+                true  => (self.prg_bank_a & 0b111, 8 * 1024),
                 false => {return}
             },
             0xA000 ..= 0xBFFF => match self.prg_bank_b_isram {
-                true  => (self.prg_bank_b, 8 * 1024),
+                true  => (self.prg_bank_b & 0b111, 8 * 1024),
                 false => {return}
             },
             0xC000 ..= 0xDFFF => match self.prg_bank_c_isram {
-                true  => (self.prg_bank_c, 8 * 1024),
+                true  => (self.prg_bank_c & 0b111, 8 * 1024),
                 false => {return}
             },
             _ => {return}
@@ -433,13 +454,16 @@ impl Mmc5 {
         }
     }
 
-    pub fn read_banked_chr(&self, address: u16) -> u8 {
+    // (bank size, bank number) of the CHR window covering this address. With 8x16 sprites,
+    // backgrounds use the extended set of registers and sprites the standard one; outside
+    // of rendering, whichever set was written last wins.
+    fn chr_window(&self, address: u16) -> Option<(usize, usize)> {
         let chr_bank_size = match self.chr_mode {
             0 => 8192,
             1 => 4096,
             2 => 2048,
             3 => 1024,
-            _ => return 0
+            _ => return None
         };
 
         let chr_region = address / chr_bank_size;
@@ -452,14 +476,19 @@ impl Mmc5 {
         let wrote_ext_register_last = self.chr_last_write_ext;
 
         if large_sprites_enabled && (currently_reading_backgrounds || (ppu_inactive && wrote_ext_register_last)) {
-            let chr_bank = self.chr_ext_banks[extended_bank_index as usize];
-            return self.chr.banked_read(chr_bank_size as usize, chr_bank as usize, address as usize).unwrap_or(0);
+            return Some((chr_bank_size as usize, self.chr_ext_banks[extended_bank_index as usize]));
         } else {
-            let chr_bank = self.chr_banks[standard_bank_index as usize];
-            return self.chr.banked_read(chr_bank_size as usize, chr_bank as usize, address as usize).unwrap_or(0);
+            return Some((chr_bank_size as usize, self.chr_banks[standard_bank_index as usize]));
         }
     }
 
+    pub fn read_banked_chr(&self, address: u16) -> u8 {
+        return match self.chr_window(address) {
+            Some((chr_bank_size, chr_bank)) => self.chr.banked_read(chr_bank_size, chr_bank, address as usize).unwrap_or(0),
+            None => 0
+        };
+    }
+
     pub fn read_extended_chr(&self, address: u16) -> u8 {
         let chr_bank_size = 4096;
         let nametable_index = self.last_bg_tile_fetch & 0x3FF;
@@ -652,7 +681,7 @@ impl Mapper for Mmc5 {
     fn print_debug_status(&self) {
         println!("======= MMC5 =======");
         println!("PRG ROM: {}k, PRG RAM: {}k, CHR ROM: {}k", self.prg_rom.len() / 1024, self.prg_ram.len() / 1024, self.chr.len() / 1024);
-        println!("PRG Mode: {} CHR Mode: {}, ExRAM Mode: {}", self.prg_mode, self.chr_mode, self.extended_ram_mode);
+        println!("PRG Mode: {} CHR Mode: {}, ExRAM Mode: {} ({})", self.prg_mode, self.chr_mode, self.extended_ram_mode, exram_mode_name(self.extended_ram_mode));
         println!("PRG Banks: A:{} B:{} C:{} D:{} RAM:{}", self.prg_bank_a, self.prg_bank_b, self.prg_bank_c, self.prg_bank_d, self.prg_ram_bank);
         println!("IRQ E:{} P:{} CMP:{} Detected Scanline: {}, PPU Fetches: {}", self.irq_enabled, self.irq_pending, self.irq_scanline_compare, self.current_scanline, self.ppu_fetches_this_scanline);
         let ppu_mode_name = match self.ppu_read_mode {
@@ -668,6 +697,37 @@ impl Mapper for Mmc5 {
         println!("====================");
     }
 
+    fn debug_exram(&self) -> Option<(&[u8], u8)> {
+        return Some((&self.extram, self.extended_ram_mode));
+    }
+
+    // In extended attribute mode, every background tile gets its palette and a 4k CHR
+    // bank from ExRAM, regardless of the attribute table and PPUCTRL
+    fn debug_tile_override(&self, nametable_address: u16) -> Option<TileOverride> {
+        if self.extended_ram_mode != 1 {
+            return None;
+        }
+        let masked_address = nametable_address & 0x3FF;
+        if masked_address >= 0x3C0 {
+            return None;
+        }
+        let extended_tile_attributes = self.extram[masked_address as usize];
+        let chr_bank = (self.chr_bank_high_bits << 6) | ((extended_tile_attributes as usize) & 0b0011_1111);
+        let tile_index = self.read_nametable(nametable_address) as usize;
+        let mut pattern = [0u8; 16];
+        for (i, byte) in pattern.iter_mut().enumerate() {
+            *byte = self.chr.banked_read(4096, chr_bank, tile_index * 16 + i).unwrap_or(0);
+        }
+        return Some(TileOverride {
+            palette: (extended_tile_attributes & 0b1100_0000) >> 6,
+            pattern: pattern,
+        });
+    }
+
+    fn has_irq(&self) -> bool {
+        return true;
+    }
+
     fn irq_flag(&self) -> bool {
         return self.irq_enabled && self.irq_pending;
     }
@@ -781,7 +841,7 @@ impl Mapper for Mmc5 {
                 // For simplicity, go ahead and store the whole attribute byte
                 self.fill_attr = (fill_color << 6) | (fill_color << 2) | (fill_color << 4) | (fill_color);
             },
-            0x5113 => {self.prg_ram_bank = data & 0b0111_1111;},
+            0x5113 => {self.prg_ram_bank = data & 0b0000_0111;},
             0x5114 => {
                 self.prg_bank_a = data & 0b0111_1111;
                 self.prg_bank_a_isram = (data & 0b1000_0000) == 0;
@@ -828,6 +888,13 @@ impl Mapper for Mmc5 {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.banked_poke(8 * 1024, self.prg_ram_bank as usize, address as usize, data),
+            _ => false
+        };
+    }
+
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
         return self._read_ppu(address);
     }
@@ -872,6 +939,18 @@ impl Mapper for Mmc5 {
             nes_sample;
     }
 
+    fn mix_expansion_audio_fixed(&self, nes_sample: i32) -> i32 {
+        let half = MIX_FIXED_ONE / 2;
+        let pulse_1_output = if !self.pulse_1.debug_disable {(self.pulse_1.output() as i32 * MIX_FIXED_ONE / 15) - half} else {0};
+        let pulse_2_output = if !self.pulse_2.debug_disable {(self.pulse_2.output() as i32 * MIX_FIXED_ONE / 15) - half} else {0};
+        let pcm_output = if !self.pcm_channel.muted {(self.pcm_channel.level as i32 * MIX_FIXED_ONE / 256) - half} else {0};
+
+        return
+            (pulse_1_output + pulse_2_output) * 12 / 100 +
+            pcm_output / 4 +
+            nes_sample;
+    }
+
     fn channels(&self) ->  Vec<& dyn AudioChannelState> {
         let mut channels: Vec<& dyn AudioChannelState> = Vec::new();
         channels.push(&self.pulse_1);
@@ -893,5 +972,120 @@ impl Mapper for Mmc5 {
         self.pulse_2.record_current_output();
         self.pcm_channel.record_current_output();
     }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        // Mirrors read_prg_mode_0 through 3: (bank, mapped to RAM, bank size)
+        let (bank_number, is_ram, bank_size) = match (self.prg_mode, cpu_address) {
+            (0, 0x8000 ..= 0xFFFF) => (self.prg_bank_d >> 2, false, 32 * 1024),
+            (1, 0x8000 ..= 0xBFFF) | (2, 0x8000 ..= 0xBFFF) => (self.prg_bank_b >> 1, self.prg_bank_b_isram, 16 * 1024),
+            (1, 0xC000 ..= 0xFFFF) => (self.prg_bank_d >> 1, false, 16 * 1024),
+            (2, 0xC000 ..= 0xDFFF) | (3, 0xC000 ..= 0xDFFF) => (self.prg_bank_c, self.prg_bank_c_isram, 8 * 1024),
+            (2, 0xE000 ..= 0xFFFF) | (3, 0xE000 ..= 0xFFFF) => (self.prg_bank_d, false, 8 * 1024),
+            (3, 0x8000 ..= 0x9FFF) => (self.prg_bank_a, self.prg_bank_a_isram, 8 * 1024),
+            (3, 0xA000 ..= 0xBFFF) => (self.prg_bank_b, self.prg_bank_b_isram, 8 * 1024),
+            _ => return None
+        };
+        if is_ram {
+            return None;
+        }
+        return banked_prg_offset(&self.prg_rom, bank_size, bank_number as usize, cpu_address as usize);
+    }
+
+    // The bank set in use right now, so the PPU's captures at dot 1 and dot 257 see the
+    // background and sprite banks respectively. ExRAM mode 1 picks a bank per tile, which
+    // no single offset can describe; that reports the ordinary banks.
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => {
+                let (chr_bank_size, chr_bank) = self.chr_window(ppu_address)?;
+                self.chr.banked_offset(chr_bank_size, chr_bank, ppu_address as usize)
+            },
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
 }
 
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mmc5() -> Mmc5 {
+        // 256k of PRG: 32 banks of 8k
+        return Mmc5::from_ines(INesCartridge::test_image(5, 16, 1)).unwrap();
+    }
+
+    #[test]
+    fn resolve_prg_offset_agrees_with_reads_in_every_mode() {
+        let mut mapper = mmc5();
+        mapper.write_cpu(0x5114, 0x80 | 5);
+        mapper.write_cpu(0x5115, 0x80 | 9);
+        mapper.write_cpu(0x5116, 0x80 | 14);
+        mapper.write_cpu(0x5117, 0x80 | 27);
+        for prg_mode in 0 .. 4 {
+            mapper.write_cpu(0x5100, prg_mode);
+            for address in (0x8000 ..= 0xFFFF).step_by(0x1000) {
+                let (_, offset) = mapper.resolve_prg_offset(address).unwrap();
+                // Every byte of the test image holds its 8k bank number
+                assert_eq!(Some((offset / 0x2000) as u8), mapper.debug_read_cpu(address),
+                    "PRG mode {} at ${:04X}", prg_mode, address);
+            }
+        }
+    }
+
+    #[test]
+    fn resolve_prg_offset_reports_bank_size_units() {
+        let mut mapper = mmc5();
+        mapper.write_cpu(0x5117, 0x80 | 27);
+        mapper.write_cpu(0x5100, 0);
+        // 32k banks: 27 >> 2 = 6
+        assert_eq!(mapper.resolve_prg_offset(0x8000), Some((6, 6 * 0x8000)));
+        mapper.write_cpu(0x5100, 1);
+        assert_eq!(mapper.resolve_prg_offset(0xC000), Some((13, 13 * 0x4000)));
+    }
+
+    #[test]
+    fn resolve_prg_offset_skips_ram_windows() {
+        let mut mapper = mmc5();
+        mapper.write_cpu(0x5100, 3);
+        mapper.write_cpu(0x5114, 0x00);
+        assert_eq!(mapper.resolve_prg_offset(0x8000), None);
+        assert_eq!(mapper.resolve_prg_offset(0x6000), None);
+    }
+
+    #[test]
+    fn resolve_chr_offset_follows_the_active_bank_set() {
+        // 32k of CHR: 32 banks of 1k
+        let mut mapper = Mmc5::from_ines(INesCartridge::test_image(5, 16, 4)).unwrap();
+        mapper.write_cpu(0x5101, 3);
+        for index in 0 .. 8 {
+            mapper.write_cpu(0x5120 + index, 10 + index as u8);
+        }
+        for index in 0 .. 4 {
+            mapper.write_cpu(0x5128 + index, 20 + index as u8);
+        }
+        // 8x16 sprites, so backgrounds get the extended set
+        mapper.write_cpu(0x2000, 0b0010_0000);
+
+        mapper.ppu_read_mode = PpuMode::Backgrounds;
+        assert_eq!(mapper.resolve_chr_offset(0x0400), Some(21 * 0x400));
+        assert_eq!(mapper.resolve_chr_offset(0x1400), Some(21 * 0x400));
+        assert_eq!(mapper.debug_read_ppu(0x1400), Some(21));
+
+        mapper.ppu_read_mode = PpuMode::Sprites;
+        assert_eq!(mapper.resolve_chr_offset(0x0400), Some(11 * 0x400));
+        assert_eq!(mapper.resolve_chr_offset(0x1400), Some(15 * 0x400));
+        assert_eq!(mapper.debug_read_ppu(0x1400), Some(15));
+    }
+}
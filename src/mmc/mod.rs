@@ -1,23 +1,52 @@
 pub mod mapper;
 pub mod mirroring;
 
+pub mod a12_filter;
 pub mod action53;
 pub mod axrom;
+pub mod bandai_fcg;
+pub mod barcode;
+pub mod bf9093;
+pub mod bf9096;
 pub mod bnrom;
 pub mod cnrom;
+pub mod color_dreams;
+pub mod eeprom;
 pub mod fds;
+pub mod flash;
+pub mod flat_ram;
 pub mod fme7;
+pub mod gtrom;
 pub mod gxrom;
 pub mod ines31;
+pub mod irem_g101;
+pub mod irem_h3001;
+pub mod lrog017;
+pub mod maxi15;
 pub mod mmc1;
 pub mod mmc3;
+pub mod mmc4;
 pub mod mmc5;
 pub mod n163;
+pub mod namco108;
+pub mod namco175;
 pub mod none;
 pub mod nrom;
 pub mod nsf;
 pub mod pxrom;
 pub mod rainbow;
+pub mod ram_cart;
+pub mod ss88006;
+pub mod sunsoft1;
+pub mod sunsoft2;
+pub mod sunsoft3;
+pub mod sunsoft4;
+pub mod taito_tc0190;
+pub mod taito_x1005;
+pub mod unrom512;
 pub mod uxrom;
+pub mod vrc1;
+pub mod vrc3;
+pub mod vrc4;
 pub mod vrc6;
 pub mod vrc7;
@@ -390,7 +390,7 @@ pub fn n163_mixing_level(submapper: u8) -> f32 {
 impl Namco163 {
     pub fn from_ines(ines: INesCartridge) -> Result<Namco163, String> {
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
         let chr_block = ines.chr_block()?;
 
         return Ok(Namco163 {
@@ -452,6 +452,15 @@ impl Namco163 {
 }
 
 impl Mapper for Namco163 {
+    fn print_debug_status(&self) {
+        println!("======= N163 =======");
+        println!("PRG: {:?}, CHR: {:?}, NT: {:?}", self.prg_banks, self.chr_banks, self.nt_banks);
+        println!("NT RAM at $0000: {}, at $1000: {}", self.nt_ram_at_0000, self.nt_ram_at_1000);
+        println!("IRQ: Counter: {:04X}, Enabled: {}", self.irq_counter, self.irq_enabled);
+        println!("Sound: Enabled: {}, Channels: {}", self.sound_enabled, self.expansion_audio_chip.enabled_channels());
+        println!("====================");
+    }
+
     fn mirroring(&self) -> Mirroring {
         return Mirroring::Horizontal;
     }
@@ -493,7 +502,8 @@ impl Mapper for Namco163 {
     }
 
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
-        let masked_address = address & 0xFC00;
+        // $3000-$3EFF mirrors the nametable banks
+        let masked_address = (if address >= 0x3000 {address - 0x1000} else {address}) & 0xFC00;
         match masked_address {
             0x0000 => {self.read_banked_chr(address, self.chr_banks[0], self.nt_ram_at_0000)},
             0x0400 => {self.read_banked_chr(address, self.chr_banks[1], self.nt_ram_at_0000)},
@@ -512,7 +522,7 @@ impl Mapper for Namco163 {
     }
 
     fn write_ppu(&mut self, address: u16, data: u8) {
-        let masked_address = address & 0xFC00;
+        let masked_address = (if address >= 0x3000 {address - 0x1000} else {address}) & 0xFC00;
         match masked_address {
             0x0000 => {self.write_banked_chr(address, self.chr_banks[0], self.nt_ram_at_0000, data)},
             0x0400 => {self.write_banked_chr(address, self.chr_banks[1], self.nt_ram_at_0000, data)},
@@ -587,6 +597,13 @@ impl Mapper for Namco163 {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
     fn clock_cpu(&mut self) {
         if self.irq_enabled && self.irq_counter < 0x7FFF {
             self.irq_counter += 1;
@@ -643,6 +660,23 @@ impl Mapper for Namco163 {
         return channels;
     }
 
+    fn has_irq(&self) -> bool {
+        return true;
+    }
+
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        // The counter stops, and the IRQ is raised, upon reaching $7FFF
+        let will_fire = self.irq_enabled && self.irq_counter < 0x7FFF;
+        return Some(MapperIrqState {
+            counter: self.irq_counter as u32,
+            latch: 0,
+            enabled: self.irq_enabled,
+            pending: self.irq_pending,
+            unit: IrqCounterUnit::CpuCycles,
+            clocks_until_irq: if will_fire {Some(0x7FFF - self.irq_counter as u32)} else {None},
+        });
+    }
+
     fn irq_flag(&self) -> bool {
         return self.irq_pending;
     }
@@ -651,15 +685,63 @@ impl Mapper for Namco163 {
         return true;
     }
 
+    // The 128 bytes of internal sound RAM share the battery with PRG RAM, and some
+    // games (Famista '90, among others) keep their save data there. We store it
+    // after PRG RAM in the save file.
     fn get_sram(&self) -> Vec<u8> {
-        return self.prg_ram.as_vec().clone();
+        let mut sram_data = self.prg_ram.as_vec().clone();
+        sram_data.extend_from_slice(&self.expansion_audio_chip.internal_ram);
+        return sram_data;
     }
 
     fn load_sram(&mut self, sram_data: Vec<u8>) {
-        *self.prg_ram.as_mut_vec() = sram_data;
+        let prg_ram_size = self.prg_ram.len();
+        let internal_ram_size = self.expansion_audio_chip.internal_ram.len();
+        if sram_data.len() == prg_ram_size + internal_ram_size {
+            self.prg_ram.as_mut_vec().copy_from_slice(&sram_data[0 .. prg_ram_size]);
+            self.expansion_audio_chip.internal_ram.copy_from_slice(&sram_data[prg_ram_size ..]);
+        } else {
+            // Older saves only contained PRG RAM
+            self.prg_ram.load(&sram_data);
+        }
     }
 
     fn audio_multiplexing(&mut self, emulate: bool) {
         self.expansion_audio_chip.emulate_multiplexing = emulate;
     }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0x9FFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[0] as usize, cpu_address as usize),
+            0xA000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[1] as usize, cpu_address as usize),
+            0xC000 ..= 0xDFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[2] as usize, cpu_address as usize),
+            0xE000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, 0xFF, cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        let (bank_index, use_nt) = match ppu_address {
+            0x0000 ..= 0x0FFF => (self.chr_banks[(ppu_address / 0x400) as usize], self.nt_ram_at_0000),
+            0x1000 ..= 0x1FFF => (self.chr_banks[(ppu_address / 0x400) as usize], self.nt_ram_at_1000),
+            _ => return None
+        };
+        if use_nt && (bank_index >= 0xE0) {
+            // Nametable RAM, not CHR
+            return None;
+        }
+        return self.chr.banked_offset(0x400, bank_index as usize, ppu_address as usize);
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
 }
@@ -0,0 +1,168 @@
+// Namco 108 (mapper 206) and the variants which add mirroring control to it. The 108 is
+// essentially an MMC3 without IRQs or mirroring: fixed PRG and CHR modes, and registers
+// decoded only at $8000-$9FFF.
+//   95: bit 5 of each 2k CHR bank also selects the nametable for that half of $2000
+//   154: bit 6 of any $8000-$FFFF write selects one screen mirroring, and the 1k CHR
+//        banks come from the upper 64k of CHR (as on mapper 88)
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_206
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Namco108 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub mapper_number: u16,
+    pub bank_select: u8,
+    pub bank_registers: Vec<usize>,
+    pub mirroring: Mirroring,
+}
+
+impl Namco108 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Namco108, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+        let mapper_number = ines.header.mapper_number();
+
+        return Ok(Namco108 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            mapper_number: mapper_number,
+            bank_select: 0,
+            bank_registers: vec![0usize; 8],
+            mirroring: if mapper_number == 154 {Mirroring::OneScreenLower} else {ines.header.mirroring()},
+        })
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match address {
+            0x8000 ..= 0x9FFF => self.bank_registers[6],
+            0xA000 ..= 0xBFFF => self.bank_registers[7],
+            0xC000 ..= 0xDFFF => 0xFE,
+            _ => 0xFF,
+        }
+    }
+
+    // In 1k units
+    fn chr_bank(&self, address: u16) -> usize {
+        let (register, low_bit) = match address {
+            0x0000 ..= 0x07FF => (self.bank_registers[0] & 0x3E, (address as usize & 0x400) >> 10),
+            0x0800 ..= 0x0FFF => (self.bank_registers[1] & 0x3E, (address as usize & 0x400) >> 10),
+            _ => {
+                let register = self.bank_registers[2 + ((address as usize - 0x1000) / 0x400)] & 0x3F;
+                return if self.mapper_number == 154 {register | 0x40} else {register};
+            }
+        };
+        if self.mapper_number == 95 {
+            return (register & 0x1F) | low_bit;
+        }
+        return register | low_bit;
+    }
+
+    // Mapper 95 selects the nametable for $2000-$27FF and $2800-$2FFF from bit 5 of the
+    // corresponding 2k CHR bank
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        if self.mapper_number == 95 {
+            let register = if (address & 0x0800) == 0 {self.bank_registers[0]} else {self.bank_registers[1]};
+            let page = (register & 0b0010_0000) >> 5;
+            return Some((page * 0x400) + (address as usize & 0x3FF));
+        }
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            Mirroring::OneScreenLower => Some(mirroring::one_screen_lower(address) as usize),
+            Mirroring::OneScreenUpper => Some(mirroring::one_screen_upper(address) as usize),
+            _ => None
+        }
+    }
+}
+
+impl Mapper for Namco108 {
+    fn print_debug_status(&self) {
+        println!("======= Namco 108 =======");
+        println!("Mapper: {}, Bank Select: {}, Registers: {:?}", self.mapper_number, self.bank_select, self.bank_registers);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        if address >= 0x8000 && self.mapper_number == 154 {
+            self.mirroring = if (data & 0b0100_0000) == 0 {Mirroring::OneScreenLower} else {Mirroring::OneScreenUpper};
+        }
+        if (0x8000 ..= 0x9FFF).contains(&address) {
+            if address & 0b1 == 0 {
+                self.bank_select = data & 0b111;
+            } else {
+                self.bank_registers[self.bank_select as usize] = match self.bank_select {
+                    6 | 7 => data as usize & 0x0F,
+                    _ => data as usize & 0x3F,
+                };
+            }
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x400, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|index| self.vram[index]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x400, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => {
+                if let Some(index) = self.nametable_address(address) {
+                    self.vram[index] = data;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x400, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
@@ -0,0 +1,184 @@
+// Namco 175 and 340 (mapper 210), stripped down relatives of the 163 with the same PRG
+// and CHR banking, but no IRQ, sound or nametable banking. 175 boards (submapper 1) have
+// hardwired mirroring and optional PRG RAM; 340 boards (submapper 2) have no RAM but
+// control mirroring through the high bits of the first PRG register.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_210
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Namco175 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub namco340: bool,
+    pub prg_ram_enabled: bool,
+    pub chr_banks: Vec<usize>,
+    pub prg_banks: Vec<usize>,
+    pub mirroring: Mirroring,
+}
+
+impl Namco175 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Namco175, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
+        let chr_block = ines.chr_block()?;
+        // Without a submapper, guess from the battery: only 175 boards have PRG RAM
+        let namco340 = match ines.header.submapper_number() {
+            1 => false,
+            2 => true,
+            _ => !ines.header.has_sram(),
+        };
+
+        return Ok(Namco175 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            namco340: namco340,
+            prg_ram_enabled: false,
+            chr_banks: vec![0usize; 8],
+            prg_banks: vec![0usize; 3],
+            mirroring: ines.header.mirroring(),
+        })
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match address {
+            0x8000 ..= 0x9FFF => self.prg_banks[0],
+            0xA000 ..= 0xBFFF => self.prg_banks[1],
+            0xC000 ..= 0xDFFF => self.prg_banks[2],
+            _ => 0xFF,
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address / 0x400) as usize];
+    }
+}
+
+impl Mapper for Namco175 {
+    fn print_debug_status(&self) {
+        println!("======= Namco {} =======", if self.namco340 {"340"} else {"175"});
+        println!("PRG: {:?}, CHR: {:?}, PRG RAM Enabled: {}", self.prg_banks, self.chr_banks, self.prg_ram_enabled);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF if !self.namco340 && self.prg_ram_enabled => self.prg_ram.wrapping_read(address as usize - 0x6000),
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address & 0xF800 {
+            0x6000 ..= 0x7800 if !self.namco340 && self.prg_ram_enabled => self.prg_ram.wrapping_write(address as usize - 0x6000, data),
+            0x8000 ..= 0xB800 => {
+                let index = ((address - 0x8000) >> 11) as usize;
+                self.chr_banks[index] = data as usize;
+            },
+            0xC000 if !self.namco340 => {self.prg_ram_enabled = (data & 0b1) != 0;},
+            0xE000 => {
+                self.prg_banks[0] = data as usize & 0b0011_1111;
+                if self.namco340 {
+                    self.mirroring = match (data & 0b1100_0000) >> 6 {
+                        0 => Mirroring::OneScreenLower,
+                        1 => Mirroring::Vertical,
+                        2 => Mirroring::OneScreenUpper,
+                        _ => Mirroring::Horizontal,
+                    };
+                }
+            },
+            0xE800 => {self.prg_banks[1] = data as usize & 0b0011_1111;},
+            0xF000 => {self.prg_banks[2] = data as usize & 0b0011_1111;},
+            _ => {}
+        }
+    }
+
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF if !self.namco340 => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x400, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+                Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x400, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return !self.namco340;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        self.prg_ram.load(&sram_data);
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x400, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
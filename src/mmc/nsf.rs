@@ -12,6 +12,7 @@ use mmc::mapper::*;
 use mmc::mirroring;
 use nsf::NsfFile;
 use nsf::NsfHeader;
+use shift_jis;
 
 // various expansion audio chips
 use mmc::vrc6::Vrc6PulseChannel;
@@ -55,12 +56,46 @@ const PLAYER_TRACK_SELECT: u16 = 0x4901;
 const PLAYER_CURRENT_TRACK: u16 = 0x01FD;
 const PLAYER_BUTTON_REPORT: u16 = 0x4902;
 const PLAYER_RESET_BANKS: u16 = 0x4903;
+const PLAYER_RESTART_TRACK: u16 = 0x4904;
+const PLAYER_ROUTINE: u16 = 0x4905;
+const PLAYER_NMI_REASON: u16 = 0x4906;
 const PLAYER_ORIGIN: u16 = 0x4A00;
+// The player begins with a jump to the reset code, so the NMI handler's jump follows it
+const PLAYER_NMI_ENTRY: u16 = PLAYER_ORIGIN + 3;
 const PLAYER_SIZE: u16 = 0x0200;
 const PLAYER_END: u16 = PLAYER_ORIGIN + PLAYER_SIZE - 1;
 
 const JOYPAD1: u16 = 0x4016;
 
+// Marquee timing, in display updates (usually once per frame)
+const MARQUEE_HOLD: u32 = 90;
+const MARQUEE_SPEED: u32 = 8;
+const MARQUEE_GAP: &[u8] = b"    ";
+
+const NSF_CLOCK_RATE: u64 = 1_789_773;
+
+// Which of the tune's routines the player is currently inside, written to PLAYER_ROUTINE
+const ROUTINE_IDLE: u8 = 0;
+const ROUTINE_INIT: u8 = 1;
+const ROUTINE_PLAY: u8 = 2;
+
+// Why the mapper raised NMI, read (and acknowledged) from PLAYER_NMI_REASON
+const NMI_REASON_NONE: u8 = 0;
+const NMI_REASON_PLAY: u8 = 1;
+const NMI_REASON_WATCHDOG: u8 = 2;
+
+// Generous enough for INIT routines which decompress their data up front
+const DEFAULT_INIT_TIMEOUT: u64 = NSF_CLOCK_RATE * 2;
+// Several frames' worth; a PLAY call this long has almost certainly hung
+const DEFAULT_PLAY_TIMEOUT: u64 = NSF_CLOCK_RATE / 4;
+
+// Play periods are specified in microseconds
+const PLAYBACK_UNITS_PER_CYCLE: u64 = 1_000_000;
+
+// Samples differing by less than 0.005 (of full scale 1.0) count as silence
+const SILENCE_SAMPLE_SCALE: f32 = 65536.0;
+const SILENCE_THRESHOLD: i32 = 328;
+
 //const BUTTON_A: u8      = 1 << 7;
 //const BUTTON_B: u8      = 1 << 6;
 //const BUTTON_SELECT: u8 = 1 << 5;
@@ -198,6 +233,14 @@ fn initialize_memory() -> Opcode {
     ]);
 }
 
+// Tells the mapper which routine we're about to run, so it can time it. Clobbers only Y.
+fn enter_routine(routine: u8) -> Opcode {
+    return List(vec![
+        Ldy(Immediate(routine)),
+        Sty(Absolute(PLAYER_ROUTINE)),
+    ]);
+}
+
 fn init_track(init_address: u16) -> Opcode {
     return List(vec![
         // (bank initialization is handled by the mapper)
@@ -206,7 +249,9 @@ fn init_track(init_address: u16) -> Opcode {
         Sta(Absolute(PLAYER_CURRENT_TRACK)),
         // Indicate NTSC mode in X
         Ldx(Immediate(0x00)),
+        enter_routine(ROUTINE_INIT),
         Jsr(Absolute(init_address)),
+        enter_routine(ROUTINE_IDLE),
     ]);
 }
 
@@ -250,7 +295,12 @@ fn switch_tracks(init_address: u16) -> Opcode  {
         Label(String::from("switch_tracks")),
         Lda(Absolute(PLAYER_TRACK_SELECT)),
         Cmp(Absolute(PLAYER_CURRENT_TRACK)),
+        Bne(RelativeLabel(String::from("begin_switching_tracks"))),
+        // Same track; the mapper may still ask us to start it over
+        Lda(Absolute(PLAYER_RESTART_TRACK)),
         Beq(RelativeLabel(String::from("done_switching_tracks"))),
+        Lda(Absolute(PLAYER_TRACK_SELECT)),
+        Label(String::from("begin_switching_tracks")),
         // save the current track which we are about to switch to
         Sta(Absolute(PLAYER_CURRENT_TRACK)),
         // Reset the banks prior to the init call
@@ -263,13 +313,27 @@ fn switch_tracks(init_address: u16) -> Opcode  {
         // load X for NTSC mode and call Init with the new track number
         Ldx(Immediate(0x00)),
         Lda(Absolute(PLAYER_CURRENT_TRACK)),
+        enter_routine(ROUTINE_INIT),
         Jsr(Absolute(init_address)),
+        enter_routine(ROUTINE_IDLE),
         Label(String::from("done_switching_tracks")),
         Rts
     ]);
 }
 
-fn playback_loop(play_address: u16) -> Opcode {
+// PLAY, bracketed for the watchdog. NSF2 tunes may ask for it not to be called at all.
+fn call_play(play_address: u16, suppress_play: bool) -> Opcode {
+    if suppress_play {
+        return List(vec![]);
+    }
+    return List(vec![
+        enter_routine(ROUTINE_PLAY),
+        Jsr(Absolute(play_address)),
+        enter_routine(ROUTINE_IDLE),
+    ]);
+}
+
+fn playback_loop(play_address: u16, suppress_play: bool) -> Opcode {
     return List(vec![
         // setup playback counter wait condition
         Lda(Absolute(PLAYER_PLAYBACK_COUNTER)),
@@ -285,8 +349,9 @@ fn playback_loop(play_address: u16) -> Opcode {
         Sta(Absolute(PLAYER_COUNTER_COMPARE)),
         // Pop A off the stack, and call the play address
         Pla,
-        Jsr(Absolute(play_address)), // not yet
+        call_play(play_address, suppress_play),
         // Preserve A, since we are about to clobber it
+        Label(String::from("playback_resume")),
         Pha,
         // Poll for input (clobbers only A)
         Jsr(AbsoluteLabel(String::from("readjoy_safe"))),
@@ -296,8 +361,56 @@ fn playback_loop(play_address: u16) -> Opcode {
     ]);
 }
 
-fn nsf_player(init_address: u16, play_address: u16) -> Vec<Opcode> {
+// The mapper raises NMI for two reasons: to call PLAY while an NSF2 tune's INIT routine
+// never returns, and when the watchdog decides INIT or PLAY has hung. In the latter case
+// (or when the user picks another track from inside a non-returning INIT) we abandon
+// whatever the tune was doing and go back to the playback loop.
+fn nmi_handler(play_address: u16, suppress_play: bool) -> Opcode {
+    return List(vec![
+        Label(String::from("nmi_handler")),
+        Pha,
+        Txa,
+        Pha,
+        Tya,
+        Pha,
+        // Reading the reason also acknowledges it
+        Lda(Absolute(PLAYER_NMI_REASON)),
+        Beq(RelativeLabel(String::from("nmi_return"))),
+        Cmp(Immediate(NMI_REASON_PLAY)),
+        Bne(RelativeLabel(String::from("abandon_routine"))),
+        // Interrupting INIT, so put it back when we're done
+        call_play(play_address, suppress_play),
+        enter_routine(ROUTINE_INIT),
+        Jsr(AbsoluteLabel(String::from("readjoy_safe"))),
+        Lda(Absolute(PLAYER_TRACK_SELECT)),
+        Cmp(Absolute(PLAYER_CURRENT_TRACK)),
+        Bne(RelativeLabel(String::from("abandon_routine"))),
+        Lda(Absolute(PLAYER_RESTART_TRACK)),
+        Bne(RelativeLabel(String::from("abandon_routine"))),
+        Label(String::from("nmi_return")),
+        Pla,
+        Tay,
+        Pla,
+        Tax,
+        Pla,
+        Rti,
+
+        Label(String::from("abandon_routine")),
+        enter_routine(ROUTINE_IDLE),
+        Ldx(Immediate(0xF0)),
+        Txs,
+        Lda(Immediate(0x00)),
+        Jmp(AbsoluteLabel(String::from("playback_resume"))),
+    ]);
+}
+
+fn nsf_player(init_address: u16, play_address: u16, suppress_play: bool) -> Vec<Opcode> {
     vec![
+        // Fixed entry points, so the vectors can find them
+        Jmp(AbsoluteLabel(String::from("reset"))),
+        Jmp(AbsoluteLabel(String::from("nmi_handler"))),
+
+        Label(String::from("reset")),
         // Disable IRQ-based interrupts (We don't need them; NSF code by spec
         // shouldn't use them, and if it does, shenanigans.)
         Sei,
@@ -311,9 +424,10 @@ fn nsf_player(init_address: u16, play_address: u16) -> Vec<Opcode> {
         init_track(init_address),
 
         // This loop will never exit, it drives the playback indefinitely
-        playback_loop(play_address),
+        playback_loop(play_address, suppress_play),
 
         // subroutines
+        nmi_handler(play_address, suppress_play),
         poll_input(),
         switch_tracks(init_address),
         initialize_apu(),
@@ -340,21 +454,40 @@ pub struct NsfMapper {
     current_cycles: u64,
     fade_cycles: u64,
     max_cycles: u64,
-    current_sample: f32, // used for silence detection
-    last_sample: f32,
+    // used for silence detection, quantized so the comparison is exact across platforms
+    current_sample: i32,
+    last_sample: i32,
     silence_counter: u64,
     silence_threshold: u64,
     gui_row: u8,
+    marquee_timer: u32,
 
     // input shadows, populated by 6502 code
     p1_held: u8,
     p1_pressed: u8,
 
+    // Seek and loop controls. Seeking is carried out by NesState, which fast forwards
+    // while nsf_seeking() reports true; the mapper only tracks how far there is to go.
+    restart_requested: bool,
+    seek_cycles: u64,
+    loop_region: Option<(u64, u64)>,
+    loop_seek_pending: u64,
+
     prg_rom_banks: Vec<usize>,
-    playback_accumulator: f32,
-    playback_period: f32,
+    // Playback timing is kept in integer units of (cycles * 1,000,000), so a play period
+    // given in microseconds divides evenly and no float rounding accumulates
+    playback_accumulator: u64,
+    playback_period: u64,
     playback_counter: u8,
 
+    // Watchdog and NSF2 non-returning INIT support; see nmi_handler
+    routine: u8,
+    routine_cycles: u64,
+    init_timeout: u64,
+    play_timeout: u64,
+    nmi_reason: u8,
+    non_returning_init: bool,
+
     mirroring: Mirroring,
     vram: Vec<u8>,
 
@@ -392,8 +525,11 @@ pub struct NsfMapper {
 
 impl NsfMapper {
     pub fn from_nsf(nsf: NsfFile) -> Result<NsfMapper, String> {
-        let nsf_player_opcodes = nsf_player(nsf.header.init_address(), nsf.header.play_address());
+        let nsf_player_opcodes = nsf_player(nsf.header.init_address(), nsf.header.play_address(), nsf.header.suppressed_play());
         let mut nsf_player = assemble(nsf_player_opcodes, PLAYER_ORIGIN)?;
+        if nsf_player.len() > PLAYER_SIZE as usize {
+            return Err(format!("NSF player is {} bytes, which doesn't fit in its {} byte window.", nsf_player.len(), PLAYER_SIZE));
+        }
         nsf_player.resize(PLAYER_SIZE as usize, 0);
 
         let mut prg_rom = nsf.prg.clone();
@@ -412,14 +548,14 @@ impl NsfMapper {
             prg_rom_banks = vec![0, 1, 2, 3, 4, 5, 6, 7];
         }
 
-        let ntsc_clockrate = 1786860.0;
-        let cycles_per_play = (nsf.header.ntsc_playback_speed() as f32) * ntsc_clockrate / 1000000.0;
+        let ntsc_clockrate: u64 = 1786860;
+        let cycles_per_play = (nsf.header.ntsc_playback_speed() as u64) * ntsc_clockrate;
         let mut font_chr = include_bytes!("../../assets/troll8x8.chr").to_vec();
         font_chr.resize(0x2000, 0);
 
         // MMC5 pulses have no sweep unit, so we need to explicitly disable sweep muting
-        let mut mmc5_pulse_1 = PulseChannelState::new("Pulse 1", "MMC5", 1_789_773, false);
-        let mut mmc5_pulse_2 = PulseChannelState::new("Pulse 2", "MMC5", 1_789_773, false);
+        let mut mmc5_pulse_1 = PulseChannelState::new("Pulse 1", "MMC5", NSF_CLOCK_RATE, false);
+        let mut mmc5_pulse_2 = PulseChannelState::new("Pulse 2", "MMC5", NSF_CLOCK_RATE, false);
         mmc5_pulse_1.sweep_negate = true;
         mmc5_pulse_2.sweep_negate = true;
 
@@ -428,20 +564,33 @@ impl NsfMapper {
             chr: font_chr,
             nsf_player: nsf_player,
             header: nsf.header,
-            playback_accumulator: 0.0,
+            playback_accumulator: 0,
             playback_period: cycles_per_play,
             playback_counter: 0,
 
+            routine: ROUTINE_IDLE,
+            routine_cycles: 0,
+            init_timeout: DEFAULT_INIT_TIMEOUT,
+            play_timeout: DEFAULT_PLAY_TIMEOUT,
+            nmi_reason: NMI_REASON_NONE,
+            non_returning_init: nsf.header.non_returning_init(),
+
             current_track: nsf.header.starting_song(),
             advance_mode: if nsf.header.total_songs() > 1 {TrackAdvanceMode::Timer} else {TrackAdvanceMode::Manual},
             current_cycles: 0,
-            fade_cycles: 1_789_773 * 2,
-            max_cycles: 1_789_773 * 180,
-            current_sample: 0.0,
-            last_sample: 0.0,
+            fade_cycles: NSF_CLOCK_RATE * 2,
+            max_cycles: NSF_CLOCK_RATE * 180,
+            current_sample: 0,
+            last_sample: 0,
             silence_counter: 0,
-            silence_threshold: 1_789_773 * 3,
+            silence_threshold: NSF_CLOCK_RATE * 3,
             gui_row: 0,
+            marquee_timer: 0,
+
+            restart_requested: false,
+            seek_cycles: 0,
+            loop_region: None,
+            loop_seek_pending: 0,
 
             p1_held: 0,
             p1_pressed: 0,
@@ -502,6 +651,23 @@ impl NsfMapper {
         }
     }
 
+    // Like draw_string, but text too long for the field scrolls slowly to the left, pausing
+    // briefly each time the start comes back around
+    pub fn draw_marquee(&mut self, x: usize, y: usize, width: usize, chars: Vec<u8>) {
+        if chars.len() <= width {
+            self.draw_string(x, y, width, chars);
+            return;
+        }
+        let mut looped_chars = chars;
+        looped_chars.extend_from_slice(MARQUEE_GAP);
+        let loop_length = looped_chars.len();
+        let period = MARQUEE_HOLD + (loop_length as u32) * MARQUEE_SPEED;
+        let phase = self.marquee_timer % period;
+        let offset = if phase < MARQUEE_HOLD {0} else {((phase - MARQUEE_HOLD) / MARQUEE_SPEED) as usize};
+        let visible_chars = (0 .. width).map(|i| looped_chars[(offset + i) % loop_length]).collect();
+        self.draw_string(x, y, width, visible_chars);
+    }
+
     pub fn set_tile(&mut self, x: usize, y: usize, index: u8) {
         let tile = y * 32 + x;
         self.vram[tile] = index;
@@ -544,24 +710,25 @@ impl NsfMapper {
 
     pub fn update_display(&mut self) {
         self.clear_display();
+        self.marquee_timer = self.marquee_timer.wrapping_add(1);
 
         self.draw_string(21, 2, 9,  "RusticNES".as_bytes().to_vec());
         self.draw_string(20, 3, 10, "NSF Player".as_bytes().to_vec());
 
         self.draw_string(2, 5, 28, "Title".as_bytes().to_vec());
-        let song_name = self.header.song_name();
-        self.draw_string(2, 6, 28, song_name);
+        let song_name = shift_jis::transliterate(&self.header.song_name()).into_bytes();
+        self.draw_marquee(2, 6, 28, song_name);
 
         self.draw_string(2, 9, 28, "Artist".as_bytes().to_vec());
-        let artist_name = self.header.artist_name();
-        self.draw_string(2, 10, 28, artist_name);
+        let artist_name = shift_jis::transliterate(&self.header.artist_name()).into_bytes();
+        self.draw_marquee(2, 10, 28, artist_name);
 
         self.draw_string(2, 13, 28, "Copyright".as_bytes().to_vec());
-        let copyright_holder = self.header.copyright_holder();
-        self.draw_string(2, 14, 28, copyright_holder);
+        let copyright_holder = shift_jis::transliterate(&self.header.copyright_holder()).into_bytes();
+        self.draw_marquee(2, 14, 28, copyright_holder);
 
-        let current_seconds = self.current_cycles / 1_789_773;
-        let max_seconds = self.max_cycles / 1_789_773;
+        let current_seconds = self.current_cycles / NSF_CLOCK_RATE;
+        let max_seconds = self.max_cycles / NSF_CLOCK_RATE;
 
         let track_display = if self.header.total_songs() <= 1 {
             format!("{}", self.current_track)
@@ -669,10 +836,10 @@ impl NsfMapper {
                     self.gui_row -= 1;
                 }
                 if (self.p1_pressed & BUTTON_RIGHT) != 0  {
-                    self.max_cycles += 1_789_773 * 30;
+                    self.max_cycles += NSF_CLOCK_RATE * 30;
                 }
-                if (self.p1_pressed & BUTTON_LEFT) != 0 && self.max_cycles > 1_789_773 * 30 {
-                    self.max_cycles -= 1_789_773 * 30;
+                if (self.p1_pressed & BUTTON_LEFT) != 0 && self.max_cycles > NSF_CLOCK_RATE * 30 {
+                    self.max_cycles -= NSF_CLOCK_RATE * 30;
                 }
             },
             _ => {}
@@ -689,6 +856,10 @@ impl NsfMapper {
     }
 
     pub fn update_player(&mut self) {
+        if self.loop_region.is_some() {
+            // An A/B loop overrides automatic advancing entirely
+            return;
+        }
         match self.advance_mode {
             TrackAdvanceMode::Timer => {
                 if self.current_cycles > self.max_cycles {
@@ -1104,6 +1275,9 @@ impl NsfMapper {
     }
 
     fn fade_weight(&self) -> f32 {
+        if self.loop_region.is_some() {
+            return 1.0;
+        }
         match self.advance_mode {
             TrackAdvanceMode::Timer => {
                 let fade_start = self.max_cycles - self.fade_cycles;
@@ -1118,13 +1292,42 @@ impl NsfMapper {
         }
     }
 
+    fn clock_watchdog(&mut self, playback_tick: bool) {
+        if self.routine == ROUTINE_IDLE {
+            return;
+        }
+        self.routine_cycles += 1;
+        if self.nmi_reason != NMI_REASON_NONE {
+            return;
+        }
+        let timeout = match self.routine {
+            ROUTINE_INIT if self.non_returning_init => {
+                // INIT is expected to run forever, so PLAY has to interrupt it
+                if playback_tick {
+                    self.nmi_reason = NMI_REASON_PLAY;
+                }
+                return;
+            },
+            ROUTINE_INIT => self.init_timeout,
+            _ => self.play_timeout,
+        };
+        if timeout > 0 && self.routine_cycles > timeout {
+            self.nmi_reason = NMI_REASON_WATCHDOG;
+            self.routine = ROUTINE_IDLE;
+        }
+    }
+
     fn detect_silence(&self) -> bool {
         let delta = (self.last_sample - self.current_sample).abs();
-        return delta < 0.005;
+        return delta < SILENCE_THRESHOLD;
     }
 }
 
 impl Mapper for NsfMapper {
+    fn nsf_total_songs(&self) -> u8 {
+        return self.header.total_songs();
+    }
+
     fn nsf_set_track(&mut self, track_index: u8) {
         self.current_track = track_index;
     }
@@ -1133,17 +1336,55 @@ impl Mapper for NsfMapper {
         self.advance_mode = TrackAdvanceMode::Manual;
     }
 
+    fn nsf_restart_track(&mut self) {
+        self.restart_requested = true;
+        self.loop_seek_pending = 0;
+    }
+
+    fn nsf_seek(&mut self, seconds: u32) {
+        self.seek_cycles += (seconds as u64) * NSF_CLOCK_RATE;
+    }
+
+    fn nsf_set_loop(&mut self, region_seconds: Option<(u32, u32)>) {
+        self.loop_region = match region_seconds {
+            Some((start, end)) if end > start => Some(((start as u64) * NSF_CLOCK_RATE, (end as u64) * NSF_CLOCK_RATE)),
+            _ => None
+        };
+    }
+
+    fn nsf_seeking(&self) -> bool {
+        return self.seek_cycles > 0 || (self.restart_requested && self.loop_seek_pending > 0);
+    }
+
+    fn nsf_set_timeouts(&mut self, init_cycles: u64, play_cycles: u64) {
+        self.init_timeout = init_cycles;
+        self.play_timeout = play_cycles;
+    }
+
+    fn nmi_flag(&self) -> bool {
+        return self.nmi_reason != NMI_REASON_NONE;
+    }
+
+    fn reset(&mut self) {
+        // The player starts over, so whatever it was timing is gone
+        self.routine = ROUTINE_IDLE;
+        self.routine_cycles = 0;
+        self.nmi_reason = NMI_REASON_NONE;
+    }
+
     fn mirroring(&self) -> Mirroring {
         return self.mirroring;
     }
 
     fn clock_cpu(&mut self) {
-        self.playback_accumulator += 1.0;
-        if self.playback_accumulator > self.playback_period {
+        self.playback_accumulator += PLAYBACK_UNITS_PER_CYCLE;
+        let playback_tick = self.playback_accumulator > self.playback_period;
+        if playback_tick {
             self.playback_counter = self.playback_counter.wrapping_add(1);
             self.playback_accumulator -= self.playback_period;
             self.update_gui();
         }
+        self.clock_watchdog(playback_tick);
 
         self.clock_vrc6();
         self.clock_mmc5();
@@ -1153,6 +1394,16 @@ impl Mapper for NsfMapper {
         self.clock_fds();
         self.current_cycles += 1;
 
+        if self.seek_cycles > 0 {
+            self.seek_cycles -= 1;
+        }
+        if let Some((loop_start, loop_end)) = self.loop_region {
+            if self.current_cycles >= loop_end && !self.restart_requested {
+                self.restart_requested = true;
+                self.loop_seek_pending = loop_start;
+            }
+        }
+
         if self.detect_silence() {
             self.silence_counter += 1;
         } else {
@@ -1172,6 +1423,17 @@ impl Mapper for NsfMapper {
         return mixed_sample * self.fade_weight();
     }
 
+    fn expansion_chip_outputs(&self, _nes_sample: f32) -> Vec<(String, f32)> {
+        let mut outputs: Vec<(String, f32)> = Vec::new();
+        if self.vrc6_enabled {outputs.push((String::from("VRC6"), self.vrc6_output()));}
+        if self.mmc5_enabled {outputs.push((String::from("MMC5"), self.mmc5_output()));}
+        if self.s5b_enabled {outputs.push((String::from("YM2149F"), self.s5b_output()));}
+        if self.n163_enabled {outputs.push((String::from("N163"), self.n163_output()));}
+        if self.vrc7_enabled {outputs.push((String::from("VRC7"), self.vrc7_output()));}
+        if self.fds_enabled {outputs.push((String::from("FDS"), self.fds_output()));}
+        return outputs;
+    }
+
     fn channels(&self) ->  Vec<& dyn AudioChannelState> {
         let mut channels: Vec<& dyn AudioChannelState> = Vec::new();
         if self.vrc6_enabled {
@@ -1290,11 +1552,14 @@ impl Mapper for NsfMapper {
             self.fds_audio.record_current_output();
         }
         self.last_sample = self.current_sample;
-        self.current_sample = self.mix_expansion_audio(nes_sample);
+        self.current_sample = (self.mix_expansion_audio(nes_sample) * SILENCE_SAMPLE_SCALE) as i32;
     }
     
     fn read_cpu(&mut self, address: u16) -> Option<u8> {
         let data = self.debug_read_cpu(address);
+        if address == PLAYER_NMI_REASON {
+            self.nmi_reason = NMI_REASON_NONE;
+        }
         self.snoop_mmc5(address);
         self.n163_snoop(address);
         return data;
@@ -1314,6 +1579,8 @@ impl Mapper for NsfMapper {
         match address {
             PLAYER_PLAYBACK_COUNTER => Some(self.playback_counter),
             PLAYER_TRACK_SELECT => Some(self.current_track - 1),
+            PLAYER_RESTART_TRACK => Some(self.restart_requested as u8),
+            PLAYER_NMI_REASON => Some(self.nmi_reason),
             PLAYER_ORIGIN ..= PLAYER_END => Some(self.nsf_player[(address - PLAYER_ORIGIN) as usize]),
             0x6000 ..= 0x7FFF => Some(self.prg_ram[(address - 0x6000) as usize]),
             0x8000 ..= 0x8FFF => self.prg.banked_read(0x1000, self.prg_rom_banks[0], (address - 0x8000) as usize),
@@ -1323,7 +1590,9 @@ impl Mapper for NsfMapper {
             0xC000 ..= 0xCFFF => self.prg.banked_read(0x1000, self.prg_rom_banks[4], (address - 0xC000) as usize),
             0xD000 ..= 0xDFFF => self.prg.banked_read(0x1000, self.prg_rom_banks[5], (address - 0xD000) as usize),
             0xE000 ..= 0xEFFF => self.prg.banked_read(0x1000, self.prg_rom_banks[6], (address - 0xE000) as usize),
-            0xF000 ..= 0xFFFB => self.prg.banked_read(0x1000, self.prg_rom_banks[7], (address - 0xF000) as usize),
+            0xF000 ..= 0xFFF9 => self.prg.banked_read(0x1000, self.prg_rom_banks[7], (address - 0xF000) as usize),
+            0xFFFA => {Some(((PLAYER_NMI_ENTRY & 0x00FF) >> 0) as u8)}, // nmi vector
+            0xFFFB => {Some(((PLAYER_NMI_ENTRY & 0xFF00) >> 8) as u8)},
             0xFFFC => {Some(((PLAYER_ORIGIN & 0x00FF) >> 0) as u8)}, // reset vector
             0xFFFD => {Some(((PLAYER_ORIGIN & 0xFF00) >> 8) as u8)},
             0xFFFE ..= 0xFFFF => self.prg.banked_read(0x1000, self.prg_rom_banks[7], (address - 0xF000) as usize),
@@ -1337,11 +1606,22 @@ impl Mapper for NsfMapper {
                 self.p1_pressed = data & (!self.p1_held);
                 self.p1_held = data;
             },
+            PLAYER_ROUTINE => {
+                self.routine = data;
+                self.routine_cycles = 0;
+            },
             PLAYER_RESET_BANKS => {
                 self.prg_rom_banks = self.header.initial_banks();
                 if !self.header.is_bank_switched() {
                     self.prg_rom_banks = vec![0, 1, 2, 3, 4, 5, 6, 7];
                 }
+                if self.restart_requested {
+                    self.restart_requested = false;
+                    self.current_cycles = 0;
+                    self.silence_counter = 0;
+                    self.seek_cycles = self.loop_seek_pending;
+                    self.loop_seek_pending = 0;
+                }
             },
             0x5FF8 => {self.prg_rom_banks[0] = data as usize},
             0x5FF9 => {self.prg_rom_banks[1] = data as usize},
@@ -1374,6 +1654,13 @@ impl Mapper for NsfMapper {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => {self.prg_ram[(address - 0x6000) as usize] = data; true},
+            _ => false
+        };
+    }
+
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
         match address {
             0x0000 ..= 0x1FFF => return Some(self.chr[address as usize]),
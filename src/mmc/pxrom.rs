@@ -25,7 +25,7 @@ pub struct PxRom {
 impl PxRom {
     pub fn from_ines(ines: INesCartridge) -> Result<PxRom, String> {
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
         let chr_block = ines.chr_block()?;
 
         return Ok(PxRom {
@@ -89,7 +89,18 @@ impl Mapper for PxRom {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
     fn read_ppu(&mut self, address: u16) -> Option<u8> {
+        // The fetch that trips a latch still comes from the old bank; only later fetches
+        // see the switch. Note that MMC2 watches one exact address for the lower pattern
+        // table, but a range of 8 for the upper one.
+        let data = self.debug_read_ppu(address);
         match address {
             0x0FD8 => {self.chr_0_latch = 0;},
             0x0FE8 => {self.chr_0_latch = 1;},
@@ -97,7 +108,7 @@ impl Mapper for PxRom {
             0x1FE8 ..= 0x1FEF => {self.chr_1_latch = 1;},
             _ => {}
         }
-        return self.debug_read_ppu(address);
+        return data;
     }
 
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
@@ -137,4 +148,40 @@ impl Mapper for PxRom {
             _ => {}
         }
     }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0x9FFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank, cpu_address as usize - 0x8000),
+            0xA000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x2000, 0xFD,          cpu_address as usize - 0xA000),
+            0xC000 ..= 0xDFFF => banked_prg_offset(&self.prg_rom, 0x2000, 0xFE,          cpu_address as usize - 0xC000),
+            0xE000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, 0xFF,          cpu_address as usize - 0xE000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        let (latch, fd_bank, fe_bank) = match ppu_address {
+            0x0000 ..= 0x0FFF => (self.chr_0_latch, self.chr_0_fd_bank, self.chr_0_fe_bank),
+            0x1000 ..= 0x1FFF => (self.chr_1_latch, self.chr_1_fd_bank, self.chr_1_fe_bank),
+            _ => return None
+        };
+        let chr_bank = match latch {
+            0 => fd_bank,
+            1 => fe_bank,
+            _ => 0
+        };
+        return self.chr.banked_offset(0x1000, chr_bank, ppu_address as usize);
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
 }
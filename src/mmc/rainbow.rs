@@ -360,6 +360,16 @@ impl Rainbow {
         }
     }
 
+    fn poke_banked_memory(&mut self, is_fpga: bool, is_ram: bool, bank_number: usize, blocksize: usize, address: usize, data: u8) -> bool {
+        if is_fpga {
+            self.fpga_ram.banked_poke(blocksize, bank_number, address, data)
+        } else if is_ram {
+            self.prg_ram.banked_poke(blocksize, bank_number, address, data)
+        } else {
+            false
+        }
+    }
+
     fn read_fpga_area(&self, address: usize) -> Option<u8> {
         self.read_banked_memory(true, false, self.fpga_bank_at_5000, 0x1000, address)
     }
@@ -394,6 +404,19 @@ impl Rainbow {
         }
     }
 
+    fn poke_prg_ram_area(&mut self, address: usize, data: u8) -> bool {
+        match self.prg_ram_mode {
+            PrgRamBankingMode::Mode0Bank1x8k => self.poke_banked_memory(self.fpga_ram_at_6000, self.prg_ram_at_6000, self.prg_bank_at_6000, 0x2000, address, data),
+            PrgRamBankingMode::Mode1Bank2x4k => {
+                match address {
+                    0x6000 ..= 0x6FFF => self.poke_banked_memory(self.fpga_ram_at_6000, self.prg_ram_at_6000, self.prg_bank_at_6000, 0x1000, address, data),
+                    0x7000 ..= 0x7FFF => self.poke_banked_memory(self.fpga_ram_at_7000, self.prg_ram_at_7000, self.prg_bank_at_7000, 0x1000, address, data),
+                    _ => {false}
+                }
+            }
+        }
+    }
+
     fn read_prg_rom_area(&self, address: usize) -> Option<u8> {
         match self.prg_rom_mode {
             PrgRomBankingMode::Mode0Bank1x32k => self.read_banked_memory(false, self.prg_ram_at_8000, self.prg_bank_at_8000, 0x8000, address),
@@ -504,116 +527,26 @@ impl Rainbow {
         }
     }
 
+    // (bank number, bank size) of the regular CHR window covering this address
+    fn chr_window(&self, address: usize) -> (usize, usize) {
+        let blocksize = match self.chr_mode {
+            ChrBankingMode::Mode0Bank1x8k    => 0x2000,
+            ChrBankingMode::Mode1Bank2x4k    => 0x1000,
+            ChrBankingMode::Mode2Bank4x2k    => 0x0800,
+            ChrBankingMode::Mode3Bank8x1k    => 0x0400,
+            ChrBankingMode::Mode4Bank16x512b => 0x0200,
+        };
+        return (self.chr_banks[(address & 0x1FFF) / blocksize], blocksize);
+    }
+
     fn read_banked_chr_area(&self, address: usize) -> Option<u8> {
-        match self.chr_mode {
-            ChrBankingMode::Mode0Bank1x8k => self.read_banked_chr(self.chr_banks[0], 0x2000, address),
-            ChrBankingMode::Mode1Bank2x4k => {
-                match address {
-                    0x0000 ..= 0x0FFF => self.read_banked_chr(self.chr_banks[0], 0x1000, address),
-                    0x1000 ..= 0x1FFF => self.read_banked_chr(self.chr_banks[1], 0x1000, address),
-                    _ => {None}
-                }
-            },
-            ChrBankingMode::Mode2Bank4x2k => {
-                match address {
-                    0x0000 ..= 0x07FF => self.read_banked_chr(self.chr_banks[0], 0x0800, address),
-                    0x0800 ..= 0x0FFF => self.read_banked_chr(self.chr_banks[1], 0x0800, address),
-                    0x1000 ..= 0x17FF => self.read_banked_chr(self.chr_banks[2], 0x0800, address),
-                    0x1800 ..= 0x1FFF => self.read_banked_chr(self.chr_banks[3], 0x0800, address),
-                    _ => {None}
-                }
-            },
-            ChrBankingMode::Mode3Bank8x1k => {
-                match address {
-                    0x0000 ..= 0x03FF => self.read_banked_chr(self.chr_banks[0], 0x0400, address),
-                    0x0400 ..= 0x07FF => self.read_banked_chr(self.chr_banks[1], 0x0400, address),
-                    0x0800 ..= 0x0BFF => self.read_banked_chr(self.chr_banks[2], 0x0400, address),
-                    0x0C00 ..= 0x0FFF => self.read_banked_chr(self.chr_banks[3], 0x0400, address),
-                    0x1000 ..= 0x13FF => self.read_banked_chr(self.chr_banks[4], 0x0400, address),
-                    0x1400 ..= 0x17FF => self.read_banked_chr(self.chr_banks[5], 0x0400, address),
-                    0x1800 ..= 0x1BFF => self.read_banked_chr(self.chr_banks[6], 0x0400, address),
-                    0x1C00 ..= 0x1FFF => self.read_banked_chr(self.chr_banks[7], 0x0400, address),
-                    _ => {None}
-                }
-            },
-            ChrBankingMode::Mode4Bank16x512b => {
-                match address {
-                    0x0000 ..= 0x01FF => self.read_banked_chr(self.chr_banks[0],  0x0200, address),
-                    0x0200 ..= 0x03FF => self.read_banked_chr(self.chr_banks[1],  0x0200, address),
-                    0x0400 ..= 0x05FF => self.read_banked_chr(self.chr_banks[2],  0x0200, address),
-                    0x0600 ..= 0x07FF => self.read_banked_chr(self.chr_banks[3],  0x0200, address),
-                    0x0800 ..= 0x09FF => self.read_banked_chr(self.chr_banks[4],  0x0200, address),
-                    0x0A00 ..= 0x0BFF => self.read_banked_chr(self.chr_banks[5],  0x0200, address),
-                    0x0C00 ..= 0x0DFF => self.read_banked_chr(self.chr_banks[6],  0x0200, address),
-                    0x0E00 ..= 0x0FFF => self.read_banked_chr(self.chr_banks[7],  0x0200, address),
-                    0x1000 ..= 0x11FF => self.read_banked_chr(self.chr_banks[8],  0x0200, address),
-                    0x1200 ..= 0x13FF => self.read_banked_chr(self.chr_banks[9],  0x0200, address),
-                    0x1400 ..= 0x15FF => self.read_banked_chr(self.chr_banks[10], 0x0200, address),
-                    0x1600 ..= 0x17FF => self.read_banked_chr(self.chr_banks[11], 0x0200, address),
-                    0x1800 ..= 0x19FF => self.read_banked_chr(self.chr_banks[12], 0x0200, address),
-                    0x1A00 ..= 0x1BFF => self.read_banked_chr(self.chr_banks[13], 0x0200, address),
-                    0x1C00 ..= 0x1DFF => self.read_banked_chr(self.chr_banks[14], 0x0200, address),
-                    0x1E00 ..= 0x1FFF => self.read_banked_chr(self.chr_banks[15], 0x0200, address),
-                    _ => {None}
-                }
-            }
-        }
+        let (bank_number, blocksize) = self.chr_window(address);
+        return self.read_banked_chr(bank_number, blocksize, address);
     }
 
     fn write_banked_chr_area(&mut self, address: usize, data: u8) {
-        match self.chr_mode {
-            ChrBankingMode::Mode0Bank1x8k => self.write_banked_chr(self.chr_banks[0], 0x2000, address, data),
-            ChrBankingMode::Mode1Bank2x4k => {
-                match address {
-                    0x0000 ..= 0x0FFF => self.write_banked_chr(self.chr_banks[0], 0x1000, address, data),
-                    0x1000 ..= 0x1FFF => self.write_banked_chr(self.chr_banks[1], 0x1000, address, data),
-                    _ => {}
-                }
-            },
-            ChrBankingMode::Mode2Bank4x2k => {
-                match address {
-                    0x0000 ..= 0x07FF => self.write_banked_chr(self.chr_banks[0], 0x0800, address, data),
-                    0x0800 ..= 0x0FFF => self.write_banked_chr(self.chr_banks[1], 0x0800, address, data),
-                    0x1000 ..= 0x17FF => self.write_banked_chr(self.chr_banks[2], 0x0800, address, data),
-                    0x1800 ..= 0x1FFF => self.write_banked_chr(self.chr_banks[3], 0x0800, address, data),
-                    _ => {}
-                }
-            },
-            ChrBankingMode::Mode3Bank8x1k => {
-                match address {
-                    0x0000 ..= 0x03FF => self.write_banked_chr(self.chr_banks[0], 0x0400, address, data),
-                    0x0400 ..= 0x07FF => self.write_banked_chr(self.chr_banks[1], 0x0400, address, data),
-                    0x0800 ..= 0x0BFF => self.write_banked_chr(self.chr_banks[2], 0x0400, address, data),
-                    0x0C00 ..= 0x0FFF => self.write_banked_chr(self.chr_banks[3], 0x0400, address, data),
-                    0x1000 ..= 0x13FF => self.write_banked_chr(self.chr_banks[4], 0x0400, address, data),
-                    0x1400 ..= 0x17FF => self.write_banked_chr(self.chr_banks[5], 0x0400, address, data),
-                    0x1800 ..= 0x1BFF => self.write_banked_chr(self.chr_banks[6], 0x0400, address, data),
-                    0x1C00 ..= 0x1FFF => self.write_banked_chr(self.chr_banks[7], 0x0400, address, data),
-                    _ => {}
-                }
-            },
-            ChrBankingMode::Mode4Bank16x512b => {
-                match address {
-                    0x0000 ..= 0x01FF => self.write_banked_chr(self.chr_banks[0],  0x0200, address, data),
-                    0x0200 ..= 0x03FF => self.write_banked_chr(self.chr_banks[1],  0x0200, address, data),
-                    0x0400 ..= 0x05FF => self.write_banked_chr(self.chr_banks[2],  0x0200, address, data),
-                    0x0600 ..= 0x07FF => self.write_banked_chr(self.chr_banks[3],  0x0200, address, data),
-                    0x0800 ..= 0x09FF => self.write_banked_chr(self.chr_banks[4],  0x0200, address, data),
-                    0x0A00 ..= 0x0BFF => self.write_banked_chr(self.chr_banks[5],  0x0200, address, data),
-                    0x0C00 ..= 0x0DFF => self.write_banked_chr(self.chr_banks[6],  0x0200, address, data),
-                    0x0E00 ..= 0x0FFF => self.write_banked_chr(self.chr_banks[7],  0x0200, address, data),
-                    0x1000 ..= 0x11FF => self.write_banked_chr(self.chr_banks[8],  0x0200, address, data),
-                    0x1200 ..= 0x13FF => self.write_banked_chr(self.chr_banks[9],  0x0200, address, data),
-                    0x1400 ..= 0x15FF => self.write_banked_chr(self.chr_banks[10], 0x0200, address, data),
-                    0x1600 ..= 0x17FF => self.write_banked_chr(self.chr_banks[11], 0x0200, address, data),
-                    0x1800 ..= 0x19FF => self.write_banked_chr(self.chr_banks[12], 0x0200, address, data),
-                    0x1A00 ..= 0x1BFF => self.write_banked_chr(self.chr_banks[13], 0x0200, address, data),
-                    0x1C00 ..= 0x1DFF => self.write_banked_chr(self.chr_banks[14], 0x0200, address, data),
-                    0x1E00 ..= 0x1FFF => self.write_banked_chr(self.chr_banks[15], 0x0200, address, data),
-                    _ => {}
-                }
-            }
-        }
+        let (bank_number, blocksize) = self.chr_window(address);
+        self.write_banked_chr(bank_number, blocksize, address, data);
     }
 
     // Aaand a nametable variant, to round out the set
@@ -752,7 +685,7 @@ impl Rainbow {
 
     fn snoop_ppu_read(&mut self, address: u16) {
         self.cpu_cycles_since_last_ppu_read = 0;
-        self.ppu_fetches_this_scanline += 1;
+        self.ppu_fetches_this_scanline = self.ppu_fetches_this_scanline.saturating_add(1);
         if self.in_frame && self.ppu_fetches_this_scanline >= 127 {
             self.ppu_read_mode = PpuMode::Sprites;
             self.in_hblank = true;
@@ -823,6 +756,10 @@ impl Mapper for Rainbow {
         self.clock_irq();
     }
 
+    fn has_irq(&self) -> bool {
+        return true;
+    }
+
     fn irq_flag(&self) -> bool {
         return (self.cpu_irq_pending) || (self.scanline_irq_enabled && self.scanline_irq_pending);
     }
@@ -1246,6 +1183,13 @@ impl Mapper for Rainbow {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.poke_prg_ram_area(address as usize, data),
+            _ => false
+        };
+    }
+
     fn read_ppu(&mut self, address: u16) -> Option<u8> {
         self.snoop_ppu_read(address);
         return self.debug_read_ppu(address);
@@ -1310,6 +1254,22 @@ impl Mapper for Rainbow {
         self.vrc6_pulse2.record_current_output();
         self.vrc6_sawtooth.record_current_output();
     }
+
+    // An offset into whichever chip the CHR chip select points at. Extended backgrounds
+    // and sprites pick banks per tile, so this reports the regular banks only.
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_number, blocksize) = self.chr_window(ppu_address as usize);
+                match self.chr_chip {
+                    ChrChipSelect::ChrRom => self.chr_rom.banked_offset(blocksize, bank_number, ppu_address as usize),
+                    ChrChipSelect::ChrRam => self.chr_ram.banked_offset(blocksize, bank_number, ppu_address as usize),
+                    ChrChipSelect::FpgaRam => self.fpga_ram.banked_offset(0x1000, 0, ppu_address as usize),
+                }
+            },
+            _ => None
+        };
+    }
 }
 
 // Provided courtesy of Broke Studio. The raster font contained within is assumed
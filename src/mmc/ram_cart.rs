@@ -0,0 +1,150 @@
+// Not a real board, although it behaves like an EPROM emulator or a flash cart in its
+// programming mode: NROM layout, but every chip is RAM. A frontend uploads a program
+// through Mapper::upload_prg and upload_chr, then resets the console to run it, which
+// makes for an instant-run target for assemblers without building an iNES file first.
+//
+// CPU writes to $8000-$FFFF are ignored while write protected (the default), so software
+// sees ordinary ROM. Uploads are never write protected.
+
+use memoryblock::MemoryBlock;
+use memoryblock::MemoryType;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct RamCart {
+    pub prg: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub mirroring: Mirroring,
+    pub write_protect: bool,
+}
+
+impl RamCart {
+    pub fn new(mirroring: Mirroring) -> RamCart {
+        return RamCart {
+            prg: MemoryBlock::new(&[0u8; 0x8000], MemoryType::Ram),
+            prg_ram: MemoryBlock::new(&[0u8; 0x2000], MemoryType::Ram),
+            chr: MemoryBlock::new(&[0u8; 0x2000], MemoryType::Ram),
+            vram: vec![0u8; 0x1000],
+            mirroring: mirroring,
+            write_protect: true,
+        }
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            Mirroring::OneScreenLower => Some(mirroring::one_screen_lower(address) as usize),
+            Mirroring::OneScreenUpper => Some(mirroring::one_screen_upper(address) as usize),
+            Mirroring::FourScreen => Some(mirroring::four_banks(address) as usize),
+        }
+    }
+}
+
+impl Mapper for RamCart {
+    fn print_debug_status(&self) {
+        println!("======= RAM Cart =======");
+        println!("32k PRG RAM, 8k WRAM, 8k CHR RAM, Write Protected: {}", self.write_protect);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read(address as usize - 0x6000),
+            0x8000 ..= 0xFFFF => self.prg.wrapping_read(address as usize - 0x8000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_write(address as usize - 0x6000, data),
+            0x8000 ..= 0xFFFF if !self.write_protect => self.prg.wrapping_write(address as usize - 0x8000, data),
+            _ => {}
+        }
+    }
+
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_read(address as usize),
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|index| self.vram[index]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_write(address as usize, data),
+            0x2000 ..= 0x3FFF => {
+                if let Some(index) = self.nametable_address(address) {
+                    self.vram[index] = data;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn upload_prg(&mut self, address: u16, data: &[u8]) -> Result<(), String> {
+        let start = address as usize;
+        if start < 0x6000 || start + data.len() > 0x10000 {
+            return Err(format!("{} bytes at ${:04X} don't fit in $6000-$FFFF", data.len(), address));
+        }
+        for (i, &byte) in data.iter().enumerate() {
+            match start + i {
+                offset @ 0x6000 ..= 0x7FFF => self.prg_ram.wrapping_write(offset - 0x6000, byte),
+                offset => self.prg.wrapping_write(offset - 0x8000, byte),
+            }
+        }
+        return Ok(());
+    }
+
+    fn upload_chr(&mut self, address: u16, data: &[u8]) -> Result<(), String> {
+        let start = address as usize;
+        if start + data.len() > 0x2000 {
+            return Err(format!("{} bytes at ${:04X} don't fit in $0000-$1FFF", data.len(), address));
+        }
+        self.chr.as_mut_vec()[start .. start + data.len()].copy_from_slice(data);
+        return Ok(());
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg, 0x8000, 0, cpu_address as usize - 0x8000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_offset(ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
@@ -0,0 +1,327 @@
+// Jaleco SS88006 (mapper 18): three switchable 8k PRG banks, eight 1k CHR banks, and a CPU
+// cycle IRQ counter which can be narrowed to 12, 8 or 4 bits. Every bank number and the
+// IRQ reload value are written one nybble at a time.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_018
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Ss88006 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub prg_banks: Vec<usize>,
+    pub chr_banks: Vec<usize>,
+    pub mirroring: Mirroring,
+    pub prg_ram_enabled: bool,
+    pub prg_ram_writable: bool,
+
+    pub irq_reload: u16,
+    pub irq_counter: u16,
+    // Only these bits of the counter decrement; the rest are left alone
+    pub irq_counter_mask: u16,
+    pub irq_enabled: bool,
+    pub irq_pending: bool,
+}
+
+impl Ss88006 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Ss88006, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Ss88006 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            prg_banks: vec![0usize; 3],
+            chr_banks: vec![0usize; 8],
+            mirroring: Mirroring::Horizontal,
+            prg_ram_enabled: false,
+            prg_ram_writable: false,
+
+            irq_reload: 0,
+            irq_counter: 0,
+            irq_counter_mask: 0xFFFF,
+            irq_enabled: false,
+            irq_pending: false,
+        })
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match address {
+            0x8000 ..= 0x9FFF => self.prg_banks[0],
+            0xA000 ..= 0xBFFF => self.prg_banks[1],
+            0xC000 ..= 0xDFFF => self.prg_banks[2],
+            _ => 0xFF,
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address / 0x400) as usize];
+    }
+
+    // Odd registers hold the high nybble, even registers the low one
+    fn write_bank_nybble(bank: &mut usize, high: bool, data: u8) {
+        if high {
+            *bank = (*bank & 0x0F) | ((data as usize & 0x0F) << 4);
+        } else {
+            *bank = (*bank & 0xF0) | (data as usize & 0x0F);
+        }
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            Mirroring::OneScreenLower => Some(mirroring::one_screen_lower(address) as usize),
+            Mirroring::OneScreenUpper => Some(mirroring::one_screen_upper(address) as usize),
+            _ => None
+        }
+    }
+}
+
+impl Mapper for Ss88006 {
+    fn print_debug_status(&self) {
+        println!("======= SS88006 =======");
+        println!("PRG: {:?}, CHR: {:?}", self.prg_banks, self.chr_banks);
+        println!("IRQ: Counter: {:04X}, Reload: {:04X}, Mask: {:04X}, Enabled: {}", self.irq_counter, self.irq_reload, self.irq_counter_mask, self.irq_enabled);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn clock_cpu(&mut self) {
+        if self.irq_enabled {
+            let counter = self.irq_counter & self.irq_counter_mask;
+            if counter == 0 {
+                self.irq_pending = true;
+            }
+            let counter = counter.wrapping_sub(1) & self.irq_counter_mask;
+            self.irq_counter = (self.irq_counter & !self.irq_counter_mask) | counter;
+        }
+    }
+
+    fn irq_flag(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        let counter = self.irq_counter & self.irq_counter_mask;
+        return Some(MapperIrqState {
+            counter: self.irq_counter as u32,
+            latch: self.irq_reload as u32,
+            enabled: self.irq_enabled,
+            pending: self.irq_pending,
+            unit: IrqCounterUnit::CpuCycles,
+            clocks_until_irq: if self.irq_enabled {Some(counter as u32 + 1)} else {None},
+        });
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF => {
+                if self.prg_ram_enabled {
+                    self.prg_ram.wrapping_read(address as usize - 0x6000)
+                } else {
+                    None
+                }
+            },
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        let high = (address & 0b1) != 0;
+        match address & 0xF003 {
+            0x6000 ..= 0x7FFF if self.prg_ram_enabled && self.prg_ram_writable => {
+                self.prg_ram.wrapping_write(address as usize - 0x6000, data);
+            },
+            0x8000 | 0x8001 => Ss88006::write_bank_nybble(&mut self.prg_banks[0], high, data),
+            0x8002 | 0x8003 => Ss88006::write_bank_nybble(&mut self.prg_banks[1], high, data),
+            0x9000 | 0x9001 => Ss88006::write_bank_nybble(&mut self.prg_banks[2], high, data),
+            0x9002 => {
+                self.prg_ram_enabled = (data & 0b01) != 0;
+                self.prg_ram_writable = (data & 0b10) != 0;
+            },
+            0xA000 ..= 0xD003 => {
+                // Two registers per bank, two banks per $1000
+                let bank_index = (((address - 0xA000) >> 12) * 2 + ((address & 0b10) >> 1)) as usize;
+                Ss88006::write_bank_nybble(&mut self.chr_banks[bank_index], high, data);
+            },
+            0xE000 ..= 0xE003 => {
+                let shift = (address & 0b11) * 4;
+                self.irq_reload = (self.irq_reload & !(0xF << shift)) | (((data & 0x0F) as u16) << shift);
+            },
+            0xF000 => {
+                self.irq_pending = false;
+                self.irq_counter = self.irq_reload;
+            },
+            0xF001 => {
+                self.irq_pending = false;
+                self.irq_enabled = (data & 0b0001) != 0;
+                self.irq_counter_mask = if (data & 0b1000) != 0 {
+                    0x000F
+                } else if (data & 0b0100) != 0 {
+                    0x00FF
+                } else if (data & 0b0010) != 0 {
+                    0x0FFF
+                } else {
+                    0xFFFF
+                };
+            },
+            0xF002 => {
+                self.mirroring = match data & 0b11 {
+                    0 => Mirroring::Horizontal,
+                    1 => Mirroring::Vertical,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+            },
+            // $F003 drives the uPD7756 speech chip on a few boards, which isn't emulated
+            _ => {}
+        }
+    }
+
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x400, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|index| self.vram[index]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x400, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => {
+                if let Some(index) = self.nametable_address(address) {
+                    self.vram[index] = data;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return true;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        self.prg_ram.load(&sram_data);
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x400, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bank_numbers_are_assembled_from_nybbles() {
+        // 256k PRG and 128k CHR, so bank numbers need both nybbles
+        let mut mapper = Ss88006::from_ines(INesCartridge::test_image(18, 16, 16)).unwrap();
+        mapper.write_cpu(0x8000, 0x05);
+        mapper.write_cpu(0x8001, 0x01);
+        mapper.write_cpu(0x8002, 0xF6);
+        mapper.write_cpu(0x8003, 0xF0);
+        mapper.write_cpu(0x9000, 0x03);
+        mapper.write_cpu(0x9001, 0x01);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(0x15));
+        assert_eq!(mapper.debug_read_cpu(0xA000), Some(0x06));
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(0x13));
+        assert_eq!(mapper.debug_read_cpu(0xE000), Some(31));
+
+        // Rewriting one nybble leaves the other alone
+        mapper.write_cpu(0x8001, 0x00);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(0x05));
+
+        mapper.write_cpu(0xA000, 0x0A);
+        mapper.write_cpu(0xA001, 0x04);
+        mapper.write_cpu(0xA002, 0x01);
+        mapper.write_cpu(0xA003, 0x02);
+        mapper.write_cpu(0xD002, 0x0F);
+        mapper.write_cpu(0xD003, 0x07);
+        assert_eq!(mapper.debug_read_ppu(0x0000), Some(0x4A));
+        assert_eq!(mapper.debug_read_ppu(0x0400), Some(0x21));
+        assert_eq!(mapper.debug_read_ppu(0x1C00), Some(0x7F));
+    }
+
+    // Reloads the counter with $1234 in the given width, then counts down to the IRQ
+    fn clocks_until_irq(width_bits: u8) -> (u32, u16) {
+        let mut mapper = Ss88006::from_ines(INesCartridge::test_image(18, 2, 1)).unwrap();
+        mapper.write_cpu(0xE000, 0x4);
+        mapper.write_cpu(0xE001, 0x3);
+        mapper.write_cpu(0xE002, 0x2);
+        mapper.write_cpu(0xE003, 0x1);
+        mapper.write_cpu(0xF000, 0);
+        mapper.write_cpu(0xF001, width_bits | 0b0001);
+        let mut clocks = 0;
+        while !mapper.irq_flag() {
+            mapper.clock_cpu();
+            clocks += 1;
+            assert!(clocks <= 0x10000);
+        }
+        return (clocks, mapper.irq_counter);
+    }
+
+    #[test]
+    fn irq_counter_widths() {
+        // Only the masked bits count; the counter fires as they wrap from 0, and whatever is
+        // above them stays put
+        assert_eq!(clocks_until_irq(0b0000), (0x1235, 0xFFFF));
+        assert_eq!(clocks_until_irq(0b0010), (0x235, 0x1FFF));
+        assert_eq!(clocks_until_irq(0b0100), (0x35, 0x12FF));
+        assert_eq!(clocks_until_irq(0b1000), (0x5, 0x123F));
+        // The narrowest width set wins
+        assert_eq!(clocks_until_irq(0b1110), (0x5, 0x123F));
+    }
+}
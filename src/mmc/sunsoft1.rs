@@ -0,0 +1,117 @@
+// Sunsoft-1 (mapper 184): fixed 32k PRG with a pair of 4k CHR banks, selected through a
+// register at $6000-$7FFF. The upper bank's high bit is wired high, so it always comes
+// from the second half of an 8-bank CHR ROM.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_184
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Sunsoft1 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mirroring: Mirroring,
+    pub chr_banks: Vec<usize>,
+    pub vram: Vec<u8>,
+}
+
+impl Sunsoft1 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Sunsoft1, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Sunsoft1 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            mirroring: ines.header.mirroring(),
+            chr_banks: vec![0usize, 4usize],
+            vram: vec![0u8; 0x1000],
+        })
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address / 0x1000) as usize];
+    }
+}
+
+impl Mapper for Sunsoft1 {
+    fn print_debug_status(&self) {
+        println!("======= Sunsoft 1 =======");
+        println!("CHR Banks: {:?}", self.chr_banks);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.wrapping_read(address as usize - 0x8000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        if (0x6000 ..= 0x7FFF).contains(&address) {
+            self.chr_banks[0] = (data & 0b0000_0111) as usize;
+            self.chr_banks[1] = (((data & 0b0111_0000) >> 4) | 0b100) as usize;
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x1000, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x1000, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x8000, 0, cpu_address as usize - 0x8000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x1000, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
@@ -0,0 +1,136 @@
+// Sunsoft-2, UxROM style PRG banking plus 8k CHR banking, on two different boards:
+//   89 (Sunsoft-3 board): CPPP MCCC, with a one screen mirroring select
+//   93 (Sunsoft-3R board): .PPP ...E, CHR RAM which can be disabled, and fixed mirroring
+// Both decode $8000-$FFFF without disabling the ROM, so writes conflict with the byte the
+// ROM drives onto the bus and only bits set in both take effect.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_089
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_093
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Sunsoft2 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mapper_number: u16,
+    pub mirroring: Mirroring,
+    pub prg_bank: usize,
+    pub chr_bank: usize,
+    pub chr_enabled: bool,
+    pub vram: Vec<u8>,
+}
+
+impl Sunsoft2 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Sunsoft2, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+        let mapper_number = ines.header.mapper_number();
+
+        return Ok(Sunsoft2 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            mapper_number: mapper_number,
+            mirroring: if mapper_number == 89 {Mirroring::OneScreenLower} else {ines.header.mirroring()},
+            prg_bank: 0,
+            chr_bank: 0,
+            chr_enabled: true,
+            vram: vec![0u8; 0x1000],
+        })
+    }
+}
+
+impl Mapper for Sunsoft2 {
+    fn print_debug_status(&self) {
+        println!("======= Sunsoft 2 =======");
+        println!("Mapper: {}, PRG Bank: {}, CHR Bank: {}, CHR Enabled: {}", self.mapper_number, self.prg_bank, self.chr_bank, self.chr_enabled);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, 0xFF, address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        if address < 0x8000 {
+            return;
+        }
+        let data = data & self.debug_read_cpu(address).unwrap_or(0xFF);
+        self.prg_bank = ((data & 0b0111_0000) >> 4) as usize;
+        if self.mapper_number == 89 {
+            self.chr_bank = (((data & 0b1000_0000) >> 4) | (data & 0b0000_0111)) as usize;
+            self.mirroring = if (data & 0b0000_1000) == 0 {Mirroring::OneScreenLower} else {Mirroring::OneScreenUpper};
+        } else {
+            self.chr_enabled = (data & 0b0000_0001) != 0;
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF if self.chr_enabled => self.chr.banked_read(0x2000, self.chr_bank, address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+                Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank;
+                self.chr.banked_write(0x2000, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank, cpu_address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x4000, 0xFF, cpu_address as usize - 0xC000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x2000, self.chr_bank, ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
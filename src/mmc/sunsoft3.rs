@@ -0,0 +1,244 @@
+// Sunsoft-3 (mapper 67): 2k CHR banking, 16k PRG banking, and a 16-bit CPU cycle IRQ
+// counter which is loaded a byte at a time through a single register.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_067
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Sunsoft3 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub prg_bank: usize,
+    pub chr_banks: Vec<usize>,
+    pub mirroring: Mirroring,
+
+    pub irq_enabled: bool,
+    pub irq_counter: u16,
+    pub irq_pending: bool,
+    // Writes to $C800 alternate between the high byte (first) and the low byte
+    pub irq_write_low: bool,
+}
+
+impl Sunsoft3 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Sunsoft3, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Sunsoft3 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            prg_bank: 0,
+            chr_banks: vec![0usize; 4],
+            mirroring: Mirroring::Vertical,
+
+            irq_enabled: false,
+            irq_counter: 0,
+            irq_pending: false,
+            irq_write_low: false,
+        })
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address / 0x800) as usize];
+    }
+}
+
+impl Mapper for Sunsoft3 {
+    fn print_debug_status(&self) {
+        println!("======= Sunsoft 3 =======");
+        println!("PRG Bank: {}, CHR: {:?}", self.prg_bank, self.chr_banks);
+        println!("IRQ: Counter: {:04X}, Enabled: {}", self.irq_counter, self.irq_enabled);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn clock_cpu(&mut self) {
+        if self.irq_enabled {
+            self.irq_counter = self.irq_counter.wrapping_sub(1);
+            // The counter stops after wrapping, until enabled again
+            if self.irq_counter == 0xFFFF {
+                self.irq_enabled = false;
+                self.irq_pending = true;
+            }
+        }
+    }
+
+    fn irq_flag(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        return Some(MapperIrqState {
+            counter: self.irq_counter as u32,
+            latch: 0,
+            enabled: self.irq_enabled,
+            pending: self.irq_pending,
+            unit: IrqCounterUnit::CpuCycles,
+            clocks_until_irq: if self.irq_enabled {Some(self.irq_counter as u32 + 1)} else {None},
+        });
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read(address as usize - 0x6000),
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, 0xFF, address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address & 0xF800 {
+            0x6000 ..= 0x7800 => self.prg_ram.wrapping_write(address as usize - 0x6000, data),
+            0x8000 => {self.irq_pending = false;},
+            0x8800 => {self.chr_banks[0] = data as usize;},
+            0x9800 => {self.chr_banks[1] = data as usize;},
+            0xA800 => {self.chr_banks[2] = data as usize;},
+            0xB800 => {self.chr_banks[3] = data as usize;},
+            0xC800 => {
+                if self.irq_write_low {
+                    self.irq_counter = (self.irq_counter & 0xFF00) | (data as u16);
+                } else {
+                    self.irq_counter = (self.irq_counter & 0x00FF) | ((data as u16) << 8);
+                }
+                self.irq_write_low = !self.irq_write_low;
+            },
+            0xD800 => {
+                self.irq_enabled = (data & 0b0001_0000) != 0;
+                self.irq_write_low = false;
+            },
+            0xE800 => {
+                self.mirroring = match data & 0b11 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+            },
+            0xF800 => {self.prg_bank = data as usize;},
+            _ => {}
+        }
+    }
+
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x800, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+                Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x800, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank, cpu_address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x4000, 0xFF, cpu_address as usize - 0xC000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x800, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn banking() {
+        let mut mapper = Sunsoft3::from_ines(INesCartridge::test_image(67, 8, 8)).unwrap();
+        mapper.write_cpu(0xF800, 0x02);
+        mapper.write_cpu(0x8800, 0x05);
+        mapper.write_cpu(0xB800, 0x1F);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(4));
+        assert_eq!(mapper.debug_read_cpu(0xFFFF), Some(15));
+        // 2k banks, so 1k banks 10 and 11
+        assert_eq!(mapper.debug_read_ppu(0x0000), Some(10));
+        assert_eq!(mapper.debug_read_ppu(0x0400), Some(11));
+        assert_eq!(mapper.debug_read_ppu(0x1C00), Some(63));
+    }
+
+    #[test]
+    fn irq_counter_loads_high_byte_first_and_stops_after_wrapping() {
+        let mut mapper = Sunsoft3::from_ines(INesCartridge::test_image(67, 8, 8)).unwrap();
+        mapper.write_cpu(0xC800, 0x01);
+        mapper.write_cpu(0xC800, 0x02);
+        assert_eq!(mapper.irq_counter, 0x0102);
+        mapper.write_cpu(0xD800, 0b0001_0000);
+        for _ in 0 .. 0x102 {
+            mapper.clock_cpu();
+        }
+        assert!(!mapper.irq_flag());
+        mapper.clock_cpu();
+        assert!(mapper.irq_flag());
+        assert!(!mapper.irq_enabled);
+        mapper.clock_cpu();
+        assert_eq!(mapper.irq_counter, 0xFFFF);
+
+        // Acknowledge, then writing $D800 rewinds the byte toggle to the high byte
+        mapper.write_cpu(0x8000, 0);
+        assert!(!mapper.irq_flag());
+        mapper.write_cpu(0xC800, 0x03);
+        mapper.write_cpu(0xD800, 0);
+        mapper.write_cpu(0xC800, 0x04);
+        assert_eq!(mapper.irq_counter, 0x04FF);
+    }
+}
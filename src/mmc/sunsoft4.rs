@@ -0,0 +1,193 @@
+// Sunsoft-4 (mapper 68): 16k PRG banking, 2k CHR banking, and the ability to map the
+// nametables from CHR ROM, which After Burner uses for its large background graphics.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_068
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Sunsoft4 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub prg_bank: usize,
+    pub prg_ram_enabled: bool,
+    pub chr_banks: Vec<usize>,
+    // 1k CHR ROM banks standing in for CIRAM pages 0 and 1
+    pub nametable_banks: Vec<usize>,
+    pub nametable_chrrom: bool,
+    pub mirroring: Mirroring,
+}
+
+impl Sunsoft4 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Sunsoft4, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Sunsoft4 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            prg_bank: 0,
+            prg_ram_enabled: false,
+            chr_banks: vec![0usize; 4],
+            nametable_banks: vec![0x80usize; 2],
+            nametable_chrrom: false,
+            mirroring: Mirroring::Vertical,
+        })
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address / 0x800) as usize];
+    }
+
+    // Which of the two 1k pages the current mirroring selects. In CHR ROM mode, the same
+    // mirroring rules pick between the two nametable banks instead of CIRAM.
+    fn nametable_page(&self, address: u16) -> Option<usize> {
+        let ciram_address = match self.mirroring {
+            Mirroring::Horizontal => mirroring::horizontal_mirroring(address),
+            Mirroring::Vertical   => mirroring::vertical_mirroring(address),
+            Mirroring::OneScreenLower => mirroring::one_screen_lower(address),
+            Mirroring::OneScreenUpper => mirroring::one_screen_upper(address),
+            _ => return None
+        };
+        return Some((ciram_address as usize & 0x400) >> 10);
+    }
+
+    fn nametable_read(&self, address: u16) -> Option<u8> {
+        let page = self.nametable_page(address)?;
+        if self.nametable_chrrom {
+            return self.chr.banked_read(0x400, self.nametable_banks[page], address as usize);
+        }
+        return Some(self.vram[(page * 0x400) + (address as usize & 0x3FF)]);
+    }
+
+    fn nametable_write(&mut self, address: u16, data: u8) {
+        if self.nametable_chrrom {
+            return;
+        }
+        if let Some(page) = self.nametable_page(address) {
+            self.vram[(page * 0x400) + (address as usize & 0x3FF)] = data;
+        }
+    }
+}
+
+impl Mapper for Sunsoft4 {
+    fn print_debug_status(&self) {
+        println!("======= Sunsoft 4 =======");
+        println!("PRG Bank: {}, PRG RAM Enabled: {}, CHR: {:?}", self.prg_bank, self.prg_ram_enabled, self.chr_banks);
+        println!("Nametable Banks: {:?}, From CHR ROM: {}", self.nametable_banks, self.nametable_chrrom);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF if self.prg_ram_enabled => self.prg_ram.wrapping_read(address as usize - 0x6000),
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, 0xFF, address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x6000 ..= 0x7FFF if self.prg_ram_enabled => self.prg_ram.wrapping_write(address as usize - 0x6000, data),
+            0x8000 ..= 0xBFFF => {
+                let index = ((address - 0x8000) / 0x1000) as usize;
+                self.chr_banks[index] = data as usize;
+            },
+            // Nametable banks always come from the upper 128k of CHR ROM
+            0xC000 ..= 0xCFFF => {self.nametable_banks[0] = (data as usize & 0x7F) | 0x80;},
+            0xD000 ..= 0xDFFF => {self.nametable_banks[1] = (data as usize & 0x7F) | 0x80;},
+            0xE000 ..= 0xEFFF => {
+                self.mirroring = match data & 0b11 {
+                    0 => Mirroring::Vertical,
+                    1 => Mirroring::Horizontal,
+                    2 => Mirroring::OneScreenLower,
+                    _ => Mirroring::OneScreenUpper,
+                };
+                self.nametable_chrrom = (data & 0b0001_0000) != 0;
+            },
+            0xF000 ..= 0xFFFF => {
+                self.prg_bank = data as usize & 0b0000_1111;
+                self.prg_ram_enabled = (data & 0b0001_0000) != 0;
+            },
+            _ => {}
+        }
+    }
+
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x800, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => self.nametable_read(address),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x800, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => self.nametable_write(address, data),
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return true;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        self.prg_ram.load(&sram_data);
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank, cpu_address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x4000, 0xFF, cpu_address as usize - 0xC000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x800, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
@@ -0,0 +1,333 @@
+// Taito TC0190 (mapper 33) and its successor TC0690 (mapper 48): two switchable 8k PRG
+// banks, two 2k and four 1k CHR banks. The TC0690 moves the mirroring control to its own
+// register and adds an MMC3 style scanline IRQ, which fires a little later than the MMC3's.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_033
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_048
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::a12_filter::A12Filter;
+use mmc::mapper::*;
+use mmc::mirroring;
+
+// CPU cycles between the counter reaching zero and the IRQ line going low
+const IRQ_DELAY: u8 = 4;
+
+pub struct TaitoTc0190 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub mirroring: Mirroring,
+    pub prg_banks: Vec<usize>,
+    // 2k banks at $0000 and $0800, then 1k banks at $1000, $1400, $1800 and $1C00
+    pub chr_banks: Vec<usize>,
+    pub tc0690: bool,
+
+    pub irq_counter: u8,
+    pub irq_reload: u8,
+    pub irq_reload_requested: bool,
+    pub irq_enabled: bool,
+    pub irq_flag: bool,
+    pub irq_delay: u8,
+    pub a12_filter: A12Filter,
+}
+
+impl TaitoTc0190 {
+    pub fn from_ines(ines: INesCartridge) -> Result<TaitoTc0190, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(TaitoTc0190 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            mirroring: Mirroring::Vertical,
+            prg_banks: vec![0usize; 2],
+            chr_banks: vec![0usize; 6],
+            tc0690: ines.header.mapper_number() == 48,
+
+            irq_counter: 0,
+            irq_reload: 0,
+            irq_reload_requested: false,
+            irq_enabled: false,
+            irq_flag: false,
+            irq_delay: 0,
+            a12_filter: A12Filter::new(),
+        });
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match address {
+            0x8000 ..= 0x9FFF => self.prg_banks[0],
+            0xA000 ..= 0xBFFF => self.prg_banks[1],
+            0xC000 ..= 0xDFFF => 0xFE,
+            _ => 0xFF,
+        }
+    }
+
+    // (bank size, bank number) of the CHR window covering this address
+    fn chr_bank(&self, address: u16) -> (usize, usize) {
+        return match address {
+            0x0000 ..= 0x07FF => (0x800, self.chr_banks[0]),
+            0x0800 ..= 0x0FFF => (0x800, self.chr_banks[1]),
+            _ => (0x400, self.chr_banks[2 + ((address as usize - 0x1000) / 0x400)]),
+        }
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            _ => None
+        }
+    }
+
+    fn snoop_ppu_a12(&mut self, address: u16) {
+        if self.tc0690 && self.a12_filter.snoop_ppu(address) {
+            self.clock_irq_counter();
+        }
+    }
+
+    fn clock_irq_counter(&mut self) {
+        if self.irq_counter == 0 || self.irq_reload_requested {
+            self.irq_counter = self.irq_reload;
+            self.irq_reload_requested = false;
+        } else {
+            self.irq_counter -= 1;
+        }
+        if self.irq_counter == 0 && self.irq_enabled && self.irq_delay == 0 {
+            self.irq_delay = IRQ_DELAY;
+        }
+    }
+}
+
+impl Mapper for TaitoTc0190 {
+    fn print_debug_status(&self) {
+        println!("======= {} =======", if self.tc0690 {"TC0690"} else {"TC0190"});
+        println!("PRG: {:?}, CHR: {:?}", self.prg_banks, self.chr_banks);
+        if self.tc0690 {
+            println!("IRQ: Current: {}, Reload: {}, Enabled: {}", self.irq_counter, self.irq_reload, self.irq_enabled);
+        }
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn clock_cpu(&mut self) {
+        if !self.tc0690 {
+            return;
+        }
+        self.a12_filter.snoop_cpu_m2();
+        if self.irq_delay > 0 {
+            self.irq_delay -= 1;
+            if self.irq_delay == 0 && self.irq_enabled {
+                self.irq_flag = true;
+            }
+        }
+    }
+
+    fn irq_flag(&self) -> bool {
+        return self.irq_flag;
+    }
+
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        if !self.tc0690 {
+            return None;
+        }
+        let clocks_until_irq = if self.irq_counter == 0 || self.irq_reload_requested {
+            if self.irq_reload == 0 {1} else {self.irq_reload as u32 + 1}
+        } else {
+            self.irq_counter as u32
+        };
+        return Some(MapperIrqState {
+            counter: self.irq_counter as u32,
+            latch: self.irq_reload as u32,
+            enabled: self.irq_enabled,
+            pending: self.irq_flag,
+            unit: IrqCounterUnit::Scanlines,
+            clocks_until_irq: if self.irq_enabled {Some(clocks_until_irq)} else {None},
+        });
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address & 0xE003 {
+            0x8000 => {
+                if self.tc0690 {
+                    self.prg_banks[0] = data as usize;
+                } else {
+                    self.prg_banks[0] = data as usize & 0x3F;
+                    self.mirroring = if data & 0b0100_0000 == 0 {Mirroring::Vertical} else {Mirroring::Horizontal};
+                }
+            },
+            0x8001 => {self.prg_banks[1] = if self.tc0690 {data as usize} else {data as usize & 0x3F};},
+            0x8002 => {self.chr_banks[0] = data as usize;},
+            0x8003 => {self.chr_banks[1] = data as usize;},
+            0xA000 ..= 0xA003 => {self.chr_banks[2 + (address as usize & 0x3)] = data as usize;},
+            _ if !self.tc0690 => {},
+            // The latch is stored inverted
+            0xC000 => {self.irq_reload = data ^ 0xFF;},
+            0xC001 => {
+                self.irq_counter = 0;
+                self.irq_reload_requested = true;
+            },
+            0xC002 => {self.irq_enabled = true;},
+            0xC003 => {
+                self.irq_enabled = false;
+                self.irq_flag = false;
+                self.irq_delay = 0;
+            },
+            0xE000 => {
+                self.mirroring = if data & 0b0100_0000 == 0 {Mirroring::Vertical} else {Mirroring::Horizontal};
+            },
+            _ => {}
+        }
+    }
+
+    fn read_ppu(&mut self, address: u16) -> Option<u8> {
+        self.snoop_ppu_a12(address);
+        return self.debug_read_ppu(address);
+    }
+
+    fn access_ppu(&mut self, address: u16) {
+        self.snoop_ppu_a12(address);
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_size, bank) = self.chr_bank(address);
+                self.chr.banked_read(bank_size, bank, address as usize)
+            },
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|index| self.vram[index]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        self.snoop_ppu_a12(address);
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_size, bank) = self.chr_bank(address);
+                self.chr.banked_write(bank_size, bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => {
+                if let Some(index) = self.nametable_address(address) {
+                    self.vram[index] = data;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_size, bank) = self.chr_bank(ppu_address);
+                self.chr.banked_offset(bank_size, bank, ppu_address as usize)
+            },
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tc0190_banking_and_mirroring() {
+        let mut mapper = TaitoTc0190::from_ines(INesCartridge::test_image(33, 8, 4)).unwrap();
+        mapper.write_cpu(0x8000, 0b0100_0101);
+        mapper.write_cpu(0x8001, 0x06);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(5));
+        assert_eq!(mapper.debug_read_cpu(0xA000), Some(6));
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(14));
+        assert_eq!(mapper.debug_read_cpu(0xE000), Some(15));
+        assert!(mapper.mirroring() == Mirroring::Horizontal);
+
+        mapper.write_cpu(0x8002, 3);
+        mapper.write_cpu(0x8003, 4);
+        mapper.write_cpu(0xA000, 20);
+        mapper.write_cpu(0xA003, 31);
+        assert_eq!(mapper.debug_read_ppu(0x0000), Some(6));
+        assert_eq!(mapper.debug_read_ppu(0x0400), Some(7));
+        assert_eq!(mapper.debug_read_ppu(0x0800), Some(8));
+        assert_eq!(mapper.debug_read_ppu(0x1000), Some(20));
+        assert_eq!(mapper.debug_read_ppu(0x1C00), Some(31));
+
+        // No IRQ hardware on the TC0190
+        assert!(mapper.irq_state().is_none());
+    }
+
+    #[test]
+    fn tc0690_has_its_own_mirroring_register() {
+        let mut mapper = TaitoTc0190::from_ines(INesCartridge::test_image(48, 8, 4)).unwrap();
+        mapper.write_cpu(0x8000, 0b0100_0101);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(5));
+        assert!(mapper.mirroring() == Mirroring::Vertical);
+        mapper.write_cpu(0xE000, 0b0100_0000);
+        assert!(mapper.mirroring() == Mirroring::Horizontal);
+    }
+
+    // Background fetches from $0000, then a sprite fetch raising A12 well after the filter
+    // has settled
+    fn scanline(mapper: &mut TaitoTc0190) {
+        mapper.read_ppu(0x0000);
+        for _ in 0 .. 3 {
+            mapper.clock_cpu();
+        }
+        mapper.read_ppu(0x1000);
+    }
+
+    #[test]
+    fn tc0690_irq_fires_a_few_cycles_after_reaching_zero() {
+        let mut mapper = TaitoTc0190::from_ines(INesCartridge::test_image(48, 8, 4)).unwrap();
+        // Reload with 2, stored inverted
+        mapper.write_cpu(0xC000, 0xFF ^ 2);
+        mapper.write_cpu(0xC001, 0);
+        mapper.write_cpu(0xC002, 0);
+        // The first clock reloads, then two more count down to zero
+        for _ in 0 .. 3 {
+            scanline(&mut mapper);
+        }
+        for _ in 0 .. IRQ_DELAY - 1 {
+            mapper.clock_cpu();
+        }
+        assert!(!mapper.irq_flag());
+        mapper.clock_cpu();
+        assert!(mapper.irq_flag());
+
+        mapper.write_cpu(0xC003, 0);
+        assert!(!mapper.irq_flag());
+    }
+}
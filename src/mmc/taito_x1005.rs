@@ -0,0 +1,211 @@
+// Taito X1-005 (mapper 80): three switchable 8k PRG banks, two 2k and four 1k CHR banks,
+// and 128 bytes of battery backed RAM inside the mapper, all mapped at $7Exx-$7Fxx.
+// Fudou Myouou Den (mapper 207) wires the top bit of each 2k CHR register to CIRAM A10
+// instead, selecting the nametable for each half of the screen.
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_080
+// https://wiki.nesdev.com/w/index.php/INES_Mapper_207
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+// Writing this to $7EF8/$7EF9 unlocks the internal RAM
+const RAM_PERMISSION_KEY: u8 = 0xA3;
+
+pub struct TaitoX1005 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub internal_ram: Vec<u8>,
+    pub ram_enabled: bool,
+    pub vram: Vec<u8>,
+    pub mirroring: Mirroring,
+    pub prg_banks: Vec<usize>,
+    // 2k banks at $0000 and $0800, then 1k banks at $1000, $1400, $1800 and $1C00
+    pub chr_banks: Vec<usize>,
+    pub nametable_select: bool,
+    pub nametable_banks: Vec<usize>,
+}
+
+impl TaitoX1005 {
+    pub fn from_ines(ines: INesCartridge) -> Result<TaitoX1005, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+
+        return Ok(TaitoX1005 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            internal_ram: vec![0u8; 0x80],
+            ram_enabled: false,
+            vram: vec![0u8; 0x1000],
+            mirroring: Mirroring::Horizontal,
+            prg_banks: vec![0usize; 3],
+            chr_banks: vec![0usize; 6],
+            nametable_select: ines.header.mapper_number() == 207,
+            nametable_banks: vec![0, 1],
+        });
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match address {
+            0x8000 ..= 0x9FFF => self.prg_banks[0],
+            0xA000 ..= 0xBFFF => self.prg_banks[1],
+            0xC000 ..= 0xDFFF => self.prg_banks[2],
+            _ => 0xFF,
+        }
+    }
+
+    // (bank size, bank number) of the CHR window covering this address
+    fn chr_bank(&self, address: u16) -> (usize, usize) {
+        return match address {
+            0x0000 ..= 0x07FF => (0x800, self.chr_banks[0]),
+            0x0800 ..= 0x0FFF => (0x800, self.chr_banks[1]),
+            _ => (0x400, self.chr_banks[2 + ((address as usize - 0x1000) / 0x400)]),
+        }
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        if self.nametable_select {
+            let bank = self.nametable_banks[(address as usize & 0xFFF) / 0x800];
+            return Some(bank * 0x400 + (address as usize & 0x3FF));
+        }
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            _ => None
+        }
+    }
+
+    fn write_chr_2k(&mut self, index: usize, data: u8) {
+        // The register holds a 1k bank number; the low bit is ignored
+        if !self.nametable_select {
+            self.chr_banks[index] = data as usize >> 1;
+        } else {
+            self.chr_banks[index] = (data as usize & 0x7F) >> 1;
+            self.nametable_banks[index] = (data as usize & 0x80) >> 7;
+            self.mirroring = match (self.nametable_banks[0], self.nametable_banks[1]) {
+                (0, 0) => Mirroring::OneScreenLower,
+                (1, 1) => Mirroring::OneScreenUpper,
+                _ => Mirroring::Horizontal,
+            };
+        }
+    }
+}
+
+impl Mapper for TaitoX1005 {
+    fn print_debug_status(&self) {
+        println!("======= X1-005 =======");
+        println!("PRG: {:?}, CHR: {:?}", self.prg_banks, self.chr_banks);
+        println!("Internal RAM enabled: {}", self.ram_enabled);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x7F00 ..= 0x7FFF => if self.ram_enabled {Some(self.internal_ram[address as usize & 0x7F])} else {None},
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x7EF0 => self.write_chr_2k(0, data),
+            0x7EF1 => self.write_chr_2k(1, data),
+            0x7EF2 ..= 0x7EF5 => {self.chr_banks[2 + (address as usize - 0x7EF2)] = data as usize;},
+            0x7EF6 | 0x7EF7 if !self.nametable_select => {
+                self.mirroring = if data & 0b1 == 0 {Mirroring::Horizontal} else {Mirroring::Vertical};
+            },
+            0x7EF8 | 0x7EF9 => {self.ram_enabled = data == RAM_PERMISSION_KEY;},
+            0x7EFA | 0x7EFB => {self.prg_banks[0] = data as usize;},
+            0x7EFC | 0x7EFD => {self.prg_banks[1] = data as usize;},
+            0x7EFE | 0x7EFF => {self.prg_banks[2] = data as usize;},
+            0x7F00 ..= 0x7FFF if self.ram_enabled => {
+                self.internal_ram[address as usize & 0x7F] = data;
+            },
+            _ => {}
+        }
+    }
+
+    // Only the internal RAM; the registers sit just below it, at $7EF0-$7EFF
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x7F00 ..= 0x7FFF => {self.internal_ram[address as usize & 0x7F] = data; true},
+            _ => false
+        };
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_size, bank) = self.chr_bank(address);
+                self.chr.banked_read(bank_size, bank, address as usize)
+            },
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|index| self.vram[index]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_size, bank) = self.chr_bank(address);
+                self.chr.banked_write(bank_size, bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => {
+                if let Some(index) = self.nametable_address(address) {
+                    self.vram[index] = data;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return true;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.internal_ram.clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        let length = sram_data.len().min(self.internal_ram.len());
+        self.internal_ram[.. length].copy_from_slice(&sram_data[.. length]);
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_size, bank) = self.chr_bank(ppu_address);
+                self.chr.banked_offset(bank_size, bank, ppu_address as usize)
+            },
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
@@ -0,0 +1,284 @@
+// UNROM 512 (mapper 30), by RetroUSB and InfiniteNESLives: UxROM style 16k PRG banking
+// extended to 512k, plus banked CHR RAM and a choice of nametable layouts picked by the
+// header. Boards with the battery bit set are self-flashable: the register moves to
+// $C000-$FFFF, and writes to $8000-$BFFF talk to the flash chip instead. Flash contents
+// are saved and restored through the SRAM interface.
+// https://wiki.nesdev.com/w/index.php/UNROM_512
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+use memoryblock::MemoryType;
+
+use mmc::flash::SstFlash;
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Unrom512 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mirroring: Mirroring,
+    pub one_screen_switchable: bool,
+    pub flashable: bool,
+    pub flash: SstFlash,
+    pub prg_bank: usize,
+    pub chr_bank: usize,
+    pub vram: Vec<u8>,
+}
+
+impl Unrom512 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Unrom512, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        // iNES 1.0 can't describe this much CHR RAM, so assume the full 32k
+        let chr_ram_size = if ines.header.version() == 2 && ines.header.chr_ram_size() > 0 {ines.header.chr_ram_size()} else {0x8000};
+
+        // The nametable flags have their own meaning on this board:
+        // H/V are hard wired as usual, alternative + horizontal is switchable one-screen,
+        // and alternative + vertical is four-screen using the last 8k of CHR RAM.
+        let alternative = ines.header.alternative_nametables();
+        let mirroring = match (alternative, ines.header.vertical_arrangement_bit()) {
+            (false, false) => Mirroring::Horizontal,
+            (false, true)  => Mirroring::Vertical,
+            (true, false)  => Mirroring::OneScreenLower,
+            (true, true)   => Mirroring::FourScreen,
+        };
+
+        return Ok(Unrom512 {
+            prg_rom: prg_rom_block.clone(),
+            chr: MemoryBlock::new(&vec![0u8; chr_ram_size], MemoryType::Ram),
+            mirroring: mirroring,
+            one_screen_switchable: mirroring == Mirroring::OneScreenLower,
+            flashable: ines.header.has_sram(),
+            flash: SstFlash::new(),
+            prg_bank: 0,
+            chr_bank: 0,
+            vram: vec![0u8; 0x1000],
+        })
+    }
+
+    fn write_register(&mut self, data: u8) {
+        self.prg_bank = (data & 0b0001_1111) as usize;
+        self.chr_bank = ((data & 0b0110_0000) >> 5) as usize;
+        if self.one_screen_switchable {
+            self.mirroring = if (data & 0b1000_0000) == 0 {Mirroring::OneScreenLower} else {Mirroring::OneScreenUpper};
+        }
+    }
+
+    fn flash_address(&self, address: u16) -> usize {
+        return (self.prg_bank * 0x4000) + (address as usize & 0x3FFF);
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            Mirroring::OneScreenLower => Some(mirroring::one_screen_lower(address) as usize),
+            Mirroring::OneScreenUpper => Some(mirroring::one_screen_upper(address) as usize),
+            _ => None
+        };
+    }
+}
+
+impl Mapper for Unrom512 {
+    fn print_debug_status(&self) {
+        println!("======= UNROM 512 =======");
+        println!("PRG Bank: {}, CHR Bank: {}, Flashable: {}", self.prg_bank, self.chr_bank, self.flashable);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            // While identifying itself, the flash chip answers for the whole of PRG
+            0x8000 ..= 0xFFFF if self.flash.id_mode => self.flash.read(&self.prg_rom, self.flash_address(address)),
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, 0xFF, address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x8000 ..= 0xBFFF => {
+                if self.flashable {
+                    let flash_address = self.flash_address(address);
+                    self.flash.write(&mut self.prg_rom, flash_address, data);
+                } else {
+                    self.write_register(data);
+                }
+            },
+            0xC000 ..= 0xFFFF => self.write_register(data),
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x2000, self.chr_bank, address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::FourScreen => self.chr.banked_read(0x2000, 3, address as usize - 0x2000),
+                _ => self.nametable_address(address).map(|vram_address| self.vram[vram_address]),
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_write(0x2000, self.chr_bank, address as usize, data),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::FourScreen => self.chr.banked_write(0x2000, 3, address as usize - 0x2000, data),
+                _ => {
+                    if let Some(vram_address) = self.nametable_address(address) {
+                        self.vram[vram_address] = data;
+                    }
+                },
+            },
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return self.flashable;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        if !self.flashable {
+            return vec![0u8; 0];
+        }
+        return self.prg_rom.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        if self.flashable {
+            self.prg_rom.load(&sram_data);
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank, cpu_address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x4000, 0xFF, cpu_address as usize - 0xC000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x2000, self.chr_bank, ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 128k of PRG, with the battery bit that makes the board self-flashable
+    fn flashable_mapper() -> Unrom512 {
+        let mut mapper = Unrom512::from_ines(INesCartridge::test_image(30, 8, 0)).unwrap();
+        mapper.flashable = true;
+        return mapper;
+    }
+
+    // The register picks which 16k of flash $8000-$BFFF talks to, so the chip's $5555 is
+    // $9555 in bank 1 and its $2AAA is $AAAA in bank 0
+    fn flash_write(mapper: &mut Unrom512, bank: u8, address: u16, data: u8) {
+        mapper.write_cpu(0xC000, bank);
+        mapper.write_cpu(address, data);
+    }
+
+    fn command(mapper: &mut Unrom512, command: u8) {
+        flash_write(mapper, 1, 0x9555, 0xAA);
+        flash_write(mapper, 0, 0xAAAA, 0x55);
+        flash_write(mapper, 1, 0x9555, command);
+    }
+
+    #[test]
+    fn byte_program_only_clears_bits() {
+        let mut mapper = flashable_mapper();
+        // 16k bank 3 starts with 8k bank 6
+        command(&mut mapper, 0xA0);
+        flash_write(&mut mapper, 3, 0x8010, 0b0000_0101);
+        assert_eq!(mapper.debug_read_cpu(0x8010), Some(0b0000_0100));
+        assert_eq!(mapper.debug_read_cpu(0x8011), Some(6));
+
+        // A write without the unlock sequence changes nothing
+        flash_write(&mut mapper, 3, 0x8011, 0x00);
+        assert_eq!(mapper.debug_read_cpu(0x8011), Some(6));
+
+        // And the result survives a save and reload
+        let sram = mapper.get_sram();
+        let mut reloaded = flashable_mapper();
+        reloaded.load_sram(sram);
+        reloaded.write_cpu(0xC000, 3);
+        assert_eq!(reloaded.debug_read_cpu(0x8010), Some(0b0000_0100));
+    }
+
+    #[test]
+    fn sector_erase_clears_one_4k_sector() {
+        let mut mapper = flashable_mapper();
+        command(&mut mapper, 0x80);
+        flash_write(&mut mapper, 1, 0x9555, 0xAA);
+        flash_write(&mut mapper, 0, 0xAAAA, 0x55);
+        flash_write(&mut mapper, 2, 0x9234, 0x30);
+        mapper.write_cpu(0xC000, 2);
+        assert_eq!(mapper.debug_read_cpu(0x8FFF), Some(4));
+        assert_eq!(mapper.debug_read_cpu(0x9000), Some(0xFF));
+        assert_eq!(mapper.debug_read_cpu(0x9FFF), Some(0xFF));
+        assert_eq!(mapper.debug_read_cpu(0xA000), Some(5));
+    }
+
+    #[test]
+    fn chip_erase_clears_everything() {
+        let mut mapper = flashable_mapper();
+        command(&mut mapper, 0x80);
+        flash_write(&mut mapper, 1, 0x9555, 0xAA);
+        flash_write(&mut mapper, 0, 0xAAAA, 0x55);
+        flash_write(&mut mapper, 1, 0x9555, 0x10);
+        assert!(mapper.get_sram().iter().all(|&byte| byte == 0xFF));
+    }
+
+    #[test]
+    fn software_id_mode() {
+        let mut mapper = flashable_mapper();
+        command(&mut mapper, 0x90);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(0xBF));
+        assert_eq!(mapper.debug_read_cpu(0xC001), Some(0xB5));
+        command(&mut mapper, 0xF0);
+        mapper.write_cpu(0xC000, 0);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(0));
+        assert_eq!(mapper.debug_read_cpu(0xC001), Some(14));
+    }
+
+    #[test]
+    fn out_of_sequence_writes_abort_the_command() {
+        let mut mapper = flashable_mapper();
+        flash_write(&mut mapper, 1, 0x9555, 0xAA);
+        flash_write(&mut mapper, 0, 0xAAAB, 0x55);
+        flash_write(&mut mapper, 1, 0x9555, 0xA0);
+        flash_write(&mut mapper, 1, 0x8000, 0x00);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(2));
+
+        // The chip is back to waiting for a fresh unlock
+        command(&mut mapper, 0xA0);
+        flash_write(&mut mapper, 1, 0x8000, 0x00);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(0));
+    }
+}
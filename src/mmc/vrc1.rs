@@ -0,0 +1,147 @@
+// VRC1, the earliest of Konami's mappers: three switchable 8k PRG banks and two 4k CHR
+// banks, whose high bits share a register with the mirroring control.
+// https://wiki.nesdev.com/w/index.php/VRC1
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Vrc1 {
+    pub prg_rom: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub prg_banks: Vec<usize>,
+    pub chr_banks: Vec<usize>,
+    pub mirroring: Mirroring,
+    pub four_screen: bool,
+}
+
+impl Vrc1 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Vrc1, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let chr_block = ines.chr_block()?;
+        let four_screen = ines.header.mirroring() == Mirroring::FourScreen;
+
+        return Ok(Vrc1 {
+            prg_rom: prg_rom_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            prg_banks: vec![0usize; 3],
+            chr_banks: vec![0usize; 2],
+            mirroring: if four_screen {Mirroring::FourScreen} else {Mirroring::Vertical},
+            four_screen: four_screen,
+        });
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match address {
+            0x8000 ..= 0x9FFF => self.prg_banks[0],
+            0xA000 ..= 0xBFFF => self.prg_banks[1],
+            0xC000 ..= 0xDFFF => self.prg_banks[2],
+            _ => 0xFF,
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address / 0x1000) as usize];
+    }
+
+    fn nametable_address(&self, address: u16) -> Option<usize> {
+        return match self.mirroring {
+            Mirroring::Horizontal => Some(mirroring::horizontal_mirroring(address) as usize),
+            Mirroring::Vertical   => Some(mirroring::vertical_mirroring(address) as usize),
+            Mirroring::FourScreen => Some(mirroring::four_banks(address) as usize),
+            _ => None
+        }
+    }
+}
+
+impl Mapper for Vrc1 {
+    fn print_debug_status(&self) {
+        println!("======= VRC1 =======");
+        println!("PRG: {:?}, CHR: {:?}", self.prg_banks, self.chr_banks);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x8000 ..= 0x8FFF => {self.prg_banks[0] = data as usize & 0x0F;},
+            0x9000 ..= 0x9FFF => {
+                // Four screen boards ignore the mirroring bit, but still use the CHR bits
+                if !self.four_screen {
+                    self.mirroring = if data & 0b001 == 0 {Mirroring::Vertical} else {Mirroring::Horizontal};
+                }
+                self.chr_banks[0] = (self.chr_banks[0] & 0x0F) | (((data as usize & 0b010) >> 1) << 4);
+                self.chr_banks[1] = (self.chr_banks[1] & 0x0F) | (((data as usize & 0b100) >> 2) << 4);
+            },
+            0xA000 ..= 0xAFFF => {self.prg_banks[1] = data as usize & 0x0F;},
+            0xC000 ..= 0xCFFF => {self.prg_banks[2] = data as usize & 0x0F;},
+            0xE000 ..= 0xEFFF => {self.chr_banks[0] = (self.chr_banks[0] & 0x10) | (data as usize & 0x0F);},
+            0xF000 ..= 0xFFFF => {self.chr_banks[1] = (self.chr_banks[1] & 0x10) | (data as usize & 0x0F);},
+            _ => {}
+        }
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x1000, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => self.nametable_address(address).map(|index| self.vram[index]),
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x1000, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => {
+                if let Some(index) = self.nametable_address(address) {
+                    self.vram[index] = data;
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x1000, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
@@ -0,0 +1,276 @@
+// VRC3, which is UxROM banking plus a 16-bit CPU cycle IRQ counter. Salamander is its only
+// licensed game, and uses the counter for its status bar split.
+// https://wiki.nesdev.com/w/index.php/VRC3
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Vrc3 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub mirroring: Mirroring,
+    pub prg_bank: usize,
+    pub vram: Vec<u8>,
+
+    pub irq_latch: u16,
+    pub irq_counter: u16,
+    pub irq_enable: bool,
+    pub irq_enable_after_acknowledgement: bool,
+    // Only the low 8 bits count (and reload) in 8-bit mode
+    pub irq_8bit_mode: bool,
+    pub irq_pending: bool,
+}
+
+impl Vrc3 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Vrc3, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
+        let chr_block = ines.chr_block()?;
+
+        return Ok(Vrc3 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            mirroring: ines.header.mirroring(),
+            prg_bank: 0,
+            vram: vec![0u8; 0x1000],
+
+            irq_latch: 0,
+            irq_counter: 0,
+            irq_enable: false,
+            irq_enable_after_acknowledgement: false,
+            irq_8bit_mode: false,
+            irq_pending: false,
+        })
+    }
+
+    // Each write sets one nybble of the 16-bit latch
+    fn write_latch_nybble(&mut self, shift: u16, data: u8) {
+        self.irq_latch = (self.irq_latch & !(0xF << shift)) | (((data & 0x0F) as u16) << shift);
+    }
+
+    fn _clock_irq_counter(&mut self) {
+        if self.irq_8bit_mode {
+            if self.irq_counter & 0x00FF == 0x00FF {
+                self.irq_counter = (self.irq_counter & 0xFF00) | (self.irq_latch & 0x00FF);
+                self.irq_pending = true;
+            } else {
+                self.irq_counter += 1;
+            }
+        } else if self.irq_counter == 0xFFFF {
+            self.irq_counter = self.irq_latch;
+            self.irq_pending = true;
+        } else {
+            self.irq_counter += 1;
+        }
+    }
+}
+
+impl Mapper for Vrc3 {
+    fn print_debug_status(&self) {
+        println!("======= VRC3 =======");
+        println!("PRG Bank: {}, ", self.prg_bank);
+        println!("IRQ: Counter: {:04X}, Latch: {:04X}, Enabled: {}, 8-bit: {}", self.irq_counter, self.irq_latch, self.irq_enable, self.irq_8bit_mode);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn clock_cpu(&mut self) {
+        if self.irq_enable {
+            self._clock_irq_counter();
+        }
+    }
+
+    fn irq_flag(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        let (counter, reload_point) = if self.irq_8bit_mode {
+            ((self.irq_counter & 0x00FF) as u32, 0xFF)
+        } else {
+            (self.irq_counter as u32, 0xFFFF)
+        };
+        return Some(MapperIrqState {
+            counter: self.irq_counter as u32,
+            latch: self.irq_latch as u32,
+            enabled: self.irq_enable,
+            pending: self.irq_pending,
+            unit: IrqCounterUnit::CpuCycles,
+            clocks_until_irq: if self.irq_enable {Some(reload_point - counter + 1)} else {None},
+        });
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read(address as usize - 0x6000),
+            0x8000 ..= 0xBFFF => self.prg_rom.banked_read(0x4000, self.prg_bank, address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => self.prg_rom.banked_read(0x4000, 0xFF, address as usize - 0xC000),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_write(address as usize - 0x6000, data),
+            0x8000 ..= 0x8FFF => self.write_latch_nybble(0, data),
+            0x9000 ..= 0x9FFF => self.write_latch_nybble(4, data),
+            0xA000 ..= 0xAFFF => self.write_latch_nybble(8, data),
+            0xB000 ..= 0xBFFF => self.write_latch_nybble(12, data),
+            0xC000 ..= 0xCFFF => {
+                self.irq_enable_after_acknowledgement = (data & 0b0000_0001) != 0;
+                self.irq_enable = (data & 0b0000_0010) != 0;
+                self.irq_8bit_mode = (data & 0b0000_0100) != 0;
+                self.irq_pending = false;
+                if self.irq_enable {
+                    self.irq_counter = self.irq_latch;
+                }
+            },
+            0xD000 ..= 0xDFFF => {
+                self.irq_pending = false;
+                self.irq_enable = self.irq_enable_after_acknowledgement;
+            },
+            0xF000 ..= 0xFFFF => {
+                self.prg_bank = data as usize & 0b0111;
+            },
+            _ => {}
+        }
+    }
+
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_read(address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_write(address as usize, data),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return true;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        self.prg_ram.load(&sram_data);
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank, cpu_address as usize - 0x8000),
+            0xC000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x4000, 0xFF, cpu_address as usize - 0xC000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.wrapping_offset(ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clock_until_irq(mapper: &mut Vrc3) -> u32 {
+        let mut clocks = 0;
+        while !mapper.irq_flag() {
+            mapper.clock_cpu();
+            clocks += 1;
+            assert!(clocks <= 0x10000);
+        }
+        return clocks;
+    }
+
+    #[test]
+    fn prg_banking() {
+        let mut mapper = Vrc3::from_ines(INesCartridge::test_image(73, 8, 0)).unwrap();
+        mapper.write_cpu(0xF000, 0x03);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(6));
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(14));
+    }
+
+    #[test]
+    fn irq_16bit_mode() {
+        let mut mapper = Vrc3::from_ines(INesCartridge::test_image(73, 8, 0)).unwrap();
+        mapper.write_cpu(0x8000, 0x0);
+        mapper.write_cpu(0x9000, 0xF);
+        mapper.write_cpu(0xA000, 0xF);
+        mapper.write_cpu(0xB000, 0xF);
+        mapper.write_cpu(0xC000, 0b011);
+        assert_eq!(mapper.irq_state().unwrap().clocks_until_irq, Some(16));
+        assert_eq!(clock_until_irq(&mut mapper), 16);
+        assert_eq!(mapper.irq_counter, 0xFFF0);
+
+        // Acknowledging restores the enable from the E bit, and the counter carries on
+        mapper.write_cpu(0xD000, 0);
+        assert!(!mapper.irq_flag());
+        assert_eq!(clock_until_irq(&mut mapper), 16);
+    }
+
+    #[test]
+    fn irq_8bit_mode_leaves_the_high_byte_alone() {
+        let mut mapper = Vrc3::from_ines(INesCartridge::test_image(73, 8, 0)).unwrap();
+        mapper.write_cpu(0x8000, 0x0);
+        mapper.write_cpu(0x9000, 0xF);
+        mapper.write_cpu(0xA000, 0x2);
+        mapper.write_cpu(0xB000, 0x1);
+        mapper.write_cpu(0xC000, 0b110);
+        assert_eq!(mapper.irq_state().unwrap().clocks_until_irq, Some(16));
+        assert_eq!(clock_until_irq(&mut mapper), 16);
+        assert_eq!(mapper.irq_counter, 0x12F0);
+
+        // Without the E bit, acknowledging leaves the counter stopped
+        mapper.write_cpu(0xD000, 0);
+        mapper.clock_cpu();
+        assert_eq!(mapper.irq_counter, 0x12F0);
+    }
+}
@@ -0,0 +1,389 @@
+// VRC2 and VRC4, Konami's most common mappers. Both share a register layout, but each board
+// wires the chip's two register select lines to a different pair of CPU address lines, which
+// is most of what distinguishes mappers 21, 22, 23 and 25. VRC4 adds a PRG swap mode, one
+// screen mirroring and the same IRQ counter as VRC6 and VRC7.
+// https://wiki.nesdev.com/w/index.php/VRC2_and_VRC4
+
+use ines::INesCartridge;
+use memoryblock::MemoryBlock;
+
+use mmc::mapper::*;
+use mmc::mirroring;
+
+pub struct Vrc4 {
+    pub prg_rom: MemoryBlock,
+    pub prg_ram: MemoryBlock,
+    pub chr: MemoryBlock,
+    pub vram: Vec<u8>,
+    pub mapper_number: u16,
+    pub vrc2: bool,
+    // CPU address lines (as a mask) which drive the chip's A0 and A1 register selects.
+    // Without a submapper to tell the variants apart, both candidates are connected.
+    pub a0_lines: u16,
+    pub a1_lines: u16,
+    // VRC2a ignores the low bit of each CHR bank, giving 2k granularity in 1k registers
+    pub chr_shift: usize,
+
+    pub prg_bank_0: usize,
+    pub prg_bank_1: usize,
+    pub prg_swap_mode: bool,
+    pub chr_banks: Vec<usize>,
+    pub mirroring: Mirroring,
+
+    pub irq_scanline_prescaler: i16,
+    pub irq_latch: u8,
+    pub irq_scanline_mode: bool,
+    pub irq_enable: bool,
+    pub irq_enable_after_acknowledgement: bool,
+    pub irq_pending: bool,
+    pub irq_counter: u8,
+}
+
+// (A0 lines, A1 lines, is VRC2) for each mapper and submapper
+fn board_wiring(mapper_number: u16, submapper_number: u8) -> (u16, u16, bool) {
+    return match (mapper_number, submapper_number) {
+        (21, 1) => (0x02, 0x04, false), // VRC4a
+        (21, 2) => (0x40, 0x80, false), // VRC4c
+        (21, _) => (0x42, 0x84, false),
+        (22, _) => (0x02, 0x01, true),  // VRC2a
+        (23, 1) => (0x01, 0x02, false), // VRC4f
+        (23, 2) => (0x04, 0x08, false), // VRC4e
+        (23, 3) => (0x01, 0x02, true),  // VRC2b
+        (23, _) => (0x05, 0x0A, false),
+        (25, 1) => (0x02, 0x01, false), // VRC4b
+        (25, 2) => (0x08, 0x04, false), // VRC4d
+        (25, 3) => (0x02, 0x01, true),  // VRC2c
+        (25, _) => (0x0A, 0x05, false),
+        _ => (0x01, 0x02, false),
+    }
+}
+
+impl Vrc4 {
+    pub fn from_ines(ines: INesCartridge) -> Result<Vrc4, String> {
+        let prg_rom_block = ines.prg_rom_block();
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
+        let chr_block = ines.chr_block()?;
+        let mapper_number = ines.header.mapper_number();
+        let (a0_lines, a1_lines, vrc2) = board_wiring(mapper_number, ines.header.submapper_number());
+
+        return Ok(Vrc4 {
+            prg_rom: prg_rom_block.clone(),
+            prg_ram: prg_ram_block.clone(),
+            chr: chr_block.clone(),
+            vram: vec![0u8; 0x1000],
+            mapper_number: mapper_number,
+            vrc2: vrc2,
+            a0_lines: a0_lines,
+            a1_lines: a1_lines,
+            chr_shift: if mapper_number == 22 {1} else {0},
+
+            prg_bank_0: 0,
+            prg_bank_1: 0,
+            prg_swap_mode: false,
+            chr_banks: vec![0usize; 8],
+            mirroring: Mirroring::Vertical,
+
+            irq_scanline_prescaler: 0,
+            irq_latch: 0,
+            irq_scanline_mode: false,
+            irq_enable: false,
+            irq_enable_after_acknowledgement: false,
+            irq_pending: false,
+            irq_counter: 0,
+        });
+    }
+
+    // Collapses the board's address wiring back to the chip's view: $x000-$x003
+    fn register(&self, address: u16) -> u16 {
+        let mut register = address & 0xF000;
+        if address & self.a0_lines != 0 {
+            register |= 0b01;
+        }
+        if address & self.a1_lines != 0 {
+            register |= 0b10;
+        }
+        return register;
+    }
+
+    fn prg_bank(&self, address: u16) -> usize {
+        return match address {
+            0x8000 ..= 0x9FFF => if self.prg_swap_mode {0xFE} else {self.prg_bank_0},
+            0xA000 ..= 0xBFFF => self.prg_bank_1,
+            0xC000 ..= 0xDFFF => if self.prg_swap_mode {self.prg_bank_0} else {0xFE},
+            _ => 0xFF,
+        }
+    }
+
+    fn chr_bank(&self, address: u16) -> usize {
+        return self.chr_banks[(address / 0x400) as usize] >> self.chr_shift;
+    }
+
+    // Each 1k CHR bank is split across a pair of registers: the low 4 bits at the even
+    // address, and the high bits (4 on VRC2, 5 on VRC4) at the odd one
+    fn write_chr_register(&mut self, register: u16, data: u8) {
+        let bank_index = ((((register & 0xF000) - 0xB000) >> 11) | ((register & 0b10) >> 1)) as usize;
+        let high_mask = if self.vrc2 {0x0F} else {0x1F};
+        if register & 0b01 == 0 {
+            self.chr_banks[bank_index] = (self.chr_banks[bank_index] & !0x0F) | (data as usize & 0x0F);
+        } else {
+            self.chr_banks[bank_index] = (self.chr_banks[bank_index] & 0x0F) | ((data as usize & high_mask) << 4);
+        }
+    }
+
+    fn _clock_irq_prescaler(&mut self) {
+        self.irq_scanline_prescaler -= 3;
+        if self.irq_scanline_prescaler <= 0 {
+            self._clock_irq_counter();
+            self.irq_scanline_prescaler += 341;
+        }
+    }
+
+    fn _clock_irq_counter(&mut self) {
+        if self.irq_counter == 0xFF {
+            self.irq_counter = self.irq_latch;
+            self.irq_pending = true;
+        } else {
+            self.irq_counter += 1;
+        }
+    }
+}
+
+impl Mapper for Vrc4 {
+    fn print_debug_status(&self) {
+        println!("======= {} =======", if self.vrc2 {"VRC2"} else {"VRC4"});
+        println!("Mapper: {}, PRG: {} {} (swap: {})", self.mapper_number, self.prg_bank_0, self.prg_bank_1, self.prg_swap_mode);
+        println!("CHR: {:?}", self.chr_banks);
+        println!("IRQ: Counter: {}, Latch: {}, Enabled: {}", self.irq_counter, self.irq_latch, self.irq_enable);
+        println!("Mirroring Mode: {}", mirroring_mode_name(self.mirroring));
+        println!("====================");
+    }
+
+    fn mirroring(&self) -> Mirroring {
+        return self.mirroring;
+    }
+
+    fn clock_cpu(&mut self) {
+        if self.irq_enable {
+            if self.irq_scanline_mode {
+                self._clock_irq_prescaler();
+            } else {
+                self._clock_irq_counter();
+            }
+        }
+    }
+
+    fn irq_flag(&self) -> bool {
+        return self.irq_pending;
+    }
+
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        if self.vrc2 {
+            return None;
+        }
+        return Some(vrc_irq_state(self.irq_counter, self.irq_latch, self.irq_enable, self.irq_pending,
+            self.irq_scanline_mode, self.irq_scanline_prescaler));
+    }
+
+    fn debug_read_cpu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read(address as usize - 0x6000),
+            0x8000 ..= 0xFFFF => self.prg_rom.banked_read(0x2000, self.prg_bank(address), address as usize),
+            _ => None
+        }
+    }
+
+    fn write_cpu(&mut self, address: u16, data: u8) {
+        match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_write(address as usize - 0x6000, data),
+            0x8000 ..= 0xFFFF => {
+                let register = self.register(address);
+                match register {
+                    0x8000 ..= 0x8003 => {self.prg_bank_0 = data as usize & 0x1F;},
+                    0x9000 ..= 0x9003 if self.vrc2 => {
+                        self.mirroring = if data & 0b1 == 0 {Mirroring::Vertical} else {Mirroring::Horizontal};
+                    },
+                    0x9000 | 0x9001 => {
+                        self.mirroring = match data & 0b11 {
+                            0 => Mirroring::Vertical,
+                            1 => Mirroring::Horizontal,
+                            2 => Mirroring::OneScreenLower,
+                            _ => Mirroring::OneScreenUpper,
+                        };
+                    },
+                    0x9002 | 0x9003 => {self.prg_swap_mode = (data & 0b10) != 0;},
+                    0xA000 ..= 0xA003 => {self.prg_bank_1 = data as usize & 0x1F;},
+                    0xB000 ..= 0xE003 => self.write_chr_register(register, data),
+                    0xF000 ..= 0xF003 if self.vrc2 => {},
+                    0xF000 => {self.irq_latch = (self.irq_latch & 0xF0) | (data & 0x0F);},
+                    0xF001 => {self.irq_latch = (self.irq_latch & 0x0F) | ((data & 0x0F) << 4);},
+                    0xF002 => {
+                        self.irq_scanline_mode = ((data & 0b0000_0100) >> 2) == 0;
+                        self.irq_enable = (data & 0b0000_0010) != 0;
+                        self.irq_enable_after_acknowledgement = (data & 0b0000_0001) != 0;
+                        self.irq_pending = false;
+                        if self.irq_enable {
+                            self.irq_counter = self.irq_latch;
+                            self.irq_scanline_prescaler = 341;
+                        }
+                    },
+                    0xF003 => {
+                        self.irq_pending = false;
+                        self.irq_enable = self.irq_enable_after_acknowledgement;
+                    },
+                    _ => {}
+                }
+            },
+            _ => {}
+        }
+    }
+
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
+    fn debug_read_ppu(&self, address: u16) -> Option<u8> {
+        match address {
+            0x0000 ..= 0x1FFF => self.chr.banked_read(0x400, self.chr_bank(address), address as usize),
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => Some(self.vram[mirroring::horizontal_mirroring(address) as usize]),
+                Mirroring::Vertical   => Some(self.vram[mirroring::vertical_mirroring(address) as usize]),
+                Mirroring::OneScreenLower => Some(self.vram[mirroring::one_screen_lower(address) as usize]),
+                Mirroring::OneScreenUpper => Some(self.vram[mirroring::one_screen_upper(address) as usize]),
+                _ => None
+            },
+            _ => None
+        }
+    }
+
+    fn write_ppu(&mut self, address: u16, data: u8) {
+        match address {
+            0x0000 ..= 0x1FFF => {
+                let chr_bank = self.chr_bank(address);
+                self.chr.banked_write(0x400, chr_bank, address as usize, data);
+            },
+            0x2000 ..= 0x3FFF => match self.mirroring {
+                Mirroring::Horizontal => self.vram[mirroring::horizontal_mirroring(address) as usize] = data,
+                Mirroring::Vertical   => self.vram[mirroring::vertical_mirroring(address) as usize] = data,
+                Mirroring::OneScreenLower => self.vram[mirroring::one_screen_lower(address) as usize] = data,
+                Mirroring::OneScreenUpper => self.vram[mirroring::one_screen_upper(address) as usize] = data,
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+
+    fn has_sram(&self) -> bool {
+        return true;
+    }
+
+    fn get_sram(&self) -> Vec<u8> {
+        return self.prg_ram.as_vec().clone();
+    }
+
+    fn load_sram(&mut self, sram_data: Vec<u8>) {
+        self.prg_ram.load(&sram_data);
+    }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank(cpu_address), cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x400, self.chr_bank(ppu_address), ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mapper_with_wiring(mapper_number: u8, submapper_number: u8) -> Vrc4 {
+        let mut mapper = Vrc4::from_ines(INesCartridge::test_image(mapper_number, 8, 16)).unwrap();
+        let (a0_lines, a1_lines, vrc2) = board_wiring(mapper_number as u16, submapper_number);
+        mapper.a0_lines = a0_lines;
+        mapper.a1_lines = a1_lines;
+        mapper.vrc2 = vrc2;
+        return mapper;
+    }
+
+    #[test]
+    fn register_selects_follow_each_boards_wiring() {
+        // The CPU addresses each board decodes as $B000, $B001, $B002 and $B003
+        let boards: [(u8, u8, [u16; 4]); 9] = [
+            (21, 1, [0xB000, 0xB002, 0xB004, 0xB006]), // VRC4a: A1, A2
+            (21, 2, [0xB000, 0xB040, 0xB080, 0xB0C0]), // VRC4c: A6, A7
+            (22, 0, [0xB000, 0xB002, 0xB001, 0xB003]), // VRC2a: A1, A0
+            (23, 1, [0xB000, 0xB001, 0xB002, 0xB003]), // VRC4f: A0, A1
+            (23, 2, [0xB000, 0xB004, 0xB008, 0xB00C]), // VRC4e: A2, A3
+            (23, 3, [0xB000, 0xB001, 0xB002, 0xB003]), // VRC2b: A0, A1
+            (25, 1, [0xB000, 0xB002, 0xB001, 0xB003]), // VRC4b: A1, A0
+            (25, 2, [0xB000, 0xB008, 0xB004, 0xB00C]), // VRC4d: A3, A2
+            (25, 3, [0xB000, 0xB002, 0xB001, 0xB003]), // VRC2c: A1, A0
+        ];
+        for &(mapper_number, submapper_number, addresses) in boards.iter() {
+            let mapper = mapper_with_wiring(mapper_number, submapper_number);
+            for (index, &address) in addresses.iter().enumerate() {
+                assert_eq!(mapper.register(address), 0xB000 | index as u16,
+                    "mapper {} submapper {} at ${:04X}", mapper_number, submapper_number, address);
+            }
+        }
+    }
+
+    #[test]
+    fn without_a_submapper_both_wirings_are_connected() {
+        let mut mapper = Vrc4::from_ines(INesCartridge::test_image(21, 8, 16)).unwrap();
+        // VRC4a's $B003, then VRC4c's $B001 (the high bits of the first bank)
+        mapper.write_cpu(0xB006, 0x01);
+        assert_eq!(mapper.debug_read_ppu(0x0400), Some(0x10));
+        mapper.write_cpu(0xB000, 0x05);
+        mapper.write_cpu(0xB040, 0x02);
+        assert_eq!(mapper.debug_read_ppu(0x0000), Some(0x25));
+    }
+
+    #[test]
+    fn vrc4_prg_swap_mode() {
+        let mut mapper = mapper_with_wiring(25, 1);
+        mapper.write_cpu(0x8000, 3);
+        mapper.write_cpu(0xA000, 4);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(3));
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(14));
+        // VRC4b swaps the selects: its $9002 is the chip's mirroring register, and $9001
+        // is the swap mode
+        mapper.write_cpu(0x9002, 0b10);
+        assert!(!mapper.prg_swap_mode);
+        mapper.write_cpu(0x9001, 0b10);
+        assert_eq!(mapper.debug_read_cpu(0x8000), Some(14));
+        assert_eq!(mapper.debug_read_cpu(0xA000), Some(4));
+        assert_eq!(mapper.debug_read_cpu(0xC000), Some(3));
+    }
+
+    #[test]
+    fn vrc2a_chr_banks_drop_the_low_bit() {
+        let mut mapper = mapper_with_wiring(22, 0);
+        mapper.write_cpu(0xB000, 0x07);
+        mapper.write_cpu(0xB002, 0x01);
+        // Register value $17, shifted down to 1k bank $0B
+        assert_eq!(mapper.debug_read_ppu(0x0000), Some(0x0B));
+    }
+}
@@ -113,6 +113,7 @@ impl Vrc6PulseChannel {
                     // reset phase entirely
                     self.duty_counter = 15;
                     self.period_current = self.period_initial;
+                    self.last_edge = true;
                 }
             },
             3 => {
@@ -283,6 +284,7 @@ impl Vrc6SawtoothChannel {
                     // reset phase entirely
                     self.accumulator = 0;
                     self.accumulator_step = 0;
+                    self.last_edge = true;
                 }
             },
             3 => {
@@ -406,7 +408,7 @@ pub struct Vrc6 {
 impl Vrc6 {
     pub fn from_ines(ines: INesCartridge) -> Result<Vrc6, String> {
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
         let chr_block = ines.chr_block()?;
 
         return Ok(Vrc6 {
@@ -440,77 +442,54 @@ impl Vrc6 {
         });
     }
 
-    fn _chr_mode_0(&self, address: u16) -> Option<u8> {
+    // Each CHR mode resolves an address to the (bank size, bank number) covering it
+
+    fn _chr_mode_0(&self, address: u16) -> Option<(usize, usize)> {
         // All 1k banks
-        match address {
-            0x0000 ..= 0x03FF => self.chr.banked_read(0x400, self.r[0], address as usize -  0x0000),
-            0x0400 ..= 0x07FF => self.chr.banked_read(0x400, self.r[1], address as usize -  0x0400),
-            0x0800 ..= 0x0BFF => self.chr.banked_read(0x400, self.r[2], address as usize -  0x0800),
-            0x0C00 ..= 0x0FFF => self.chr.banked_read(0x400, self.r[3], address as usize -  0x0C00),
-            0x1000 ..= 0x13FF => self.chr.banked_read(0x400, self.r[4], address as usize -  0x1000),
-            0x1400 ..= 0x17FF => self.chr.banked_read(0x400, self.r[5], address as usize -  0x1400),
-            0x1800 ..= 0x1BFF => self.chr.banked_read(0x400, self.r[6], address as usize -  0x1800),
-            0x1C00 ..= 0x1FFF => self.chr.banked_read(0x400, self.r[7], address as usize -  0x1C00),
-            _ => None // never reached
-        }
+        return Some((0x400, self.r[(address as usize & 0x1FFF) / 0x400]));
     }
 
-    fn _chr_mode_1(&self, address: u16) -> Option<u8> {
-        // All 2k banks, with differing A10 behavior        
+    fn _chr_mode_1(&self, address: u16) -> Option<(usize, usize)> {
+        // All 2k banks, with differing A10 behavior
+        let register = self.r[(address as usize & 0x1FFF) / 0x800];
         if self.chr_a10_rules {
             //2k banks use PPU A10, ignore low bit of register
-            match address {
-                0x0000 ..= 0x07FF => self.chr.banked_read(0x800, (self.r[0] & 0xFE) >> 1, address as usize -  0x0000),
-                0x0800 ..= 0x0FFF => self.chr.banked_read(0x800, (self.r[1] & 0xFE) >> 1, address as usize -  0x0800),
-                0x1000 ..= 0x17FF => self.chr.banked_read(0x800, (self.r[2] & 0xFE) >> 1, address as usize -  0x1000),
-                0x1800 ..= 0x1FFF => self.chr.banked_read(0x800, (self.r[3] & 0xFE) >> 1, address as usize -  0x1800),
-
-                _ => None // never reached
-            }
+            return Some((0x800, (register & 0xFE) >> 1));
         } else {
             // Low bit of register determines A10, effectively duplicating 1k banks, similar to 1k mode
-            match address {
-                0x0000 ..= 0x03FF => self.chr.banked_read(0x400, self.r[0], address as usize -  0x0000),
-                0x0400 ..= 0x07FF => self.chr.banked_read(0x400, self.r[0], address as usize -  0x0400),
-                0x0800 ..= 0x0BFF => self.chr.banked_read(0x400, self.r[1], address as usize -  0x0800),
-                0x0C00 ..= 0x0FFF => self.chr.banked_read(0x400, self.r[1], address as usize -  0x0C00),
-                0x1000 ..= 0x13FF => self.chr.banked_read(0x400, self.r[2], address as usize -  0x1000),
-                0x1400 ..= 0x17FF => self.chr.banked_read(0x400, self.r[2], address as usize -  0x1400),
-                0x1800 ..= 0x1BFF => self.chr.banked_read(0x400, self.r[3], address as usize -  0x1800),
-                0x1C00 ..= 0x1FFF => self.chr.banked_read(0x400, self.r[3], address as usize -  0x1C00),
-                _ => None // never reached
-            }
+            return Some((0x400, register));
         }
     }
 
-    fn _chr_mode_23(&self, address: u16) -> Option<u8> {
+    fn _chr_mode_23(&self, address: u16) -> Option<(usize, usize)> {
         // Essentially a mix, mode 0 for the upper half, with 2x 2k banks in the lower half that behave similarly to mode 1
         // but pull from R4-R5 instead
         match address {
             0x0000 ..= 0x0FFF => self._chr_mode_0(address),
             0x1000 ..= 0x1FFF => {
+                let register = self.r[4 + (address as usize - 0x1000) / 0x800];
                 if self.chr_a10_rules {
                     //2k banks use PPU A10, ignore low bit of register
-                    match address {
-                        0x1000 ..= 0x17FF => self.chr.banked_read(0x800, (self.r[4] & 0xFE) >> 1, address as usize -  0x1000),
-                        0x1800 ..= 0x1FFF => self.chr.banked_read(0x800, (self.r[5] & 0xFE) >> 1, address as usize -  0x1800),
-                        _ => None // never reached
-                    }
+                    Some((0x800, (register & 0xFE) >> 1))
                 } else {
                     // Low bit of register determines A10, effectively duplicating 1k banks, similar to 1k mode
-                    match address {
-                        0x1000 ..= 0x13FF => self.chr.banked_read(0x400, self.r[4], address as usize -  0x1000),
-                        0x1400 ..= 0x17FF => self.chr.banked_read(0x400, self.r[4], address as usize -  0x1400),
-                        0x1800 ..= 0x1BFF => self.chr.banked_read(0x400, self.r[5], address as usize -  0x1800),
-                        0x1C00 ..= 0x1FFF => self.chr.banked_read(0x400, self.r[5], address as usize -  0x1C00),
-                        _ => None // never reached
-                    }
+                    Some((0x400, register))
                 }
             }
             _ => None // never reached
         }
     }
 
+    fn _chr_window(&self, address: u16) -> Option<(usize, usize)> {
+        match self.ppu_banking_mode {
+            0 => self._chr_mode_0(address),
+            1 => self._chr_mode_1(address),
+            2 => self._chr_mode_23(address),
+            3 => self._chr_mode_23(address),
+            _ => None
+        }
+    }
+
     fn _mirroring_mode_0_read(&self, address: u16) -> Option<u8> {
         let mirrored_address = address & 0x2FFF;
         if self.nametable_chrrom {
@@ -946,10 +925,25 @@ impl Mapper for Vrc6 {
             nes_sample;
     }
 
+    fn mix_expansion_audio_fixed(&self, nes_sample: i32) -> i32 {
+        // Folding the weights above together, each step of VRC6 output is worth 1/15th of a
+        // full volume APU pulse: 0.149377 / 15 * MIX_FIXED_ONE, rounded
+        let vrc6_step_weight = 653;
+        let pulse_1_output = if !self.pulse1.debug_disable {self.pulse1.output() as i32} else {0};
+        let pulse_2_output = if !self.pulse2.debug_disable {self.pulse2.output() as i32} else {0};
+        let sawtooth_output = if !self.sawtooth.debug_disable {self.sawtooth.output() as i32} else {0};
+        return (pulse_1_output + pulse_2_output + sawtooth_output) * vrc6_step_weight + nes_sample;
+    }
+
     fn irq_flag(&self) -> bool {
         return self.irq_pending;
     }
 
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        return Some(vrc_irq_state(self.irq_counter, self.irq_latch, self.irq_enable, self.irq_pending,
+            self.irq_scanline_mode, self.irq_scanline_prescaler));
+    }
+
     fn debug_read_cpu(&self, address: u16) -> Option<u8> {
         match address {
             0x6000 ..= 0x7FFF => self.prg_ram.wrapping_read(address as usize - 0x6000),
@@ -1039,17 +1033,19 @@ impl Mapper for Vrc6 {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
         match address {
             0x0000 ..= 0x1FFF => {
                 // CHR Bank Selection
-                match self.ppu_banking_mode {
-                    0 => self._chr_mode_0(address),
-                    1 => self._chr_mode_1(address),
-                    2 => self._chr_mode_23(address),
-                    3 => self._chr_mode_23(address),
-                    _ => None
-                }
+                let (bank_size, bank) = self._chr_window(address)?;
+                self.chr.banked_read(bank_size, bank, address as usize)
             },
             0x2000 ..= 0x3FFF => {
                 if self.chr_a10_rules {
@@ -1115,4 +1111,66 @@ impl Mapper for Vrc6 {
         self.pulse2.record_current_output();
         self.sawtooth.record_current_output();
     }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x4000, self.prg_bank_16, cpu_address as usize - 0x8000),
+            0xC000 ..= 0xDFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_bank_8, cpu_address as usize - 0xC000),
+            0xE000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, 0xFF, cpu_address as usize - 0xE000),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => {
+                let (bank_size, bank) = self._chr_window(ppu_address)?;
+                self.chr.banked_offset(bank_size, bank, ppu_address as usize)
+            },
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn resolve_chr_offset_agrees_with_reads() {
+        // 32k of CHR: 32 banks of 1k
+        let mut mapper = Vrc6::from_ines(INesCartridge::test_image(24, 2, 4)).unwrap();
+        let registers = [0xD000, 0xD001, 0xD002, 0xD003, 0xE000, 0xE001, 0xE002, 0xE003];
+        for (index, &register) in registers.iter().enumerate() {
+            mapper.write_cpu(register, 3 + index as u8 * 3);
+        }
+        mapper.write_cpu(0xB003, 0b0000_0000);
+        assert_eq!(mapper.resolve_chr_offset(0x0400), Some(6 * 0x400));
+
+        for &a10_rules in [0b0000_0000, 0b0010_0000].iter() {
+            for banking_mode in 0 .. 4 {
+                mapper.write_cpu(0xB003, a10_rules | banking_mode);
+                for address in (0x0000 .. 0x2000).step_by(0x200) {
+                    let offset = mapper.resolve_chr_offset(address).unwrap();
+                    // Every byte of the test image holds its 1k bank number
+                    assert_eq!(Some((offset / 0x400) as u8), mapper.debug_read_ppu(address),
+                        "$B003 = {:02X} at ${:04X}", a10_rules | banking_mode, address);
+                    assert_eq!(offset & 0x1FF, 0);
+                }
+            }
+        }
+        assert_eq!(mapper.resolve_chr_offset(0x2000), None);
+    }
 }
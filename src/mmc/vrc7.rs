@@ -43,7 +43,7 @@ pub struct Vrc7 {
 impl Vrc7 {
     pub fn from_ines(ines: INesCartridge) -> Result<Vrc7, String> {
         let prg_rom_block = ines.prg_rom_block();
-        let prg_ram_block = ines.prg_ram_block()?;
+        let prg_ram_block = ines.prg_ram_block_up_to(0x2000)?;
         let chr_block = ines.chr_block()?;
 
         return Ok(Vrc7 {
@@ -133,6 +133,11 @@ impl Mapper for Vrc7 {
         return self.irq_pending;
     }
 
+    fn irq_state(&self) -> Option<MapperIrqState> {
+        return Some(vrc_irq_state(self.irq_counter, self.irq_latch, self.irq_enable, self.irq_pending,
+            self.irq_scanline_mode, self.irq_scanline_prescaler));
+    }
+
     fn mirroring(&self) -> Mirroring {
         return self.mirroring;
     }
@@ -216,6 +221,13 @@ impl Mapper for Vrc7 {
         }
     }
 
+    fn poke_prg_ram(&mut self, address: u16, data: u8) -> bool {
+        return match address {
+            0x6000 ..= 0x7FFF => self.prg_ram.wrapping_poke(address as usize - 0x6000, data),
+            _ => false
+        };
+    }
+
     fn debug_read_ppu(&self, address: u16) -> Option<u8> {
         match address {
             0x0000 ..= 0x03FF => {self.chr.banked_read(0x400, self.chr_banks[0] as usize, address as usize)},
@@ -267,7 +279,7 @@ impl Mapper for Vrc7 {
     }
 
     fn load_sram(&mut self, sram_data: Vec<u8>) {
-        *self.prg_ram.as_mut_vec() = sram_data;
+        self.prg_ram.load(&sram_data);
     }
 
     fn channels(&self) ->  Vec<& dyn AudioChannelState> {
@@ -295,6 +307,35 @@ impl Mapper for Vrc7 {
     fn record_expansion_audio_output(&mut self, _nes_sample: f32) {
         self.audio.record_output();
     }
+
+    fn prg_rom(&self) -> Option<&MemoryBlock> {
+        return Some(&self.prg_rom);
+    }
+
+    fn resolve_prg_offset(&self, cpu_address: u16) -> Option<(usize, usize)> {
+        return match cpu_address {
+            0x8000 ..= 0x9FFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[0] as usize, cpu_address as usize),
+            0xA000 ..= 0xBFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[1] as usize, cpu_address as usize),
+            0xC000 ..= 0xDFFF => banked_prg_offset(&self.prg_rom, 0x2000, self.prg_banks[2] as usize, cpu_address as usize),
+            0xE000 ..= 0xFFFF => banked_prg_offset(&self.prg_rom, 0x2000, 0xFF, cpu_address as usize),
+            _ => None
+        };
+    }
+
+    fn resolve_chr_offset(&self, ppu_address: u16) -> Option<usize> {
+        return match ppu_address {
+            0x0000 ..= 0x1FFF => self.chr.banked_offset(0x400, self.chr_banks[(ppu_address / 0x400) as usize] as usize, ppu_address as usize),
+            _ => None
+        };
+    }
+
+    fn chr(&self) -> Option<&MemoryBlock> {
+        return Some(&self.chr);
+    }
+
+    fn chr_mut(&mut self) -> Option<&mut MemoryBlock> {
+        return Some(&mut self.chr);
+    }
 }
 
 // TODO: explore and see if we can't somehow make these constant while keeping them
@@ -891,7 +932,7 @@ impl Vrc7AudioChannel {
         } else {
             0
         };
-        let effective_mod_phase = ((self.modulator_phase - 1) & 0x7FFFF) as i32;
+        let effective_mod_phase = (self.modulator_phase.wrapping_sub(1) & 0x7FFFF) as i32;
         let mod_logsin = self.lookup_logsin((((effective_mod_phase >> 9) + (feedback as i32)) & 0x7FFFF) as usize, self.modulator_rectified);
         let mod_output_attenuation = 32 * self.modulator_output_level;
         let mod_env_attenuation = 16 * self.modulator_env_level as u16;
@@ -0,0 +1,315 @@
+// Frame-indexed input recordings, and a player which decides each frame how the movie and
+// the live controller share the input. Unlike a macro, a movie is absolute: frame 0 is
+// the first frame after power on (or after loading the savestate the movie is anchored
+// to), and the movie owns the controllers while it plays.
+
+use nes::NesState;
+
+pub const COMMAND_RESET: u8    = 1 << 0;
+pub const COMMAND_POWER_ON: u8 = 1 << 1;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MovieFrame {
+    pub commands: u8,
+    pub p1: u8,
+    pub p2: u8,
+}
+
+impl MovieFrame {
+    pub fn new(p1: u8, p2: u8) -> MovieFrame {
+        return MovieFrame {
+            commands: 0,
+            p1: p1,
+            p2: p2,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Movie {
+    pub frames: Vec<MovieFrame>,
+    // The savestate playback starts from, for movies which don't begin at power on. The
+    // core doesn't look inside; it's whatever the frontend's savestates contain.
+    pub anchor: Option<Vec<u8>>,
+}
+
+const BASE64_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+// FM2 embeds its anchor savestate as base64
+fn base64_encode(data: &[u8]) -> String {
+    let mut text = String::new();
+    for chunk in data.chunks(3) {
+        let bits = chunk.iter().enumerate().fold(0u32, |bits, (i, &byte)| bits | ((byte as u32) << (16 - i * 8)));
+        for i in 0 .. 4 {
+            if i <= chunk.len() {
+                text.push(BASE64_ALPHABET[((bits >> (18 - i * 6)) & 0x3F) as usize] as char);
+            } else {
+                text.push('=');
+            }
+        }
+    }
+    return text;
+}
+
+fn base64_decode(text: &str) -> Result<Vec<u8>, String> {
+    let mut data = Vec::new();
+    let mut bits: u32 = 0;
+    let mut bit_count = 0;
+    for c in text.trim_end_matches('=').bytes() {
+        let value = match BASE64_ALPHABET.iter().position(|&a| a == c) {
+            Some(value) => value as u32,
+            None => return Err(format!("Invalid base64 character: {}", c as char))
+        };
+        bits = (bits << 6) | value;
+        bit_count += 6;
+        if bit_count >= 8 {
+            bit_count -= 8;
+            data.push((bits >> bit_count) as u8);
+        }
+    }
+    return Ok(data);
+}
+
+// FM2 buttons are listed as "RLDUTSBA", the reverse of the controller's shift order
+fn parse_fm2_buttons(field: &str) -> u8 {
+    let mut buttons = 0;
+    for (i, c) in field.chars().take(8).enumerate() {
+        if c != '.' && c != ' ' {
+            buttons |= 1 << (7 - i);
+        }
+    }
+    return buttons;
+}
+
+fn fm2_buttons(buttons: u8) -> String {
+    return "RLDUTSBA".chars().enumerate()
+        .map(|(i, c)| if buttons & (1 << (7 - i)) != 0 {c} else {'.'})
+        .collect();
+}
+
+impl Movie {
+    pub fn new() -> Movie {
+        return Movie {
+            frames: Vec::new(),
+            anchor: None,
+        }
+    }
+
+    // An empty movie starting from a savestate the frontend has just taken
+    pub fn from_savestate(state: Vec<u8>) -> Movie {
+        let mut movie = Movie::new();
+        movie.anchor = Some(state);
+        return movie;
+    }
+
+    pub fn is_anchored(&self) -> bool {
+        return self.anchor.is_some();
+    }
+
+    // Reads the input log of an FM2 movie. Header lines are ignored apart from an embedded
+    // savestate; only the commands field and the first two standard controllers are kept.
+    pub fn from_fm2(text: &str) -> Result<Movie, String> {
+        let mut movie = Movie::new();
+        for line in text.lines() {
+            if let Some(value) = line.strip_prefix("savestate ") {
+                let encoded = match value.trim().strip_prefix("base64:") {
+                    Some(encoded) => encoded,
+                    None => return Err(String::from("Only base64 encoded savestates are supported"))
+                };
+                movie.anchor = Some(base64_decode(encoded)?);
+                continue;
+            }
+            if !line.starts_with('|') {
+                continue;
+            }
+            let fields: Vec<&str> = line.split('|').collect();
+            if fields.len() < 4 {
+                continue;
+            }
+            movie.frames.push(MovieFrame {
+                commands: fields[1].trim().parse::<u8>().unwrap_or(0),
+                p1: parse_fm2_buttons(fields[2]),
+                p2: parse_fm2_buttons(fields[3]),
+            });
+        }
+        return Ok(movie);
+    }
+
+    pub fn to_fm2(&self) -> String {
+        let mut text = String::from("version 3\nport0 1\nport1 1\nport2 0\n");
+        if let Some(ref state) = self.anchor {
+            text += &format!("savestate base64:{}\n", base64_encode(state));
+        }
+        for frame in &self.frames {
+            text += &format!("|{}|{}|{}||\n", frame.commands, fm2_buttons(frame.p1), fm2_buttons(frame.p2));
+        }
+        return text;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.frames.len();
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.frames.is_empty();
+    }
+
+    // Discards frame_count and everything after it
+    pub fn truncate(&mut self, frame_count: usize) {
+        self.frames.truncate(frame_count);
+    }
+
+    pub fn append(&mut self, frame: MovieFrame) {
+        self.frames.push(frame);
+    }
+
+    pub fn append_movie(&mut self, other: &Movie) {
+        self.frames.extend_from_slice(&other.frames);
+    }
+
+    // Overwrites one frame, padding with empty input if the movie is too short to reach it
+    pub fn set_frame(&mut self, index: usize, frame: MovieFrame) {
+        if index >= self.frames.len() {
+            self.frames.resize(index + 1, MovieFrame::new(0, 0));
+        }
+        self.frames[index] = frame;
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MergePolicy {
+    // The movie has the controllers to itself; live input is ignored until it ends
+    MovieOnly,
+    // Any frame on which a player holds a button uses that player's live input instead.
+    // The movie itself is left alone, so letting go resumes playback as recorded.
+    LiveOverrides,
+    // Plays back until live input appears, then truncates the movie at that frame and
+    // records live input from there on. This is how a player takes over from a point.
+    RecordResume,
+}
+
+pub struct MoviePlayer {
+    pub movie: Movie,
+    pub policy: MergePolicy,
+    // Index of the next frame to be played or recorded
+    pub position: usize,
+    pub recording: bool,
+}
+
+impl MoviePlayer {
+    pub fn new(movie: Movie, policy: MergePolicy) -> MoviePlayer {
+        return MoviePlayer {
+            movie: movie,
+            policy: policy,
+            position: 0,
+            recording: false,
+        }
+    }
+
+    // An empty movie which records from the first frame
+    pub fn record() -> MoviePlayer {
+        let mut player = MoviePlayer::new(Movie::new(), MergePolicy::RecordResume);
+        player.recording = true;
+        return player;
+    }
+
+    // An empty movie which records from a savestate the frontend has just taken, leaving
+    // the running game where it is
+    pub fn record_from_savestate(state: Vec<u8>) -> MoviePlayer {
+        let mut player = MoviePlayer::new(Movie::from_savestate(state), MergePolicy::RecordResume);
+        player.recording = true;
+        return player;
+    }
+
+    // Call before the first frame of playback, with the console freshly powered on. For an
+    // anchored movie, load_state is handed the anchor to restore in the frontend's own
+    // savestate format; a power on movie is already where it needs to be.
+    pub fn begin<F>(&mut self, nes: &mut NesState, load_state: F) -> Result<(), String>
+        where F: FnOnce(&mut NesState, &[u8]) -> Result<(), String> {
+        if let Some(ref state) = self.movie.anchor {
+            load_state(nes, state)?;
+            nes.resync_audio();
+        }
+        self.position = 0;
+        return Ok(());
+    }
+
+    pub fn finished(&self) -> bool {
+        return !self.recording && self.position >= self.movie.len();
+    }
+
+    // Stops playback here, discarding the rest of the movie, and records from this frame on
+    pub fn take_over(&mut self) {
+        self.movie.truncate(self.position);
+        self.recording = true;
+    }
+
+    // Decides the input for the upcoming frame from the movie and the live buttons, and
+    // advances by one frame. Once the movie runs out, live input passes straight through.
+    pub fn next_frame(&mut self, live_p1: u8, live_p2: u8) -> MovieFrame {
+        let live = MovieFrame::new(live_p1, live_p2);
+        if !self.recording && self.policy == MergePolicy::RecordResume && (live_p1 != 0 || live_p2 != 0) {
+            self.take_over();
+        }
+        if self.recording {
+            self.movie.set_frame(self.position, live);
+            self.position += 1;
+            return live;
+        }
+        let frame = match self.movie.frames.get(self.position) {
+            Some(&frame) => frame,
+            None => {return live;}
+        };
+        self.position += 1;
+        return match self.policy {
+            MergePolicy::LiveOverrides => MovieFrame {
+                commands: frame.commands,
+                p1: if live_p1 != 0 {live_p1} else {frame.p1},
+                p2: if live_p2 != 0 {live_p2} else {frame.p2},
+            },
+            _ => frame,
+        };
+    }
+
+    // Call once per frame after the frontend has written live input and before running
+    // the frame. Reset and power commands in the movie are carried out here. Macros
+    // applied beforehand count as live input.
+    pub fn apply(&mut self, nes: &mut NesState) {
+        let frame = self.next_frame(nes.p1_input, nes.p2_input);
+        if frame.commands & COMMAND_POWER_ON != 0 {
+            nes.power_on();
+        } else if frame.commands & COMMAND_RESET != 0 {
+            nes.reset();
+        }
+        nes.p1_input = frame.p1;
+        nes.p2_input = frame.p2;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trip() {
+        let expected = ["", "AA==", "AAE=", "AAEC", "AAECAw=="];
+        for length in 0 .. 5 {
+            let data: Vec<u8> = (0 .. length as u8).collect();
+            let text = base64_encode(&data);
+            assert_eq!(text, expected[length]);
+            assert_eq!(base64_decode(&text).unwrap(), data);
+        }
+        assert_eq!(base64_encode(b"\xFB\xFF"), "+/8=");
+        assert!(base64_decode("AA*=").is_err());
+    }
+
+    #[test]
+    fn fm2_round_trip_with_an_anchor() {
+        let mut movie = Movie::from_savestate(vec![0x00, 0xFF, 0x10, 0x80, 0x7F]);
+        movie.frames.push(MovieFrame::new(0b1000_0001, 0));
+        movie.frames.push(MovieFrame {commands: COMMAND_RESET, p1: 0, p2: 0b0101_0000});
+        let loaded = Movie::from_fm2(&movie.to_fm2()).unwrap();
+        assert_eq!(loaded.anchor, movie.anchor);
+        assert_eq!(loaded.frames, movie.frames);
+    }
+}
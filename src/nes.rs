@@ -1,14 +1,80 @@
 use apu::ApuState;
+use audio_activity::AudioActivity;
 use cartridge;
 use cycle_cpu;
 use cycle_cpu::CpuState;
 use cycle_cpu::Registers;
+use debug_port::DebugPort;
+use ines::INesCartridge;
+use instruction_info;
+use instruction_info::ExecutedInstructions;
+use instruction_info::InstructionInfo;
+use interrupt_timeline::InterruptTimeline;
 use memory;
 use memory::CpuMemory;
+use opcode_info;
+use pitch_history::PitchHistory;
 use ppu::PpuState;
+use profiler::CpuProfiler;
+use region::Region;
 use mmc::mapper::Mapper;
+use mmc::mapper::IrqCounterUnit;
 use tracked_events::EventTracker;
 
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum HotReloadResult {
+    Unchanged,
+    ChrReloaded,
+    PrgChanged,
+    Restarted,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FreezeMode {
+    EveryInstruction,
+    EveryFrame,
+}
+
+// A RAM address held at a fixed value, the classic "infinite lives" cheat. Unlike a ROM
+// patch, this rewrites the value after the game changes it.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct FrozenAddress {
+    pub address: u16,
+    pub value: u8,
+    pub mode: FreezeMode,
+}
+
+// Everything produced by one call to run_frame
+pub struct FrameOutput {
+    pub frame_number: u32,
+    // 256x240, as ppu.indexed_screen
+    pub palette_indices: Vec<u8>,
+    pub emphasis: Vec<u8>,
+    // Every output sample generated while emulating this frame
+    pub audio: Vec<i16>,
+    // The game never read a controller during this frame
+    pub lag: bool,
+    pub cpu_cycles: u64,
+    // Tracked CPU reads and writes (PPU and APU registers, by default) this PPU frame
+    pub event_count: usize,
+}
+
+pub type ChrCallback = Box<dyn FnMut(&[u8]) + Send>;
+
+// Given the frame and the scanline about to start, returns (p1_input, p2_input)
+pub type ScanlineInputCallback = Box<dyn FnMut(u32, u16) -> (u8, u8) + Send>;
+
+// Input which changes partway through a frame, for light guns and paddles whose position
+// software samples at particular scanlines. New values land in p1_input and p2_input at
+// the end of the CPU cycle in which each scanline starts, so playback is deterministic no
+// matter when the frontend got around to supplying them.
+pub enum ScanlineInput {
+    // One (p1, p2) pair per scanline, reused every frame until replaced. Scanlines past
+    // the end of the table leave the input as it was.
+    Table(Vec<(u8, u8)>),
+    Callback(ScanlineInputCallback),
+}
+
 pub struct NesState {
     pub apu: ApuState,
     pub cpu: CpuState,
@@ -24,12 +90,54 @@ pub struct NesState {
     pub mapper: Box<dyn Mapper>,
     pub last_frame: u32,
     pub event_tracker: EventTracker,
+    pub power_on_alignment: u8,
+    // Optional hook for CHR viewers, called at most once per frame with a copy of the
+    // pattern tables ($0000-$1FFF) whenever they differ from the last time it fired
+    pub chr_callback: Option<ChrCallback>,
+    pub chr_snapshot: Vec<u8>,
+    // Emulation speed in percent, and the fractional frames carried between host frames
+    pub speed_percent: u32,
+    pub speed_accumulator: u32,
+    // While paused, the run_* functions do nothing. Stepping manually still works, so
+    // debuggers can single-step a paused game.
+    pub paused: bool,
+    pub auto_pause: bool,
+    pub paused_by_focus: bool,
+    pub frozen_addresses: Vec<FrozenAddress>,
+    pub interrupt_timeline: InterruptTimeline,
+    pub profiler: CpuProfiler,
+    pub debug_port: DebugPort,
+    pub audio_activity: AudioActivity,
+    pub pitch_history: PitchHistory,
+    // Set whenever $4016 or $4017 is read; used for lag detection
+    pub input_polled: bool,
+    pub scanline_input: Option<ScanlineInput>,
+    pub last_input_scanline: u16,
+}
+
+// The CPU divides the master clock by 12 and the PPU by 4, so at power on the PPU may
+// begin anywhere within a CPU cycle. We model this at dot granularity: 0-2 PPU clocks
+// of lead over the CPU.
+pub const PPU_ALIGNMENT_COUNT: u8 = 3;
+
+// Length of the fade applied to the audio output when pausing and resuming; a little
+// under 6ms at 44.1 kHz.
+pub const PAUSE_FADE_SAMPLES: usize = 256;
+
+// Small xorshift generator, so alignment selection is reproducible from a seed
+// without pulling in a dependency.
+fn xorshift32(state: u32) -> u32 {
+    let mut x = if state == 0 {0x2545F491} else {state};
+    x ^= x << 13;
+    x ^= x >> 17;
+    x ^= x << 5;
+    return x;
 }
 
 impl NesState {
     pub fn new(m: Box<dyn Mapper>) -> NesState {
         return NesState {
-            apu: ApuState::new(),
+            apu: ApuState::new(Region::Ntsc),
             cpu: CpuState::new(),
             memory: CpuMemory::new(),
             ppu: PpuState::new(),
@@ -43,6 +151,23 @@ impl NesState {
             mapper: m,
             last_frame: 0,
             event_tracker: EventTracker::new(),
+            power_on_alignment: 0,
+            chr_callback: None,
+            chr_snapshot: Vec::new(),
+            speed_percent: 100,
+            speed_accumulator: 0,
+            paused: false,
+            auto_pause: false,
+            paused_by_focus: false,
+            frozen_addresses: Vec::new(),
+            interrupt_timeline: InterruptTimeline::new(),
+            profiler: CpuProfiler::new(),
+            debug_port: DebugPort::new(),
+            audio_activity: AudioActivity::new(),
+            pitch_history: PitchHistory::new(),
+            input_polled: false,
+            scanline_input: None,
+            last_input_scanline: 0,
         }
     }
 
@@ -61,6 +186,20 @@ impl NesState {
         }
     }
 
+    // CPU cycles elapsed since power on
+    pub fn cpu_cycle(&self) -> u64 {
+        return self.master_clock / 12;
+    }
+
+    pub fn set_power_on_alignment(&mut self, alignment: u8) {
+        self.power_on_alignment = alignment % PPU_ALIGNMENT_COUNT;
+    }
+
+    pub fn randomize_power_on_alignment(&mut self, seed: u32) {
+        let random = xorshift32(seed);
+        self.power_on_alignment = (random % (PPU_ALIGNMENT_COUNT as u32)) as u8;
+    }
+
     pub fn power_on(&mut self) {
         // Initialize CPU register state for power-up sequence
         self.registers.a = 0;
@@ -70,12 +209,16 @@ impl NesState {
 
         self.registers.set_status_from_byte(0x34);
 
-        // Initialize I/O and Audio registers to known startup values
-        for i in 0x4000 .. (0x400F + 1) {
-            memory::write_byte(self, i, 0);
+        // Initialize I/O and Audio registers to known startup values. With a flat address
+        // space these addresses are plain RAM, so go to the APU directly instead.
+        let flat = self.mapper.flat_address_space();
+        for i in (0x4000 .. (0x400F + 1)).chain(vec!(0x4015, 0x4017)) {
+            if flat {
+                self.apu.write_register(i, 0);
+            } else {
+                memory::write_byte(self, i, 0);
+            }
         }
-        memory::write_byte(self, 0x4015, 0);
-        memory::write_byte(self, 0x4017, 0);
 
         let pc_low = memory::read_byte(self, 0xFFFC);
         let pc_high = memory::read_byte(self, 0xFFFD);
@@ -85,14 +228,25 @@ impl NesState {
         for _ in 0 .. 10 {
             self.apu.clock_apu(&mut *self.mapper);
         }
+
+        // Apply the selected CPU/PPU clock alignment. The PPU starts over from the top of
+        // the frame, in step with the CPU, so nudges from an earlier power on don't pile up.
+        self.ppu.current_scanline = 0;
+        self.ppu.current_scanline_cycle = 0;
+        self.ppu.overall_cycle = (self.cpu_cycle() * 3) as usize;
+        for _ in 0 .. self.power_on_alignment {
+            self.nudge_ppu_alignment();
+        }
     }
 
     pub fn reset(&mut self) {
+        self.mapper.reset();
         self.registers.s = self.registers.s.wrapping_sub(3);
         self.registers.flags.interrupts_disabled = true;
 
         // Silence the APU
         memory::write_byte(self, 0x4015, 0);
+        self.apu.reset();
 
         let pc_low = memory::read_byte(self, 0xFFFC);
         let pc_high = memory::read_byte(self, 0xFFFD);
@@ -108,11 +262,22 @@ impl NesState {
         self.ppu.clock(&mut *self.mapper);
         self.event_tracker.current_scanline = self.ppu.current_scanline;
         self.event_tracker.current_cycle = self.ppu.current_scanline_cycle;
+        if self.ppu.current_scanline != self.last_input_scanline {
+            self.last_input_scanline = self.ppu.current_scanline;
+            self.apply_scanline_input();
+        }
         self.apu.clock_apu(&mut *self.mapper);
+        if self.audio_activity.due(self.apu.generated_samples) {
+            let mut channels = self.apu.channels();
+            channels.extend(self.mapper.channels());
+            self.audio_activity.sample(self.apu.generated_samples, &channels);
+        }
         self.mapper.clock_cpu();
     }
 
     pub fn step(&mut self) {
+        let instruction_pc = self.registers.pc;
+        let start_cycle = self.master_clock;
         // Always run at least one cycle
         self.cycle();
         let mut i = 0;
@@ -122,13 +287,181 @@ impl NesState {
             self.cycle();
             i += 1;
         }
+        if !self.frozen_addresses.is_empty() {
+            self.apply_frozen_addresses(FreezeMode::EveryInstruction);
+        }
+        if self.profiler.enabled {
+            let bank = self.mapper.resolve_prg_offset(instruction_pc).map(|(bank, _)| bank);
+            self.profiler.record(instruction_pc, bank, (self.master_clock - start_cycle) / 12);
+        }
         if self.ppu.current_frame != self.last_frame {
+            if self.profiler.enabled {
+                self.profiler.end_frame();
+            }
+            if self.audio_activity.enabled {
+                let mut channels = self.apu.channels();
+                channels.extend(self.mapper.channels());
+                self.audio_activity.end_frame(&channels);
+            }
+            if self.pitch_history.enabled {
+                let mut channels = self.apu.channels();
+                channels.extend(self.mapper.channels());
+                self.pitch_history.end_frame(&channels);
+            }
             self.event_tracker.swap_buffers();
             self.last_frame = self.ppu.current_frame;
+            self.check_chr_changes();
+            if !self.frozen_addresses.is_empty() {
+                self.apply_frozen_addresses(FreezeMode::EveryFrame);
+            }
+            if self.mapper.nsf_seeking() {
+                self.fast_forward_nsf();
+            }
+        }
+    }
+
+    // Runs one instruction like step, and reports what it did. An OAM DMA started by the
+    // instruction runs to completion first, so that its cycles are counted here.
+    pub fn step_with_info(&mut self) -> InstructionInfo {
+        let pc = self.registers.pc;
+        let opcode = memory::debug_read_byte(self, pc);
+        let interrupt = self.cpu.tick == 0 && cycle_cpu::interrupt_requested(self);
+        let operand_bytes = if interrupt {0} else {opcode_info::addressing_bytes(opcode_info::addressing_mode(opcode))};
+        let operand: Vec<u8> = (1 ..= operand_bytes as u16).map(|i| memory::debug_read_byte(self, pc.wrapping_add(i))).collect();
+        let effective_address = if interrupt {None} else {instruction_info::effective_address(self, opcode, &operand)};
+        let start_cycle = self.master_clock;
+
+        self.memory.bus_log = Some(Vec::new());
+        self.step();
+        while self.cpu.oam_dma_active {
+            self.step();
+        }
+        let bus_log = self.memory.bus_log.take().unwrap_or_default();
+
+        return InstructionInfo {
+            pc: pc,
+            opcode: opcode,
+            operand: operand,
+            effective_address: effective_address,
+            reads: bus_log.iter().filter(|access| !access.write).cloned().collect(),
+            writes: bus_log.iter().filter(|access| access.write).cloned().collect(),
+            cycles: (self.master_clock - start_cycle) / 12,
+            interrupt: interrupt,
+        };
+    }
+
+    // An endless iterator over step_with_info
+    pub fn instructions<'a>(&'a mut self) -> ExecutedInstructions<'a> {
+        return ExecutedInstructions {
+            nes: self,
+        };
+    }
+
+    // Runs the NSF player without producing audio until its seek completes, then fades
+    // back in. Frame bookkeeping is skipped; there's nothing to see while seeking.
+    pub fn fast_forward_nsf(&mut self) {
+        self.apu.fade_out(PAUSE_FADE_SAMPLES);
+        self.apu.discard_output = true;
+        while self.mapper.nsf_seeking() {
+            self.cycle();
+        }
+        self.apu.discard_output = false;
+        self.apu.fade_in(PAUSE_FADE_SAMPLES);
+    }
+
+    // Only CPU RAM ($0000-$1FFF) and cartridge RAM ($6000-$7FFF) may be frozen. Cartridge
+    // RAM is written through Mapper::poke_prg_ram, so a freeze still holds while the game
+    // has its RAM write protected, and can't land on a mapper register that shares the
+    // range. Where the board has no RAM at the address (right now), the freeze is refused.
+    pub fn freeze_address(&mut self, address: u16, value: u8, mode: FreezeMode) -> Result<(), String> {
+        match address {
+            0x0000 ..= 0x1FFF => {},
+            0x6000 ..= 0x7FFF if self.mapper.poke_prg_ram(address, value) => {},
+            _ => return Err(format!("Cannot freeze ${:04X}: not a RAM address", address))
+        }
+        self.unfreeze_address(address);
+        self.frozen_addresses.push(FrozenAddress{address: address, value: value, mode: mode});
+        return Ok(());
+    }
+
+    pub fn unfreeze_address(&mut self, address: u16) {
+        self.frozen_addresses.retain(|frozen| frozen.address != address);
+    }
+
+    pub fn clear_frozen_addresses(&mut self) {
+        self.frozen_addresses.clear();
+    }
+
+    pub fn apply_frozen_addresses(&mut self, mode: FreezeMode) {
+        for frozen in self.frozen_addresses.iter().filter(|frozen| frozen.mode == mode) {
+            match frozen.address {
+                0x0000 ..= 0x1FFF => self.memory.iram_raw[(frozen.address & 0x7FF) as usize] = frozen.value,
+                _ => {self.mapper.poke_prg_ram(frozen.address, frozen.value);}
+            }
+        }
+    }
+
+    pub fn set_scanline_input_table(&mut self, table: Vec<(u8, u8)>) {
+        self.scanline_input = Some(ScanlineInput::Table(table));
+    }
+
+    pub fn set_scanline_input_callback(&mut self, callback: ScanlineInputCallback) {
+        self.scanline_input = Some(ScanlineInput::Callback(callback));
+    }
+
+    // Input goes back to changing only when the frontend writes it
+    pub fn clear_scanline_input(&mut self) {
+        self.scanline_input = None;
+    }
+
+    fn apply_scanline_input(&mut self) {
+        let scanline = self.ppu.current_scanline;
+        let input = match self.scanline_input {
+            Some(ScanlineInput::Table(ref table)) => table.get(scanline as usize).cloned(),
+            Some(ScanlineInput::Callback(ref mut callback)) => Some(callback(self.ppu.current_frame, scanline)),
+            None => None
+        };
+        if let Some((p1, p2)) = input {
+            self.p1_input = p1;
+            self.p2_input = p2;
+        }
+    }
+
+    pub fn set_chr_callback(&mut self, callback: ChrCallback) {
+        self.chr_callback = Some(callback);
+        // Force the first check to fire, so the viewer has something to draw
+        self.chr_snapshot.clear();
+    }
+
+    pub fn clear_chr_callback(&mut self) {
+        self.chr_callback = None;
+        self.chr_snapshot.clear();
+    }
+
+    // Compares the mapper's current view of pattern memory against the last snapshot,
+    // which catches both bank switches and CHR RAM writes. Called automatically at the
+    // start of each frame; frontends wanting to catch mid-frame bank changes may call
+    // this more often.
+    pub fn check_chr_changes(&mut self) {
+        if self.chr_callback.is_none() {
+            return;
+        }
+        let mut pattern_data = vec!(0u8; 0x2000);
+        for (address, byte) in pattern_data.iter_mut().enumerate() {
+            *byte = self.mapper.debug_read_ppu(address as u16).unwrap_or(0);
+        }
+        if pattern_data != self.chr_snapshot {
+            if let Some(ref mut callback) = self.chr_callback {
+                callback(&pattern_data);
+            }
+            self.chr_snapshot = pattern_data;
         }
     }
 
     pub fn run_until_hblank(&mut self) {
+        if self.paused {
+            return;
+        }
         let old_scanline = self.ppu.current_scanline;
         while old_scanline == self.ppu.current_scanline {
             self.step();
@@ -136,6 +469,9 @@ impl NesState {
     }
 
     pub fn run_until_vblank(&mut self) {
+        if self.paused {
+            return;
+        }
         while self.ppu.current_scanline == 242 {
             self.step();
         }
@@ -144,6 +480,97 @@ impl NesState {
         }
     }
 
+    // Runs until the next vblank, and returns the frame along with exactly the audio
+    // generated along the way. Any samples still queued from before are discarded, so
+    // frontends should use either this or consume_samples, not both.
+    pub fn run_frame(&mut self) -> FrameOutput {
+        let _ = self.apu.consume_samples();
+        self.input_polled = false;
+        let start_cycle = self.cpu_cycle();
+        self.run_until_vblank();
+        let (palette_indices, emphasis) = self.ppu.indexed_screen();
+        return FrameOutput {
+            frame_number: self.ppu.current_frame,
+            palette_indices: palette_indices,
+            emphasis: emphasis,
+            audio: self.apu.consume_samples(),
+            lag: !self.input_polled,
+            cpu_cycles: self.cpu_cycle() - start_cycle,
+            event_count: self.event_tracker.events_this_frame().len(),
+        };
+    }
+
+    pub fn set_speed(&mut self, percent: u32, pitch_follows_speed: bool) {
+        self.speed_percent = percent.max(1);
+        self.speed_accumulator = 0;
+        self.apu.set_playback_speed(self.speed_percent, pitch_follows_speed);
+    }
+
+    // Runs as many emulated frames as the current speed calls for in a single host
+    // frame: at 50% this alternates between 0 and 1, at 250% between 2 and 3.
+    // Returns the number of frames emulated.
+    pub fn run_host_frame(&mut self) -> u32 {
+        if self.paused {
+            return 0;
+        }
+        self.speed_accumulator += self.speed_percent;
+        let mut frames_run = 0;
+        while self.speed_accumulator >= 100 {
+            self.run_until_vblank();
+            self.speed_accumulator -= 100;
+            frames_run += 1;
+        }
+        return frames_run;
+    }
+
+    // Pauses at a frame boundary. If the current frame is only partly emulated, it is
+    // finished first, so the framebuffer never holds half a frame. The audio output gets
+    // a short fade to silence, which the frontend drains with consume_samples as usual.
+    pub fn pause(&mut self) {
+        if self.paused {
+            return;
+        }
+        if self.ppu.current_scanline != 242 {
+            self.run_until_vblank();
+        }
+        self.apu.fade_out(PAUSE_FADE_SAMPLES);
+        self.paused = true;
+        self.paused_by_focus = false;
+    }
+
+    pub fn resume(&mut self) {
+        if !self.paused {
+            return;
+        }
+        self.paused = false;
+        self.paused_by_focus = false;
+        self.apu.fade_in(PAUSE_FADE_SAMPLES);
+    }
+
+    // Frontends call this right after restoring emulation state from somewhere else (a
+    // savestate, a rewind buffer), before running again. The audio filters pick up the
+    // restored channels without a click, and the first few ms crossfade out of whatever
+    // was playing. Keep any samples already consumed or queued; see ApuState::resync_output.
+    pub fn resync_audio(&mut self) {
+        self.apu.resync_output(&*self.mapper, PAUSE_FADE_SAMPLES);
+    }
+
+    // Frontends call these when their window loses or regains focus. With auto_pause
+    // set, losing focus pauses, and regaining it only resumes if the pause was ours;
+    // a pause the user asked for is left alone.
+    pub fn focus_lost(&mut self) {
+        if self.auto_pause && !self.paused {
+            self.pause();
+            self.paused_by_focus = true;
+        }
+    }
+
+    pub fn focus_gained(&mut self) {
+        if self.paused_by_focus {
+            self.resume();
+        }
+    }
+
     pub fn nudge_ppu_alignment(&mut self) {
         // Give the PPU a swift kick:
         self.ppu.clock(&mut *self.mapper);
@@ -151,6 +578,95 @@ impl NesState {
         self.event_tracker.current_cycle = self.ppu.current_scanline_cycle;
     }
 
+    // Predicts the (scanline, dot) at which the mapper IRQ will next assert, assuming
+    // nothing reconfigures it first. Scanline counters are assumed to clock at dot 260
+    // of each rendered scanline, which matches the common MMC3 setup of backgrounds at
+    // $0000 and sprites at $1000.
+    pub fn predict_mapper_irq(&self) -> Option<(u16, u16)> {
+        let irq_state = self.mapper.irq_state()?;
+        let clocks = irq_state.clocks_until_irq?;
+        let mut scanline = self.ppu.current_scanline;
+        let mut dot = self.ppu.current_scanline_cycle;
+        match irq_state.unit {
+            IrqCounterUnit::CpuCycles => {
+                let total_dots = (dot as u32) + clocks * 3;
+                let lines = total_dots / 341;
+                dot = (total_dots % 341) as u16;
+                scanline = (((scanline as u32) + lines) % 262) as u16;
+            },
+            IrqCounterUnit::Scanlines => {
+                if !self.ppu.rendering_enabled() {
+                    return None;
+                }
+                let mut remaining = clocks;
+                if dot >= 260 {
+                    scanline = (scanline + 1) % 262;
+                }
+                dot = 260;
+                loop {
+                    let counted = scanline < 240 || scanline == 261;
+                    if counted {
+                        remaining -= 1;
+                        if remaining == 0 {
+                            break;
+                        }
+                    }
+                    scanline = (scanline + 1) % 262;
+                }
+            }
+        }
+        return Some((scanline, dot));
+    }
+
+    // Reloads an iNES file in place, intended for homebrew development. If only CHR ROM
+    // differs, it is swapped into the running mapper and all other state (RAM, CPU,
+    // bank registers) is left alone. If PRG ROM differs, the game is only restarted
+    // when requested, since the running code would otherwise be out of sync.
+    pub fn hot_reload(&mut self, cart_data: &[u8], restart_on_prg_change: bool) -> Result<HotReloadResult, String> {
+        let mut reader = cart_data;
+        let ines = INesCartridge::from_reader(&mut reader).map_err(|e| format!("{}", e))?;
+
+        let prg_matches = match self.mapper.prg_rom() {
+            Some(prg_rom) => *prg_rom.as_vec() == ines.prg,
+            None => return Err(String::from("Hot reload is not supported for this mapper"))
+        };
+
+        if !prg_matches {
+            if !restart_on_prg_change {
+                return Ok(HotReloadResult::PrgChanged);
+            }
+            let sram = self.mapper.get_sram();
+            // Self-flashing boards save into PRG itself, which is exactly what just changed
+            let sram_is_prg = self.mapper.prg_rom().map(|prg_rom| prg_rom.as_vec()) == Some(&sram);
+            self.mapper = cartridge::mapper_from_file(cart_data)?;
+            if !sram_is_prg && self.mapper.has_sram() && sram.len() == self.mapper.get_sram().len() {
+                self.mapper.load_sram(sram);
+            }
+            self.power_on();
+            self.resync_audio();
+            return Ok(HotReloadResult::Restarted);
+        }
+
+        if ines.chr.is_empty() {
+            // CHR RAM; everything we could reload lives in PRG
+            return Ok(HotReloadResult::Unchanged);
+        }
+        return match self.mapper.chr_mut() {
+            Some(chr) => {
+                if !chr.is_readonly() || chr.len() != ines.chr.len() {
+                    return Err(String::from("CHR layout changed, a full reload is required"));
+                }
+                if *chr.as_vec() == ines.chr {
+                    Ok(HotReloadResult::Unchanged)
+                } else {
+                    chr.as_mut_vec().copy_from_slice(&ines.chr);
+                    Ok(HotReloadResult::ChrReloaded)
+                }
+            },
+            None => Err(String::from("Hot reload is not supported for this mapper"))
+        }
+    }
+
     pub fn sram(&self) -> Vec<u8> {
         return self.mapper.get_sram();
     }
@@ -163,3 +679,45 @@ impl NesState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ines::INesCartridge;
+    use mmc::nrom::Nrom;
+    use mmc::taito_x1005::TaitoX1005;
+
+    #[test]
+    fn freezes_land_in_ram_but_never_in_registers() {
+        let mapper = TaitoX1005::from_ines(INesCartridge::test_image(80, 8, 1)).unwrap();
+        let mut nes = NesState::new(Box::new(mapper));
+        // $7EFA is the first PRG bank register, sharing the range with the internal RAM
+        assert!(nes.freeze_address(0x7EFA, 0x05, FreezeMode::EveryFrame).is_err());
+        assert_eq!(nes.mapper.debug_read_cpu(0x8000), Some(0));
+
+        // The game hasn't unlocked its RAM, but the freeze holds anyway
+        nes.freeze_address(0x7F10, 0x42, FreezeMode::EveryFrame).unwrap();
+        nes.mapper.write_cpu(0x7EF8, 0xA3);
+        assert_eq!(nes.mapper.debug_read_cpu(0x7F10), Some(0x42));
+        nes.mapper.write_cpu(0x7F10, 0x00);
+        nes.mapper.write_cpu(0x7EF8, 0x00);
+        nes.apply_frozen_addresses(FreezeMode::EveryFrame);
+        nes.mapper.write_cpu(0x7EF8, 0xA3);
+        assert_eq!(nes.mapper.debug_read_cpu(0x7F10), Some(0x42));
+    }
+
+    #[test]
+    fn power_on_alignment_does_not_accumulate() {
+        let mapper = Nrom::from_ines(INesCartridge::test_image(0, 2, 1)).unwrap();
+        let mut nes = NesState::new(Box::new(mapper));
+        nes.set_power_on_alignment(2);
+        nes.power_on();
+        assert_eq!((nes.ppu.current_scanline, nes.ppu.current_scanline_cycle), (0, 2));
+        for _ in 0 .. 1000 {
+            nes.cycle();
+        }
+        nes.power_on();
+        assert_eq!((nes.ppu.current_scanline, nes.ppu.current_scanline_cycle), (0, 2));
+        assert_eq!(nes.ppu.overall_cycle as u64, nes.cpu_cycle() * 3 + 2);
+    }
+}
@@ -40,7 +40,7 @@ const NSF_BANK_INIT: usize = 0x070;
 const NSF_PAL_PLAY_SPEED: usize = 0x078;
 //const NSF_NTSC_PAL_SELECTION: usize = 0x07A;
 const NSF_EXPANSION_CHIPS: usize = 0x07B;
-//const NSF2_FLAGS: usize = 0x07C;
+const NSF2_FLAGS: usize = 0x07C;
 const NSF_PRG_LENGTH: usize = 0x07D;
 
 impl NsfHeader {
@@ -157,16 +157,35 @@ impl NsfHeader {
         return (self.raw_bytes[NSF_EXPANSION_CHIPS] & 0b0010_0000) != 0;
     }
 
+    // NSF2 feature flags. Older files used this byte as padding, so it only counts from
+    // version 2 onwards.
+    pub fn nsf2_flags(&self) -> u8 {
+        if self.version_number() < 2 {
+            return 0;
+        }
+        return self.raw_bytes[NSF2_FLAGS];
+    }
+
+    // INIT may never return; PLAY is then called by interrupting it
+    pub fn non_returning_init(&self) -> bool {
+        return (self.nsf2_flags() & 0b0010_0000) != 0;
+    }
+
+    // PLAY isn't used at all, usually alongside a non-returning INIT which does the work
+    pub fn suppressed_play(&self) -> bool {
+        return (self.nsf2_flags() & 0b0100_0000) != 0;
+    }
+
     pub fn song_name(&self) -> Vec<u8> {
-        return self.raw_bytes[NSF_SONG_NAME ..= (NSF_SONG_NAME + 32)].to_vec();
+        return self.raw_bytes[NSF_SONG_NAME .. (NSF_SONG_NAME + 32)].to_vec();
     }
 
     pub fn artist_name(&self) -> Vec<u8> {
-        return self.raw_bytes[NSF_ARTIST_NAME ..= (NSF_ARTIST_NAME + 32)].to_vec();
+        return self.raw_bytes[NSF_ARTIST_NAME .. (NSF_ARTIST_NAME + 32)].to_vec();
     }
 
     pub fn copyright_holder(&self) -> Vec<u8> {
-        return self.raw_bytes[NSF_COPYRIGHT_HOLDER ..= (NSF_COPYRIGHT_HOLDER + 32)].to_vec();
+        return self.raw_bytes[NSF_COPYRIGHT_HOLDER .. (NSF_COPYRIGHT_HOLDER + 32)].to_vec();
     }
 }
 
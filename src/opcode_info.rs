@@ -141,12 +141,43 @@ pub fn control_block(opcode: u8) -> (&'static str, &'static str) {
 
 pub fn addressing_bytes(addressing_mode: &str) -> u8 {
 	return match addressing_mode {
-		"#i" | "d" | "(d, x)" | "(d), y" | "d, x"  => 1,
+		"#i" | "d" | "(d, x)" | "(d), y" | "d, x" | "d, y" | "r" => 1,
 		"a" | "a, x" | "a, y" | "(a)" => 2,
 		_ => 0
 	}
 }
 
+// Like the mode disassemble_instruction shows, but filled in for every opcode, including
+// the unofficial ones. Branches use "r", for their relative target.
+pub fn addressing_mode(opcode: u8) -> &'static str {
+  let logic_block = opcode & 0b0000_0011;
+  let addressing_mode_index = (opcode & 0b0001_1100) >> 2;
+  let opcode_index = (opcode & 0b1110_0000) >> 5;
+  return match opcode {
+    0x10 | 0x30 | 0x50 | 0x70 | 0x90 | 0xB0 | 0xD0 | 0xF0 => "r",
+    0x20 => "a",
+    0x97 | 0xB7 => "d, y",
+    0x9E | 0x9F | 0xBF => "a, y",
+    0x9C => "a, x",
+    _ => {
+      let (_, mode) = match logic_block {
+        0b00 => control_block(opcode),
+        0b10 => rmw_block(opcode, addressing_mode_index, opcode_index),
+        _ => alu_block(addressing_mode_index, opcode_index),
+      };
+      match (mode, addressing_mode_index) {
+        // The unofficial NOPs, and the rest of the STPs
+        ("???", 0b001) => "d",
+        ("???", 0b011) => "a",
+        ("???", 0b101) => "d, x",
+        ("???", 0b111) => "a, x",
+        ("???", _) => "",
+        _ => mode,
+      }
+    }
+  };
+}
+
 pub fn disassemble_instruction(opcode: u8, _: u8, _: u8) -> (String, u8) {
   let logic_block = opcode & 0b0000_0011;
   let addressing_mode_index = (opcode & 0b0001_1100) >> 2;
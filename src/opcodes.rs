@@ -1,5 +1,7 @@
 use addressing;
+use cycle_cpu;
 use cycle_cpu::Registers;
+use interrupt_timeline::InterruptSource;
 use nes::NesState;
 use memory::read_byte;
 use memory::write_byte;
@@ -337,7 +339,10 @@ pub fn branch(nes: &mut NesState) {
       nes.registers.pc = (nes.registers.pc & 0xFF00) | (result & 0xFF);
 
       if (nes.registers.pc & 0xFF00) == (result & 0xFF00) {
-        // No need to adjust the high byte, so bail here
+        // No need to adjust the high byte, so bail here. Taken branches which stay on
+        // the same page don't poll for interrupts during this last cycle, so one arriving
+        // now waits until after the next instruction.
+        cycle_cpu::skip_interrupt_poll(nes);
         nes.cpu.tick = 0;
       } else {
         // store high byte of result into data2 for further processing
@@ -361,6 +366,16 @@ pub fn branch(nes: &mut NesState) {
   }
 }
 
+fn record_interrupt(nes: &mut NesState, source: InterruptSource) {
+  if nes.interrupt_timeline.enabled {
+    let vector = nes.cpu.temp_address;
+    let cycle = nes.cpu_cycle();
+    let scanline = nes.ppu.current_scanline;
+    let scanline_cycle = nes.ppu.current_scanline_cycle;
+    nes.interrupt_timeline.acknowledge(source, vector, cycle, scanline, scanline_cycle);
+  }
+}
+
 // This isn't strictly an opcode, but it's very similar to the BRK instruction below,
 // and is the routine the processer runs when an interrupt occurs. Close enough.
 pub fn service_interrupt(nes: &mut NesState) {
@@ -394,6 +409,8 @@ pub fn service_interrupt(nes: &mut NesState) {
       } else {
         nes.cpu.temp_address = 0xFFFE;
       }
+      let source = if nes.cpu.temp_address == 0xFFFA {InterruptSource::Nmi} else {InterruptSource::Irq};
+      record_interrupt(nes, source);
       let status_byte = nes.registers.status_as_byte(false);
       push(nes, status_byte);
       nes.cpu.upcoming_write = false;
@@ -436,6 +453,8 @@ pub fn brk(nes: &mut NesState) {
       } else {
         nes.cpu.temp_address = 0xFFFE;
       }
+      let source = if nes.cpu.temp_address == 0xFFFA {InterruptSource::Nmi} else {InterruptSource::Brk};
+      record_interrupt(nes, source);
       // Here we set the B flag to signal a BRK, even if we end up servicing an NMI instead.
       let status_byte = nes.registers.status_as_byte(true);
       push(nes, status_byte);
@@ -519,6 +538,10 @@ pub fn rti(nes: &mut NesState) {
       let pcl = nes.cpu.data1 as u16;
       nes.registers.pc = (pch << 8) | pcl;
       nes.cpu.tick = 0;
+      if nes.interrupt_timeline.enabled {
+        let cycle = nes.cpu_cycle();
+        nes.interrupt_timeline.return_from_interrupt(cycle);
+      }
     },
     _ => ()
   };
@@ -0,0 +1,171 @@
+// Generates a 512 entry palette (64 colors, for each of the 8 emphasis combinations) by
+// decoding the same composite signal used by the NTSC filter, with knobs for hue,
+// saturation, contrast, brightness, gamma and the primaries of the TV being imitated.
+// The layout matches palettes::NTSC_PAL: index = (emphasis << 6) | color, 3 bytes RGB.
+// Based on the approach of http://bisqwit.iki.fi/utils/nespalette.php
+
+use ppu::ntsc_signal;
+
+use std::f32::consts::PI;
+
+// Composite levels for black and white, matching the PPU's NTSC filter
+const SIGNAL_BLACK: f32 = 0.518;
+const SIGNAL_WHITE: f32 = 1.962;
+
+// Matches the phase alignment of the PPU's NTSC filter
+const BASE_PHASE_OFFSET: f32 = 3.9;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Colorimetry {
+    // Use the decoded RGB as is, which is what most baked palettes do
+    Direct,
+    // The original 1953 NTSC primaries; deeper greens and reds than a modern display
+    Fcc1953,
+    // The phosphors of most consumer CRTs from the late 80s onward
+    SmpteC,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PaletteSettings {
+    // In degrees; 30 degrees is one step of the PPU's 12 phase color generator
+    pub hue: f32,
+    pub saturation: f32,
+    pub contrast: f32,
+    pub brightness: f32,
+    // The display gamma of the imitated TV. Output is encoded for a 2.2 gamma display.
+    pub gamma: f32,
+    pub colorimetry: Colorimetry,
+}
+
+impl PaletteSettings {
+    pub fn new() -> PaletteSettings {
+        return PaletteSettings {
+            hue: 0.0,
+            saturation: 1.0,
+            contrast: 1.0,
+            brightness: 0.0,
+            gamma: 2.2,
+            colorimetry: Colorimetry::Direct,
+        }
+    }
+}
+
+const OUTPUT_GAMMA: f32 = 2.2;
+
+type Matrix = [[f32; 3]; 3];
+
+// Chromaticities (x, y) of the red, green and blue primaries, then the white point
+fn primaries(colorimetry: Colorimetry) -> [(f32, f32); 4] {
+    return match colorimetry {
+        Colorimetry::Fcc1953 => [(0.67, 0.33), (0.21, 0.71), (0.14, 0.08), (0.310, 0.316)],
+        Colorimetry::SmpteC => [(0.630, 0.340), (0.310, 0.595), (0.155, 0.070), (0.3127, 0.3290)],
+        // sRGB, which makes the conversion an identity
+        Colorimetry::Direct => SRGB_PRIMARIES,
+    }
+}
+
+const SRGB_PRIMARIES: [(f32, f32); 4] = [(0.640, 0.330), (0.300, 0.600), (0.150, 0.060), (0.3127, 0.3290)];
+
+fn multiply(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut result = [[0.0; 3]; 3];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            *value = (0 .. 3).map(|k| a[row][k] * b[k][column]).sum();
+        }
+    }
+    return result;
+}
+
+fn invert(m: &Matrix) -> Matrix {
+    let determinant =
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1]) -
+        m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0]) +
+        m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0]);
+    let mut result = [[0.0; 3]; 3];
+    for (row, result_row) in result.iter_mut().enumerate() {
+        for (column, value) in result_row.iter_mut().enumerate() {
+            // Cofactor of the transposed position
+            let r0 = (column + 1) % 3;
+            let r1 = (column + 2) % 3;
+            let c0 = (row + 1) % 3;
+            let c1 = (row + 2) % 3;
+            *value = (m[r0][c0] * m[r1][c1] - m[r0][c1] * m[r1][c0]) / determinant;
+        }
+    }
+    return result;
+}
+
+// Linear RGB to XYZ for a set of primaries, scaled so RGB white lands on `white`
+fn rgb_to_xyz(primaries: &[(f32, f32); 4], white: (f32, f32)) -> Matrix {
+    let mut m = [[0.0; 3]; 3];
+    for channel in 0 .. 3 {
+        let (x, y) = primaries[channel];
+        m[0][channel] = x / y;
+        m[1][channel] = 1.0;
+        m[2][channel] = (1.0 - x - y) / y;
+    }
+    let (wx, wy) = white;
+    let white_xyz = [wx / wy, 1.0, (1.0 - wx - wy) / wy];
+    let inverse = invert(&m);
+    for channel in 0 .. 3 {
+        let scale: f32 = (0 .. 3).map(|k| inverse[channel][k] * white_xyz[k]).sum();
+        for row in &mut m {
+            row[channel] *= scale;
+        }
+    }
+    return m;
+}
+
+// Converts linear RGB in the given primaries to linear sRGB. The source white point is
+// kept as the output's white, as a viewer adapted to the TV would perceive it.
+fn colorimetry_matrix(colorimetry: Colorimetry) -> Matrix {
+    let source = primaries(colorimetry);
+    let white = source[3];
+    return multiply(&invert(&rgb_to_xyz(&SRGB_PRIMARIES, white)), &rgb_to_xyz(&source, white));
+}
+
+// Decodes one palette entry to YIQ by averaging a full cycle of the color generator
+fn decode_yiq(pixel: u16, hue_offset: f32) -> (f32, f32, f32) {
+    let mut y = 0.0;
+    let mut i = 0.0;
+    let mut q = 0.0;
+    for phase in 0 .. 12 {
+        let level = (ntsc_signal(pixel, phase) - SIGNAL_BLACK) / (SIGNAL_WHITE - SIGNAL_BLACK) / 12.0;
+        let angle = PI * (phase as f32 + BASE_PHASE_OFFSET + hue_offset) / 6.0;
+        y += level;
+        i += level * angle.cos();
+        q += level * angle.sin();
+    }
+    return (y, i, q);
+}
+
+fn to_byte(value: f32) -> u8 {
+    return (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+}
+
+pub fn generate_palette(settings: &PaletteSettings) -> Vec<u8> {
+    let matrix = colorimetry_matrix(settings.colorimetry);
+    let hue_offset = settings.hue / 30.0;
+    let mut palette = Vec::with_capacity(512 * 3);
+    for pixel in 0 .. 512u16 {
+        let (y, i, q) = decode_yiq(pixel, hue_offset);
+        let y = y * settings.contrast + settings.brightness;
+        let i = i * settings.saturation * settings.contrast;
+        let q = q * settings.saturation * settings.contrast;
+        let encoded = [
+            y + 0.946882 * i + 0.623557 * q,
+            y - 0.274788 * i - 0.635691 * q,
+            y - 1.108545 * i + 1.709007 * q,
+        ];
+        // Into linear light for the imitated display, then back out for ours
+        let mut linear = [0.0; 3];
+        for channel in 0 .. 3 {
+            linear[channel] = encoded[channel].max(0.0).powf(settings.gamma);
+        }
+        for row in &matrix {
+            let value: f32 = (0 .. 3).map(|k| row[k] * linear[k]).sum();
+            palette.push(to_byte(value.max(0.0).powf(1.0 / OUTPUT_GAMMA)));
+        }
+    }
+    return palette;
+}
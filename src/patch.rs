@@ -0,0 +1,337 @@
+// Soft patching: applies IPS or BPS patches to a ROM image in memory at load time, so
+// translations and hacks can be played without modifying the original file.
+// IPS: https://zerosoft.zophar.net/ips.php
+// BPS: https://github.com/blakesmith/rombp/blob/master/docs/bps_spec.md
+
+pub fn is_ips(patch: &[u8]) -> bool {
+    return patch.starts_with(b"PATCH");
+}
+
+pub fn is_bps(patch: &[u8]) -> bool {
+    return patch.starts_with(b"BPS1");
+}
+
+// Detects the patch format from its magic bytes
+pub fn apply_patch(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if is_ips(patch) {
+        return apply_ips(rom, patch);
+    }
+    if is_bps(patch) {
+        return apply_bps(rom, patch);
+    }
+    return Err(String::from("Unrecognized patch format"));
+}
+
+fn read_u8(patch: &[u8], position: &mut usize) -> Result<u8, String> {
+    match patch.get(*position) {
+        Some(&byte) => {
+            *position += 1;
+            return Ok(byte);
+        },
+        None => return Err(String::from("Unexpected end of patch data"))
+    }
+}
+
+fn read_be(patch: &[u8], position: &mut usize, byte_count: usize) -> Result<usize, String> {
+    let mut value = 0;
+    for _ in 0 .. byte_count {
+        value = (value << 8) | (read_u8(patch, position)? as usize);
+    }
+    return Ok(value);
+}
+
+pub fn apply_ips(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_ips(patch) {
+        return Err(String::from("Missing IPS header"));
+    }
+    let mut target = rom.to_vec();
+    let mut position = 5;
+    loop {
+        if patch[position ..].starts_with(b"EOF") {
+            position += 3;
+            break;
+        }
+        let offset = read_be(patch, &mut position, 3)?;
+        let mut length = read_be(patch, &mut position, 2)?;
+        let rle = length == 0;
+        if rle {
+            length = read_be(patch, &mut position, 2)?;
+        }
+        if target.len() < offset + length {
+            target.resize(offset + length, 0);
+        }
+        if rle {
+            let value = read_u8(patch, &mut position)?;
+            for byte in &mut target[offset .. offset + length] {
+                *byte = value;
+            }
+        } else {
+            if position + length > patch.len() {
+                return Err(String::from("Unexpected end of patch data"));
+            }
+            target[offset .. offset + length].copy_from_slice(&patch[position .. position + length]);
+            position += length;
+        }
+    }
+    // A common extension: three more bytes after EOF truncate the output
+    if patch.len() >= position + 3 {
+        let truncated_length = read_be(patch, &mut position, 3)?;
+        target.truncate(truncated_length);
+    }
+    return Ok(target);
+}
+
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0 .. 8 {
+            let mask = (!(crc & 1)).wrapping_add(1);
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    return !crc;
+}
+
+fn read_u32_le(data: &[u8], position: usize) -> u32 {
+    return (data[position] as u32) |
+        ((data[position + 1] as u32) << 8) |
+        ((data[position + 2] as u32) << 16) |
+        ((data[position + 3] as u32) << 24);
+}
+
+// BPS stores numbers as a variable length encoding, 7 bits at a time, with
+// the continuation implicit in the high bit being clear
+fn read_bps_number(patch: &[u8], position: &mut usize) -> Result<usize, String> {
+    let mut data: usize = 0;
+    let mut shift: usize = 1;
+    loop {
+        let byte = read_u8(patch, position)?;
+        let digit = ((byte & 0x7F) as usize).checked_mul(shift).ok_or("Malformed BPS number")?;
+        data = data.checked_add(digit).ok_or("Malformed BPS number")?;
+        if byte & 0x80 != 0 {
+            break;
+        }
+        shift = shift.checked_mul(0x80).ok_or("Malformed BPS number")?;
+        data = data.checked_add(shift).ok_or("Malformed BPS number")?;
+    }
+    return Ok(data);
+}
+
+fn apply_relative_offset(offset: usize, encoded: usize) -> Result<usize, String> {
+    let distance = encoded >> 1;
+    let result = if encoded & 1 != 0 {
+        offset.checked_sub(distance)
+    } else {
+        offset.checked_add(distance)
+    };
+    return result.ok_or(String::from("BPS copy offset out of range"));
+}
+
+// Copies can repeat a few patch bytes into any amount of output, so the target size can't
+// be checked exactly up front. These bounds are far beyond any real ROM expansion, and stop
+// a corrupt header from allocating gigabytes before the checksum catches it.
+const MAX_BPS_TARGET_SIZE: usize = 64 * 1024 * 1024;
+const MAX_BPS_GROWTH_FACTOR: usize = 16;
+
+// The end of a length byte range starting at offset, or an error if it overflows
+fn range_end(offset: usize, length: usize) -> Result<usize, String> {
+    return offset.checked_add(length).ok_or(String::from("BPS patch offset out of range"));
+}
+
+pub fn apply_bps(rom: &[u8], patch: &[u8]) -> Result<Vec<u8>, String> {
+    if !is_bps(patch) {
+        return Err(String::from("Missing BPS header"));
+    }
+    if patch.len() < 4 + 12 {
+        return Err(String::from("BPS patch is too short"));
+    }
+    let footer = patch.len() - 12;
+    let source_crc = read_u32_le(patch, footer);
+    let target_crc = read_u32_le(patch, footer + 4);
+    let patch_crc = read_u32_le(patch, footer + 8);
+    if crc32(&patch[0 .. footer + 8]) != patch_crc {
+        return Err(String::from("BPS patch is corrupt (patch checksum mismatch)"));
+    }
+    if crc32(rom) != source_crc {
+        return Err(format!("BPS patch does not apply to this ROM (expected CRC32 {:08X}, got {:08X})", source_crc, crc32(rom)));
+    }
+
+    let mut position = 4;
+    let source_size = read_bps_number(patch, &mut position)?;
+    let target_size = read_bps_number(patch, &mut position)?;
+    let metadata_size = read_bps_number(patch, &mut position)?;
+    position = range_end(position, metadata_size)?;
+    if source_size != rom.len() {
+        return Err(String::from("BPS source size does not match this ROM"));
+    }
+    let growth_limit = rom.len().saturating_add(patch.len()).saturating_mul(MAX_BPS_GROWTH_FACTOR);
+    if target_size > MAX_BPS_TARGET_SIZE || target_size > growth_limit {
+        return Err(format!("BPS target size of {} bytes is unreasonably large", target_size));
+    }
+
+    let mut target = vec!(0u8; target_size);
+    let mut output_offset = 0;
+    let mut source_relative_offset = 0;
+    let mut target_relative_offset = 0;
+    while position < footer {
+        let data = read_bps_number(patch, &mut position)?;
+        let command = data & 0b11;
+        let length = (data >> 2) + 1;
+        let output_end = range_end(output_offset, length)?;
+        if output_end > target_size {
+            return Err(String::from("BPS patch writes past the end of the target"));
+        }
+        match command {
+            0 => {
+                // SourceRead
+                if output_end > rom.len() {
+                    return Err(String::from("BPS source read out of range"));
+                }
+                target[output_offset .. output_end].copy_from_slice(&rom[output_offset .. output_end]);
+            },
+            1 => {
+                // TargetRead
+                let patch_end = range_end(position, length)?;
+                if patch_end > footer {
+                    return Err(String::from("Unexpected end of patch data"));
+                }
+                target[output_offset .. output_end].copy_from_slice(&patch[position .. patch_end]);
+                position = patch_end;
+            },
+            2 => {
+                // SourceCopy
+                let encoded = read_bps_number(patch, &mut position)?;
+                source_relative_offset = apply_relative_offset(source_relative_offset, encoded)?;
+                let source_end = range_end(source_relative_offset, length)?;
+                if source_end > rom.len() {
+                    return Err(String::from("BPS source copy out of range"));
+                }
+                target[output_offset .. output_end].copy_from_slice(&rom[source_relative_offset .. source_end]);
+                source_relative_offset = source_end;
+            },
+            _ => {
+                // TargetCopy; may overlap the bytes it is writing, so copy one at a time
+                let encoded = read_bps_number(patch, &mut position)?;
+                target_relative_offset = apply_relative_offset(target_relative_offset, encoded)?;
+                for i in 0 .. length {
+                    if target_relative_offset >= output_offset + i {
+                        return Err(String::from("BPS target copy out of range"));
+                    }
+                    target[output_offset + i] = target[target_relative_offset];
+                    target_relative_offset += 1;
+                }
+            }
+        }
+        output_offset = output_end;
+    }
+
+    if crc32(&target) != target_crc {
+        return Err(String::from("Patched ROM failed verification (target checksum mismatch)"));
+    }
+    return Ok(target);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_bps_number(patch: &mut Vec<u8>, mut value: usize) {
+        loop {
+            let low_bits = (value & 0x7F) as u8;
+            value >>= 7;
+            if value == 0 {
+                patch.push(0x80 | low_bits);
+                return;
+            }
+            patch.push(low_bits);
+            value -= 1;
+        }
+    }
+
+    // Header numbers, then the commands, then a valid footer
+    fn bps_patch(rom: &[u8], target: &[u8], header: &[usize], commands: &[u8]) -> Vec<u8> {
+        let mut patch = b"BPS1".to_vec();
+        for &number in header {
+            encode_bps_number(&mut patch, number);
+        }
+        patch.extend_from_slice(commands);
+        patch.extend_from_slice(&crc32(rom).to_le_bytes());
+        patch.extend_from_slice(&crc32(target).to_le_bytes());
+        let patch_crc = crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        return patch;
+    }
+
+    #[test]
+    fn applies_source_and_target_reads() {
+        let rom = [1, 2, 3, 4];
+        let target = [1, 2, 9, 9];
+        let mut commands = Vec::new();
+        // SourceRead 2 bytes, then TargetRead 2 bytes
+        encode_bps_number(&mut commands, ((2 - 1) << 2) | 0);
+        encode_bps_number(&mut commands, ((2 - 1) << 2) | 1);
+        commands.extend_from_slice(&[9, 9]);
+        let patch = bps_patch(&rom, &target, &[4, 4, 0], &commands);
+        assert_eq!(apply_bps(&rom, &patch), Ok(target.to_vec()));
+    }
+
+    #[test]
+    fn rejects_oversized_metadata() {
+        let rom = [1, 2, 3, 4];
+        let patch = bps_patch(&rom, &rom, &[4, 4, usize::MAX - 2], &[]);
+        assert_eq!(apply_bps(&rom, &patch), Err(String::from("BPS patch offset out of range")));
+    }
+
+    #[test]
+    fn rejects_unreasonable_target_sizes() {
+        let rom = [1, 2, 3, 4];
+        let patch = bps_patch(&rom, &rom, &[4, 1 << 40, 0], &[]);
+        assert!(apply_bps(&rom, &patch).is_err());
+        let patch = bps_patch(&rom, &rom, &[4, 4096, 0], &[]);
+        assert!(apply_bps(&rom, &patch).is_err());
+    }
+
+    #[test]
+    fn rejects_numbers_too_long_to_decode() {
+        let rom = [1, 2, 3, 4];
+        let mut patch = b"BPS1".to_vec();
+        patch.extend_from_slice(&[0x7F; 16]);
+        patch.push(0xFF);
+        patch.extend_from_slice(&crc32(&rom).to_le_bytes());
+        patch.extend_from_slice(&crc32(&rom).to_le_bytes());
+        let patch_crc = crc32(&patch);
+        patch.extend_from_slice(&patch_crc.to_le_bytes());
+        assert!(apply_bps(&rom, &patch).is_err());
+    }
+
+    #[test]
+    fn ips_record() {
+        let patch = b"PATCH\x00\x00\x01\x00\x02\xAA\xBBEOF";
+        assert_eq!(apply_ips(&[1, 2, 3, 4], patch).unwrap(), vec![1, 0xAA, 0xBB, 4]);
+    }
+
+    #[test]
+    fn ips_rle_record() {
+        // Length 0, then an RLE run of 3
+        let patch = b"PATCH\x00\x00\x01\x00\x00\x00\x03\x55EOF";
+        assert_eq!(apply_ips(&[1, 2, 3, 4, 5], patch).unwrap(), vec![1, 0x55, 0x55, 0x55, 5]);
+    }
+
+    #[test]
+    fn ips_record_past_the_end_grows_the_rom() {
+        let patch = b"PATCH\x00\x00\x05\x00\x02\xAA\xBBEOF";
+        assert_eq!(apply_ips(&[1, 2, 3], patch).unwrap(), vec![1, 2, 3, 0, 0, 0xAA, 0xBB]);
+    }
+
+    #[test]
+    fn ips_truncation_trailer() {
+        let patch = b"PATCH\x00\x00\x00\x00\x01\xAAEOF\x00\x00\x02";
+        assert_eq!(apply_ips(&[1, 2, 3, 4], patch).unwrap(), vec![0xAA, 2]);
+    }
+
+    #[test]
+    fn ips_without_eof_is_rejected() {
+        assert!(apply_ips(&[1, 2, 3, 4], b"PATCH\x00\x00\x01\x00\x02\xAA").is_err());
+    }
+}
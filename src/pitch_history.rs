@@ -0,0 +1,101 @@
+// A short per-channel history of pitch and volume, for tuner displays and piano roll
+// style pitch trails. Each channel's rate() and volume() are sampled once at the end of
+// every frame, and the last few seconds are kept, oldest first.
+
+use std::collections::VecDeque;
+
+use apu::AudioChannelState;
+use apu::PlaybackRate;
+use apu::Volume;
+
+// A second of history at NTSC rates
+pub const DEFAULT_HISTORY_FRAMES: usize = 60;
+
+#[derive(Clone)]
+pub struct PitchSample {
+    pub rate: PlaybackRate,
+    pub volume: Option<Volume>,
+    pub amplitude: f32,
+    pub playing: bool,
+}
+
+impl PitchSample {
+    // The channel's pitch in Hz, if it has one. Noise rates and sample playback rates
+    // aren't pitches, so they report None.
+    pub fn frequency(&self) -> Option<f32> {
+        if !self.playing {
+            return None;
+        }
+        return match self.rate {
+            PlaybackRate::FundamentalFrequency{frequency} => Some(frequency),
+            _ => None
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct ChannelPitchHistory {
+    pub name: String,
+    pub chip: String,
+    pub samples: VecDeque<PitchSample>,
+}
+
+pub struct PitchHistory {
+    pub enabled: bool,
+    pub length: usize,
+    pub channels: Vec<ChannelPitchHistory>,
+}
+
+impl PitchHistory {
+    pub fn new() -> PitchHistory {
+        return PitchHistory {
+            enabled: false,
+            length: DEFAULT_HISTORY_FRAMES,
+            channels: Vec::new(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.channels.clear();
+    }
+
+    pub fn set_length(&mut self, frames: usize) {
+        self.length = frames;
+        for channel in &mut self.channels {
+            while channel.samples.len() > frames {
+                channel.samples.pop_front();
+            }
+        }
+    }
+
+    pub fn end_frame(&mut self, channels: &[&dyn AudioChannelState]) {
+        // The channel list only changes when the mapper does; start over if it has
+        let unchanged = self.channels.len() == channels.len() && self.channels.iter().zip(channels.iter())
+            .all(|(history, channel)| history.name == channel.name() && history.chip == channel.chip());
+        if !unchanged {
+            self.channels = channels.iter().map(|channel| ChannelPitchHistory {
+                name: channel.name(),
+                chip: channel.chip(),
+                samples: VecDeque::with_capacity(self.length),
+            }).collect();
+        }
+        for (history, channel) in self.channels.iter_mut().zip(channels.iter()) {
+            if self.length == 0 {
+                continue;
+            }
+            if history.samples.len() >= self.length {
+                history.samples.pop_front();
+            }
+            history.samples.push_back(PitchSample {
+                rate: channel.rate(),
+                volume: channel.volume(),
+                amplitude: channel.amplitude(),
+                playing: channel.playing(),
+            });
+        }
+    }
+
+    pub fn channel(&self, chip: &str, name: &str) -> Option<&ChannelPitchHistory> {
+        return self.channels.iter().find(|channel| channel.chip == chip && channel.name == name);
+    }
+}
@@ -0,0 +1,57 @@
+// Debug: out of band writes to the hardware, for experimenting with palettes, scroll
+// positions and volume levels while paused. These are INVASIVE. No CPU time passes and the
+// event tracker never sees them, but the write itself has every side effect the real one
+// would, and the game is not told. Expect a movie being played or recorded to desync
+// after using any of these.
+
+use memory;
+use nes::NesState;
+
+// A write to any CPU address, exactly as the CPU would perform it. Registers react in
+// full: a write to $2005 or $2006 flips the PPU's write toggle, $2007 advances the VRAM
+// address, $4014 starts an OAM DMA, and mapper registers switch banks or acknowledge IRQs.
+pub fn poke_cpu_bus(nes: &mut NesState, address: u16, data: u8) {
+    memory::write_byte_untracked(nes, address, data);
+}
+
+// Writes PPU memory directly, leaving the VRAM address and write toggle alone. This is the
+// safe way to change palette entries ($3F00-$3F1F) or nametables. Pattern table writes
+// only stick on CHR RAM, and the mapper sees the access just as if the PPU made it.
+pub fn poke_ppu_memory(nes: &mut NesState, ppu_address: u16, data: u8) {
+    nes.ppu.write_byte(&mut *nes.mapper, ppu_address, data);
+}
+
+pub fn poke_oam(nes: &mut NesState, index: u8, data: u8) {
+    nes.ppu.oam[index as usize] = data;
+}
+
+// Sets the scroll position the next frame starts from, as a $2000/$2005/$2005 sequence
+// would, but without touching the write toggle. x ranges from 0-511 and y from 0-479,
+// spanning all four logical nametables. Mid-frame scroll changes made by the game
+// itself still apply afterwards.
+pub fn poke_scroll(nes: &mut NesState, x: u16, y: u16) {
+    let x = x % 512;
+    let y = y % 480;
+    let nametable_x = x / 256;
+    let nametable_y = y / 240;
+    let coarse_x = (x % 256) >> 3;
+    let coarse_y = (y % 240) >> 3;
+    let fine_y = y & 0b111;
+    //                                  yyy_nn_YYYYY_XXXXX
+    nes.ppu.temporary_vram_address =
+        (fine_y << 12) | (nametable_y << 11) | (nametable_x << 10) | (coarse_y << 5) | coarse_x;
+    nes.ppu.fine_x = (x & 0b111) as u8;
+    nes.ppu.control = (nes.ppu.control & 0b1111_1100) | ((nametable_y << 1) | nametable_x) as u8;
+}
+
+// A write to one of the 2A03's audio registers ($4000-$4013, $4015 or $4017). Unlike
+// poke_cpu_bus, the mapper doesn't see this one.
+pub fn poke_apu_register(nes: &mut NesState, address: u16, data: u8) {
+    nes.apu.write_register(address, data);
+}
+
+// A write which only the cartridge sees, for mapper registers (including expansion audio)
+// that share an address with nothing else
+pub fn poke_mapper(nes: &mut NesState, address: u16, data: u8) {
+    nes.mapper.write_cpu(address, data);
+}
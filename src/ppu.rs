@@ -3,6 +3,7 @@
 // and prototype stages.
 
 use mmc::mapper::*;
+use region::Region;
 
 #[derive(Copy, Clone)]
 pub struct SpriteLatch {
@@ -75,6 +76,14 @@ impl SpriteLatch {
     }
 }
 
+// The composite signal is generated at 8 samples per dot; one color subcarrier cycle
+// spans 12 of them
+pub const NTSC_SAMPLES_PER_SCANLINE: usize = 256 * 8;
+pub const NTSC_SAMPLES_PER_COLOR_CYCLE: usize = 12;
+
+pub const SCREEN_PALETTE_MASK: u16 = 0b0011_1111;
+pub const SCREEN_EMPHASIS_SHIFT: u16 = 6;
+
 pub struct PpuState {
     // PPU Memory (incl. cart CHR ROM for now)
     pub internal_vram: Vec<u8>,
@@ -99,6 +108,11 @@ pub struct PpuState {
 
     pub oam_dma_high: u8,
 
+    // Writes to PPUMASK take a few dots to propagate through the PPU, which matters
+    // for games that toggle rendering mid-scanline
+    pub pending_mask: u8,
+    pub mask_delay: u8,
+
     // Internal
     pub current_frame: u32,
     pub current_scanline: u16,
@@ -106,11 +120,15 @@ pub struct PpuState {
 
     pub overall_cycle: usize,
     pub frame_starting_cycle: usize,
-    pub scanline_ntsc_samples: [f32; 256*8],
+    pub scanline_ntsc_samples: [f32; NTSC_SAMPLES_PER_SCANLINE],
 
-    // Framebuffer
+    // Framebuffer. Each pixel is packed as 0b0000_000E_EEPP_PPPP: a 6-bit palette index,
+    // with the three PPUMASK emphasis bits above it. Prefer the accessors below, which
+    // don't depend on this layout.
     pub screen: Vec<u16>,
+    // Output of render_ntsc or render_pal, ntsc_width pixels per row
     pub filtered_screen: Vec<u32>,
+    pub ntsc_width: usize,
     pub sprite_color: Vec<u8>,
     pub sprite_index: Vec<u8>,
     pub sprite_bg_priority: Vec<bool>,
@@ -134,9 +152,23 @@ pub struct PpuState {
 
     pub sprite_zero_on_scanline: bool,
 
+    // Effective scroll position at the start of each visible scanline
+    pub scanline_scroll: Vec<ScrollSnapshot>,
+    // Sprite evaluation results, indexed by the scanline the evaluation ran on
+    pub scanline_sprites: Vec<SpriteEvaluation>,
+    // Mapper banks in use on each visible scanline, while record_scanline_banks is set
+    pub record_scanline_banks: bool,
+    pub scanline_banks: Vec<ScanlineBanks>,
+
     // Debug Viewer
     pub recent_reads: Vec<u16>,
     pub recent_writes: Vec<u16>,
+
+    // Hide a layer from the output without touching PPUMASK. Applied only when picking
+    // the final pixel color, so sprite zero hits and everything else the game can
+    // observe behave as if both layers were visible.
+    pub debug_hide_background: bool,
+    pub debug_hide_sprites: bool,
 }
 
 fn debug_default_palette() -> Vec<u8> {
@@ -158,6 +190,89 @@ fn debug_default_palette() -> Vec<u8> {
     ];
 }
 
+// x ranges from 0-511 and y from 0-495, spanning all four logical nametables. Normally y
+// stays below 480, but coarse Y 30 and 31 (the attribute rows, reachable by writing them
+// to $2006 or $2005) land in 240-255 and 480-495.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ScrollSnapshot {
+    pub rendering: bool,
+    pub x: u16,
+    pub y: u16,
+}
+
+impl ScrollSnapshot {
+    pub fn new() -> ScrollSnapshot {
+        return ScrollSnapshot {
+            rendering: false,
+            x: 0,
+            y: 0,
+        }
+    }
+}
+
+// Which part of the ROM each bank window pointed at on one scanline, as offsets into
+// PRG ROM and CHR divided by the window size. None where the mapper doesn't say, or the
+// window isn't backed by ROM / CHR (PRG RAM, say). Comparing neighbouring scanlines
+// shows where a game switched banks mid-frame.
+#[derive(Clone, Copy, PartialEq)]
+pub struct ScanlineBanks {
+    // 8k windows at $8000, $A000, $C000 and $E000
+    pub prg: [Option<usize>; 4],
+    // 1k windows from $0000 to $1FFF, as background tiles for this scanline were fetched
+    pub chr: [Option<usize>; 8],
+    // The same windows as sprite tiles for the next scanline were fetched
+    pub sprite_chr: [Option<usize>; 8],
+}
+
+impl ScanlineBanks {
+    pub fn new() -> ScanlineBanks {
+        return ScanlineBanks {
+            prg: [None; 4],
+            chr: [None; 8],
+            sprite_chr: [None; 8],
+        }
+    }
+}
+
+fn chr_windows(mapper: &dyn Mapper) -> [Option<usize>; 8] {
+    let mut windows = [None; 8];
+    for (index, window) in windows.iter_mut().enumerate() {
+        *window = mapper.resolve_chr_offset(index as u16 * 0x400).map(|offset| offset / 0x400);
+    }
+    return windows;
+}
+
+// Evaluation on one scanline picks the sprites drawn on the next. Overflow means more
+// than 8 sprites were in range, and the extras were dropped (flicker, if the game
+// cycles its OAM order).
+#[derive(Clone, Copy, PartialEq)]
+pub struct SpriteEvaluation {
+    // OAM index (0-63) of each sprite copied into secondary OAM, in priority order
+    pub oam_indices: [u8; 8],
+    pub selected: u8,
+    // Every sprite in range, including those dropped from secondary OAM
+    pub in_range: u8,
+    pub overflow: bool,
+}
+
+impl SpriteEvaluation {
+    pub fn new() -> SpriteEvaluation {
+        return SpriteEvaluation {
+            oam_indices: [0xFF; 8],
+            selected: 0,
+            in_range: 0,
+            overflow: false,
+        }
+    }
+
+    pub fn selected_indices(&self) -> &[u8] {
+        return &self.oam_indices[0 .. self.selected as usize];
+    }
+}
+
+// Number of dots between a $2001 write and the new mask value taking effect
+pub const PPUMASK_DELAY: u8 = 3;
+
 impl PpuState {
     pub fn new() -> PpuState {
         return PpuState {
@@ -172,8 +287,9 @@ impl PpuState {
             overall_cycle: 0,
             frame_starting_cycle: 0,
             screen: vec!(0u16; 256 * 240),
-            filtered_screen: vec!(0u32; 2048 * 240),
-            scanline_ntsc_samples: [0f32; 256 * 8],
+            filtered_screen: vec!(0u32; NTSC_SAMPLES_PER_SCANLINE * 240),
+            ntsc_width: NTSC_SAMPLES_PER_SCANLINE,
+            scanline_ntsc_samples: [0f32; NTSC_SAMPLES_PER_SCANLINE],
             sprite_color: vec!(0u8; 256),
             sprite_index: vec!(0u8; 256),
             sprite_bg_priority: vec!(false; 256),
@@ -181,6 +297,8 @@ impl PpuState {
     
             control: 0,
             mask: 0,
+            pending_mask: 0,
+            mask_delay: 0,
             status: 0,
             oam_addr: 0,
             oam_dma_high: 0,
@@ -208,6 +326,12 @@ impl PpuState {
             // Debug
             recent_reads: Vec::new(),
             recent_writes: Vec::new(),
+            scanline_scroll: vec!(ScrollSnapshot::new(); 240),
+            scanline_sprites: vec!(SpriteEvaluation::new(); 240),
+            record_scanline_banks: false,
+            scanline_banks: vec!(ScanlineBanks::new(); 240),
+            debug_hide_background: false,
+            debug_hide_sprites: false,
        };
     }
 
@@ -219,7 +343,8 @@ impl PpuState {
                 // space in the read_buffer. This is intentional, a very odd quirk of PPU reading due to the way
                 // palette reads are implemented in hardware.
                 self.read_buffer = mapper.read_ppu(masked_address).unwrap_or(self.open_bus);
-                return self.read_byte(mapper, address);
+                let palette_entry = self.read_byte(mapper, address);
+                return self.apply_grayscale(palette_entry);
             },
             _ => {
                 let result = self.read_buffer;
@@ -244,11 +369,7 @@ impl PpuState {
                 if palette_address & 0x13 == 0x10 {
                     palette_address = palette_address - 0x10;
                 }
-                let mut palette_entry = self.palette[palette_address as usize];
-                if self.mask & 0b0000_0001 != 0 {
-                    palette_entry &= 0x30;
-                }
-                return palette_entry;
+                return self.palette[palette_address as usize];
             },
             _ => return 0
         }
@@ -315,12 +436,15 @@ impl PpuState {
         self.sprite_zero_on_scanline = false;
 
         self.initialize_secondary_oam();
+        let mut evaluation = SpriteEvaluation::new();
 
         // Gather first 8 visible sprites (and pay attention if there are more)
         for i in 0 .. 64 {
             let y = self.oam[i * 4 + 0];
             if scanline >= y && scanline < y + sprite_size {
+                evaluation.in_range += 1;
                 if self.secondary_oam_index < 8 {
+                    evaluation.oam_indices[self.secondary_oam_index] = i as u8;
                     // Copy this sprite's data into temporary secondary OAM for this scanline
                     self.secondary_oam[self.secondary_oam_index].y_pos =      self.oam[i * 4 + 0];
                     self.secondary_oam[self.secondary_oam_index].tile_index = self.oam[i * 4 + 1];
@@ -334,9 +458,62 @@ impl PpuState {
                     }
                 } else {
                     self.status = self.status | 0x20; // bit 5 = sprite overflow this frame
+                    evaluation.overflow = true;
                 }
             }
         }
+
+        evaluation.selected = self.secondary_oam_index as u8;
+        if (self.current_scanline as usize) < self.scanline_sprites.len() {
+            self.scanline_sprites[self.current_scanline as usize] = evaluation;
+        }
+    }
+
+    // Palette index (0x00 - 0x3F) of a pixel in the most recent frame, after grayscale
+    pub fn pixel_palette_index(&self, x: usize, y: usize) -> u8 {
+        return (self.screen[y * 256 + x] & SCREEN_PALETTE_MASK) as u8;
+    }
+
+    // The PPUMASK emphasis bits active when a pixel was drawn, shifted down to 0b0000_0BGR
+    // (on PAL, red and green are swapped by the hardware; this returns the register bits
+    // as written)
+    pub fn pixel_emphasis(&self, x: usize, y: usize) -> u8 {
+        return (self.screen[y * 256 + x] >> SCREEN_EMPHASIS_SHIFT) as u8;
+    }
+
+    // The whole framebuffer as separate planes of palette indices and emphasis bits,
+    // 256x240 each, in the formats of pixel_palette_index and pixel_emphasis
+    pub fn indexed_screen(&self) -> (Vec<u8>, Vec<u8>) {
+        let palette_indices = self.screen.iter().map(|pixel| (pixel & SCREEN_PALETTE_MASK) as u8).collect();
+        let emphasis = self.screen.iter().map(|pixel| (pixel >> SCREEN_EMPHASIS_SHIFT) as u8).collect();
+        return (palette_indices, emphasis);
+    }
+
+    // Brightness of a pixel as a TV would see it, 0.0 (black) to roughly 1.0 (white),
+    // with emphasis applied. Pixels the beam hasn't reached yet still hold the previous
+    // frame.
+    pub fn pixel_luminance(&self, x: usize, y: usize) -> f32 {
+        return pixel_luminance(self.screen[y * 256 + x]);
+    }
+
+    // The visible pixel the PPU is drawing right now, if any (so nothing during hblank,
+    // vblank, or the pre-render line)
+    pub fn beam_position(&self) -> Option<(usize, usize)> {
+        if self.current_scanline < 240 && self.current_scanline_cycle >= 1 && self.current_scanline_cycle <= 256 {
+            return Some(((self.current_scanline_cycle - 1) as usize, self.current_scanline as usize));
+        }
+        return None;
+    }
+
+    // Scanlines since the beam drew (x, y) this frame, or None if it hasn't yet. A light
+    // gun's photodiode stays lit for a while after the beam passes a bright spot, so a
+    // Zapper model compares this against its sense window.
+    pub fn scanlines_since_drawn(&self, x: usize, y: usize) -> Option<usize> {
+        let (scanline, dot) = (self.current_scanline as usize, self.current_scanline_cycle as usize);
+        if scanline >= 262 || y > scanline || (y == scanline && dot <= x + 1) || y >= 240 {
+            return None;
+        }
+        return Some(scanline - y);
     }
 
     pub fn rendering_enabled(&self) -> bool {
@@ -367,9 +544,21 @@ impl PpuState {
         self.palette_latch = (self.attribute_byte >> palette_shift) & 0b11;
     }
 
+    // Grayscale mode forces the hue bits to 0, leaving only the brightness. This happens
+    // after the palette lookup, so it affects both the displayed color and $2007 reads
+    // of palette memory, but not the palette contents themselves. Emphasis is applied
+    // on top of the masked color.
+    pub fn apply_grayscale(&self, color: u8) -> u8 {
+        if self.mask & 0b0000_0001 != 0 {
+            return color & 0x30;
+        }
+        return color;
+    }
+
     fn plot_pixel(&mut self, x: u16, y: u16, color: u8) {
         let index = ((y as usize) * 256) + (x as usize);
-        let pixel_color = (((self.mask as u16) & 0b1110_0000) << 1) | ((color as u16) & 0b0011_1111);
+        let output_color = self.apply_grayscale(color);
+        let pixel_color = (((self.mask as u16) & 0b1110_0000) << 1) | ((output_color as u16) & SCREEN_PALETTE_MASK);
         self.screen[index] = pixel_color;
     }
 
@@ -395,12 +584,19 @@ impl PpuState {
             bg_palette_index = 0;
         }
 
-        if bg_palette_index == 0 {
+        // What the game sees stays in bg_palette_index; the debug toggle only affects
+        // what we draw
+        let mut visible_bg_index = bg_palette_index;
+        if self.debug_hide_background {
+            visible_bg_index = 0;
+        }
+
+        if visible_bg_index == 0 {
             // bg color 0 always uses the first palette
             bg_palette_number = 0;
         }
 
-        let mut pixel_color = self.read_byte(mapper, (((bg_palette_number as u16) << 2) + bg_palette_index) as u16 + 0x3F00);
+        let mut pixel_color = self.read_byte(mapper, (((bg_palette_number as u16) << 2) + visible_bg_index) as u16 + 0x3F00);
 
         // If sprites are enabled
         if self.mask & 0b0001_0000 != 0 && ((self.mask & 0b0000_0100 != 0) || px >= 8) {
@@ -411,7 +607,7 @@ impl PpuState {
                         // Sprite zero hit!
                         self.status = self.status | 0x40;
                     }
-                    if bg_palette_index == 0 || !self.secondary_oam[sprite_index].bg_priority() {
+                    if !self.debug_hide_sprites && (visible_bg_index == 0 || !self.secondary_oam[sprite_index].bg_priority()) {
                         let sprite_palette_number = self.secondary_oam[sprite_index].palette() as u16;
                         let sprite_palette_index = self.secondary_oam[sprite_index].palette_index() as u16;
                         pixel_color = self.read_byte(mapper, (sprite_palette_number << 2) + sprite_palette_index + 0x3F10);
@@ -661,6 +857,8 @@ impl PpuState {
                         self.current_scanline_cycle = 0;
                         self.current_scanline = 0;
                         self.current_frame += 1;
+                        // Dot 0 of scanline 0 never happens, so it starts here instead
+                        mapper.start_scanline(0);
                     }
                 }
             }
@@ -668,7 +866,56 @@ impl PpuState {
         }
     }
 
+    // The PPU is disabled. Usually, we should show the backdrop color. However, if the current
+    // VRAM address is within palette memory, the PPU instead outputs whatever that color is. Only
+    // the lower 14 bits of v reach the address bus, so $7F00-$7FFF count too.
+    // https://wiki.nesdev.com/w/index.php/PPU_palettes#The_background_palette_hack
+    fn forced_blank_color(&mut self, mapper: &mut dyn Mapper) -> u8 {
+        let vram_address = self.current_vram_address & 0x3FFF;
+        if vram_address >= 0x3F00 {
+            return self.read_byte(mapper, vram_address);
+        }
+        return self.read_byte(mapper, 0x3F00);
+    }
+
+    fn capture_scroll(&mut self) {
+        let v = self.current_vram_address;
+        let coarse_x = v & 0b00_00000_11111;
+        let coarse_y = (v & 0b00_11111_00000) >> 5;
+        let nametable_x = (v & 0b01_00000_00000) >> 10;
+        let nametable_y = (v & 0b10_00000_00000) >> 11;
+        let fine_y = (v & 0b111_00_00000_00000) >> 12;
+        // By dot 1, v has already been advanced past the two prefetched tiles
+        let x = ((nametable_x * 256) + (coarse_x * 8) + (self.fine_x as u16) + 512 - 16) % 512;
+        let y = (nametable_y * 240) + (coarse_y * 8) + fine_y;
+        self.scanline_scroll[self.current_scanline as usize] = ScrollSnapshot {
+            rendering: self.rendering_enabled(),
+            x: x,
+            y: y,
+        };
+    }
+
+    fn capture_banks(&mut self, mapper: &dyn Mapper) {
+        let banks = &mut self.scanline_banks[self.current_scanline as usize];
+        match self.current_scanline_cycle {
+            1 => {
+                for (index, window) in banks.prg.iter_mut().enumerate() {
+                    *window = mapper.resolve_prg_offset(0x8000 + index as u16 * 0x2000).map(|(_, offset)| offset / 0x2000);
+                }
+                banks.chr = chr_windows(mapper);
+            },
+            257 => banks.sprite_chr = chr_windows(mapper),
+            _ => {}
+        }
+    }
+
     fn render_scanline(&mut self, mapper: &mut dyn Mapper) {
+        if self.current_scanline_cycle == 1 {
+            self.capture_scroll();
+        }
+        if self.record_scanline_banks {
+            self.capture_banks(mapper);
+        }
         if self.rendering_enabled() {
             match self.current_scanline_cycle {
                 0 => {
@@ -716,15 +963,7 @@ impl PpuState {
         } else {
             match self.current_scanline_cycle {
                 1 ..= 256 => {
-                    // The PPU is disabled. Usually, we should show the backdrop color:
-                    let mut pixel_color = self.read_byte(mapper, 0x3F00);
-                    // However, if the current VRAM address is within palette memory, instead
-                    // show whatever that color is:
-                    if self.current_vram_address >= 0x3F00 && self.current_vram_address <= 0x3FFF {
-                        let vram_address = self.current_vram_address;
-                        pixel_color = self.read_byte(mapper, vram_address);
-                    }
-
+                    let pixel_color = self.forced_blank_color(mapper);
                     let px = self.current_scanline_cycle - 1;
                     let py = self.current_scanline;
                     self.plot_pixel(px, py, pixel_color);
@@ -734,14 +973,33 @@ impl PpuState {
         }
     }
 
-    fn vblank_scanline(&mut self) {
+    fn vblank_scanline(&mut self, mapper: &mut dyn Mapper) {
         if self.current_scanline_cycle == 1 {
             // VBlank! Set NMI flag here
             self.status = (self.status & 0x7F) + 0x80;
+            mapper.start_vblank();
+        }
+    }
+
+    pub fn write_mask(&mut self, data: u8) {
+        self.pending_mask = data;
+        self.mask_delay = PPUMASK_DELAY;
+    }
+
+    fn update_mask(&mut self) {
+        if self.mask_delay > 0 {
+            self.mask_delay -= 1;
+            if self.mask_delay == 0 {
+                self.mask = self.pending_mask;
+            }
         }
     }
 
     pub fn clock(&mut self, mapper: &mut dyn Mapper) {
+        self.update_mask();
+        if self.current_scanline_cycle == 0 {
+            mapper.start_scanline(self.current_scanline);
+        }
         match self.current_scanline {
             0 => {
                 if self.current_scanline_cycle == 1 {
@@ -758,7 +1016,7 @@ impl PpuState {
                     let _ = self.read_byte(mapper, vram_address);
                 }
             }
-            241 => self.vblank_scanline(),
+            241 => self.vblank_scanline(mapper),
             261 => self.prerender_scanline(mapper),
             _ => ()
         }
@@ -803,7 +1061,23 @@ impl PpuState {
         return (attr_byte & mask) >> shift;
     }
 
+    // Common choices are 602 (roughly square pixels), 1204, 2048 (one pixel per sample,
+    // the default) and 2408
+    pub fn set_ntsc_width(&mut self, width: usize) {
+        self.ntsc_width = width.max(1);
+        self.filtered_screen = vec!(0u32; self.ntsc_width * 240);
+    }
+
     pub fn render_ntsc(&mut self, width: usize) {
+        if width != self.ntsc_width {
+            self.set_ntsc_width(width);
+        }
+        let width = self.ntsc_width;
+        // Each output pixel averages a whole number of color cycles, enough to cover every
+        // sample it spans, so narrow outputs don't alias and chroma stays balanced
+        let span = NTSC_SAMPLES_PER_SCANLINE.div_ceil(width);
+        let window = span.div_ceil(NTSC_SAMPLES_PER_COLOR_CYCLE) * NTSC_SAMPLES_PER_COLOR_CYCLE;
+        let half_window = window / 2;
         // One scanline logic, needs wrapping for Y yet.
         for scanline in 0 .. 240 {
             // Compute ntsc signal from raw palette+emphasis values
@@ -818,14 +1092,14 @@ impl PpuState {
             // Decode scanline into framebuffer
             let phase = (self.frame_starting_cycle + (scanline * 341)) * 8;
             for x in 0 .. width {
-                let center = x * (256 * 8) / width + 0;
-                let begin = if center >= 6 {center - 6} else {0};
-                let end = if (center + 6) < (256 * 8) {center + 6} else {256*8};
+                let center = x * NTSC_SAMPLES_PER_SCANLINE / width + span / 2;
+                let begin = center.saturating_sub(half_window);
+                let end = (center + half_window).min(NTSC_SAMPLES_PER_SCANLINE);
                 let mut y = 0.0;
                 let mut i = 0.0;
                 let mut q = 0.0;
                 for p in begin .. end {
-                    let level = self.scanline_ntsc_samples[p] / 12.0;
+                    let level = self.scanline_ntsc_samples[p] / window as f32;
                     y = y + level;
                     i = i + level * PHASED_COS[(phase + p) % 12];
                     q = q + level * PHASED_SIN[(phase + p) % 12];
@@ -834,6 +1108,65 @@ impl PpuState {
             }
         }
     }
+
+    // The PAL equivalent of render_ntsc. A 2C07 dot lasts 5/6 of a color cycle and the
+    // phase of the V component flips every line, so a PAL TV's delay line decoder never
+    // shows the NTSC fringes: each pixel's color comes out whole, but blended with the
+    // line above. The 2C07 also swaps the red and green emphasis bits.
+    pub fn render_pal(&mut self, width: usize) {
+        if width != self.ntsc_width {
+            self.set_ntsc_width(width);
+        }
+        let width = self.ntsc_width;
+        let decoded: Vec<(f32, f32, f32)> = (0 .. 512u16).map(|pixel| decode_pixel_yiq(pal_emphasis(pixel))).collect();
+        let mut previous_line = [(0.0f32, 0.0f32, 0.0f32); 256];
+        for scanline in 0 .. 240 {
+            let mut line = [(0.0f32, 0.0f32, 0.0f32); 256];
+            for dot in 0 .. 256 {
+                line[dot] = decoded[(self.screen[scanline * 256 + dot] & 0x1FF) as usize];
+            }
+            if scanline == 0 {
+                previous_line = line;
+            }
+            for x in 0 .. width {
+                let dot = x * 256 / width;
+                let (y, i, q) = line[dot];
+                let (_, previous_i, previous_q) = previous_line[dot];
+                self.filtered_screen[scanline * width + x] = yiq_to_argb(y, (i + previous_i) / 2.0, (q + previous_q) / 2.0);
+            }
+            previous_line = line;
+        }
+    }
+
+    // Picks the composite decoder matching the console's video standard
+    pub fn render_composite(&mut self, region: Region, width: usize) {
+        match region {
+            Region::Ntsc => self.render_ntsc(width),
+            Region::Pal => self.render_pal(width),
+        }
+    }
+}
+
+// One palette entry decoded over a whole color cycle, free of any artifacts
+fn decode_pixel_yiq(pixel: u16) -> (f32, f32, f32) {
+    let mut y = 0.0;
+    let mut i = 0.0;
+    let mut q = 0.0;
+    for phase in 0 .. NTSC_SAMPLES_PER_COLOR_CYCLE {
+        let level = render_ntsc_sample(pixel, phase) / NTSC_SAMPLES_PER_COLOR_CYCLE as f32;
+        y += level;
+        i += level * PHASED_COS[phase];
+        q += level * PHASED_SIN[phase];
+    }
+    return (y, i, q);
+}
+
+// The 2C07 wires PPUMASK's red and green emphasis bits the other way around
+pub fn pal_emphasis(pixel: u16) -> u16 {
+    let red = (pixel >> SCREEN_EMPHASIS_SHIFT) & 0b001;
+    let green = (pixel >> SCREEN_EMPHASIS_SHIFT) & 0b010;
+    let swapped = (red << 1) | (green >> 1);
+    return (pixel & !(0b011 << SCREEN_EMPHASIS_SHIFT)) | (swapped << SCREEN_EMPHASIS_SHIFT);
 }
 
 const PHASED_SIN: [f32; 12] = [
@@ -909,6 +1242,16 @@ pub fn ntsc_signal(pixel: u16, phase: usize) -> f32 {
     return signal;
 }
 
+// The average of the composite signal over one color cycle, which is what the TV's
+// brightness (and a light gun's photodiode) responds to
+pub fn pixel_luminance(pixel: u16) -> f32 {
+    let mut total = 0.0;
+    for phase in 0 .. 12 {
+        total += render_ntsc_sample(pixel, phase);
+    }
+    return total / 12.0;
+}
+
 pub fn in_color_phase(color: u16, phase: usize)  -> bool {
     return ((color as usize + phase) % 12) < 6;
 }
@@ -0,0 +1,154 @@
+// Debug: a per-frame CPU profiler. Every cycle an instruction takes is charged to the
+// address it started at, then collected into buckets: the enclosing label when labels are
+// loaded, otherwise a PRG bank and a fixed size address range. At the end of each frame
+// the totals are published and the counters start over, so a frontend can show where a
+// game spends its frame budget.
+
+use std::collections::HashMap;
+
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum ProfileBucket {
+    // Index into CpuProfiler::labels
+    Label(usize),
+    // Code in PRG ROM; range_start is the CPU address at the start of the range
+    PrgBank{bank: usize, range_start: u16},
+    // RAM, or anything the mapper can't place in ROM
+    Unmapped{range_start: u16},
+}
+
+#[derive(Clone, Debug)]
+pub struct ProfileEntry {
+    pub bucket: ProfileBucket,
+    pub name: String,
+    pub cycles: u64,
+}
+
+#[derive(Clone, Debug)]
+pub struct ProfileLabel {
+    pub address: u16,
+    // None matches this address in any bank
+    pub bank: Option<usize>,
+    pub name: String,
+}
+
+pub struct CpuProfiler {
+    pub enabled: bool,
+    // Size of the address ranges used when no label applies; a power of two
+    range_size: u16,
+    // Sorted by address
+    pub labels: Vec<ProfileLabel>,
+    pub current_frame: HashMap<ProfileBucket, u64>,
+    // The finished frame, busiest bucket first
+    pub last_frame: Vec<ProfileEntry>,
+    pub last_frame_cycles: u64,
+}
+
+impl CpuProfiler {
+    pub fn new() -> CpuProfiler {
+        return CpuProfiler {
+            enabled: false,
+            range_size: 0x1000,
+            labels: Vec::new(),
+            current_frame: HashMap::new(),
+            last_frame: Vec::new(),
+            last_frame_cycles: 0,
+        }
+    }
+
+    pub fn range_size(&self) -> u16 {
+        return self.range_size;
+    }
+
+    // Rounds up to the next power of two, between 1 byte and half the address space.
+    // Counts so far this frame were bucketed by the old size, so they're thrown out.
+    pub fn set_range_size(&mut self, range_size: u16) {
+        self.range_size = range_size.max(1).min(0x8000).next_power_of_two();
+        self.current_frame.clear();
+    }
+
+    pub fn add_label(&mut self, address: u16, bank: Option<usize>, name: &str) {
+        let position = self.labels.iter().position(|label| label.address > address).unwrap_or(self.labels.len());
+        self.labels.insert(position, ProfileLabel {
+            address: address,
+            bank: bank,
+            name: name.to_string(),
+        });
+    }
+
+    pub fn clear_labels(&mut self) {
+        self.labels.clear();
+        self.current_frame.clear();
+    }
+
+    // The closest label at or before this address, from the same bank where it matters.
+    // Labels are only searched within the same range, so RAM variables don't swallow
+    // the whole of ROM.
+    fn find_label(&self, pc: u16, bank: Option<usize>) -> Option<usize> {
+        let range_start = pc & !(self.range_size - 1);
+        for (index, label) in self.labels.iter().enumerate().rev() {
+            if label.address > pc {
+                continue;
+            }
+            if label.address < range_start {
+                return None;
+            }
+            if label.bank.is_none() || label.bank == bank {
+                return Some(index);
+            }
+        }
+        return None;
+    }
+
+    pub fn bucket_for(&self, pc: u16, bank: Option<usize>) -> ProfileBucket {
+        if let Some(index) = self.find_label(pc, bank) {
+            return ProfileBucket::Label(index);
+        }
+        let range_start = pc & !(self.range_size - 1);
+        return match bank {
+            Some(bank) => ProfileBucket::PrgBank{bank: bank, range_start: range_start},
+            None => ProfileBucket::Unmapped{range_start: range_start},
+        }
+    }
+
+    pub fn record(&mut self, pc: u16, bank: Option<usize>, cycles: u64) {
+        let bucket = self.bucket_for(pc, bank);
+        *self.current_frame.entry(bucket).or_insert(0) += cycles;
+    }
+
+    pub fn bucket_name(&self, bucket: &ProfileBucket) -> String {
+        return match *bucket {
+            ProfileBucket::Label(index) => self.labels[index].name.clone(),
+            ProfileBucket::PrgBank{bank, range_start} => format!("{:02X}:{:04X}-{:04X}", bank, range_start, range_start as u32 + self.range_size as u32 - 1),
+            ProfileBucket::Unmapped{range_start} => format!("--:{:04X}-{:04X}", range_start, range_start as u32 + self.range_size as u32 - 1),
+        }
+    }
+
+    pub fn end_frame(&mut self) {
+        let mut entries: Vec<ProfileEntry> = self.current_frame.iter().map(|(bucket, &cycles)| ProfileEntry {
+            bucket: bucket.clone(),
+            name: self.bucket_name(bucket),
+            cycles: cycles,
+        }).collect();
+        entries.sort_by(|a, b| b.cycles.cmp(&a.cycles).then(a.name.cmp(&b.name)));
+        self.last_frame_cycles = entries.iter().map(|entry| entry.cycles).sum();
+        self.last_frame = entries;
+        self.current_frame.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_size_is_a_nonzero_power_of_two() {
+        let mut profiler = CpuProfiler::new();
+        profiler.set_range_size(0);
+        assert_eq!(profiler.range_size(), 1);
+        profiler.set_range_size(0x0C00);
+        assert_eq!(profiler.range_size(), 0x1000);
+        profiler.set_range_size(0xFFFF);
+        assert_eq!(profiler.range_size(), 0x8000);
+        assert_eq!(profiler.bucket_for(0xC123, Some(3)), ProfileBucket::PrgBank{bank: 3, range_start: 0x8000});
+    }
+}
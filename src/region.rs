@@ -0,0 +1,94 @@
+// Console region. The 2A03 (NTSC) and 2A07 (PAL) CPUs run from different master clocks,
+// and the APU's internal timing tables differ to compensate.
+// Reference capabilities: https://wiki.nesdev.com/w/index.php/Cycle_reference_chart
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Region {
+    Ntsc,
+    Pal,
+}
+
+impl Region {
+    pub fn cpu_clock_rate(&self) -> u64 {
+        return match *self {
+            Region::Ntsc => 1_789_773,
+            Region::Pal => 1_662_607,
+        }
+    }
+
+    // Average CPU cycles per frame with rendering enabled. NTSC skips one dot on odd
+    // frames, for 89341.5 dots / 3; PAL is 312 lines of 341 dots at 3.2 dots per cycle.
+    pub fn cpu_cycles_per_frame(&self) -> f64 {
+        return match *self {
+            Region::Ntsc => 29780.5,
+            Region::Pal => 33247.5,
+        }
+    }
+
+    // ~16.639 ms for NTSC, ~19.997 ms for PAL
+    pub fn frame_duration(&self) -> f64 {
+        return self.cpu_cycles_per_frame() / (self.cpu_clock_rate() as f64);
+    }
+
+    pub fn frame_rate(&self) -> f64 {
+        return 1.0 / self.frame_duration();
+    }
+
+    // Width:height of one PPU pixel on a TV, from the ratio of the pixel clock to the
+    // square pixel sampling rate: 8:7 for NTSC, and 2950000:2128137 (about 11:8) for PAL
+    pub fn pixel_aspect_ratio(&self) -> f64 {
+        return match *self {
+            Region::Ntsc => 8.0 / 7.0,
+            Region::Pal => 2_950_000.0 / 2_128_137.0,
+        }
+    }
+
+    pub fn chip_name(&self) -> &'static str {
+        return match *self {
+            Region::Ntsc => "2A03",
+            Region::Pal => "2A07",
+        }
+    }
+}
+
+// Accumulates host time and reports how many emulated frames should run to keep up.
+// A frontend locked to a 60.000 Hz display will occasionally see 0 or 2 here instead of
+// 1, which keeps audio from drifting; on VRR displays, pass the actual time since the
+// last present.
+pub struct FrameTimer {
+    pub frame_duration: f64,
+    pub accumulator: f64,
+}
+
+impl FrameTimer {
+    pub fn new(region: Region) -> FrameTimer {
+        return FrameTimer {
+            frame_duration: region.frame_duration(),
+            accumulator: 0.0,
+        }
+    }
+
+    pub fn add_host_time(&mut self, elapsed_seconds: f64) -> u32 {
+        self.accumulator += elapsed_seconds;
+        let mut frames = 0;
+        while self.accumulator >= self.frame_duration {
+            self.accumulator -= self.frame_duration;
+            frames += 1;
+        }
+        return frames;
+    }
+
+    pub fn add_host_refresh(&mut self, refresh_rate: f64) -> u32 {
+        return self.add_host_time(1.0 / refresh_rate);
+    }
+
+    // How far into the next emulated frame the host currently is, from 0.0 to 1.0.
+    // Useful for frame blending or deciding whether to present early.
+    pub fn fraction(&self) -> f64 {
+        return self.accumulator / self.frame_duration;
+    }
+
+    pub fn reset(&mut self) {
+        self.accumulator = 0.0;
+    }
+}
@@ -0,0 +1,73 @@
+// Groups per-scanline scroll snapshots into regions which scroll together, so debug
+// views can tell a status bar apart from the playfield. A region continues for as long
+// as X stays put and Y advances by one each scanline, which is what the PPU does on its
+// own; any mid-frame scroll write breaks that and starts a new region.
+
+use ppu::ScrollSnapshot;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct ScrollRegion {
+    pub first_scanline: u16,
+    pub last_scanline: u16,
+    pub rendering: bool,
+    pub scroll_x: u16,
+    pub scroll_y: u16,
+    // The tallest rendered region is assumed to be the playfield; the others are
+    // likely HUD elements
+    pub playfield: bool,
+}
+
+impl ScrollRegion {
+    pub fn height(&self) -> u16 {
+        return self.last_scanline - self.first_scanline + 1;
+    }
+}
+
+fn continues_region(previous: &ScrollSnapshot, current: &ScrollSnapshot) -> bool {
+    if previous.rendering != current.rendering {
+        return false;
+    }
+    if !current.rendering {
+        return true;
+    }
+    return previous.x == current.x && (previous.y + 1) % 480 == current.y;
+}
+
+pub fn detect_scroll_splits(snapshots: &[ScrollSnapshot]) -> Vec<ScrollRegion> {
+    let mut regions: Vec<ScrollRegion> = Vec::new();
+    for (scanline, snapshot) in snapshots.iter().enumerate() {
+        let continues = scanline > 0 && continues_region(&snapshots[scanline - 1], snapshot);
+        if continues {
+            if let Some(region) = regions.last_mut() {
+                region.last_scanline = scanline as u16;
+            }
+        } else {
+            regions.push(ScrollRegion {
+                first_scanline: scanline as u16,
+                last_scanline: scanline as u16,
+                rendering: snapshot.rendering,
+                scroll_x: snapshot.x,
+                scroll_y: snapshot.y,
+                playfield: false,
+            });
+        }
+    }
+
+    let mut tallest: Option<usize> = None;
+    for i in 0 .. regions.len() {
+        if regions[i].rendering {
+            match tallest {
+                Some(t) if regions[t].height() >= regions[i].height() => {},
+                _ => tallest = Some(i)
+            }
+        }
+    }
+    if let Some(t) = tallest {
+        regions[t].playfield = true;
+    }
+    return regions;
+}
+
+pub fn hud_regions(regions: &[ScrollRegion]) -> Vec<ScrollRegion> {
+    return regions.iter().filter(|region| region.rendering && !region.playfield).cloned().collect();
+}
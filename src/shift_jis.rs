@@ -0,0 +1,233 @@
+// A very rough Shift-JIS to ASCII transliteration, good enough to display the metadata of
+// Japanese NSF files with a plain ASCII font. Kana become romaji, full width letters and
+// punctuation become their ASCII equivalents, and everything else (kanji included)
+// becomes '?'. This is a readability aid only; it does not attempt to be correct Hepburn.
+
+// Hiragana from U+3041, in JIS order. Katakana follow the same order, so one table serves
+// both. Entries starting with '~' are small kana, which modify the previous syllable.
+const KANA_ROMAJI: [&str; 86] = [
+    "~a", "a", "~i", "i", "~u", "u", "~e", "e", "~o", "o",
+    "ka", "ga", "ki", "gi", "ku", "gu", "ke", "ge", "ko", "go",
+    "sa", "za", "shi", "ji", "su", "zu", "se", "ze", "so", "zo",
+    "ta", "da", "chi", "ji", "~tsu", "tsu", "zu", "te", "de", "to", "do",
+    "na", "ni", "nu", "ne", "no",
+    "ha", "ba", "pa", "hi", "bi", "pi", "fu", "bu", "pu", "he", "be", "pe", "ho", "bo", "po",
+    "ma", "mi", "mu", "me", "mo",
+    "~ya", "ya", "~yu", "yu", "~yo", "yo",
+    "ra", "ri", "ru", "re", "ro",
+    "~wa", "wa", "wi", "we", "wo", "n",
+    "vu", "~ka", "~ke",
+];
+
+// Half width katakana, 0xA6 - 0xDD
+const HALF_WIDTH_ROMAJI: [&str; 56] = [
+    "wo", "~a", "~i", "~u", "~e", "~o", "~ya", "~yu", "~yo", "~tsu", "-",
+    "a", "i", "u", "e", "o",
+    "ka", "ki", "ku", "ke", "ko",
+    "sa", "shi", "su", "se", "so",
+    "ta", "chi", "tsu", "te", "to",
+    "na", "ni", "nu", "ne", "no",
+    "ha", "hi", "fu", "he", "ho",
+    "ma", "mi", "mu", "me", "mo",
+    "ya", "yu", "yo",
+    "ra", "ri", "ru", "re", "ro",
+    "wa", "n",
+];
+
+// Placeholder for a small tsu, until we know which consonant it doubles. Private use, so
+// it can't collide with anything decoded from the input.
+const SMALL_TSU: char = '\u{E000}';
+
+fn double_byte_punctuation(code: u16) -> Option<char> {
+    return match code {
+        0x8140 => Some(' '),
+        0x8141 | 0x8143 => Some(','),
+        0x8142 | 0x8144 | 0x8145 => Some('.'),
+        0x8146 => Some(':'),
+        0x8147 => Some(';'),
+        0x8148 => Some('?'),
+        0x8149 => Some('!'),
+        0x814F => Some('^'),
+        0x8151 => Some('_'),
+        0x815B | 0x815C | 0x815D | 0x817C => Some('-'),
+        0x815E => Some('/'),
+        0x815F => Some('\\'),
+        0x8160 => Some('~'),
+        0x8162 => Some('|'),
+        0x8165 | 0x8166 => Some('\''),
+        0x8167 | 0x8168 => Some('"'),
+        0x8169 => Some('('),
+        0x816A => Some(')'),
+        0x816D | 0x8175 | 0x8177 | 0x8179 => Some('['),
+        0x816E | 0x8176 | 0x8178 | 0x817A => Some(']'),
+        0x816F => Some('{'),
+        0x8170 => Some('}'),
+        0x817B => Some('+'),
+        0x817E => Some('x'),
+        0x8181 => Some('='),
+        0x8183 => Some('<'),
+        0x8184 => Some('>'),
+        0x8190 => Some('$'),
+        0x8193 => Some('%'),
+        0x8194 => Some('#'),
+        0x8195 => Some('&'),
+        0x8196 => Some('*'),
+        0x8197 => Some('@'),
+        _ => None
+    }
+}
+
+fn kana_index(code: u16) -> Option<usize> {
+    return match code {
+        // Hiragana
+        0x829F ..= 0x82F1 => Some((code - 0x829F) as usize),
+        // Katakana, which skip 0x7F as a trail byte
+        0x8340 ..= 0x837E => Some((code - 0x8340) as usize),
+        0x8380 ..= 0x8396 => Some((code - 0x8341) as usize),
+        _ => None
+    }
+}
+
+// A small tsu only doubles a consonant; anything else in between cancels it
+fn drop_small_tsu(output: &mut String) {
+    if output.ends_with(SMALL_TSU) {
+        output.pop();
+    }
+}
+
+fn push_other(output: &mut String, c: char) {
+    drop_small_tsu(output);
+    output.push(c);
+}
+
+fn push_syllable(output: &mut String, syllable: &str) {
+    if let Some(small) = syllable.strip_prefix('~') {
+        if small != "tsu" {
+            drop_small_tsu(output);
+        }
+        match small {
+            "ya" | "yu" | "yo" if output.ends_with('i') && output.len() >= 2 => {
+                // kya, sha, cha, ja, etc.
+                output.pop();
+                if output.ends_with("sh") || output.ends_with("ch") || output.ends_with('j') {
+                    output.push_str(&small[1 ..]);
+                } else {
+                    output.push_str(small);
+                }
+            },
+            "a" | "i" | "e" | "o" if output.ends_with('u') && output.len() >= 2 => {
+                // fa, vi, tsa, etc.
+                output.pop();
+                output.push_str(small);
+            },
+            "tsu" => {
+                // Doubles the following consonant; resolved by the next syllable
+                output.push(SMALL_TSU);
+            },
+            _ => output.push_str(small)
+        }
+        return;
+    }
+    if output.ends_with(SMALL_TSU) {
+        output.pop();
+        if let Some(consonant) = syllable.chars().next() {
+            // The long vowel mark ("-") comes through here too, and has nothing to double
+            if consonant.is_ascii_lowercase() && !"aiueon".contains(consonant) {
+                output.push(if consonant == 'c' {'t'} else {consonant});
+            }
+        }
+    }
+    output.push_str(syllable);
+}
+
+// Applies a half width dakuten (voiced) or handakuten (semi-voiced) mark to the final syllable
+fn apply_voicing(output: &mut String, semi_voiced: bool) {
+    let replacements: &[(&str, &str)] = if semi_voiced {
+        &[("ha", "pa"), ("hi", "pi"), ("fu", "pu"), ("he", "pe"), ("ho", "po")]
+    } else {
+        &[("ka", "ga"), ("ki", "gi"), ("ku", "gu"), ("ke", "ge"), ("ko", "go"),
+          ("sa", "za"), ("shi", "ji"), ("su", "zu"), ("se", "ze"), ("so", "zo"),
+          ("ta", "da"), ("chi", "ji"), ("tsu", "zu"), ("te", "de"), ("to", "do"),
+          ("ha", "ba"), ("hi", "bi"), ("fu", "bu"), ("he", "be"), ("ho", "bo"),
+          ("u", "vu")]
+    };
+    for &(plain, voiced) in replacements {
+        if output.ends_with(plain) {
+            let new_length = output.len() - plain.len();
+            output.truncate(new_length);
+            output.push_str(voiced);
+            return;
+        }
+    }
+}
+
+// Converts until the end of the data or the first null byte
+pub fn transliterate(data: &[u8]) -> String {
+    let mut output = String::new();
+    let mut i = 0;
+    while i < data.len() {
+        let byte = data[i];
+        i += 1;
+        match byte {
+            0x00 => break,
+            0x20 ..= 0x7E => push_other(&mut output, byte as char),
+            0x01 ..= 0x1F | 0x7F => {},
+            0xA1 => push_other(&mut output, '.'),
+            0xA2 => push_other(&mut output, '['),
+            0xA3 => push_other(&mut output, ']'),
+            0xA4 => push_other(&mut output, ','),
+            0xA5 => push_other(&mut output, '.'),
+            0xA6 ..= 0xDD => push_syllable(&mut output, HALF_WIDTH_ROMAJI[(byte - 0xA6) as usize]),
+            0xDE => apply_voicing(&mut output, false),
+            0xDF => apply_voicing(&mut output, true),
+            0x81 ..= 0x9F | 0xE0 ..= 0xEF => {
+                if i >= data.len() {
+                    break;
+                }
+                let code = ((byte as u16) << 8) | (data[i] as u16);
+                i += 1;
+                match code {
+                    // Full width digits and letters
+                    0x824F ..= 0x8258 => push_other(&mut output, (b'0' + (code - 0x824F) as u8) as char),
+                    0x8260 ..= 0x8279 => push_other(&mut output, (b'A' + (code - 0x8260) as u8) as char),
+                    0x8281 ..= 0x829A => push_other(&mut output, (b'a' + (code - 0x8281) as u8) as char),
+                    _ => {
+                        if let Some(index) = kana_index(code) {
+                            push_syllable(&mut output, KANA_ROMAJI[index]);
+                        } else if let Some(c) = double_byte_punctuation(code) {
+                            push_other(&mut output, c);
+                        } else {
+                            push_other(&mut output, '?');
+                        }
+                    }
+                }
+            },
+            _ => push_other(&mut output, '?')
+        }
+    }
+    // A trailing small tsu has nothing to double
+    drop_small_tsu(&mut output);
+    return output;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn small_tsu_doubles_the_next_consonant() {
+        // キッテ, in full and half width
+        assert_eq!(transliterate(&[0x83, 0x4C, 0x83, 0x62, 0x83, 0x65]), "kitte");
+        assert_eq!(transliterate(&[0xB7, 0xAF, 0xC3]), "kitte");
+    }
+
+    #[test]
+    fn small_tsu_before_anything_else_is_dropped() {
+        // キッ followed by ASCII, full width punctuation, and both long vowel marks
+        assert_eq!(transliterate(&[0x83, 0x4C, 0x83, 0x62, b'!']), "ki!");
+        assert_eq!(transliterate(&[0x83, 0x4C, 0x83, 0x62, 0x81, 0x49]), "ki!");
+        assert_eq!(transliterate(&[0x83, 0x4C, 0x83, 0x62, 0x81, 0x5B]), "ki-");
+        assert_eq!(transliterate(&[0xB7, 0xAF, 0xB0]), "ki-");
+        assert_eq!(transliterate(&[0xB7, 0xAF]), "ki");
+    }
+}
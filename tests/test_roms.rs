@@ -0,0 +1,86 @@
+// Runs the public test ROM suites through the same harness as the test-suite command.
+// The ROMs aren't redistributed here; point RUSTICNES_TEST_ROMS at a checkout of
+// https://github.com/christopherpow/nes-test-roms to run these. Without it they pass
+// trivially, so a plain cargo test still works offline.
+
+extern crate rusticnes_core;
+
+use rusticnes_core::blargg;
+use rusticnes_core::cartridge;
+use rusticnes_core::nes::NesState;
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const MAX_FRAMES: u32 = 60 * 60;
+
+fn suite_directory(suite: &str) -> Option<PathBuf> {
+    let root = match env::var_os("RUSTICNES_TEST_ROMS") {
+        Some(root) => PathBuf::from(root),
+        None => {
+            println!("RUSTICNES_TEST_ROMS is not set, skipping {}", suite);
+            return None;
+        }
+    };
+    return Some(root.join(suite));
+}
+
+fn run_rom(path: &PathBuf) -> Result<String, String> {
+    let data = fs::read(path).map_err(|e| format!("Couldn't read {}: {}", path.display(), e))?;
+    let mut nes = NesState::new(cartridge::mapper_from_file(&data)?);
+    nes.power_on();
+    return blargg::run_test_rom(&mut nes, MAX_FRAMES);
+}
+
+// Runs each of the suite's single test ROMs, reporting every failure rather than the first
+fn run_suite(suite: &str, roms: &[&str]) {
+    let directory = match suite_directory(suite) {
+        Some(directory) => directory,
+        None => return,
+    };
+    let mut failures = Vec::new();
+    for rom in roms {
+        let path = directory.join(rom);
+        if let Err(why) = run_rom(&path) {
+            failures.push(format!("{}: {}", rom, why));
+        }
+    }
+    assert!(failures.is_empty(), "{} failures:\n{}", failures.len(), failures.join("\n"));
+}
+
+#[test]
+fn apu_reset() {
+    run_suite("apu_reset", &[
+        "4015_cleared.nes",
+        "4017_timing.nes",
+        "4017_written.nes",
+        "irq_flag_cleared.nes",
+        "len_ctrs_enabled.nes",
+        "works_immediately.nes",
+    ]);
+}
+
+// The frame counter timing tests from blargg's apu_test
+#[test]
+fn apu_frame_timing() {
+    run_suite("apu_test/rom_singles", &[
+        "3-irq_flag.nes",
+        "4-jitter.nes",
+        "5-len_timing.nes",
+        "6-irq_flag_timing.nes",
+    ]);
+}
+
+// Interrupt latency and hijacking from blargg's cpu_interrupts_v2, including the taken
+// branch that delays an IRQ
+#[test]
+fn cpu_interrupts_v2() {
+    run_suite("cpu_interrupts_v2/rom_singles", &[
+        "1-cli_latency.nes",
+        "2-nmi_and_brk.nes",
+        "3-nmi_and_irq.nes",
+        "4-irq_and_dma.nes",
+        "5-branch_delays_irq.nes",
+    ]);
+}